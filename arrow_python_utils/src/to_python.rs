@@ -23,7 +23,7 @@
 // SOFTWARE.
 
 use polars_core::frame::ArrowChunk;
-use polars_core::prelude::{ArrayRef, ArrowField};
+use polars_core::prelude::{ArrayRef, ArrowField, Series};
 use polars_core::utils::arrow::ffi;
 use pyo3::ffi::Py_uintptr_t;
 use pyo3::prelude::*;
@@ -87,3 +87,16 @@ pub fn to_py_df(
     let df = polars.call_method1("from_arrow", (py_table,))?;
     Ok(df.to_object(py))
 }
+
+pub fn to_py_series(
+    series: &Series,
+    py: Python,
+    pyarrow: &PyModule,
+    polars: &PyModule,
+) -> PyResult<PyObject> {
+    let series = series.rechunk();
+    let py_array = to_py_array(series.to_arrow(0), py, pyarrow)?;
+    let py_series = polars.call_method1("from_arrow", (py_array,))?;
+    let py_series = py_series.call_method1("alias", (series.name(),))?;
+    Ok(py_series.to_object(py))
+}
@@ -80,6 +80,12 @@ pub fn polars_df_to_rust_df(df: &PyAny) -> PyResult<DataFrame> {
     array_to_rust_df(&[batch])
 }
 
+pub fn polars_series_to_rust_series(series: &PyAny) -> PyResult<Series> {
+    let name = series.getattr("name")?.extract::<String>()?;
+    let arr = array_to_rust(series.call_method0("to_arrow")?)?;
+    Ok(Series::try_from((name.as_str(), arr)).map_err(ToRustError::from)?)
+}
+
 pub fn array_to_rust_df(rb: &[&PyAny]) -> PyResult<DataFrame> {
     let schema = rb
         .get(0)
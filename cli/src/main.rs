@@ -0,0 +1,243 @@
+mod output;
+
+use crate::output::{write_df, OutputFormat};
+use clap::{Args, Parser, Subcommand};
+use dsl::connective_mapping::ConnectiveMapping;
+use dsl::name_templates::{name_template, type_name_template};
+use dsl::parser::ts_query;
+use dsl::translator::Translator;
+use hybrid::config::{EngineConfig, VocabularyConfig};
+use hybrid::construct::RDFFormat;
+use hybrid::engine::Engine;
+use log::debug;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::{read_to_string, File};
+use std::io::{stdin, stdout, BufWriter, Read, Write};
+use std::path::PathBuf;
+use tokio::runtime::{Builder, Runtime};
+
+/// Hybrid SPARQL and time series queries
+#[derive(Parser)]
+#[clap(name = "otit_swt_query", version)]
+struct Cli {
+    /// Engine configuration file, TOML if it ends with .toml and YAML otherwise
+    #[clap(short, long)]
+    config: PathBuf,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Execute a hybrid SPARQL query
+    Query(QueryArgs),
+    /// Translate a DSL query to SPARQL and execute it
+    Dsl(QueryArgs),
+    /// Execute a hybrid SPARQL construct query, writing N-Triples
+    Construct {
+        /// Query file, the query is read from stdin when this is not given
+        file: Option<PathBuf>,
+        /// Write Turtle rather than N-Triples
+        #[clap(long)]
+        turtle: bool,
+        /// Output file, triples are written to stdout when this is not given
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Execute a hybrid SPARQL ask query, printing true or false
+    Ask {
+        /// Query file, the query is read from stdin when this is not given
+        file: Option<PathBuf>,
+    },
+    /// Execute a SPARQL describe query, writing the triples and time series summaries of the described resources
+    Describe {
+        /// Query file, the query is read from stdin when this is not given
+        file: Option<PathBuf>,
+        /// Write Turtle rather than N-Triples
+        #[clap(long)]
+        turtle: bool,
+        /// Output file, triples are written to stdout when this is not given
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the static rewrite and the generated time series queries without executing them
+    Explain {
+        /// Query file, the query is read from stdin when this is not given
+        file: Option<PathBuf>,
+        /// The query is a DSL query
+        #[clap(long)]
+        dsl: bool,
+    },
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Query file, the query is read from stdin when this is not given
+    file: Option<PathBuf>,
+    #[clap(short, long, arg_enum, default_value = "table")]
+    format: OutputFormat,
+    /// Output file, results are written to stdout when this is not given
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Explain the query instead of executing it
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug)]
+enum CliError {
+    MissingNamePredicate,
+    DSLParsingError(String),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::MissingNamePredicate => {
+                write!(f, "DSL queries require a name predicate in the vocabulary")
+            }
+            CliError::DSLParsingError(s) => {
+                write!(f, "Could not parse DSL query: {}", s)
+            }
+        }
+    }
+}
+
+impl Error for CliError {}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let cli = Cli::parse();
+    let config = EngineConfig::from_file(&cli.config)?;
+    let runtime = Builder::new_multi_thread().enable_all().build()?;
+    let mut engine = runtime.block_on(Engine::from_config(&config))?;
+    let endpoint = &config.sparql_endpoint;
+    match cli.command {
+        Command::Query(args) => {
+            let sparql = read_query(&args.file)?;
+            run_query(&runtime, &mut engine, &sparql, endpoint, &args)?;
+        }
+        Command::Dsl(args) => {
+            let sparql = translate_dsl_query(&read_query(&args.file)?, &config.vocabulary)?;
+            if args.dry_run {
+                println!("Translated query:");
+                println!("{}", sparql);
+            }
+            run_query(&runtime, &mut engine, &sparql, endpoint, &args)?;
+        }
+        Command::Construct {
+            file,
+            turtle,
+            output,
+        } => {
+            let sparql = read_query(&file)?;
+            let mut writer = triple_writer(output)?;
+            let triples = runtime.block_on(engine.execute_construct_query(
+                &sparql,
+                endpoint,
+                rdf_format(turtle),
+                &mut writer,
+            ))?;
+            debug!("Wrote {} triples", triples);
+        }
+        Command::Ask { file } => {
+            let sparql = read_query(&file)?;
+            let answer = runtime.block_on(engine.execute_ask_query(&sparql, endpoint))?;
+            println!("{}", answer);
+        }
+        Command::Describe {
+            file,
+            turtle,
+            output,
+        } => {
+            let sparql = read_query(&file)?;
+            let mut writer = triple_writer(output)?;
+            let triples = runtime.block_on(engine.execute_describe_query(
+                &sparql,
+                endpoint,
+                rdf_format(turtle),
+                &mut writer,
+            ))?;
+            debug!("Wrote {} triples", triples);
+        }
+        Command::Explain { file, dsl } => {
+            let mut sparql = read_query(&file)?;
+            if dsl {
+                sparql = translate_dsl_query(&sparql, &config.vocabulary)?;
+                println!("Translated query:");
+                println!("{}", sparql);
+            }
+            let explanation = runtime.block_on(engine.explain_hybrid_query(&sparql, endpoint))?;
+            print!("{}", explanation);
+        }
+    }
+    Ok(())
+}
+
+fn run_query(
+    runtime: &Runtime,
+    engine: &mut Engine,
+    sparql: &str,
+    endpoint: &str,
+    args: &QueryArgs,
+) -> Result<(), Box<dyn Error>> {
+    if args.dry_run {
+        let explanation = runtime.block_on(engine.explain_hybrid_query(sparql, endpoint))?;
+        print!("{}", explanation);
+    } else {
+        let (mut df, iri_variables) =
+            runtime.block_on(engine.execute_hybrid_query_with_iri_variables(sparql, endpoint))?;
+        let iri_columns: HashSet<String> = iri_variables
+            .iter()
+            .map(|v| v.as_str().to_string())
+            .collect();
+        write_df(&mut df, args.format, args.output.as_deref(), &iri_columns)?;
+    }
+    Ok(())
+}
+
+fn rdf_format(turtle: bool) -> RDFFormat {
+    if turtle {
+        RDFFormat::Turtle
+    } else {
+        RDFFormat::NTriples
+    }
+}
+
+fn triple_writer(output: Option<PathBuf>) -> Result<Box<dyn Write>, std::io::Error> {
+    if let Some(path) = output {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    } else {
+        Ok(Box::new(BufWriter::new(stdout())))
+    }
+}
+
+fn read_query(file: &Option<PathBuf>) -> Result<String, std::io::Error> {
+    if let Some(path) = file {
+        read_to_string(path)
+    } else {
+        let mut query = String::new();
+        stdin().read_to_string(&mut query)?;
+        Ok(query)
+    }
+}
+
+fn translate_dsl_query(query: &str, vocabulary: &VocabularyConfig) -> Result<String, CliError> {
+    let name_predicate = vocabulary
+        .name_predicate
+        .as_ref()
+        .ok_or(CliError::MissingNamePredicate)?;
+    let (_, parsed) = ts_query(query).map_err(|e| CliError::DSLParsingError(e.to_string()))?;
+    let mut translator = Translator::new(
+        name_template(name_predicate),
+        type_name_template(name_predicate),
+        ConnectiveMapping {
+            map: vocabulary.connective_mapping.clone(),
+        },
+    );
+    let sparql = translator.translate(&parsed).to_string();
+    debug!("Translated DSL query: {}", sparql);
+    Ok(sparql)
+}
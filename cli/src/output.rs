@@ -0,0 +1,157 @@
+use clap::ArgEnum;
+use polars::export::chrono::{NaiveDate, NaiveDateTime};
+use polars::prelude::{
+    AnyValue, CsvWriter, DataFrame, IpcWriter, ParquetWriter, PolarsError, SerWriter, TimeUnit,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::Path;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Parquet,
+    Ipc,
+    //SPARQL 1.1 Query Results JSON
+    Json,
+}
+
+pub fn write_df(
+    df: &mut DataFrame,
+    format: OutputFormat,
+    output: Option<&Path>,
+    iri_columns: &HashSet<String>,
+) -> Result<(), PolarsError> {
+    let mut writer: Box<dyn Write> = if let Some(path) = output {
+        Box::new(File::create(path)?)
+    } else {
+        Box::new(stdout())
+    };
+    match format {
+        OutputFormat::Table => {
+            //Polars truncates displayed frames unless the limits cover the whole frame
+            env::set_var("POLARS_FMT_MAX_ROWS", df.height().to_string());
+            env::set_var("POLARS_FMT_MAX_COLS", df.width().to_string());
+            writeln!(writer, "{}", df)?
+        }
+        OutputFormat::Csv => CsvWriter::new(writer).has_header(true).finish(df)?,
+        OutputFormat::Parquet => {
+            ParquetWriter::new(writer).finish(df)?;
+        }
+        OutputFormat::Ipc => IpcWriter::new(writer).finish(df)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &sparql_results_json(df, iri_columns))
+                .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+//IRIs from the static query are strings in the results, so the columns holding IRIs are given
+pub fn sparql_results_json(df: &DataFrame, iri_columns: &HashSet<String>) -> Value {
+    let columns = df.get_columns();
+    let variables: Vec<String> = columns.iter().map(|s| s.name().to_string()).collect();
+    let mut iters: Vec<_> = columns.iter().map(|s| s.iter()).collect();
+    let mut bindings = vec![];
+    for _ in 0..df.height() {
+        let mut binding = Map::new();
+        for (variable, it) in variables.iter().zip(iters.iter_mut()) {
+            let any_value = it.next().unwrap();
+            let term = match any_value {
+                AnyValue::Utf8(s) if iri_columns.contains(variable) => {
+                    Some(json!({"type": "uri", "value": s}))
+                }
+                _ => any_value_to_json_term(any_value),
+            };
+            if let Some(term) = term {
+                binding.insert(variable.clone(), term);
+            }
+        }
+        bindings.push(Value::Object(binding));
+    }
+    json!({"head": {"vars": variables}, "results": {"bindings": bindings}})
+}
+
+fn any_value_to_json_term(any_value: AnyValue) -> Option<Value> {
+    let (value, datatype) = match any_value {
+        AnyValue::Null => return None,
+        AnyValue::Utf8(s) => return Some(json!({"type": "literal", "value": s})),
+        AnyValue::Boolean(b) => (b.to_string(), "boolean"),
+        AnyValue::UInt8(u) => (u.to_string(), "unsignedByte"),
+        AnyValue::UInt16(u) => (u.to_string(), "unsignedShort"),
+        AnyValue::UInt32(u) => (u.to_string(), "unsignedInt"),
+        AnyValue::UInt64(u) => (u.to_string(), "unsignedLong"),
+        AnyValue::Int8(i) => (i.to_string(), "byte"),
+        AnyValue::Int16(i) => (i.to_string(), "short"),
+        AnyValue::Int32(i) => (i.to_string(), "int"),
+        AnyValue::Int64(i) => (i.to_string(), "long"),
+        AnyValue::Float32(f) => (f.to_string(), "float"),
+        AnyValue::Float64(f) => (f.to_string(), "double"),
+        AnyValue::Date(days) => (
+            (NaiveDate::from_ymd(1970, 1, 1) + polars::export::chrono::Duration::days(days as i64))
+                .format("%Y-%m-%d")
+                .to_string(),
+            "date",
+        ),
+        AnyValue::Datetime(t, time_unit, _) => {
+            let nanos = match time_unit {
+                TimeUnit::Nanoseconds => t,
+                TimeUnit::Microseconds => t * 1_000,
+                TimeUnit::Milliseconds => t * 1_000_000,
+            };
+            let datetime = NaiveDateTime::from_timestamp(
+                nanos.div_euclid(1_000_000_000),
+                nanos.rem_euclid(1_000_000_000) as u32,
+            );
+            (
+                datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+                "dateTime",
+            )
+        }
+        other => return Some(json!({"type": "literal", "value": other.to_string()})),
+    };
+    Some(json!({"type": "literal", "value": value, "datatype": format!("{}{}", XSD, datatype)}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sparql_results_json;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_sparql_results_json() {
+        let df = DataFrame::new(vec![
+            Series::new("w", [Some("ts1"), None]),
+            Series::new("i", ["http://example.org/w1", "http://example.org/w2"]),
+            Series::new("v", [1u32, 2u32]),
+        ])
+        .unwrap();
+        let iri_columns = HashSet::from(["i".to_string()]);
+        assert_eq!(
+            sparql_results_json(&df, &iri_columns),
+            json!({
+                "head": {"vars": ["w", "i", "v"]},
+                "results": {"bindings": [
+                    {
+                        "w": {"type": "literal", "value": "ts1"},
+                        "i": {"type": "uri", "value": "http://example.org/w1"},
+                        "v": {"type": "literal", "value": "1", "datatype": "http://www.w3.org/2001/XMLSchema#unsignedInt"}
+                    },
+                    {
+                        "i": {"type": "uri", "value": "http://example.org/w2"},
+                        "v": {"type": "literal", "value": "2", "datatype": "http://www.w3.org/2001/XMLSchema#unsignedInt"}
+                    }
+                ]}
+            })
+        );
+    }
+}
@@ -1,5 +1,6 @@
 pub mod ast;
 pub mod connective_mapping;
 pub mod costants;
+pub mod name_templates;
 pub mod parser;
 pub mod translator;
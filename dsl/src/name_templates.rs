@@ -0,0 +1,29 @@
+use crate::costants::{REPLACE_STR_LITERAL, REPLACE_VARIABLE_NAME};
+use oxrdf::vocab::{rdf, xsd};
+use oxrdf::{Literal, NamedNode, Variable};
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+
+//Templates for looking up things by name and types by the name of the type, used by the translator
+pub fn type_name_template(predicate: &str) -> Vec<TriplePattern> {
+    let type_variable = Variable::new_unchecked("type_var");
+    let type_triple = TriplePattern {
+        subject: TermPattern::Variable(Variable::new_unchecked(REPLACE_VARIABLE_NAME)),
+        predicate: NamedNodePattern::NamedNode(NamedNode::from(rdf::TYPE)),
+        object: TermPattern::Variable(type_variable.clone()),
+    };
+    let type_name_triple = TriplePattern {
+        subject: TermPattern::Variable(type_variable),
+        predicate: NamedNodePattern::NamedNode(NamedNode::new(predicate).unwrap()),
+        object: TermPattern::Literal(Literal::new_typed_literal(REPLACE_STR_LITERAL, xsd::STRING)),
+    };
+    vec![type_triple, type_name_triple]
+}
+
+pub fn name_template(predicate: &str) -> Vec<TriplePattern> {
+    let name_triple = TriplePattern {
+        subject: TermPattern::Variable(Variable::new_unchecked(REPLACE_VARIABLE_NAME)),
+        predicate: NamedNodePattern::NamedNode(NamedNode::new_unchecked(predicate)),
+        object: TermPattern::Literal(Literal::new_typed_literal(REPLACE_STR_LITERAL, xsd::STRING)),
+    };
+    vec![name_triple]
+}
@@ -9,25 +9,80 @@ use crate::combiner::lazy_aggregate::sparql_aggregate_expression_as_lazy_column_
 use crate::combiner::lazy_expressions::lazy_expression;
 use crate::combiner::lazy_order::lazy_order_expression;
 use crate::combiner::lazy_triple::lazy_triple_pattern;
+use crate::construct::find_in_scope_variables;
+use crate::find_query_variables::find_all_used_variables_in_aggregate_expression;
+use crate::function_registry::FunctionRegistry;
 use crate::query_context::{Context, PathEntry};
 
 use crate::timeseries_query::TimeSeriesQuery;
 use log::debug;
 use oxrdf::Variable;
 use polars::frame::DataFrame;
-use polars::prelude::{col, concat, Expr, IntoLazy, LazyFrame, LiteralValue, UniqueKeepStrategy};
+use polars::prelude::{
+    col, concat, Expr, IdxSize, IntoLazy, JoinType, LazyFrame, LiteralValue, PolarsError,
+    UniqueKeepStrategy,
+};
 use spargebra::algebra::{AggregateExpression, GraphPattern};
 use spargebra::Query;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::ops::Not;
 
-pub struct Combiner {
+const SUB_SELECT_ROW_COLUMN_NAME: &str = "sub_select_row";
+
+#[derive(Debug)]
+pub enum CombinerError {
+    TimestampNotInScope(String),
+    WrongNumberOfArguments(String, usize),
+    FunctionNotRegistered(String),
+    AggregateNotRegistered(String),
+    SubSelectError(PolarsError),
+}
+
+impl Display for CombinerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CombinerError::TimestampNotInScope(aggregate) => {
+                write!(
+                    f,
+                    "Aggregation {} requires the timestamp of the aggregated values to be in scope",
+                    aggregate
+                )
+            }
+            CombinerError::WrongNumberOfArguments(function, n) => {
+                write!(
+                    f,
+                    "Function {} can not be called with {} arguments",
+                    function, n
+                )
+            }
+            CombinerError::FunctionNotRegistered(iri) => {
+                write!(f, "Custom function {} is not registered", iri)
+            }
+            CombinerError::AggregateNotRegistered(iri) => {
+                write!(f, "Custom aggregation {} is not registered", iri)
+            }
+            CombinerError::SubSelectError(err) => {
+                write!(f, "Problem combining sub-select solutions: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for CombinerError {}
+
+pub struct Combiner<'a> {
     counter: u16,
+    function_registry: &'a FunctionRegistry,
 }
 
-impl Combiner {
-    pub fn new() -> Combiner {
-        Combiner { counter: 0 }
+impl Combiner<'_> {
+    pub fn new(function_registry: &FunctionRegistry) -> Combiner {
+        Combiner {
+            counter: 0,
+            function_registry,
+        }
     }
 
     pub fn combine_static_and_time_series_results(
@@ -35,37 +90,41 @@ impl Combiner {
         query: &Query,
         static_result_df: DataFrame,
         time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
-    ) -> LazyFrame {
-        let project_variables;
-        let inner_graph_pattern;
-        let mut distinct = false;
+    ) -> Result<LazyFrame, CombinerError> {
         let mut context = Context::new();
-        if let Query::Select {
-            dataset: _,
-            pattern,
-            base_iri: _,
-        } = query
+        let mut pattern = if let Query::Select { pattern, .. } = query {
+            pattern
+        } else {
+            panic!("Only select queries are combined");
+        };
+        //The solution modifiers of the top level select wrap its projection
+        let mut slice = None;
+        if let GraphPattern::Slice {
+            inner,
+            start,
+            length,
+        } = pattern
         {
+            slice = Some((start, length));
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::SliceInner);
+        }
+        let mut distinct = false;
+        if let GraphPattern::Distinct { inner } = pattern {
+            distinct = true;
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::DistinctInner);
+        } else if let GraphPattern::Reduced { inner } = pattern {
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::ReducedInner);
+        }
+        let (project_variables, inner_graph_pattern) =
             if let GraphPattern::Project { inner, variables } = pattern {
-                project_variables = variables.clone();
-                inner_graph_pattern = inner;
                 context = context.extension_with(PathEntry::ProjectInner);
-            } else if let GraphPattern::Distinct { inner } = pattern {
-                context = context.extension_with(PathEntry::DistinctInner);
-                if let GraphPattern::Project { inner, variables } = inner.as_ref() {
-                    distinct = true;
-                    project_variables = variables.clone();
-                    inner_graph_pattern = inner;
-                    context = context.extension_with(PathEntry::ProjectInner);
-                } else {
-                    panic!("Wrong!");
-                }
+                (variables, inner)
             } else {
-                panic!("Also wrong!");
-            }
-        } else {
-            panic!("Wrong!!!");
-        }
+                panic!("Select query without top level projection");
+            };
         let mut columns = static_result_df
             .get_column_names()
             .iter()
@@ -73,7 +132,8 @@ impl Combiner {
             .collect();
 
         let mut lf = static_result_df.lazy();
-        lf = self.lazy_graph_pattern(&mut columns, lf, inner_graph_pattern, time_series, &context);
+        lf =
+            self.lazy_graph_pattern(&mut columns, lf, inner_graph_pattern, time_series, &context)?;
         let projections = project_variables
             .iter()
             .map(|c| col(c.as_str()))
@@ -82,7 +142,10 @@ impl Combiner {
         if distinct {
             lf = lf.unique_stable(None, UniqueKeepStrategy::First);
         }
-        lf
+        if let Some((start, length)) = slice {
+            lf = lazy_slice(lf, start, length);
+        }
+        Ok(lf)
     }
 
     fn lazy_graph_pattern(
@@ -92,8 +155,8 @@ impl Combiner {
         graph_pattern: &GraphPattern,
         time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
         context: &Context,
-    ) -> LazyFrame {
-        match graph_pattern {
+    ) -> Result<LazyFrame, CombinerError> {
+        let output_lf = match graph_pattern {
             GraphPattern::Bgp { patterns } => {
                 //No action, handled statically
                 let mut output_lf = input_lf;
@@ -115,14 +178,14 @@ impl Combiner {
                     left,
                     time_series,
                     &context.extension_with(PathEntry::JoinLeftSide),
-                );
+                )?;
                 let right_lf = self.lazy_graph_pattern(
                     columns,
                     left_lf,
                     right,
                     time_series,
                     &context.extension_with(PathEntry::JoinRightSide),
-                );
+                )?;
                 right_lf
             }
             GraphPattern::LeftJoin {
@@ -138,7 +201,7 @@ impl Combiner {
                         left,
                         time_series,
                         &context.extension_with(PathEntry::LeftJoinLeftSide),
-                    )
+                    )?
                     .with_column(
                         Expr::Literal(LiteralValue::Int64(1)).alias(&left_join_distinct_column),
                     )
@@ -153,12 +216,18 @@ impl Combiner {
                     right,
                     time_series,
                     &context.extension_with(PathEntry::LeftJoinRightSide),
-                );
+                )?;
 
                 if let Some(expr) = expression {
                     let expression_context = context.extension_with(PathEntry::LeftJoinExpression);
-                    right_lf =
-                        lazy_expression(expr, right_lf, columns, time_series, &expression_context);
+                    right_lf = lazy_expression(
+                        expr,
+                        right_lf,
+                        columns,
+                        time_series,
+                        self.function_registry,
+                        &expression_context,
+                    )?;
                     right_lf = right_lf
                         .filter(col(&expression_context.as_str()))
                         .drop_columns([&expression_context.as_str()]);
@@ -221,10 +290,16 @@ impl Combiner {
                     inner,
                     time_series,
                     &context.extension_with(PathEntry::FilterInner),
-                );
+                )?;
                 let expression_context = context.extension_with(PathEntry::FilterExpression);
-                inner_lf =
-                    lazy_expression(expr, inner_lf, columns, time_series, &expression_context);
+                inner_lf = lazy_expression(
+                    expr,
+                    inner_lf,
+                    columns,
+                    time_series,
+                    self.function_registry,
+                    &expression_context,
+                )?;
                 inner_lf = inner_lf
                     .filter(col(&expression_context.as_str()))
                     .drop_columns([&expression_context.as_str()]);
@@ -239,7 +314,7 @@ impl Combiner {
                     left,
                     time_series,
                     &context.extension_with(PathEntry::UnionLeftSide),
-                );
+                )?;
                 let mut right_columns = columns.clone();
                 let mut right_input_lf = input_lf;
                 for t in &original_timeseries_columns {
@@ -254,7 +329,7 @@ impl Combiner {
                     right,
                     time_series,
                     &context.extension_with(PathEntry::UnionRightSide),
-                );
+                )?;
 
                 for t in &original_timeseries_columns {
                     if !right_columns.contains(t) {
@@ -284,7 +359,7 @@ impl Combiner {
                 inner,
                 time_series,
                 &context.extension_with(PathEntry::GraphInner),
-            ),
+            )?,
             GraphPattern::Extend {
                 inner,
                 variable,
@@ -292,11 +367,17 @@ impl Combiner {
             } => {
                 let inner_context = context.extension_with(PathEntry::ExtendInner);
                 let mut inner_lf =
-                    self.lazy_graph_pattern(columns, input_lf, inner, time_series, &inner_context);
+                    self.lazy_graph_pattern(columns, input_lf, inner, time_series, &inner_context)?;
                 if !columns.contains(variable.as_str()) {
-                    inner_lf =
-                        lazy_expression(expression, inner_lf, columns, time_series, &inner_context)
-                            .rename([inner_context.as_str()], &[variable.as_str()]);
+                    inner_lf = lazy_expression(
+                        expression,
+                        inner_lf,
+                        columns,
+                        time_series,
+                        self.function_registry,
+                        &inner_context,
+                    )?
+                    .rename([inner_context.as_str()], &[variable.as_str()]);
                     columns.insert(variable.as_str().to_string());
                 }
                 inner_lf
@@ -312,7 +393,7 @@ impl Combiner {
                         left,
                         time_series,
                         &context.extension_with(PathEntry::MinusLeftSide),
-                    )
+                    )?
                     .with_column(Expr::Literal(LiteralValue::Int64(1)).alias(&minus_column))
                     .with_column(col(&minus_column).cumsum(false).keep_name())
                     .collect()
@@ -327,7 +408,7 @@ impl Combiner {
                         right,
                         time_series,
                         &context.extension_with(PathEntry::MinusRightSide),
-                    )
+                    )?
                     .select([col(&minus_column)])
                     .collect()
                     .expect("Minus right df collect problem");
@@ -357,7 +438,7 @@ impl Combiner {
                     inner,
                     time_series,
                     &context.extension_with(PathEntry::OrderByInner),
-                );
+                )?;
                 let order_expression_contexts: Vec<Context> = (0..expression.len())
                     .map(|i| context.extension_with(PathEntry::OrderByExpression(i as u16)))
                     .collect();
@@ -369,8 +450,9 @@ impl Combiner {
                         inner_lf,
                         columns,
                         time_series,
+                        self.function_registry,
                         order_expression_contexts.get(i).unwrap(),
-                    );
+                    )?;
                     inner_lf = lf;
                     inner_contexts.push(inner_context);
                     asc_ordering.push(reverse);
@@ -391,34 +473,11 @@ impl Combiner {
                 );
                 inner_lf
             }
-            GraphPattern::Project { inner, variables } => {
-                let inner_lf = self.lazy_graph_pattern(
-                    columns,
-                    input_lf,
-                    inner,
-                    time_series,
-                    &context.extension_with(PathEntry::ProjectInner),
-                );
-                let mut cols: Vec<Expr> = variables.iter().map(|c| col(c.as_str())).collect();
-                for ts_identifier_variable_name in get_timeseries_identifier_names(time_series) {
-                    cols.push(col(&ts_identifier_variable_name));
-                }
-                inner_lf.select(cols.as_slice())
-            }
-            GraphPattern::Distinct { inner } => self
-                .lazy_graph_pattern(
-                    columns,
-                    input_lf,
-                    inner,
-                    time_series,
-                    &context.extension_with(PathEntry::DistinctInner),
-                )
-                .unique_stable(None, UniqueKeepStrategy::First),
-            GraphPattern::Reduced { .. } => {
-                todo!()
-            }
-            GraphPattern::Slice { .. } => {
-                todo!()
+            GraphPattern::Project { .. }
+            | GraphPattern::Distinct { .. }
+            | GraphPattern::Reduced { .. }
+            | GraphPattern::Slice { .. } => {
+                self.lazy_sub_select(columns, input_lf, graph_pattern, time_series, context)?
             }
             GraphPattern::Group {
                 inner,
@@ -447,11 +506,173 @@ impl Combiner {
                         aggregates,
                         time_series,
                         context,
-                    )
+                    )?
+                }
+            }
+            //Services are queried with the static query
+            GraphPattern::Service { inner, .. } => self.lazy_graph_pattern(
+                columns,
+                input_lf,
+                inner,
+                time_series,
+                &context.extension_with(PathEntry::ServiceInner),
+            )?,
+        };
+        Ok(output_lf)
+    }
+
+    //Sub-selects are combined separately from the solutions they are joined with, so that
+    //the time series solutions they hide do not multiply the solutions outside.
+    //Their solution modifiers wrap the projection, and only apply to the solutions of the sub-select.
+    fn lazy_sub_select(
+        &mut self,
+        columns: &mut HashSet<String>,
+        input_lf: LazyFrame,
+        graph_pattern: &GraphPattern,
+        time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
+        context: &Context,
+    ) -> Result<LazyFrame, CombinerError> {
+        let mut pattern = graph_pattern;
+        let mut context = context.clone();
+        let mut slice = None;
+        if let GraphPattern::Slice {
+            inner,
+            start,
+            length,
+        } = pattern
+        {
+            slice = Some((start, length));
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::SliceInner);
+        }
+        let mut distinct = false;
+        if let GraphPattern::Distinct { inner } = pattern {
+            distinct = true;
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::DistinctInner);
+        } else if let GraphPattern::Reduced { inner } = pattern {
+            //Eliminating duplicates is optional for reduced patterns
+            pattern = inner.as_ref();
+            context = context.extension_with(PathEntry::ReducedInner);
+        }
+        let (inner, variables) = if let GraphPattern::Project { inner, variables } = pattern {
+            (inner.as_ref(), variables)
+        } else {
+            let mut lf =
+                self.lazy_graph_pattern(columns, input_lf, pattern, time_series, &context)?;
+            if distinct {
+                lf = lf.unique_stable(None, UniqueKeepStrategy::First);
+            }
+            if let Some((start, length)) = slice {
+                lf = lazy_slice(lf, start, length);
+            }
+            return Ok(lf);
+        };
+        let inner_context = context.extension_with(PathEntry::ProjectInner);
+        let mut in_scope = HashSet::new();
+        find_in_scope_variables(inner, &mut in_scope);
+        let projected: HashSet<String> = variables
+            .iter()
+            .filter(|v| in_scope.contains(*v))
+            .map(|v| v.as_str().to_string())
+            .collect();
+        let helper_names = get_timeseries_helper_names(time_series, &inner_context);
+
+        //Each static solution pairs an outer solution with an inner solution. When the inner solutions
+        //are computed row by row, they are joined back to the static solutions they come from, which
+        //keeps duplicate solutions. Otherwise the inner solutions are computed from the distinct inner
+        //static solutions, as an outer solution may be paired with several of them.
+        let row_wise = !distinct && slice.is_none() && is_row_wise(inner);
+        let mut input_df = input_lf.collect().map_err(CombinerError::SubSelectError)?;
+        if row_wise {
+            input_df = input_df
+                .with_row_count(SUB_SELECT_ROW_COLUMN_NAME, None)
+                .map_err(CombinerError::SubSelectError)?;
+        }
+        let mut inner_select = vec![];
+        let mut outer_select = vec![];
+        let mut join_on = vec![];
+        let mut static_projected = HashSet::new();
+        for c in input_df.get_column_names() {
+            if c == SUB_SELECT_ROW_COLUMN_NAME {
+                inner_select.push(col(c));
+                outer_select.push(col(c));
+                join_on.push(col(c));
+            } else if helper_names.contains(c) {
+                inner_select.push(col(c));
+            } else {
+                outer_select.push(col(c));
+                //The static rewrite only keeps projected variables of the sub-select
+                if projected.contains(c) {
+                    inner_select.push(col(c));
+                    static_projected.insert(c.to_string());
+                    if !row_wise {
+                        join_on.push(col(c));
+                    }
                 }
             }
-            GraphPattern::Service { .. } => {
-                todo!()
+        }
+        let mut inner_columns: HashSet<String> = columns
+            .iter()
+            .filter(|c| helper_names.contains(*c) || projected.contains(*c))
+            .cloned()
+            .collect();
+        let mut inner_lf = input_df.clone().lazy().select(inner_select.as_slice());
+        if !row_wise {
+            inner_lf = inner_lf.unique_stable(None, UniqueKeepStrategy::First);
+        }
+        inner_lf = self.lazy_graph_pattern(
+            &mut inner_columns,
+            inner_lf,
+            inner,
+            time_series,
+            &inner_context,
+        )?;
+        //The projected static variables of row by row solutions are already in the outer solutions
+        let mut projections: Vec<Expr> = variables
+            .iter()
+            .filter(|v| {
+                inner_columns.contains(v.as_str())
+                    && !(row_wise && static_projected.contains(v.as_str()))
+            })
+            .map(|v| col(v.as_str()))
+            .collect();
+        if row_wise {
+            projections.push(col(SUB_SELECT_ROW_COLUMN_NAME));
+        }
+        inner_lf = inner_lf.select(projections.as_slice());
+        if distinct {
+            inner_lf = inner_lf.unique_stable(None, UniqueKeepStrategy::First);
+        }
+        if let Some((start, length)) = slice {
+            inner_lf = lazy_slice(inner_lf, start, length);
+        }
+
+        let mut outer_lf = input_df.lazy().select(outer_select.as_slice());
+        if !row_wise {
+            outer_lf = outer_lf.unique_stable(None, UniqueKeepStrategy::First);
+        }
+        for h in &helper_names {
+            columns.remove(h);
+        }
+        for v in variables {
+            if inner_columns.contains(v.as_str()) {
+                columns.insert(v.as_str().to_string());
+            }
+        }
+        if join_on.is_empty() {
+            Ok(outer_lf.cross_join(inner_lf))
+        } else {
+            let lf = outer_lf.join(
+                inner_lf,
+                join_on.as_slice(),
+                join_on.as_slice(),
+                JoinType::Inner,
+            );
+            if row_wise {
+                Ok(lf.drop_columns([SUB_SELECT_ROW_COLUMN_NAME]))
+            } else {
+                Ok(lf)
             }
         }
     }
@@ -465,14 +686,16 @@ impl Combiner {
         aggregates: &Vec<(Variable, AggregateExpression)>,
         time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
         context: &Context,
-    ) -> LazyFrame {
+    ) -> Result<LazyFrame, CombinerError> {
+        //Time series are consumed when the inner pattern is combined, so we look up timestamps first
+        let value_timestamp_names = get_value_timestamp_names(time_series);
         let mut lazy_inner = self.lazy_graph_pattern(
             columns,
             input_lf,
             inner,
             time_series,
             &context.extension_with(PathEntry::GroupInner),
-        );
+        )?;
         let by: Vec<Expr> = variables.iter().map(|v| col(v.as_str())).collect();
 
         let time_series_identifier_names = get_timeseries_identifier_names(time_series);
@@ -488,6 +711,12 @@ impl Combiner {
         for i in 0..aggregates.len() {
             let aggregate_context = context.extension_with(PathEntry::GroupAggregation(i as u16));
             let (v, a) = aggregates.get(i).unwrap();
+            let mut used_vars = HashSet::new();
+            find_all_used_variables_in_aggregate_expression(a, &mut used_vars);
+            let timestamp_column = used_vars
+                .iter()
+                .find_map(|u| value_timestamp_names.get(u.as_str()))
+                .map(|x| x.as_str());
             let (lf, expr, used_context) =
                 sparql_aggregate_expression_as_lazy_column_and_expression(
                     v,
@@ -496,8 +725,10 @@ impl Combiner {
                     columns,
                     lazy_inner,
                     time_series,
+                    self.function_registry,
+                    timestamp_column,
                     &aggregate_context,
-                );
+                )?;
             lazy_inner = lf;
             aggregate_expressions.push(expr);
             if let Some(aggregate_inner_context) = used_context {
@@ -522,7 +753,7 @@ impl Combiner {
         for (v, _) in aggregates {
             columns.insert(v.as_str().to_string());
         }
-        aggregated_lf
+        Ok(aggregated_lf)
     }
 }
 
@@ -538,3 +769,72 @@ fn get_timeseries_identifier_names(
         coll
     })
 }
+
+//Identifier and grouping columns of the time series with variables inside the context
+fn get_timeseries_helper_names(
+    time_series: &Vec<(TimeSeriesQuery, DataFrame)>,
+    context: &Context,
+) -> HashSet<String> {
+    let mut helper_names = HashSet::new();
+    for (tsq, _) in time_series {
+        let mut variables = tsq.get_timestamp_variables();
+        variables.extend(tsq.get_value_variables());
+        variables.extend(tsq.get_quality_variables());
+        if variables
+            .iter()
+            .any(|v| v.context.path.starts_with(&context.path))
+        {
+            for id in tsq.get_identifier_variables() {
+                helper_names.insert(id.as_str().to_string());
+            }
+            if let Some(groupby_col) = tsq.get_groupby_column() {
+                helper_names.insert(groupby_col.clone());
+            }
+        }
+    }
+    helper_names
+}
+
+//Whether the solutions of a pattern are computed from each static solution separately,
+//so that the static solution of each of them is known
+fn is_row_wise(graph_pattern: &GraphPattern) -> bool {
+    match graph_pattern {
+        GraphPattern::Group { .. }
+        | GraphPattern::Distinct { .. }
+        | GraphPattern::Reduced { .. }
+        | GraphPattern::Slice { .. } => false,
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => is_row_wise(left) && is_row_wise(right),
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::Graph { inner, .. }
+        | GraphPattern::Extend { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::Service { inner, .. } => is_row_wise(inner),
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } | GraphPattern::Values { .. } => true,
+    }
+}
+
+fn lazy_slice(lf: LazyFrame, start: &usize, length: &Option<usize>) -> LazyFrame {
+    let length = length.map(|l| l as IdxSize).unwrap_or(IdxSize::MAX);
+    lf.slice(*start as i64, length)
+}
+
+fn get_value_timestamp_names(
+    time_series: &Vec<(TimeSeriesQuery, DataFrame)>,
+) -> HashMap<String, String> {
+    let mut value_timestamp_names = HashMap::new();
+    for (tsq, _) in time_series {
+        if let Some(timestamp_variable) = tsq.get_timestamp_variables().get(0) {
+            for value_variable in tsq.get_value_variables() {
+                value_timestamp_names.insert(
+                    value_variable.variable.as_str().to_string(),
+                    timestamp_variable.variable.as_str().to_string(),
+                );
+            }
+        }
+    }
+    value_timestamp_names
+}
@@ -1,9 +1,16 @@
 use crate::combiner::lazy_expressions::lazy_expression;
-use crate::constants::NEST;
+use crate::combiner::CombinerError;
+use crate::constants::{
+    DELTA, DURATION_BAD, DURATION_GOOD, FIRST, INTERPOLATIVE, LAST, NEST, PERCENT_BAD,
+    PERCENT_GOOD, RANGE, STANDARD_DEVIATION, TIME_WEIGHTED_AVERAGE,
+};
+use crate::function_registry::FunctionRegistry;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_query::TimeSeriesQuery;
 use oxrdf::Variable;
-use polars::prelude::{col, DataFrame, DataType, Expr, GetOutput, IntoSeries, LazyFrame};
+use polars::prelude::{
+    col, lit, DataFrame, DataType, Expr, GetOutput, IntoSeries, LazyFrame, TimeUnit,
+};
 use spargebra::algebra::AggregateExpression;
 use std::collections::HashSet;
 
@@ -14,8 +21,10 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
     columns: &HashSet<String>,
     lf: LazyFrame,
     time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
+    function_registry: &FunctionRegistry,
+    timestamp_column: Option<&str>,
     context: &Context,
-) -> (LazyFrame, Expr, Option<Context>) {
+) -> Result<(LazyFrame, Expr, Option<Context>), CombinerError> {
     let out_lf;
     let mut out_expr;
     let column_context;
@@ -28,8 +37,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                     lf,
                     columns,
                     time_series,
+                    function_registry,
                     column_context.as_ref().unwrap(),
-                );
+                )?;
                 if *distinct {
                     out_expr = col(column_context.as_ref().unwrap().as_str()).n_unique();
                 } else {
@@ -55,8 +65,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             if *distinct {
                 out_expr = col(column_context.as_ref().unwrap().as_str())
@@ -73,8 +84,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             if *distinct {
                 out_expr = col(column_context.as_ref().unwrap().as_str())
@@ -92,8 +104,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             out_expr = col(column_context.as_ref().unwrap().as_str()).min();
         }
@@ -105,8 +118,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             out_expr = col(column_context.as_ref().unwrap().as_str()).max();
         }
@@ -122,8 +136,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             let use_sep = if let Some(sep) = separator {
                 sep.to_string()
@@ -163,8 +178,9 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                 lf,
                 columns,
                 time_series,
+                function_registry,
                 column_context.as_ref().unwrap(),
-            );
+            )?;
 
             out_expr = col(column_context.as_ref().unwrap().as_str()).first();
         }
@@ -182,14 +198,104 @@ pub fn sparql_aggregate_expression_as_lazy_column_and_expression(
                     lf,
                     columns,
                     time_series,
+                    function_registry,
                     column_context.as_ref().unwrap(),
-                );
+                )?;
                 out_expr = col(column_context.as_ref().unwrap().as_str()).list();
+            } else if iri == RANGE || iri == STANDARD_DEVIATION {
+                column_context = Some(context.extension_with(PathEntry::AggregationOperation));
+
+                out_lf = lazy_expression(
+                    expr,
+                    lf,
+                    columns,
+                    time_series,
+                    function_registry,
+                    column_context.as_ref().unwrap(),
+                )?;
+                let aggregated_column = col(column_context.as_ref().unwrap().as_str());
+                out_expr = if iri == RANGE {
+                    aggregated_column.clone().max() - aggregated_column.min()
+                } else {
+                    aggregated_column.cast(DataType::Float64).std()
+                };
+            } else if iri == FIRST
+                || iri == LAST
+                || iri == TIME_WEIGHTED_AVERAGE
+                || iri == INTERPOLATIVE
+                || iri == DELTA
+                || iri == DURATION_GOOD
+                || iri == DURATION_BAD
+                || iri == PERCENT_GOOD
+                || iri == PERCENT_BAD
+            {
+                column_context = Some(context.extension_with(PathEntry::AggregationOperation));
+
+                out_lf = lazy_expression(
+                    expr,
+                    lf,
+                    columns,
+                    time_series,
+                    function_registry,
+                    column_context.as_ref().unwrap(),
+                )?;
+                let timestamp_column = if let Some(timestamp_column) = timestamp_column {
+                    timestamp_column
+                } else {
+                    return Err(CombinerError::TimestampNotInScope(iri.to_string()));
+                };
+                let sorted = col(column_context.as_ref().unwrap().as_str())
+                    .sort_by(vec![col(timestamp_column)], vec![false]);
+                //Each value is weighted by the time until the next value (stepped interpolation)
+                let sorted_timestamps = col(timestamp_column)
+                    .sort(false)
+                    .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                    .cast(DataType::Int64)
+                    .cast(DataType::Float64);
+                let durations = sorted_timestamps.clone().shift(-1) - sorted_timestamps;
+                out_expr = if iri == FIRST || iri == INTERPOLATIVE {
+                    //Without the interval bounds we can not interpolate, so we use the first value
+                    sorted.first()
+                } else if iri == LAST {
+                    sorted.last()
+                } else if iri == DELTA {
+                    sorted.clone().last() - sorted.first()
+                } else if iri == TIME_WEIGHTED_AVERAGE {
+                    (sorted.cast(DataType::Float64) * durations.clone()).sum() / durations.sum()
+                } else {
+                    //The aggregated values are OPC UA StatusCodes, where the top two bits give the severity
+                    let severity = sorted.cast(DataType::Int64) / lit(1i64 << 30);
+                    let selected = if iri == DURATION_GOOD || iri == PERCENT_GOOD {
+                        severity.eq(lit(0i64))
+                    } else {
+                        severity.eq(lit(2i64))
+                    };
+                    let selected_duration =
+                        (selected.cast(DataType::Float64) * durations.clone()).sum();
+                    if iri == DURATION_GOOD || iri == DURATION_BAD {
+                        selected_duration
+                    } else {
+                        selected_duration / durations.sum() * lit(100.0)
+                    }
+                };
+            } else if let Some(custom_aggregate) = function_registry.get_aggregate(iri) {
+                column_context = Some(context.extension_with(PathEntry::AggregationOperation));
+
+                out_lf = lazy_expression(
+                    expr,
+                    lf,
+                    columns,
+                    time_series,
+                    function_registry,
+                    column_context.as_ref().unwrap(),
+                )?;
+                let aggregated_column = col(column_context.as_ref().unwrap().as_str());
+                out_expr = (custom_aggregate.polars_aggregate)(aggregated_column);
             } else {
-                panic!("Custom aggregation not supported")
+                return Err(CombinerError::AggregateNotRegistered(iri.to_string()));
             }
         }
     }
     out_expr = out_expr.alias(variable.as_str());
-    (out_lf, out_expr, column_context)
+    Ok((out_lf, out_expr, column_context))
 }
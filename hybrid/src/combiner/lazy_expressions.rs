@@ -1,10 +1,16 @@
 mod exists_helper;
+mod sparql_functions;
 
 use crate::combiner::lazy_expressions::exists_helper::rewrite_exists_graph_pattern;
-use crate::combiner::Combiner;
+use crate::combiner::lazy_expressions::sparql_functions::{
+    same_term_expression, sparql_function_expression,
+};
+use crate::combiner::{Combiner, CombinerError};
 use crate::constants::{
-    DATETIME_AS_NANOS, DATETIME_AS_SECONDS, NANOS_AS_DATETIME, SECONDS_AS_DATETIME,
+    DATETIME_AS_NANOS, DATETIME_AS_SECONDS, FILL_FORWARD, INTERPOLATE_LINEAR, NANOS_AS_DATETIME,
+    RESAMPLE, SECONDS_AS_DATETIME,
 };
+use crate::function_registry::FunctionRegistry;
 use crate::query_context::{Context, PathEntry};
 use crate::sparql_result_to_polars::{
     sparql_literal_to_polars_literal_value, sparql_named_node_to_polars_literal_value,
@@ -14,7 +20,7 @@ use oxrdf::vocab::xsd;
 use polars::datatypes::DataType;
 use polars::frame::DataFrame;
 use polars::prelude::{
-    col, concat_str, lit, Expr, IntoLazy, LazyFrame, LiteralValue, Operator, Series, TimeUnit,
+    col, lit, Expr, IntoLazy, LazyFrame, LiteralValue, Operator, Series, TimeUnit,
     UniqueKeepStrategy,
 };
 use spargebra::algebra::{Expression, Function};
@@ -26,8 +32,9 @@ pub fn lazy_expression(
     inner_lf: LazyFrame,
     columns: &HashSet<String>,
     time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
+    function_registry: &FunctionRegistry,
     context: &Context,
-) -> LazyFrame {
+) -> Result<LazyFrame, CombinerError> {
     let lf = match expr {
         Expression::NamedNode(nn) => {
             let inner_lf = inner_lf.with_column(
@@ -48,9 +55,23 @@ pub fn lazy_expression(
         }
         Expression::Or(left, right) => {
             let left_context = context.extension_with(PathEntry::OrLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::OrRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -65,9 +86,23 @@ pub fn lazy_expression(
         }
         Expression::And(left, right) => {
             let left_context = context.extension_with(PathEntry::AndLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::AndRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -82,9 +117,23 @@ pub fn lazy_expression(
         }
         Expression::Equal(left, right) => {
             let left_context = context.extension_with(PathEntry::EqualLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::EqualRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -97,14 +146,52 @@ pub fn lazy_expression(
                 .drop_columns([left_context.as_str(), right_context.as_str()]);
             inner_lf
         }
-        Expression::SameTerm(_, _) => {
-            todo!("Not implemented")
+        Expression::SameTerm(left, right) => {
+            let left_context = context.extension_with(PathEntry::SameTermLeft);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
+            let right_context = context.extension_with(PathEntry::SameTermRight);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
+            inner_lf = inner_lf
+                .with_column(
+                    same_term_expression(col(left_context.as_str()), col(right_context.as_str()))
+                        .alias(context.as_str()),
+                )
+                .drop_columns([left_context.as_str(), right_context.as_str()]);
+            inner_lf
         }
         Expression::Greater(left, right) => {
             let left_context = context.extension_with(PathEntry::GreaterLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::GreaterRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -119,9 +206,23 @@ pub fn lazy_expression(
         }
         Expression::GreaterOrEqual(left, right) => {
             let left_context = context.extension_with(PathEntry::GreaterOrEqualLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::GreaterOrEqualRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
 
             inner_lf = inner_lf
                 .with_column(
@@ -137,9 +238,23 @@ pub fn lazy_expression(
         }
         Expression::Less(left, right) => {
             let left_context = context.extension_with(PathEntry::LessLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::LessRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -154,9 +269,23 @@ pub fn lazy_expression(
         }
         Expression::LessOrEqual(left, right) => {
             let left_context = context.extension_with(PathEntry::LessOrEqualLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::LessOrEqualRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
 
             inner_lf = inner_lf
                 .with_column(
@@ -175,7 +304,14 @@ pub fn lazy_expression(
             let right_contexts: Vec<Context> = (0..right.len())
                 .map(|i| context.extension_with(PathEntry::InRight(i as u16)))
                 .collect();
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             for i in 0..right.len() {
                 let expr = right.get(i).unwrap();
                 inner_lf = lazy_expression(
@@ -183,8 +319,9 @@ pub fn lazy_expression(
                     inner_lf,
                     columns,
                     time_series,
+                    function_registry,
                     right_contexts.get(i).unwrap(),
-                );
+                )?;
             }
             let mut expr = Expr::Literal(LiteralValue::Boolean(false));
 
@@ -212,9 +349,23 @@ pub fn lazy_expression(
         }
         Expression::Add(left, right) => {
             let left_context = context.extension_with(PathEntry::AddLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::AddRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -229,9 +380,23 @@ pub fn lazy_expression(
         }
         Expression::Subtract(left, right) => {
             let left_context = context.extension_with(PathEntry::SubtractLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::SubtractRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -251,10 +416,18 @@ pub fn lazy_expression(
                 inner_lf,
                 columns,
                 time_series,
+                function_registry,
                 &context.extension_with(PathEntry::MultiplyLeft),
-            );
+            )?;
             let right_context = context.extension_with(PathEntry::MultiplyRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
 
             inner_lf = inner_lf
                 .with_column(
@@ -270,9 +443,23 @@ pub fn lazy_expression(
         }
         Expression::Divide(left, right) => {
             let left_context = context.extension_with(PathEntry::DivideLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let right_context = context.extension_with(PathEntry::DivideRight);
-            inner_lf = lazy_expression(right, inner_lf, columns, time_series, &right_context);
+            inner_lf = lazy_expression(
+                right,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &right_context,
+            )?;
 
             inner_lf = inner_lf
                 .with_column(
@@ -288,8 +475,14 @@ pub fn lazy_expression(
         }
         Expression::UnaryPlus(inner) => {
             let plus_context = context.extension_with(PathEntry::UnaryPlus);
-            let mut inner_lf =
-                lazy_expression(inner, inner_lf, columns, time_series, &plus_context);
+            let mut inner_lf = lazy_expression(
+                inner,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &plus_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -304,8 +497,14 @@ pub fn lazy_expression(
         }
         Expression::UnaryMinus(inner) => {
             let minus_context = context.extension_with(PathEntry::UnaryMinus);
-            let mut inner_lf =
-                lazy_expression(inner, inner_lf, columns, time_series, &minus_context);
+            let mut inner_lf = lazy_expression(
+                inner,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &minus_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(
                     (Expr::BinaryExpr {
@@ -320,7 +519,14 @@ pub fn lazy_expression(
         }
         Expression::Not(inner) => {
             let not_context = context.extension_with(PathEntry::Not);
-            let mut inner_lf = lazy_expression(inner, inner_lf, columns, time_series, &not_context);
+            let mut inner_lf = lazy_expression(
+                inner,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &not_context,
+            )?;
             inner_lf = inner_lf
                 .with_column(col(&not_context.as_str()).not().alias(context.as_str()))
                 .drop_columns([&not_context.as_str()]);
@@ -334,7 +540,7 @@ pub fn lazy_expression(
                 .with_column(col(&exists_context.as_str()).cumsum(false).keep_name())
                 .collect()
                 .expect("Collect lazy error");
-            let mut combiner = Combiner::new();
+            let mut combiner = Combiner::new(function_registry);
             let new_inner = rewrite_exists_graph_pattern(inner, &exists_context.as_str());
             let exists_lf = combiner.lazy_graph_pattern(
                 &mut columns.clone(),
@@ -342,7 +548,7 @@ pub fn lazy_expression(
                 &new_inner,
                 time_series,
                 &exists_context,
-            );
+            )?;
             let exists_df = exists_lf
                 .select([col(&exists_context.as_str())])
                 .unique(None, UniqueKeepStrategy::First)
@@ -360,21 +566,36 @@ pub fn lazy_expression(
             df.lazy()
         }
         Expression::Bound(v) => {
-            inner_lf.with_column(col(v.as_str()).is_null().alias(context.as_str()))
+            inner_lf.with_column(col(v.as_str()).is_not_null().alias(context.as_str()))
         }
         Expression::If(left, middle, right) => {
             let left_context = context.extension_with(PathEntry::IfLeft);
-            let mut inner_lf = lazy_expression(left, inner_lf, columns, time_series, &left_context);
+            let mut inner_lf = lazy_expression(
+                left,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &left_context,
+            )?;
             let middle_context = context.extension_with(PathEntry::IfMiddle);
-            inner_lf = lazy_expression(middle, inner_lf, columns, time_series, &middle_context);
+            inner_lf = lazy_expression(
+                middle,
+                inner_lf,
+                columns,
+                time_series,
+                function_registry,
+                &middle_context,
+            )?;
             let right_context = context.extension_with(PathEntry::IfRight);
             inner_lf = lazy_expression(
                 right,
                 inner_lf,
                 columns,
                 time_series,
+                function_registry,
                 &context.extension_with(PathEntry::IfRight),
-            );
+            )?;
 
             inner_lf = inner_lf
                 .with_column(
@@ -403,8 +624,9 @@ pub fn lazy_expression(
                     inner_lf,
                     columns,
                     time_series,
+                    function_registry,
                     inner_contexts.get(i).unwrap(),
-                );
+                )?;
             }
 
             let coalesced_context = inner_contexts.get(0).unwrap();
@@ -437,15 +659,16 @@ pub fn lazy_expression(
                     inner_lf,
                     columns,
                     time_series,
+                    function_registry,
                     args_contexts.get(i).unwrap(),
-                )
+                )?
                 .collect()
                 .unwrap()
                 .lazy(); //TODO: workaround for stack overflow - post bug?
             }
             match func {
                 Function::Year => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -455,7 +678,7 @@ pub fn lazy_expression(
                     );
                 }
                 Function::Month => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -465,7 +688,7 @@ pub fn lazy_expression(
                     );
                 }
                 Function::Day => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -475,7 +698,7 @@ pub fn lazy_expression(
                     );
                 }
                 Function::Hours => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -485,7 +708,7 @@ pub fn lazy_expression(
                     );
                 }
                 Function::Minutes => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -495,7 +718,7 @@ pub fn lazy_expression(
                     );
                 }
                 Function::Seconds => {
-                    assert_eq!(args.len(), 1);
+                    check_number_of_arguments(func, args, &[1])?;
                     let first_context = args_contexts.get(0).unwrap();
                     inner_lf = inner_lf.with_column(
                         col(&first_context.as_str())
@@ -504,50 +727,10 @@ pub fn lazy_expression(
                             .alias(context.as_str()),
                     );
                 }
-                Function::Abs => {
-                    assert_eq!(args.len(), 1);
-                    let first_context = args_contexts.get(0).unwrap();
-                    inner_lf = inner_lf
-                        .with_column(col(&first_context.as_str()).abs().alias(context.as_str()));
-                }
-                Function::Ceil => {
-                    assert_eq!(args.len(), 1);
-                    let first_context = args_contexts.get(0).unwrap();
-                    inner_lf = inner_lf
-                        .with_column(col(&first_context.as_str()).ceil().alias(context.as_str()));
-                }
-                Function::Floor => {
-                    assert_eq!(args.len(), 1);
-                    let first_context = args_contexts.get(0).unwrap();
-                    inner_lf = inner_lf
-                        .with_column(col(&first_context.as_str()).floor().alias(context.as_str()));
-                }
-                Function::Concat => {
-                    assert!(args.len() > 1);
-                    inner_lf = inner_lf.with_column(
-                        concat_str(
-                            args_contexts
-                                .iter()
-                                .map(|c| col(c.as_str()))
-                                .collect::<Vec<Expr>>(),
-                            "",
-                        )
-                        .alias(context.as_str()),
-                    );
-                }
-                Function::Round => {
-                    assert_eq!(args.len(), 1);
-                    let first_context = args_contexts.get(0).unwrap();
-                    inner_lf = inner_lf.with_column(
-                        col(&first_context.as_str())
-                            .round(0)
-                            .alias(context.as_str()),
-                    );
-                }
                 Function::Custom(nn) => {
                     let iri = nn.as_str();
                     if iri == xsd::INTEGER.as_str() {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -555,7 +738,7 @@ pub fn lazy_expression(
                                 .alias(context.as_str()),
                         );
                     } else if iri == xsd::STRING.as_str() {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -563,7 +746,7 @@ pub fn lazy_expression(
                                 .alias(context.as_str()),
                         );
                     } else if iri == DATETIME_AS_NANOS {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -572,7 +755,7 @@ pub fn lazy_expression(
                                 .alias(context.as_str()),
                         );
                     } else if iri == DATETIME_AS_SECONDS {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -582,7 +765,7 @@ pub fn lazy_expression(
                                 .alias(context.as_str()),
                         );
                     } else if iri == NANOS_AS_DATETIME {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -590,7 +773,7 @@ pub fn lazy_expression(
                                 .alias(context.as_str()),
                         );
                     } else if iri == SECONDS_AS_DATETIME {
-                        assert_eq!(args.len(), 1);
+                        check_number_of_arguments(func, args, &[1])?;
                         let first_context = args_contexts.get(0).unwrap();
                         inner_lf = inner_lf.with_column(
                             col(&first_context.as_str())
@@ -598,12 +781,78 @@ pub fn lazy_expression(
                                 .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
                                 .alias(context.as_str()),
                         );
+                    } else if iri == RESAMPLE {
+                        //Floors the timestamp to the start of the interval (given in seconds) it falls into.
+                        //Only existing timestamps are bucketed, intervals without data are not generated.
+                        check_number_of_arguments(func, args, &[2])?;
+                        let first_context = args_contexts.get(0).unwrap();
+                        let second_context = args_contexts.get(1).unwrap();
+                        let interval_millis = col(&second_context.as_str())
+                            .cast(DataType::Float64)
+                            .mul(lit(1000.0));
+                        inner_lf = inner_lf.with_column(
+                            col(&first_context.as_str())
+                                .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                                .cast(DataType::Int64)
+                                .cast(DataType::Float64)
+                                .div(interval_millis.clone())
+                                .floor()
+                                .mul(interval_millis)
+                                .cast(DataType::Int64)
+                                .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                                .alias(context.as_str()),
+                        );
+                    } else if iri == FILL_FORWARD || iri == INTERPOLATE_LINEAR {
+                        //Gaps are filled in the order of the timestamps given by the second argument,
+                        //optionally within partitions given by the third argument
+                        check_number_of_arguments(func, args, &[2, 3])?;
+                        let first_context = args_contexts.get(0).unwrap();
+                        let timestamp_context = args_contexts.get(1).unwrap();
+                        let mut sort_by = vec![];
+                        if let Some(partition_context) = args_contexts.get(2) {
+                            sort_by.push(col(&partition_context.as_str()));
+                        }
+                        sort_by.push(col(&timestamp_context.as_str()));
+                        let reverse = vec![false; sort_by.len()];
+                        inner_lf = inner_lf.sort_by_exprs(sort_by, reverse, false);
+                        let mut filled = if iri == FILL_FORWARD {
+                            col(&first_context.as_str()).forward_fill()
+                        } else {
+                            col(&first_context.as_str())
+                                .cast(DataType::Float64)
+                                .interpolate()
+                        };
+                        if let Some(partition_context) = args_contexts.get(2) {
+                            filled = filled.over(vec![col(&partition_context.as_str())]);
+                        }
+                        inner_lf = inner_lf.with_column(filled.alias(context.as_str()));
+                    } else if let Some(custom_function) = function_registry.get_function(iri) {
+                        let args_exprs = args_contexts
+                            .iter()
+                            .map(|c| col(c.as_str()))
+                            .collect::<Vec<Expr>>();
+                        inner_lf = inner_lf.with_column(
+                            (custom_function.polars_function)(args_exprs).alias(context.as_str()),
+                        );
                     } else {
-                        todo!("{:?}", nn)
+                        return Err(CombinerError::FunctionNotRegistered(iri.to_string()));
                     }
                 }
                 _ => {
-                    todo!()
+                    let args_exprs = args_contexts
+                        .iter()
+                        .map(|c| col(c.as_str()))
+                        .collect::<Vec<Expr>>();
+                    //Functions without arguments need a column giving the number of rows
+                    let row_reference = if let Some(c) = columns.iter().next() {
+                        col(c)
+                    } else {
+                        lit(0)
+                    };
+                    inner_lf = inner_lf.with_column(
+                        sparql_function_expression(func, args, args_exprs, row_reference)
+                            .alias(context.as_str()),
+                    );
                 }
             }
             inner_lf.drop_columns(
@@ -614,5 +863,20 @@ pub fn lazy_expression(
             )
         }
     };
-    lf
+    Ok(lf)
+}
+
+fn check_number_of_arguments(
+    func: &Function,
+    args: &Vec<Expression>,
+    allowed: &[usize],
+) -> Result<(), CombinerError> {
+    if allowed.contains(&args.len()) {
+        Ok(())
+    } else {
+        Err(CombinerError::WrongNumberOfArguments(
+            func.to_string(),
+            args.len(),
+        ))
+    }
 }
@@ -0,0 +1,583 @@
+//Implementations of the SPARQL 1.1 function library on polars expressions.
+//Following SPARQL error semantics, unbound arguments and type errors produce nulls instead of panics.
+//IRIs and plain strings are both represented as Utf8, and language tags and time zones are not kept,
+//so functions that depend on these distinctions are approximated.
+use md5::Md5;
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNode;
+use polars::export::chrono::{NaiveDateTime, Utc};
+use polars::prelude::{
+    lit, map_multiple, DataType, Expr, GetOutput, LiteralValue, NamedFrom, PolarsError, Series,
+    TimeUnit,
+};
+use rand::Rng;
+use regex::{Regex, RegexBuilder};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use spargebra::algebra::{Expression, Function};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub(crate) fn sparql_function_expression(
+    func: &Function,
+    args: &Vec<Expression>,
+    args_exprs: Vec<Expr>,
+    row_reference: Expr,
+) -> Expr {
+    match func {
+        Function::Str => map_series(args_exprs, DataType::Utf8, |s| {
+            Ok(Series::new("", string_representations(&s[0])?))
+        }),
+        Function::Lang => map_series(args_exprs, DataType::Utf8, |s| {
+            Ok(Series::new("", map_non_null(&s[0], |_| "".to_string())))
+        }),
+        Function::LangMatches => row_wise_strings(args_exprs, DataType::Boolean, |a| {
+            let tag = a[0].to_lowercase();
+            let range = a[1].to_lowercase();
+            if range == "*" {
+                Some(!tag.is_empty())
+            } else {
+                Some(tag == range || tag.starts_with(&(range + "-")))
+            }
+        }),
+        Function::Datatype => map_series(args_exprs, DataType::Utf8, |s| {
+            let datatype = datatype_iri(s[0].dtype());
+            Ok(Series::new(
+                "",
+                map_non_null(&s[0], |_| datatype.clone())
+                    .into_iter()
+                    .map(|x| x.flatten())
+                    .collect::<Vec<Option<String>>>(),
+            ))
+        }),
+        Function::Iri => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            NamedNode::new(a[0]).ok().map(|nn| nn.as_str().to_string())
+        }),
+        Function::BNode => {
+            if args_exprs.is_empty() {
+                map_series(vec![row_reference], DataType::Utf8, |s| {
+                    Ok(Series::new(
+                        "",
+                        (0..s[0].len())
+                            .map(|_| Some(format!("_:{}", Uuid::new_v4().simple())))
+                            .collect::<Vec<Option<String>>>(),
+                    ))
+                })
+            } else {
+                //The same argument gives the same blank node within a solution sequence
+                map_series(args_exprs, DataType::Utf8, |s| {
+                    let mut labels: HashMap<String, String> = HashMap::new();
+                    Ok(Series::new(
+                        "",
+                        strict_strings(&s[0])
+                            .into_iter()
+                            .map(|x| {
+                                x.map(|y| {
+                                    labels
+                                        .entry(y)
+                                        .or_insert_with(|| format!("_:{}", Uuid::new_v4().simple()))
+                                        .clone()
+                                })
+                            })
+                            .collect::<Vec<Option<String>>>(),
+                    ))
+                })
+            }
+        }
+        Function::Rand => map_series(vec![row_reference], DataType::Float64, |s| {
+            let mut rng = rand::thread_rng();
+            Ok(Series::new(
+                "",
+                (0..s[0].len())
+                    .map(|_| Some(rng.gen::<f64>()))
+                    .collect::<Vec<Option<f64>>>(),
+            ))
+        }),
+        Function::Abs => numeric_function(args_exprs, |s| s.abs()),
+        Function::Ceil => numeric_function(args_exprs, |s| Ok(s.ceil()?)),
+        Function::Floor => numeric_function(args_exprs, |s| Ok(s.floor()?)),
+        //Rounds halves towards positive infinity, as required by the specification
+        Function::Round => numeric_function(args_exprs, |s| Ok((&s + 0.5).floor()?)),
+        Function::Concat => {
+            if args_exprs.is_empty() {
+                lit("")
+            } else {
+                row_wise_strings(args_exprs, DataType::Utf8, |a| Some(a.concat()))
+            }
+        }
+        Function::SubStr => map_series(args_exprs, DataType::Utf8, |s| {
+            let strings = strict_strings(&s[0]);
+            let starts = numeric_values(&s[1]);
+            let lengths = if let Some(l) = s.get(2) {
+                numeric_values(l)
+            } else {
+                vec![Some(f64::INFINITY); strings.len()]
+            };
+            let mut out = vec![];
+            for i in 0..strings.len() {
+                out.push(match (&strings[i], starts[i], lengths[i]) {
+                    (Some(string), Some(start), Some(length)) => {
+                        Some(substring(string, start, length))
+                    }
+                    _ => None,
+                });
+            }
+            Ok(Series::new("", out))
+        }),
+        Function::StrLen => row_wise_strings(args_exprs, DataType::Int64, |a| {
+            Some(a[0].chars().count() as i64)
+        }),
+        Function::Replace => {
+            let mut regexes = HashMap::new();
+            row_wise_strings(args_exprs, DataType::Utf8, move |a| {
+                let regex = cached_regex(&mut regexes, a[1], a.get(3).copied())?;
+                //An empty match is an error according to the specification
+                if regex.is_match("") {
+                    return None;
+                }
+                Some(regex.replace_all(a[0], a[2]).to_string())
+            })
+        }
+        Function::UCase => {
+            row_wise_strings(args_exprs, DataType::Utf8, |a| Some(a[0].to_uppercase()))
+        }
+        Function::LCase => {
+            row_wise_strings(args_exprs, DataType::Utf8, |a| Some(a[0].to_lowercase()))
+        }
+        Function::EncodeForUri => {
+            row_wise_strings(args_exprs, DataType::Utf8, |a| Some(encode_for_uri(a[0])))
+        }
+        Function::Contains => {
+            row_wise_strings(args_exprs, DataType::Boolean, |a| Some(a[0].contains(a[1])))
+        }
+        Function::StrStarts => row_wise_strings(args_exprs, DataType::Boolean, |a| {
+            Some(a[0].starts_with(a[1]))
+        }),
+        Function::StrEnds => row_wise_strings(args_exprs, DataType::Boolean, |a| {
+            Some(a[0].ends_with(a[1]))
+        }),
+        Function::StrBefore => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(
+                a[0].find(a[1])
+                    .map(|i| a[0][..i].to_string())
+                    .unwrap_or_default(),
+            )
+        }),
+        Function::StrAfter => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(
+                a[0].find(a[1])
+                    .map(|i| a[0][i + a[1].len()..].to_string())
+                    .unwrap_or_default(),
+            )
+        }),
+        //Time zones are normalized away when date times are parsed
+        Function::Timezone => map_series(args_exprs, DataType::Utf8, |s| {
+            Ok(Series::full_null("", s[0].len(), &DataType::Utf8))
+        }),
+        Function::Tz => map_series(args_exprs, DataType::Utf8, |s| {
+            let is_datetime = matches!(s[0].dtype(), DataType::Datetime(..));
+            Ok(Series::new(
+                "",
+                map_non_null(&s[0], |_| "".to_string())
+                    .into_iter()
+                    .map(|x| if is_datetime { x } else { None })
+                    .collect::<Vec<Option<String>>>(),
+            ))
+        }),
+        Function::Now => Expr::Literal(LiteralValue::DateTime(
+            Utc::now().naive_utc(),
+            TimeUnit::Nanoseconds,
+        )),
+        Function::Uuid | Function::StrUuid => {
+            let prefix = if func == &Function::Uuid {
+                "urn:uuid:"
+            } else {
+                ""
+            };
+            map_series(vec![row_reference], DataType::Utf8, move |s| {
+                Ok(Series::new(
+                    "",
+                    (0..s[0].len())
+                        .map(|_| Some(format!("{}{}", prefix, Uuid::new_v4().hyphenated())))
+                        .collect::<Vec<Option<String>>>(),
+                ))
+            })
+        }
+        Function::Md5 => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(hex_digest::<Md5>(a[0]))
+        }),
+        Function::Sha1 => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(hex_digest::<Sha1>(a[0]))
+        }),
+        Function::Sha256 => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(hex_digest::<Sha256>(a[0]))
+        }),
+        Function::Sha384 => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(hex_digest::<Sha384>(a[0]))
+        }),
+        Function::Sha512 => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            Some(hex_digest::<Sha512>(a[0]))
+        }),
+        //Language tags are not kept, so the lexical form is used
+        Function::StrLang => row_wise_strings(args_exprs, DataType::Utf8, |a| {
+            if a[1].is_empty() {
+                None
+            } else {
+                Some(a[0].to_string())
+            }
+        }),
+        Function::StrDt => {
+            if let Some(Expression::NamedNode(datatype)) = args.get(1) {
+                strdt(args_exprs.into_iter().next().unwrap(), datatype.clone())
+            } else {
+                row_wise_strings(args_exprs, DataType::Utf8, |a| Some(a[0].to_string()))
+            }
+        }
+        Function::IsIri => term_test(args_exprs, |s, dtype| {
+            dtype == &DataType::Utf8 && !s.starts_with("_:") && NamedNode::new(s).is_ok()
+        }),
+        Function::IsBlank => term_test(args_exprs, |s, dtype| {
+            dtype == &DataType::Utf8 && s.starts_with("_:")
+        }),
+        Function::IsLiteral => term_test(args_exprs, |s, dtype| {
+            dtype != &DataType::Utf8 || (!s.starts_with("_:") && NamedNode::new(s).is_err())
+        }),
+        Function::IsNumeric => term_test(args_exprs, |_, dtype| is_numeric(dtype)),
+        Function::Regex => {
+            let mut regexes = HashMap::new();
+            row_wise_strings(args_exprs, DataType::Boolean, move |a| {
+                let regex = cached_regex(&mut regexes, a[1], a.get(2).copied())?;
+                Some(regex.is_match(a[0]))
+            })
+        }
+        Function::Year
+        | Function::Month
+        | Function::Day
+        | Function::Hours
+        | Function::Minutes
+        | Function::Seconds
+        | Function::Custom(_) => {
+            panic!("Function {} is handled elsewhere", func)
+        }
+    }
+}
+
+pub(crate) fn same_term_expression(left: Expr, right: Expr) -> Expr {
+    map_series(vec![left, right], DataType::Boolean, |s| {
+        let same_type = s[0].dtype() == s[1].dtype();
+        let left_strings = string_representations(&s[0])?;
+        let right_strings = string_representations(&s[1])?;
+        Ok(Series::new(
+            "",
+            left_strings
+                .into_iter()
+                .zip(right_strings.into_iter())
+                .map(|(l, r)| match (l, r) {
+                    (Some(l), Some(r)) => Some(same_type && l == r),
+                    _ => None,
+                })
+                .collect::<Vec<Option<bool>>>(),
+        ))
+    })
+}
+
+fn map_series<F>(args_exprs: Vec<Expr>, output_type: DataType, f: F) -> Expr
+where
+    F: Fn(&mut [Series]) -> Result<Series, PolarsError> + Send + Sync + 'static,
+{
+    map_multiple(f, args_exprs, GetOutput::from_type(output_type))
+}
+
+//Applies a function to each row where all arguments are bound strings, other rows become null
+fn row_wise_strings<T, F>(args_exprs: Vec<Expr>, output_type: DataType, f: F) -> Expr
+where
+    F: FnMut(&[&str]) -> Option<T> + Send + Sync + Clone + 'static,
+    Series: NamedFrom<Vec<Option<T>>, [Option<T>]>,
+{
+    map_series(args_exprs, output_type, move |s| {
+        let mut f = f.clone();
+        let strings: Vec<Vec<Option<String>>> = s.iter().map(strict_strings).collect();
+        let len = s.iter().map(|x| x.len()).max().unwrap_or(0);
+        let mut out = Vec::with_capacity(len);
+        for i in 0..len {
+            let row: Option<Vec<&str>> = strings
+                .iter()
+                .map(|v| v.get(i).or_else(|| v.get(0)).and_then(|x| x.as_deref()))
+                .collect();
+            out.push(row.and_then(|r| f(&r)));
+        }
+        Ok(Series::new("", out))
+    })
+}
+
+fn numeric_function<F>(args_exprs: Vec<Expr>, f: F) -> Expr
+where
+    F: Fn(Series) -> Result<Series, PolarsError> + Send + Sync + 'static,
+{
+    map_multiple(
+        move |s: &mut [Series]| {
+            let series = s[0].clone();
+            match series.dtype() {
+                DataType::Float32 | DataType::Float64 => f(series),
+                dtype if is_numeric(dtype) => {
+                    //Integers are unaffected by rounding, but abs is still required
+                    f(series.cast(&DataType::Float64)?)?.cast(dtype)
+                }
+                _ => Ok(Series::full_null("", series.len(), &DataType::Float64)),
+            }
+        },
+        args_exprs,
+        GetOutput::same_type(),
+    )
+}
+
+fn term_test<F>(args_exprs: Vec<Expr>, f: F) -> Expr
+where
+    F: Fn(&str, &DataType) -> bool + Send + Sync + 'static,
+{
+    map_series(args_exprs, DataType::Boolean, move |s| {
+        let dtype = s[0].dtype().clone();
+        Ok(Series::new(
+            "",
+            string_representations(&s[0])?
+                .into_iter()
+                .map(|x| x.map(|y| f(&y, &dtype)))
+                .collect::<Vec<Option<bool>>>(),
+        ))
+    })
+}
+
+fn strdt(arg: Expr, datatype: NamedNode) -> Expr {
+    let dt = datatype.as_ref();
+    let output_type = if dt == xsd::INTEGER || dt == xsd::LONG || dt == xsd::INT {
+        DataType::Int64
+    } else if dt == xsd::DOUBLE || dt == xsd::DECIMAL || dt == xsd::FLOAT {
+        DataType::Float64
+    } else if dt == xsd::BOOLEAN {
+        DataType::Boolean
+    } else if dt == xsd::DATE_TIME {
+        DataType::Datetime(TimeUnit::Nanoseconds, None)
+    } else {
+        DataType::Utf8
+    };
+    let parse_type = output_type.clone();
+    map_series(vec![arg], output_type, move |s| {
+        let strings = strict_strings(&s[0]);
+        Ok(match parse_type {
+            DataType::Int64 => Series::new(
+                "",
+                strings
+                    .iter()
+                    .map(|x| x.as_ref().and_then(|y| y.parse::<i64>().ok()))
+                    .collect::<Vec<Option<i64>>>(),
+            ),
+            DataType::Float64 => Series::new(
+                "",
+                strings
+                    .iter()
+                    .map(|x| x.as_ref().and_then(|y| y.parse::<f64>().ok()))
+                    .collect::<Vec<Option<f64>>>(),
+            ),
+            DataType::Boolean => Series::new(
+                "",
+                strings
+                    .iter()
+                    .map(|x| x.as_ref().and_then(|y| y.parse::<bool>().ok()))
+                    .collect::<Vec<Option<bool>>>(),
+            ),
+            DataType::Datetime(..) => Series::new(
+                "",
+                strings
+                    .iter()
+                    .map(|x| x.as_ref().and_then(|y| y.parse::<NaiveDateTime>().ok()))
+                    .collect::<Vec<Option<NaiveDateTime>>>(),
+            ),
+            _ => Series::new("", strings),
+        })
+    })
+}
+
+//Values of Utf8 series, values of other types are type errors and become null
+fn strict_strings(series: &Series) -> Vec<Option<String>> {
+    if let Ok(utf8) = series.utf8() {
+        utf8.into_iter().map(|x| x.map(|y| y.to_string())).collect()
+    } else {
+        vec![None; series.len()]
+    }
+}
+
+fn numeric_values(series: &Series) -> Vec<Option<f64>> {
+    if is_numeric(series.dtype()) {
+        if let Ok(cast) = series.cast(&DataType::Float64) {
+            if let Ok(floats) = cast.f64() {
+                return floats.into_iter().collect();
+            }
+        }
+    }
+    vec![None; series.len()]
+}
+
+//The lexical forms of the values in a series, as given by STR
+fn string_representations(series: &Series) -> Result<Vec<Option<String>>, PolarsError> {
+    Ok(match series.dtype() {
+        DataType::Utf8 => strict_strings(series),
+        DataType::Datetime(..) => {
+            let nanos = series
+                .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))?
+                .cast(&DataType::Int64)?;
+            nanos
+                .i64()?
+                .into_iter()
+                .map(|x| {
+                    x.map(|n| {
+                        NaiveDateTime::from_timestamp(
+                            n.div_euclid(1_000_000_000),
+                            n.rem_euclid(1_000_000_000) as u32,
+                        )
+                        .format("%Y-%m-%dT%H:%M:%S%.f")
+                        .to_string()
+                    })
+                })
+                .collect()
+        }
+        _ => {
+            let cast = series.cast(&DataType::Utf8)?;
+            strict_strings(&cast)
+        }
+    })
+}
+
+fn map_non_null<T, F>(series: &Series, f: F) -> Vec<Option<T>>
+where
+    F: Fn(usize) -> T,
+{
+    let mask = series.is_not_null();
+    mask.into_iter()
+        .enumerate()
+        .map(|(i, x)| if x == Some(true) { Some(f(i)) } else { None })
+        .collect()
+}
+
+fn is_numeric(dtype: &DataType) -> bool {
+    matches!(
+        dtype,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Float32
+            | DataType::Float64
+    )
+}
+
+fn datatype_iri(dtype: &DataType) -> Option<String> {
+    let datatype = match dtype {
+        DataType::Utf8 => xsd::STRING,
+        DataType::Boolean => xsd::BOOLEAN,
+        DataType::Int32 => xsd::INT,
+        DataType::Int8 | DataType::Int16 | DataType::Int64 => xsd::INTEGER,
+        DataType::UInt32 => xsd::UNSIGNED_INT,
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt64 => xsd::UNSIGNED_LONG,
+        DataType::Float32 => xsd::FLOAT,
+        DataType::Float64 => xsd::DOUBLE,
+        DataType::Datetime(..) => xsd::DATE_TIME,
+        _ => return None,
+    };
+    Some(datatype.as_str().to_string())
+}
+
+//Positions are one-based and counted in characters, as in XPath fn:substring
+fn substring(string: &str, start: f64, length: f64) -> String {
+    let first = start.round();
+    let last = first + length.round();
+    string
+        .chars()
+        .enumerate()
+        .filter(|(i, _)| {
+            let position = (*i + 1) as f64;
+            position >= first && position < last
+        })
+        .map(|(_, c)| c)
+        .collect()
+}
+
+fn cached_regex<'a>(
+    regexes: &'a mut HashMap<(String, String), Option<Regex>>,
+    pattern: &str,
+    flags: Option<&str>,
+) -> Option<&'a Regex> {
+    let flags = flags.unwrap_or("");
+    regexes
+        .entry((pattern.to_string(), flags.to_string()))
+        .or_insert_with(|| {
+            let mut pattern = pattern.to_string();
+            if flags.contains('q') {
+                pattern = regex::escape(&pattern);
+            }
+            let mut builder = RegexBuilder::new(&pattern);
+            for flag in flags.chars() {
+                match flag {
+                    's' => {
+                        builder.dot_matches_new_line(true);
+                    }
+                    'm' => {
+                        builder.multi_line(true);
+                    }
+                    'i' => {
+                        builder.case_insensitive(true);
+                    }
+                    'x' => {
+                        builder.ignore_whitespace(true);
+                    }
+                    'q' => {}
+                    _ => return None,
+                }
+            }
+            builder.build().ok()
+        })
+        .as_ref()
+}
+
+fn encode_for_uri(string: &str) -> String {
+    let mut encoded = String::new();
+    for b in string.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.' || b == b'~' {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+fn hex_digest<D: Digest>(string: &str) -> String {
+    D::digest(string.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_for_uri, substring};
+
+    #[test]
+    fn test_substring() {
+        assert_eq!(substring("motor car", 6.0, f64::INFINITY), " car");
+        assert_eq!(substring("metadata", 4.0, 3.0), "ada");
+        assert_eq!(substring("12345", 1.5, 2.6), "234");
+        assert_eq!(substring("12345", 0.0, 3.0), "12");
+        assert_eq!(substring("12345", -3.0, 5.0), "1");
+    }
+
+    #[test]
+    fn test_encode_for_uri() {
+        assert_eq!(encode_for_uri("Los Angeles"), "Los%20Angeles");
+        assert_eq!(encode_for_uri("~bébé"), "~b%C3%A9b%C3%A9");
+        assert_eq!(encode_for_uri("100% organic"), "100%25%20organic");
+    }
+}
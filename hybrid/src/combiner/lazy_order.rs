@@ -1,4 +1,6 @@
 use crate::combiner::lazy_expressions::lazy_expression;
+use crate::combiner::CombinerError;
+use crate::function_registry::FunctionRegistry;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_query::TimeSeriesQuery;
 use polars::prelude::{DataFrame, LazyFrame};
@@ -10,24 +12,39 @@ pub fn lazy_order_expression(
     lazy_frame: LazyFrame,
     columns: &HashSet<String>,
     time_series: &mut Vec<(TimeSeriesQuery, DataFrame)>,
+    function_registry: &FunctionRegistry,
     context: &Context,
-) -> (LazyFrame, bool, Context) {
+) -> Result<(LazyFrame, bool, Context), CombinerError> {
     match oexpr {
         OrderExpression::Asc(expr) => {
             let inner_context = context.extension_with(PathEntry::OrderingOperation);
-            (
-                lazy_expression(expr, lazy_frame, columns, time_series, &inner_context),
+            Ok((
+                lazy_expression(
+                    expr,
+                    lazy_frame,
+                    columns,
+                    time_series,
+                    function_registry,
+                    &inner_context,
+                )?,
                 true,
                 inner_context,
-            )
+            ))
         }
         OrderExpression::Desc(expr) => {
             let inner_context = context.extension_with(PathEntry::OrderingOperation);
-            (
-                lazy_expression(expr, lazy_frame, columns, time_series, &inner_context),
+            Ok((
+                lazy_expression(
+                    expr,
+                    lazy_frame,
+                    columns,
+                    time_series,
+                    function_registry,
+                    &inner_context,
+                )?,
                 false,
                 inner_context,
-            )
+            ))
         }
     }
 }
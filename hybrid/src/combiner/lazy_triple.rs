@@ -1,5 +1,7 @@
 use crate::combiner::join_timeseries::join_tsq;
-use crate::constants::HAS_VALUE;
+use crate::constants::{
+    HAS_EVENT_TYPE, HAS_MESSAGE, HAS_QUALITY, HAS_SEVERITY, HAS_SOURCE_NODE, HAS_VALUE,
+};
 use crate::query_context::Context;
 use crate::timeseries_query::TimeSeriesQuery;
 use polars::prelude::{DataFrame, LazyFrame};
@@ -15,12 +17,21 @@ pub fn lazy_triple_pattern(
 ) -> LazyFrame {
     let mut found_index = None;
     if let NamedNodePattern::NamedNode(pn) = &triple_pattern.predicate {
-        if pn.as_str() == HAS_VALUE {
+        if pn.as_str() == HAS_VALUE
+            || pn.as_str() == HAS_QUALITY
+            || pn.as_str() == HAS_EVENT_TYPE
+            || pn.as_str() == HAS_SEVERITY
+            || pn.as_str() == HAS_MESSAGE
+            || pn.as_str() == HAS_SOURCE_NODE
+        {
             if let TermPattern::Variable(obj_var) = &triple_pattern.object {
                 if !columns.contains(obj_var.as_str()) {
                     for i in 0..time_series.len() {
                         let (tsq, _) = time_series.get(i).unwrap();
-                        if tsq.has_equivalent_value_variable(obj_var, context) {
+                        if tsq.has_equivalent_value_variable(obj_var, context)
+                            || tsq.has_equivalent_quality_variable(obj_var, context)
+                            || tsq.has_equivalent_event_field_variable(obj_var, context)
+                        {
                             found_index = Some(i);
                             break;
                         }
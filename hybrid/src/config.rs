@@ -0,0 +1,448 @@
+use crate::engine::{Engine, EngineLimits};
+use crate::pushdown_setting::{all_pushdowns, PushdownSetting};
+use crate::timeseries_database::arrow_flight_sql_database::{
+    ArrowFlightSQLDatabase, ArrowFlightSQLError,
+};
+use crate::timeseries_database::opcua_history_read::{
+    OPCUAAggregateConfiguration, OPCUAClientConfig, OPCUAHistoryRead, OPCUAHistoryReadError,
+    OPCUAIdentity, OPCUASynchronization,
+};
+use crate::timeseries_database::table_discovery::TableDiscovery;
+use crate::timeseries_database::timeseries_sql_rewrite::{
+    PartitionScheme, TimeSeriesPartition, TimeSeriesTable,
+};
+use crate::timeseries_database::TimeSeriesQueryable;
+use log::warn;
+use oxrdf::{IriParseError, NamedNode};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadError(std::io::Error),
+    YAMLError(serde_yaml::Error),
+    TOMLError(toml::de::Error),
+    MissingEnvironmentVariable(String),
+    InvalidDatatypeIRI(IriParseError),
+    InvalidNamePredicateIRI(IriParseError),
+    InvalidPartitionHash(String, u64),
+    MissingTableColumn(String, String),
+    ArrowFlightSQLError(ArrowFlightSQLError),
+    OPCUAHistoryReadError(OPCUAHistoryReadError),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::ReadError(e) => {
+                write!(f, "Could not read configuration file: {}", e)
+            }
+            ConfigError::YAMLError(e) => {
+                write!(f, "Invalid YAML configuration: {}", e)
+            }
+            ConfigError::TOMLError(e) => {
+                write!(f, "Invalid TOML configuration: {}", e)
+            }
+            ConfigError::MissingEnvironmentVariable(v) => {
+                write!(f, "Environment variable {} is not set", v)
+            }
+            ConfigError::InvalidDatatypeIRI(e) => {
+                write!(f, "Invalid datatype IRI: {}", e)
+            }
+            ConfigError::InvalidNamePredicateIRI(e) => {
+                write!(f, "Invalid name predicate IRI: {}", e)
+            }
+            ConfigError::InvalidPartitionHash(hash, modulo) => {
+                write!(
+                    f,
+                    "Invalid partition hash {} modulo {}, expected crc32 or fnv1a_64 and a positive modulo",
+                    hash, modulo
+                )
+            }
+            ConfigError::MissingTableColumn(table, column) => {
+                write!(f, "Table {} must have a {}", table, column)
+            }
+            ConfigError::ArrowFlightSQLError(e) => {
+                write!(f, "Could not connect to Arrow Flight SQL database: {}", e)
+            }
+            ConfigError::OPCUAHistoryReadError(e) => {
+                write!(f, "Could not connect to OPC UA server: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EngineConfig {
+    pub sparql_endpoint: String,
+    pub time_series_database: TimeSeriesDatabaseConfig,
+    //The default pushdowns of the time series database are used when these are not given
+    #[serde(default)]
+    pub pushdowns: Option<HashSet<PushdownSetting>>,
+    #[serde(default)]
+    pub vocabulary: VocabularyConfig,
+    #[serde(default)]
+    pub limits: EngineLimits,
+    //Endpoints that service patterns in queries may refer to
+    #[serde(default)]
+    pub federation_endpoints: HashSet<String>,
+}
+
+//Names used when translating DSL queries to SPARQL
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct VocabularyConfig {
+    pub name_predicate: Option<String>,
+    #[serde(default)]
+    pub connective_mapping: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TimeSeriesDatabaseConfig {
+    ArrowFlightSql {
+        endpoint: String,
+        username: String,
+        password: Secret,
+        #[serde(default)]
+        tables: Vec<TableConfig>,
+        //Discovered tables are used after the configured tables
+        #[serde(default)]
+        discover_tables: bool,
+    },
+    OpcuaHistoryRead {
+        endpoint: String,
+        namespace: u16,
+        #[serde(default = "default_security")]
+        security_policy: String,
+        #[serde(default = "default_security")]
+        security_mode: String,
+        username: Option<String>,
+        password: Option<Secret>,
+        user_certificate_path: Option<PathBuf>,
+        user_private_key_path: Option<PathBuf>,
+        pki_dir: Option<PathBuf>,
+        certificate_path: Option<PathBuf>,
+        private_key_path: Option<PathBuf>,
+        #[serde(default)]
+        trust_server_certs: bool,
+        #[serde(default)]
+        read_at_time: bool,
+        #[serde(default)]
+        server_timestamps: bool,
+        max_values: Option<usize>,
+        #[serde(default)]
+        aggregate_configuration: OPCUAAggregateConfiguration,
+    },
+}
+
+//A secret is either given directly or read from an environment variable, as in {env: DREMIO_PASSWORD}
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Secret {
+    Environment { env: String },
+    Value(String),
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String, ConfigError> {
+        match self {
+            Secret::Environment { env } => {
+                std::env::var(env).map_err(|_| ConfigError::MissingEnvironmentVariable(env.clone()))
+            }
+            Secret::Value(v) => Ok(v.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TableConfig {
+    pub schema: Option<String>,
+    pub time_series_table: String,
+    //Not used for wide tables
+    #[serde(default)]
+    pub value_column: String,
+    pub quality_column: Option<String>,
+    pub timestamp_column: String,
+    //Not used for wide tables
+    #[serde(default)]
+    pub identifier_column: String,
+    pub value_datatype: String,
+    pub year_column: Option<String>,
+    pub month_column: Option<String>,
+    pub day_column: Option<String>,
+    #[serde(default)]
+    pub partitions: Vec<PartitionConfig>,
+    pub wide_value_columns: Option<HashMap<String, String>>,
+}
+
+//A partition column and its scheme, as in {column: dt, timestamp_string: "%Y-%m-%d"}
+#[derive(Deserialize, Debug, Clone)]
+pub struct PartitionConfig {
+    pub column: String,
+    #[serde(flatten)]
+    pub scheme: PartitionSchemeConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PartitionSchemeConfig {
+    TimestampInteger(String),
+    TimestampString(String),
+    IdentifierMapping(HashMap<String, String>),
+    //The hash of the identifier modulo n, as in {column: bucket, identifier_hash: {hash: crc32, modulo: 16}}
+    IdentifierHash { hash: String, modulo: u64 },
+}
+
+fn default_security() -> String {
+    "None".to_string()
+}
+
+impl EngineConfig {
+    //Files ending with .toml are read as TOML, other files as YAML
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<EngineConfig, ConfigError> {
+        let s = read_to_string(path.as_ref()).map_err(ConfigError::ReadError)?;
+        if path.as_ref().extension().and_then(|e| e.to_str()) == Some("toml") {
+            EngineConfig::from_toml_str(&s)
+        } else {
+            EngineConfig::from_yaml_str(&s)
+        }
+    }
+
+    pub fn from_yaml_str(s: &str) -> Result<EngineConfig, ConfigError> {
+        let config: EngineConfig = serde_yaml::from_str(s).map_err(ConfigError::YAMLError)?;
+        config.validate()
+    }
+
+    pub fn from_toml_str(s: &str) -> Result<EngineConfig, ConfigError> {
+        let config: EngineConfig = toml::from_str(s).map_err(ConfigError::TOMLError)?;
+        config.validate()
+    }
+
+    fn validate(self) -> Result<EngineConfig, ConfigError> {
+        if let Some(name_predicate) = &self.vocabulary.name_predicate {
+            NamedNode::new(name_predicate).map_err(ConfigError::InvalidNamePredicateIRI)?;
+        }
+        Ok(self)
+    }
+}
+
+impl TableConfig {
+    pub fn to_time_series_table(&self) -> Result<TimeSeriesTable, ConfigError> {
+        //Narrow tables need the identifier and value columns that wide tables do without
+        if self.wide_value_columns.is_none() {
+            for (column, name) in [
+                (&self.identifier_column, "identifier_column"),
+                (&self.value_column, "value_column"),
+            ] {
+                if column.is_empty() {
+                    return Err(ConfigError::MissingTableColumn(
+                        self.time_series_table.clone(),
+                        name.to_string(),
+                    ));
+                }
+            }
+        }
+        let mut partitions = vec![];
+        for p in &self.partitions {
+            let scheme = match &p.scheme {
+                PartitionSchemeConfig::TimestampInteger(f) => {
+                    PartitionScheme::TimestampInteger(f.clone())
+                }
+                PartitionSchemeConfig::TimestampString(f) => {
+                    PartitionScheme::TimestampString(f.clone())
+                }
+                PartitionSchemeConfig::IdentifierMapping(m) => {
+                    PartitionScheme::IdentifierMapping(m.clone())
+                }
+                PartitionSchemeConfig::IdentifierHash { hash, modulo } => {
+                    PartitionScheme::identifier_hash(hash, *modulo)
+                        .ok_or_else(|| ConfigError::InvalidPartitionHash(hash.clone(), *modulo))?
+                }
+            };
+            partitions.push(TimeSeriesPartition {
+                column: p.column.clone(),
+                scheme,
+            });
+        }
+        Ok(TimeSeriesTable {
+            schema: self.schema.clone(),
+            time_series_table: self.time_series_table.clone(),
+            value_column: self.value_column.clone(),
+            quality_column: self.quality_column.clone(),
+            timestamp_column: self.timestamp_column.clone(),
+            identifier_column: self.identifier_column.clone(),
+            value_datatype: NamedNode::new(&self.value_datatype)
+                .map_err(ConfigError::InvalidDatatypeIRI)?,
+            year_column: self.year_column.clone(),
+            month_column: self.month_column.clone(),
+            day_column: self.day_column.clone(),
+            partitions,
+            wide_value_columns: self.wide_value_columns.clone(),
+        })
+    }
+}
+
+impl TimeSeriesDatabaseConfig {
+    pub fn default_pushdowns(&self) -> HashSet<PushdownSetting> {
+        match self {
+            TimeSeriesDatabaseConfig::ArrowFlightSql { .. } => all_pushdowns(),
+            TimeSeriesDatabaseConfig::OpcuaHistoryRead { .. } => {
+                [PushdownSetting::GroupBy, PushdownSetting::Synchronization].into()
+            }
+        }
+    }
+
+    pub async fn connect(&self) -> Result<Box<dyn TimeSeriesQueryable>, ConfigError> {
+        match self {
+            TimeSeriesDatabaseConfig::ArrowFlightSql {
+                endpoint,
+                username,
+                password,
+                tables,
+                discover_tables,
+            } => {
+                let mut time_series_tables = vec![];
+                for t in tables {
+                    time_series_tables.push(t.to_time_series_table()?);
+                }
+                let configured_datatypes: HashSet<NamedNode> = time_series_tables
+                    .iter()
+                    .filter(|t| t.wide_value_columns.is_none())
+                    .map(|t| t.value_datatype.clone())
+                    .collect();
+                let mut db = ArrowFlightSQLDatabase::new(
+                    endpoint,
+                    username,
+                    &password.resolve()?,
+                    time_series_tables,
+                )
+                .await
+                .map_err(ConfigError::ArrowFlightSQLError)?;
+                if *discover_tables {
+                    let discovered = db
+                        .discover_tables(&TableDiscovery::new())
+                        .await
+                        .map_err(ConfigError::ArrowFlightSQLError)?
+                        .into_iter()
+                        .filter(|t| {
+                            let configured = configured_datatypes.contains(&t.value_datatype);
+                            if configured {
+                                warn!(
+                                    "Discovered table {} is skipped, as a configured table has values of datatype {}",
+                                    t.time_series_table, t.value_datatype
+                                );
+                            }
+                            !configured
+                        })
+                        .collect();
+                    db.add_time_series_tables(discovered);
+                }
+                Ok(Box::new(db))
+            }
+            TimeSeriesDatabaseConfig::OpcuaHistoryRead {
+                endpoint,
+                namespace,
+                security_policy,
+                security_mode,
+                username,
+                password,
+                user_certificate_path,
+                user_private_key_path,
+                pki_dir,
+                certificate_path,
+                private_key_path,
+                trust_server_certs,
+                read_at_time,
+                server_timestamps,
+                max_values,
+                aggregate_configuration,
+            } => {
+                let mut client_config = OPCUAClientConfig::default()
+                    .with_security_names(security_policy, security_mode)
+                    .map_err(ConfigError::OPCUAHistoryReadError)?
+                    .with_trust_server_certs(*trust_server_certs);
+                if let Some(pki_dir) = pki_dir {
+                    client_config = client_config.with_pki_dir(pki_dir.clone());
+                }
+                match (certificate_path, private_key_path) {
+                    (Some(certificate_path), Some(private_key_path)) => {
+                        client_config = client_config
+                            .with_certificate(certificate_path.clone(), private_key_path.clone());
+                    }
+                    (None, None) => {}
+                    _ => {
+                        return Err(invalid_opcua_configuration(
+                            "certificate_path and private_key_path must be set together",
+                        ))
+                    }
+                }
+                match (
+                    username,
+                    password,
+                    user_certificate_path,
+                    user_private_key_path,
+                ) {
+                    (Some(username), Some(password), None, None) => {
+                        client_config = client_config.with_identity(OPCUAIdentity::UserName(
+                            username.clone(),
+                            password.resolve()?,
+                        ));
+                    }
+                    (None, None, Some(certificate_path), Some(private_key_path)) => {
+                        client_config = client_config.with_identity(OPCUAIdentity::X509(
+                            certificate_path.clone(),
+                            private_key_path.clone(),
+                        ));
+                    }
+                    (None, None, None, None) => {}
+                    _ => {
+                        return Err(invalid_opcua_configuration(
+                            "Either both username and password or both user_certificate_path and user_private_key_path must be set",
+                        ))
+                    }
+                }
+                let mut db = OPCUAHistoryRead::from_config(endpoint, *namespace, &client_config)
+                    .map_err(ConfigError::OPCUAHistoryReadError)?
+                    .with_server_timestamps(*server_timestamps)
+                    .with_aggregate_configuration(aggregate_configuration.clone());
+                if *read_at_time {
+                    db = db.with_synchronization(OPCUASynchronization::ReadAtTime);
+                }
+                if let Some(max_values) = max_values {
+                    db = db.with_max_values(*max_values);
+                }
+                Ok(Box::new(db))
+            }
+        }
+    }
+}
+
+fn invalid_opcua_configuration(message: &str) -> ConfigError {
+    ConfigError::OPCUAHistoryReadError(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+        message.to_string(),
+    ))
+}
+
+impl Engine {
+    pub async fn from_config(config: &EngineConfig) -> Result<Engine, ConfigError> {
+        let time_series_database = config.time_series_database.connect().await?;
+        let pushdown_settings = if let Some(pushdowns) = &config.pushdowns {
+            pushdowns.clone()
+        } else {
+            config.time_series_database.default_pushdowns()
+        };
+        Ok(Engine::new(pushdown_settings, time_series_database)
+            .with_sparql_endpoint(&config.sparql_endpoint)
+            .with_limits(config.limits.clone())
+            .with_federation_endpoints(config.federation_endpoints.clone()))
+    }
+}
@@ -2,11 +2,37 @@ pub const HAS_TIMESTAMP: &str = "https://github.com/magbak/otit_swt#hasTimestamp
 pub const HAS_TIMESERIES: &str = "https://github.com/magbak/otit_swt#hasTimeseries";
 pub const HAS_DATA_POINT: &str = "https://github.com/magbak/otit_swt#hasDataPoint";
 pub const HAS_VALUE: &str = "https://github.com/magbak/otit_swt#hasValue";
+pub const HAS_QUALITY: &str = "https://github.com/magbak/otit_swt#hasQuality";
+pub const HAS_LATEST_VALUE: &str = "https://github.com/magbak/otit_swt#hasLatestValue";
+pub const HAS_LATEST_TIMESTAMP: &str = "https://github.com/magbak/otit_swt#hasLatestTimestamp";
+pub const HAS_FIRST_TIMESTAMP: &str = "https://github.com/magbak/otit_swt#hasFirstTimestamp";
+pub const HAS_LAST_TIMESTAMP: &str = "https://github.com/magbak/otit_swt#hasLastTimestamp";
 pub const HAS_DATATYPE: &str = "https://github.com/magbak/otit_swt#hasDatatype";
 pub const HAS_EXTERNAL_ID: &str = "https://github.com/magbak/otit_swt#hasExternalId";
+pub const HAS_EVENT_SERIES: &str = "https://github.com/magbak/otit_swt#hasEventSeries";
+pub const HAS_EVENT: &str = "https://github.com/magbak/otit_swt#hasEvent";
+pub const HAS_EVENT_TYPE: &str = "https://github.com/magbak/otit_swt#hasEventType";
+pub const HAS_SEVERITY: &str = "https://github.com/magbak/otit_swt#hasSeverity";
+pub const HAS_MESSAGE: &str = "https://github.com/magbak/otit_swt#hasMessage";
+pub const HAS_SOURCE_NODE: &str = "https://github.com/magbak/otit_swt#hasSourceNode";
 pub const DATETIME_AS_NANOS: &str = "https://github.com/magbak/otit_swt#DateTimeAsNanos";
 pub const NANOS_AS_DATETIME: &str = "https://github.com/magbak/otit_swt#NanosAsDateTime";
 pub const DATETIME_AS_SECONDS: &str = "https://github.com/magbak/otit_swt#DateTimeAsSeconds";
 pub const SECONDS_AS_DATETIME: &str = "https://github.com/magbak/otit_swt#SecondsAsDateTime";
+pub const RESAMPLE: &str = "https://github.com/magbak/otit_swt#resample";
+pub const FILL_FORWARD: &str = "https://github.com/magbak/otit_swt#fillForward";
+pub const INTERPOLATE_LINEAR: &str = "https://github.com/magbak/otit_swt#interpolateLinear";
 pub const NEST: &str = "https://github.com/magbak/otit_swt#nestAggregation";
+pub const TIME_WEIGHTED_AVERAGE: &str = "https://github.com/magbak/otit_swt#timeWeightedAverage";
+pub const FIRST: &str = "https://github.com/magbak/otit_swt#first";
+pub const LAST: &str = "https://github.com/magbak/otit_swt#last";
+pub const INTERPOLATIVE: &str = "https://github.com/magbak/otit_swt#interpolative";
+pub const DELTA: &str = "https://github.com/magbak/otit_swt#delta";
+pub const RANGE: &str = "https://github.com/magbak/otit_swt#range";
+pub const STANDARD_DEVIATION: &str = "https://github.com/magbak/otit_swt#standardDeviation";
+//Aggregates of the StatusCodes given by quality variables
+pub const DURATION_GOOD: &str = "https://github.com/magbak/otit_swt#durationGood";
+pub const DURATION_BAD: &str = "https://github.com/magbak/otit_swt#durationBad";
+pub const PERCENT_GOOD: &str = "https://github.com/magbak/otit_swt#percentGood";
+pub const PERCENT_BAD: &str = "https://github.com/magbak/otit_swt#percentBad";
 pub const GROUPING_COL: &str = "grouping_col";
@@ -5,8 +5,13 @@ use oxrdf::Variable;
 pub enum Constraint {
     ExternalTimeseries,
     ExternalDataPoint,
+    //Data point standing in for the latest value and timestamp of a time series
+    ExternalLatestDataPoint,
     ExternalDataValue,
+    ExternalDataQuality,
     ExternalTimestamp,
+    //Event type, severity, message or source node of an event
+    ExternalEventField,
     ExternallyDerived,
 }
 
@@ -0,0 +1,409 @@
+use crate::constants::{HAS_DATA_POINT, HAS_TIMESTAMP};
+use oxrdf::vocab::xsd;
+use oxrdf::{BlankNode, Literal, NamedNode, NamedNodeRef, Subject, Term, Variable};
+use polars::export::chrono::{Duration, NaiveDate, NaiveDateTime};
+use polars::frame::DataFrame;
+use polars::prelude::{AnyValue, TimeUnit};
+use spargebra::algebra::{GraphPattern, QueryDataset};
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::Query;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RDFFormat {
+    NTriples,
+    //Triples with the same subject in a solution are written together, without prefixes
+    Turtle,
+}
+
+pub struct ConstructTemplate {
+    template: Vec<TriplePattern>,
+    //Data point variables in the template and the time series and timestamp variables used to mint their IRIs
+    data_points: HashMap<Variable, (Variable, Variable)>,
+    variables: Vec<Variable>,
+}
+
+impl ConstructTemplate {
+    pub fn new(template: Vec<TriplePattern>, where_pattern: &GraphPattern) -> ConstructTemplate {
+        let mut in_scope = HashSet::new();
+        find_in_scope_variables(where_pattern, &mut in_scope);
+        let mut time_series_of_data_point = HashMap::new();
+        let mut timestamp_of_data_point = HashMap::new();
+        find_data_point_triples(
+            where_pattern,
+            &mut time_series_of_data_point,
+            &mut timestamp_of_data_point,
+        );
+
+        let mut data_points = HashMap::new();
+        let mut variables = BTreeSet::new();
+        for v in template.iter().flat_map(template_variables) {
+            if let (Some(ts), Some(t)) = (
+                time_series_of_data_point.get(&v),
+                timestamp_of_data_point.get(&v),
+            ) {
+                variables.insert(ts.clone());
+                variables.insert(t.clone());
+                data_points.insert(v, (ts.clone(), t.clone()));
+            } else if in_scope.contains(&v) {
+                variables.insert(v);
+            }
+        }
+        ConstructTemplate {
+            template,
+            data_points,
+            variables: variables.into_iter().collect(),
+        }
+    }
+
+    //The select query producing the solutions the template is instantiated with
+    pub fn select_query(
+        &self,
+        dataset: Option<QueryDataset>,
+        where_pattern: GraphPattern,
+    ) -> Query {
+        Query::Select {
+            dataset,
+            pattern: GraphPattern::Project {
+                inner: Box::new(where_pattern),
+                variables: self.variables.clone(),
+            },
+            base_iri: None,
+        }
+    }
+
+    //Writes the triples of each solution as they are instantiated, and returns the number of triples.
+    //Strings are IRIs when they are IRIs in the static solutions or in the subject or predicate position.
+    pub fn write_triples<W: Write>(
+        &self,
+        df: &DataFrame,
+        iri_variables: &HashSet<Variable>,
+        format: RDFFormat,
+        writer: &mut W,
+    ) -> Result<usize, std::io::Error> {
+        let mut iters = vec![];
+        for v in &self.variables {
+            if let Ok(series) = df.column(v.as_str()) {
+                iters.push((v, series.iter()));
+            }
+        }
+        let mut count = 0;
+        for row in 0..df.height() {
+            let mut solution = HashMap::new();
+            for (v, it) in iters.iter_mut() {
+                solution.insert(*v, it.next().unwrap());
+            }
+            let mut blank_nodes = HashMap::new();
+            let mut triples = vec![];
+            for tp in &self.template {
+                let subject = match self.instantiate(
+                    &tp.subject,
+                    &solution,
+                    iri_variables,
+                    row,
+                    &mut blank_nodes,
+                    true,
+                ) {
+                    Some(Term::NamedNode(nn)) => Subject::NamedNode(nn),
+                    Some(Term::BlankNode(bn)) => Subject::BlankNode(bn),
+                    _ => continue,
+                };
+                let predicate = match &tp.predicate {
+                    NamedNodePattern::NamedNode(nn) => nn.clone(),
+                    NamedNodePattern::Variable(v) => {
+                        if let Some(Term::NamedNode(nn)) =
+                            self.instantiate_variable(v, &solution, iri_variables, true)
+                        {
+                            nn
+                        } else {
+                            continue;
+                        }
+                    }
+                };
+                let object = if let Some(object) = self.instantiate(
+                    &tp.object,
+                    &solution,
+                    iri_variables,
+                    row,
+                    &mut blank_nodes,
+                    false,
+                ) {
+                    object
+                } else {
+                    continue;
+                };
+                triples.push((subject, predicate, object));
+            }
+            count += triples.len();
+            write_solution_triples(triples, format, writer)?;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    fn instantiate(
+        &self,
+        term_pattern: &TermPattern,
+        solution: &HashMap<&Variable, AnyValue>,
+        iri_variables: &HashSet<Variable>,
+        row: usize,
+        blank_nodes: &mut HashMap<String, BlankNode>,
+        iri_position: bool,
+    ) -> Option<Term> {
+        match term_pattern {
+            TermPattern::NamedNode(nn) => Some(Term::NamedNode(nn.clone())),
+            TermPattern::Literal(l) => Some(Term::Literal(l.clone())),
+            //Blank nodes in the template are fresh for each solution
+            TermPattern::BlankNode(bn) => Some(Term::BlankNode(
+                blank_nodes
+                    .entry(bn.as_str().to_string())
+                    .or_insert_with(|| BlankNode::new_unchecked(format!("{}r{}", bn.as_str(), row)))
+                    .clone(),
+            )),
+            TermPattern::Variable(v) => {
+                self.instantiate_variable(v, solution, iri_variables, iri_position)
+            }
+        }
+    }
+
+    fn instantiate_variable(
+        &self,
+        variable: &Variable,
+        solution: &HashMap<&Variable, AnyValue>,
+        iri_variables: &HashSet<Variable>,
+        iri_position: bool,
+    ) -> Option<Term> {
+        if let Some((ts, t)) = self.data_points.get(variable) {
+            return mint_data_point_iri(solution.get(ts)?, solution.get(t)?).map(Term::NamedNode);
+        }
+        any_value_to_term(
+            solution.get(variable)?,
+            iri_position || iri_variables.contains(variable),
+        )
+    }
+}
+
+//Data points are named by their time series and timestamp, e.g. http://example.org/ts1/dataPoint/20220601T084653.123
+fn mint_data_point_iri(time_series: &AnyValue, timestamp: &AnyValue) -> Option<NamedNode> {
+    if let (AnyValue::Utf8(ts), Some(Term::Literal(t))) =
+        (time_series, any_value_to_term(timestamp, false))
+    {
+        let compact_timestamp: String = t
+            .value()
+            .chars()
+            .filter(|c| *c != '-' && *c != ':')
+            .collect();
+        NamedNode::new(format!("{}/dataPoint/{}", ts, compact_timestamp)).ok()
+    } else {
+        None
+    }
+}
+
+pub fn any_value_to_term(any_value: &AnyValue, iri: bool) -> Option<Term> {
+    let typed = |value: String, datatype: NamedNodeRef| {
+        Some(Term::Literal(Literal::new_typed_literal(value, datatype)))
+    };
+    match any_value {
+        AnyValue::Null => None,
+        AnyValue::Utf8(s) => {
+            if iri {
+                NamedNode::new(*s).ok().map(Term::NamedNode)
+            } else {
+                Some(Term::Literal(Literal::new_simple_literal(*s)))
+            }
+        }
+        AnyValue::Boolean(b) => typed(b.to_string(), xsd::BOOLEAN),
+        AnyValue::UInt8(u) => typed(u.to_string(), xsd::UNSIGNED_BYTE),
+        AnyValue::UInt16(u) => typed(u.to_string(), xsd::UNSIGNED_SHORT),
+        AnyValue::UInt32(u) => typed(u.to_string(), xsd::UNSIGNED_INT),
+        AnyValue::UInt64(u) => typed(u.to_string(), xsd::UNSIGNED_LONG),
+        AnyValue::Int8(i) => typed(i.to_string(), xsd::BYTE),
+        AnyValue::Int16(i) => typed(i.to_string(), xsd::SHORT),
+        AnyValue::Int32(i) => typed(i.to_string(), xsd::INT),
+        AnyValue::Int64(i) => typed(i.to_string(), xsd::LONG),
+        AnyValue::Float32(f) => typed(f.to_string(), xsd::FLOAT),
+        AnyValue::Float64(f) => typed(f.to_string(), xsd::DOUBLE),
+        AnyValue::Date(days) => typed(
+            (NaiveDate::from_ymd(1970, 1, 1) + Duration::days(*days as i64))
+                .format("%Y-%m-%d")
+                .to_string(),
+            xsd::DATE,
+        ),
+        AnyValue::Datetime(t, time_unit, _) => {
+            let nanos = match time_unit {
+                TimeUnit::Nanoseconds => *t,
+                TimeUnit::Microseconds => t * 1_000,
+                TimeUnit::Milliseconds => t * 1_000_000,
+            };
+            let datetime = NaiveDateTime::from_timestamp(
+                nanos.div_euclid(1_000_000_000),
+                nanos.rem_euclid(1_000_000_000) as u32,
+            );
+            typed(
+                datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+                xsd::DATE_TIME,
+            )
+        }
+        other => Some(Term::Literal(Literal::new_simple_literal(
+            other.to_string(),
+        ))),
+    }
+}
+
+pub(crate) fn write_solution_triples<W: Write>(
+    triples: Vec<(Subject, NamedNode, Term)>,
+    format: RDFFormat,
+    writer: &mut W,
+) -> Result<(), std::io::Error> {
+    match format {
+        RDFFormat::NTriples => {
+            for (s, p, o) in triples {
+                writeln!(writer, "{} {} {} .", s, p, o)?;
+            }
+        }
+        RDFFormat::Turtle => {
+            let mut previous_subject: Option<Subject> = None;
+            for (s, p, o) in triples {
+                if previous_subject.as_ref() == Some(&s) {
+                    write!(writer, " ;\n    {} {}", p, o)?;
+                } else {
+                    if previous_subject.is_some() {
+                        writeln!(writer, " .")?;
+                    }
+                    write!(writer, "{} {} {}", s, p, o)?;
+                    previous_subject = Some(s);
+                }
+            }
+            if previous_subject.is_some() {
+                writeln!(writer, " .")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn template_variables(tp: &TriplePattern) -> Vec<Variable> {
+    let mut variables = vec![];
+    if let TermPattern::Variable(v) = &tp.subject {
+        variables.push(v.clone());
+    }
+    if let NamedNodePattern::Variable(v) = &tp.predicate {
+        variables.push(v.clone());
+    }
+    if let TermPattern::Variable(v) = &tp.object {
+        variables.push(v.clone());
+    }
+    variables
+}
+
+fn find_data_point_triples(
+    graph_pattern: &GraphPattern,
+    time_series_of_data_point: &mut HashMap<Variable, Variable>,
+    timestamp_of_data_point: &mut HashMap<Variable, Variable>,
+) {
+    match graph_pattern {
+        GraphPattern::Bgp { patterns } => {
+            for p in patterns {
+                if let (
+                    TermPattern::Variable(s),
+                    NamedNodePattern::NamedNode(nn),
+                    TermPattern::Variable(o),
+                ) = (&p.subject, &p.predicate, &p.object)
+                {
+                    if nn.as_str() == HAS_DATA_POINT {
+                        time_series_of_data_point.insert(o.clone(), s.clone());
+                    } else if nn.as_str() == HAS_TIMESTAMP {
+                        timestamp_of_data_point.insert(s.clone(), o.clone());
+                    }
+                }
+            }
+        }
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            find_data_point_triples(left, time_series_of_data_point, timestamp_of_data_point);
+            find_data_point_triples(right, time_series_of_data_point, timestamp_of_data_point);
+        }
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::Graph { inner, .. }
+        | GraphPattern::Extend { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. } => {
+            find_data_point_triples(inner, time_series_of_data_point, timestamp_of_data_point);
+        }
+        _ => {}
+    }
+}
+
+//Variables that may be bound in the solutions of the graph pattern
+pub(crate) fn find_in_scope_variables(
+    graph_pattern: &GraphPattern,
+    variables: &mut HashSet<Variable>,
+) {
+    match graph_pattern {
+        GraphPattern::Bgp { patterns } => {
+            for p in patterns {
+                variables.extend(template_variables(p));
+            }
+        }
+        GraphPattern::Path {
+            subject, object, ..
+        } => {
+            for t in [subject, object] {
+                if let TermPattern::Variable(v) = t {
+                    variables.insert(v.clone());
+                }
+            }
+        }
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Union { left, right } => {
+            find_in_scope_variables(left, variables);
+            find_in_scope_variables(right, variables);
+        }
+        GraphPattern::Minus { left, .. } => {
+            find_in_scope_variables(left, variables);
+        }
+        GraphPattern::Extend {
+            inner, variable, ..
+        } => {
+            find_in_scope_variables(inner, variables);
+            variables.insert(variable.clone());
+        }
+        GraphPattern::Graph { name, inner } => {
+            if let NamedNodePattern::Variable(v) = name {
+                variables.insert(v.clone());
+            }
+            find_in_scope_variables(inner, variables);
+        }
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. }
+        | GraphPattern::Service { inner, .. } => {
+            find_in_scope_variables(inner, variables);
+        }
+        GraphPattern::Project {
+            variables: project_variables,
+            ..
+        } => {
+            variables.extend(project_variables.iter().cloned());
+        }
+        GraphPattern::Group {
+            variables: by,
+            aggregates,
+            ..
+        } => {
+            variables.extend(by.iter().cloned());
+            variables.extend(aggregates.iter().map(|(v, _)| v.clone()));
+        }
+        GraphPattern::Values { variables: vs, .. } => {
+            variables.extend(vs.iter().cloned());
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::combiner::Combiner;
+use crate::function_registry::FunctionRegistry;
+use crate::timeseries_database::opcua_subscription::OPCUASubscription;
+use crate::timeseries_query::TimeSeriesQuery;
+use log::debug;
+use polars::frame::DataFrame;
+use spargebra::Query;
+use std::error::Error;
+use std::time::Duration;
+
+//A hybrid query that is evaluated again each time the subscribed time series change.
+//The static part of the query is only resolved once, when the query is created.
+pub struct ContinuousQuery {
+    parsed_query: Query,
+    static_result_df: DataFrame,
+    function_registry: FunctionRegistry,
+    subscription: OPCUASubscription,
+}
+
+impl ContinuousQuery {
+    pub(crate) fn new(
+        parsed_query: Query,
+        static_result_df: DataFrame,
+        function_registry: FunctionRegistry,
+        subscription: OPCUASubscription,
+    ) -> ContinuousQuery {
+        ContinuousQuery {
+            parsed_query,
+            static_result_df,
+            function_registry,
+            subscription,
+        }
+    }
+
+    //Blocks until the next data change, and returns the query result for the changed values
+    pub fn next_result(&mut self) -> Result<DataFrame, Box<dyn Error>> {
+        let time_series = self.subscription.next_changes(None)?.unwrap();
+        self.combine(time_series)
+    }
+
+    //Returns None if there were no data changes within the timeout
+    pub fn next_result_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<DataFrame>, Box<dyn Error>> {
+        if let Some(time_series) = self.subscription.next_changes(Some(timeout))? {
+            Ok(Some(self.combine(time_series)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    //Calls the callback with each new result until it returns false
+    pub fn for_each_result<F>(&mut self, mut callback: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(DataFrame) -> bool,
+    {
+        loop {
+            let df = self.next_result()?;
+            if !callback(df) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn combine(
+        &self,
+        mut time_series: Vec<(TimeSeriesQuery, DataFrame)>,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        for (tsq, df) in &time_series {
+            tsq.validate(df)?;
+        }
+        debug!("Time series: {:?}", time_series);
+        let mut combiner = Combiner::new(&self.function_registry);
+        let lazy_frame = combiner.combine_static_and_time_series_results(
+            &self.parsed_query,
+            self.static_result_df.clone(),
+            &mut time_series,
+        )?;
+        Ok(lazy_frame.collect()?)
+    }
+}
@@ -0,0 +1,93 @@
+use crate::constants::{
+    HAS_DATA_POINT, HAS_FIRST_TIMESTAMP, HAS_LAST_TIMESTAMP, HAS_LATEST_TIMESTAMP,
+    HAS_LATEST_VALUE, HAS_TIMESTAMP,
+};
+use crate::construct::any_value_to_term;
+use oxrdf::{NamedNode, Subject, Term};
+use polars::frame::DataFrame;
+use polars::prelude::AnyValue;
+
+pub(crate) const TIME_SERIES_VARIABLE_NAME: &str = "ts";
+const FIRST_TIMESTAMP_VARIABLE_NAME: &str = "first_timestamp";
+const LAST_TIMESTAMP_VARIABLE_NAME: &str = "last_timestamp";
+const LATEST_VALUE_VARIABLE_NAME: &str = "latest_value";
+
+//The static triples with the described resources as subjects
+pub(crate) fn description_query(resources: &Vec<NamedNode>) -> String {
+    format!(
+        "SELECT ?s ?p ?o WHERE {{ VALUES ?s {{ {} }} ?s ?p ?o . }}",
+        values(resources)
+    )
+}
+
+//The data points of a single time series, of which only the earliest is read
+pub(crate) fn first_timestamp_query(time_series: &NamedNode) -> String {
+    format!(
+        "SELECT ?{ts} ?{first} WHERE {{ VALUES ?{ts} {{ {values} }} ?{ts} <{has_data_point}> ?dp . ?dp <{has_timestamp}> ?{first} . }}",
+        ts = TIME_SERIES_VARIABLE_NAME,
+        first = FIRST_TIMESTAMP_VARIABLE_NAME,
+        values = time_series,
+        has_data_point = HAS_DATA_POINT,
+        has_timestamp = HAS_TIMESTAMP
+    )
+}
+
+//The timestamp of the latest value is the last timestamp of the time series
+pub(crate) fn latest_value_query(time_series: &Vec<NamedNode>) -> String {
+    format!(
+        "SELECT ?{ts} ?{latest} ?{last} WHERE {{ VALUES ?{ts} {{ {values} }} ?{ts} <{has_latest_value}> ?{latest} . ?{ts} <{has_latest_timestamp}> ?{last} . }}",
+        ts = TIME_SERIES_VARIABLE_NAME,
+        latest = LATEST_VALUE_VARIABLE_NAME,
+        last = LAST_TIMESTAMP_VARIABLE_NAME,
+        values = values(time_series),
+        has_latest_value = HAS_LATEST_VALUE,
+        has_latest_timestamp = HAS_LATEST_TIMESTAMP
+    )
+}
+
+pub(crate) fn first_timestamp_triples(df: &DataFrame) -> Vec<(Subject, NamedNode, Term)> {
+    summary_triples(df, &[(FIRST_TIMESTAMP_VARIABLE_NAME, HAS_FIRST_TIMESTAMP)])
+}
+
+pub(crate) fn latest_value_triples(df: &DataFrame) -> Vec<(Subject, NamedNode, Term)> {
+    summary_triples(
+        df,
+        &[
+            (LATEST_VALUE_VARIABLE_NAME, HAS_LATEST_VALUE),
+            (LAST_TIMESTAMP_VARIABLE_NAME, HAS_LAST_TIMESTAMP),
+        ],
+    )
+}
+
+//Triples with the time series as subject and the values of the columns as objects
+fn summary_triples(df: &DataFrame, columns: &[(&str, &str)]) -> Vec<(Subject, NamedNode, Term)> {
+    let mut triples = vec![];
+    let time_series = if let Ok(time_series) = df.column(TIME_SERIES_VARIABLE_NAME) {
+        time_series
+    } else {
+        return triples;
+    };
+    for (c, predicate) in columns {
+        if let Ok(values) = df.column(c) {
+            for (ts, value) in time_series.iter().zip(values.iter()) {
+                if let (AnyValue::Utf8(ts), Some(object)) = (ts, any_value_to_term(&value, false)) {
+                    if let Ok(subject) = NamedNode::new(ts) {
+                        triples.push((
+                            Subject::NamedNode(subject),
+                            NamedNode::new_unchecked(*predicate),
+                            object,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    triples
+}
+
+fn values(iris: &Vec<NamedNode>) -> String {
+    iris.iter()
+        .map(|nn| nn.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -1,25 +1,49 @@
 use crate::combiner::Combiner;
+use crate::constants::{HAS_EXTERNAL_ID, HAS_TIMESERIES};
+use crate::construct::{
+    find_in_scope_variables, write_solution_triples, ConstructTemplate, RDFFormat,
+};
+use crate::continuous_query::ContinuousQuery;
+use crate::describe::{
+    description_query, first_timestamp_query, first_timestamp_triples, latest_value_query,
+    latest_value_triples,
+};
+use crate::federation::{join_service_results, split_service_queries, ServiceQuery};
+use crate::function_registry::{CustomAggregate, CustomFunction, FunctionRegistry};
 use crate::preparing::TimeSeriesQueryPrepper;
 use crate::preprocessing::Preprocessor;
 use crate::pushdown_setting::PushdownSetting;
 use crate::rewriting::StaticQueryRewriter;
 use crate::sparql_result_to_polars::create_static_query_result_df;
-use crate::splitter::parse_sparql_select_query;
+use crate::splitter::{
+    parse_sparql_ask_query, parse_sparql_construct_query, parse_sparql_describe_query,
+    parse_sparql_select_query,
+};
 use crate::static_sparql::execute_sparql_query;
+use crate::timeseries_database::opcua_subscription::OPCUASubscriber;
 use crate::timeseries_database::TimeSeriesQueryable;
 use crate::timeseries_query::{BasicTimeSeriesQuery, TimeSeriesQuery};
 use log::debug;
 use oxrdf::vocab::xsd;
-use oxrdf::Term;
+use oxrdf::{Subject, Term, Variable};
 use polars::frame::DataFrame;
+use polars::prelude::{DataType, PolarsError, Series};
+use serde::Deserialize;
 use sparesults::QuerySolution;
+use spargebra::algebra::{GraphPattern, QueryDataset};
+use spargebra::Query;
 use std::collections::HashSet;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::io::Write;
 
 #[derive(Debug)]
 pub enum OrchestrationError {
     InconsistentDatatype(String, String, String),
+    StaticSolutionLimitExceeded(usize, usize),
+    TimeSeriesRowLimitExceeded(usize, usize),
+    UnsupportedService(String),
+    ServiceEndpointNotAllowed(String),
 }
 
 impl Display for OrchestrationError {
@@ -32,15 +56,82 @@ impl Display for OrchestrationError {
                     s1, s2, s3
                 )
             }
+            OrchestrationError::StaticSolutionLimitExceeded(n, limit) => {
+                write!(
+                    f,
+                    "Static query produced {} solutions, exceeding the limit of {}",
+                    n, limit
+                )
+            }
+            OrchestrationError::TimeSeriesRowLimitExceeded(n, limit) => {
+                write!(
+                    f,
+                    "Time series queries produced {} rows, exceeding the limit of {}",
+                    n, limit
+                )
+            }
+            OrchestrationError::UnsupportedService(s) => {
+                write!(f, "Unsupported service pattern, {}", s)
+            }
+            OrchestrationError::ServiceEndpointNotAllowed(endpoint) => {
+                write!(
+                    f,
+                    "Service endpoint {} is not among the allowed federation endpoints",
+                    endpoint
+                )
+            }
         }
     }
 }
 
 impl Error for OrchestrationError {}
 
+//Limits on the size of intermediate results, no limits are applied by default
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EngineLimits {
+    pub max_static_solutions: Option<usize>,
+    //Total number of rows over all time series queries of a hybrid query
+    pub max_time_series_rows: Option<usize>,
+}
+
+pub struct QueryExplanation {
+    pub static_rewrite: Query,
+    pub static_solutions: usize,
+    pub time_series_queries: Vec<TimeSeriesQuery>,
+    //The query strings of the time series queries, if the database uses a query language
+    pub time_series_query_strings: Vec<Option<String>>,
+}
+
+impl Display for QueryExplanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Static query:")?;
+        writeln!(f, "{}", self.static_rewrite)?;
+        writeln!(f, "Static solutions: {}", self.static_solutions)?;
+        for (i, (tsq, query_string)) in self
+            .time_series_queries
+            .iter()
+            .zip(self.time_series_query_strings.iter())
+            .enumerate()
+        {
+            writeln!(f, "Time series query {}:", i)?;
+            if let Some(query_string) = query_string {
+                writeln!(f, "{}", query_string)?;
+            } else {
+                writeln!(f, "{:?}", tsq)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct Engine {
     pushdown_settings: HashSet<PushdownSetting>,
     time_series_database: Box<dyn TimeSeriesQueryable>,
+    function_registry: FunctionRegistry,
+    sparql_endpoint: Option<String>,
+    limits: EngineLimits,
+    federation_endpoints: HashSet<String>,
 }
 
 impl Engine {
@@ -51,18 +142,323 @@ impl Engine {
         Engine {
             pushdown_settings,
             time_series_database,
+            function_registry: FunctionRegistry::new(),
+            sparql_endpoint: None,
+            limits: EngineLimits::default(),
+            federation_endpoints: HashSet::new(),
         }
     }
 
+    pub fn with_sparql_endpoint(mut self, endpoint: &str) -> Self {
+        self.sparql_endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    pub fn with_limits(mut self, limits: EngineLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //Endpoints that service patterns in queries may refer to, no services are allowed by default
+    pub fn with_federation_endpoints(mut self, endpoints: HashSet<String>) -> Self {
+        self.federation_endpoints = endpoints;
+        self
+    }
+
+    //The endpoint given in the configuration of the engine, if any
+    pub fn sparql_endpoint(&self) -> Option<&str> {
+        self.sparql_endpoint.as_deref()
+    }
+
+    pub fn register_function(&mut self, iri: &str, function: CustomFunction) {
+        self.function_registry.register_function(iri, function);
+    }
+
+    pub fn register_aggregate(&mut self, iri: &str, aggregate: CustomAggregate) {
+        self.function_registry.register_aggregate(iri, aggregate);
+    }
+
     pub async fn execute_hybrid_query(
         &mut self,
         query: &str,
         endpoint: &str,
     ) -> Result<DataFrame, Box<dyn Error>> {
         let parsed_query = parse_sparql_select_query(query)?;
-        debug!("Parsed query: {:?}", &parsed_query);
+        let (df, _) = self.execute_parsed_query(&parsed_query, endpoint).await?;
+        Ok(df)
+    }
+
+    //Also returns the variables with IRIs, as these are strings in the solutions
+    pub async fn execute_hybrid_query_with_iri_variables(
+        &mut self,
+        query: &str,
+        endpoint: &str,
+    ) -> Result<(DataFrame, HashSet<Variable>), Box<dyn Error>> {
+        let parsed_query = parse_sparql_select_query(query)?;
+        self.execute_parsed_query(&parsed_query, endpoint).await
+    }
+
+    //Instantiates the template of a construct query with the solutions of its where clause,
+    //writing the triples as they are produced. Returns the number of triples written.
+    pub async fn execute_construct_query<W: Write>(
+        &mut self,
+        query: &str,
+        endpoint: &str,
+        format: RDFFormat,
+        writer: &mut W,
+    ) -> Result<usize, Box<dyn Error>> {
+        let (template, dataset, where_pattern) = parse_sparql_construct_query(query)?;
+        let construct_template = ConstructTemplate::new(template, &where_pattern);
+        let select_query = construct_template.select_query(dataset, where_pattern);
+        let (df, iri_variables) = self.execute_parsed_query(&select_query, endpoint).await?;
+        Ok(construct_template.write_triples(&df, &iri_variables, format, writer)?)
+    }
+
+    //Answers whether the where clause has any solution. When a single time series query decides the answer,
+    //it is limited to one row, and otherwise the combination stops at the first solution.
+    pub async fn execute_ask_query(
+        &mut self,
+        query: &str,
+        endpoint: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let (dataset, pattern) = parse_sparql_ask_query(query)?;
+        let mut in_scope = HashSet::new();
+        find_in_scope_variables(&pattern, &mut in_scope);
+        let mut variables: Vec<Variable> = in_scope.into_iter().collect();
+        variables.sort();
+        let select_query = Query::Select {
+            dataset,
+            pattern: GraphPattern::Project {
+                inner: Box::new(pattern.clone()),
+                variables,
+            },
+            base_iri: None,
+        };
+        let (_, static_result_df, time_series_queries, _) = self
+            .prepare_hybrid_query(&select_query, endpoint, self.pushdown_settings.clone())
+            .await?;
+        //Time series only extend the static solutions
+        if static_result_df.height() == 0 {
+            return Ok(false);
+        }
+        if time_series_queries.is_empty() {
+            return Ok(true);
+        }
+        if let Some(tsq) = deciding_time_series_query(&pattern, &time_series_queries) {
+            let df = self
+                .time_series_database
+                .execute_with_limit(tsq, &self.function_registry, 1)
+                .await?;
+            return Ok(df.height() > 0);
+        }
+        let mut time_series = self
+            .execute_time_series_queries(time_series_queries)
+            .await?;
+        let mut combiner = Combiner::new(&self.function_registry);
+        let lazy_frame = combiner.combine_static_and_time_series_results(
+            &select_query,
+            static_result_df,
+            &mut time_series,
+        )?;
+        Ok(lazy_frame.limit(1).collect()?.height() > 0)
+    }
+
+    //Writes the static triples of the described resources, and the first and last timestamps
+    //and the latest values of their time series. Returns the number of triples written.
+    pub async fn execute_describe_query<W: Write>(
+        &mut self,
+        query: &str,
+        endpoint: &str,
+        format: RDFFormat,
+        writer: &mut W,
+    ) -> Result<usize, Box<dyn Error>> {
+        let (dataset, pattern) = parse_sparql_describe_query(query)?;
+        let resources_query = Query::Select {
+            dataset: dataset.clone(),
+            pattern,
+            base_iri: None,
+        };
+        let mut resources = vec![];
+        for solution in execute_sparql_query(endpoint, &resources_query).await? {
+            for (_, term) in solution.iter() {
+                if let Term::NamedNode(nn) = term {
+                    if !resources.contains(nn) {
+                        resources.push(nn.clone());
+                    }
+                }
+            }
+        }
+        if resources.is_empty() {
+            return Ok(0);
+        }
+
+        let description_query = with_dataset(
+            Query::parse(&description_query(&resources), None)?,
+            &dataset,
+        );
+        let mut triples = vec![];
+        let mut time_series = vec![];
+        for solution in execute_sparql_query(endpoint, &description_query).await? {
+            if let (Some(Term::NamedNode(s)), Some(Term::NamedNode(p)), Some(o)) =
+                (solution.get("s"), solution.get("p"), solution.get("o"))
+            {
+                //Described resources are either time series or have time series
+                let ts = if p.as_str() == HAS_TIMESERIES {
+                    if let Term::NamedNode(ts) = o {
+                        Some(ts.clone())
+                    } else {
+                        None
+                    }
+                } else if p.as_str() == HAS_EXTERNAL_ID {
+                    Some(s.clone())
+                } else {
+                    None
+                };
+                if let Some(ts) = ts {
+                    if !time_series.contains(&ts) {
+                        time_series.push(ts);
+                    }
+                }
+                triples.push((Subject::NamedNode(s.clone()), p.clone(), o.clone()));
+            }
+        }
+        let mut count = triples.len();
+        write_solution_triples(triples, format, writer)?;
+        if !time_series.is_empty() {
+            let latest_query = with_dataset(
+                parse_sparql_select_query(&latest_value_query(&time_series))?,
+                &dataset,
+            );
+            let (latest_df, _) = self.execute_parsed_query(&latest_query, endpoint).await?;
+            let mut summary_triples = latest_value_triples(&latest_df);
+            for ts in &time_series {
+                let first_query = with_dataset(
+                    parse_sparql_select_query(&first_timestamp_query(ts))?,
+                    &dataset,
+                );
+                let first_df = self.execute_earliest_query(&first_query, endpoint).await?;
+                summary_triples.extend(first_timestamp_triples(&first_df));
+            }
+            summary_triples.sort_by(|(a, _, _), (b, _, _)| a.to_string().cmp(&b.to_string()));
+            count += summary_triples.len();
+            write_solution_triples(summary_triples, format, writer)?;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    //Returns the solutions and the variables with IRIs in the static solutions
+    async fn execute_parsed_query(
+        &mut self,
+        parsed_query: &Query,
+        endpoint: &str,
+    ) -> Result<(DataFrame, HashSet<Variable>), Box<dyn Error>> {
+        let (_, static_result_df, time_series_queries, iri_variables) = self
+            .prepare_hybrid_query(parsed_query, endpoint, self.pushdown_settings.clone())
+            .await?;
+        debug!("Static result dataframe: {}", static_result_df);
+        if static_result_df.height() == 0 {
+            //Time series only extend the static solutions
+            Ok((empty_solutions_df(parsed_query)?, iri_variables))
+        } else {
+            let mut time_series = self
+                .execute_time_series_queries(time_series_queries)
+                .await?;
+            debug!("Time series: {:?}", time_series);
+            let mut combiner = Combiner::new(&self.function_registry);
+            let lazy_frame = combiner.combine_static_and_time_series_results(
+                parsed_query,
+                static_result_df,
+                &mut time_series,
+            )?;
+            Ok((lazy_frame.collect()?, iri_variables))
+        }
+    }
+
+    //Reads only the earliest row of the single time series query of the query,
+    //so that the first data point is found without reading the whole history
+    async fn execute_earliest_query(
+        &mut self,
+        parsed_query: &Query,
+        endpoint: &str,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let (_, static_result_df, time_series_queries, _) = self
+            .prepare_hybrid_query(parsed_query, endpoint, self.pushdown_settings.clone())
+            .await?;
+        if static_result_df.height() == 0 || time_series_queries.len() != 1 {
+            return Ok(empty_solutions_df(parsed_query)?);
+        }
+        let tsq = time_series_queries.into_iter().next().unwrap();
+        let df = self
+            .time_series_database
+            .execute_earliest(&tsq, &self.function_registry, 1)
+            .await?;
+        tsq.validate(&df)?;
+        let mut combiner = Combiner::new(&self.function_registry);
+        let lazy_frame = combiner.combine_static_and_time_series_results(
+            parsed_query,
+            static_result_df,
+            &mut vec![(tsq, df)],
+        )?;
+        Ok(lazy_frame.collect()?)
+    }
+
+    //Executes the static part of the query, and describes the time series queries without executing them
+    pub async fn explain_hybrid_query(
+        &self,
+        query: &str,
+        endpoint: &str,
+    ) -> Result<QueryExplanation, Box<dyn Error>> {
+        let parsed_query = parse_sparql_select_query(query)?;
+        let (static_rewrite, static_result_df, time_series_queries, _) = self
+            .prepare_hybrid_query(&parsed_query, endpoint, self.pushdown_settings.clone())
+            .await?;
+        let mut time_series_query_strings = vec![];
+        for tsq in &time_series_queries {
+            time_series_query_strings.push(
+                self.time_series_database
+                    .query_string(tsq, &self.function_registry)?,
+            );
+        }
+        Ok(QueryExplanation {
+            static_rewrite,
+            static_solutions: static_result_df.height(),
+            time_series_queries,
+            time_series_query_strings,
+        })
+    }
+
+    //Resolves the static part of the query once, and evaluates the query on each data change
+    pub async fn subscribe_hybrid_query(
+        &self,
+        query: &str,
+        endpoint: &str,
+        subscriber: OPCUASubscriber,
+    ) -> Result<ContinuousQuery, Box<dyn Error>> {
+        let parsed_query = parse_sparql_select_query(query)?;
+        //Filters and aggregations are applied to the changed values as they arrive
+        let (_, static_result_df, time_series_queries, _) = self
+            .prepare_hybrid_query(&parsed_query, endpoint, HashSet::new())
+            .await?;
+        debug!("Static result dataframe: {}", static_result_df);
+        let subscription = subscriber.subscribe(time_series_queries)?;
+        Ok(ContinuousQuery::new(
+            parsed_query,
+            static_result_df,
+            self.function_registry.clone(),
+            subscription,
+        ))
+    }
+
+    async fn prepare_hybrid_query(
+        &self,
+        parsed_query: &Query,
+        endpoint: &str,
+        pushdown_settings: HashSet<PushdownSetting>,
+    ) -> Result<(Query, DataFrame, Vec<TimeSeriesQuery>, HashSet<Variable>), Box<dyn Error>> {
+        debug!("Parsed query: {:?}", parsed_query);
         let mut preprocessor = Preprocessor::new();
-        let (preprocessed_query, variable_constraints) = preprocessor.preprocess(&parsed_query);
+        let (preprocessed_query, variable_constraints) = preprocessor.preprocess(parsed_query);
         debug!("Constraints: {:?}", variable_constraints);
         let mut rewriter = StaticQueryRewriter::new(&variable_constraints);
         let (static_rewrite, mut basic_time_series_queries) =
@@ -72,59 +468,133 @@ impl Engine {
             "Produced basic time series queries: {:?}",
             basic_time_series_queries
         );
-        let static_query_solutions = execute_sparql_query(endpoint, &static_rewrite).await?;
-        complete_basic_time_series_queries(
-            &static_query_solutions,
-            &mut basic_time_series_queries,
-        )?;
-        let static_result_df =
-            create_static_query_result_df(&static_rewrite, static_query_solutions);
+        let (static_result_df, iri_variables) = self
+            .execute_static_query(&static_rewrite, endpoint, &mut basic_time_series_queries)
+            .await?;
         let StaticQueryRewriter {
             rewritten_filters, ..
         } = rewriter;
         let mut prepper = TimeSeriesQueryPrepper::new(
-            self.pushdown_settings.clone(),
+            pushdown_settings,
             self.time_series_database
                 .allow_compound_timeseries_queries(),
+            self.time_series_database.function_pushdowns(),
+            self.time_series_database
+                .custom_function_pushdowns(&self.function_registry),
+            self.time_series_database
+                .custom_aggregate_pushdowns(&self.function_registry),
+            self.time_series_database.aggregate_pushdowns(),
             basic_time_series_queries,
             static_result_df,
             rewritten_filters,
         );
-        let time_series_queries = prepper.prepare(&parsed_query);
+        let time_series_queries = prepper.prepare(parsed_query);
         let TimeSeriesQueryPrepper {
             static_result_df, ..
         } = prepper;
-        debug!("Static result dataframe: {}", static_result_df);
-        if static_result_df.height() == 0 {
-            todo!("Empty static df not supported yet")
-        } else {
-            let mut time_series = self
-                .execute_time_series_queries(time_series_queries)
-                .await?;
-            debug!("Time series: {:?}", time_series);
-            let mut combiner = Combiner::new();
-            let lazy_frame = combiner.combine_static_and_time_series_results(
-                &parsed_query,
-                static_result_df,
-                &mut time_series,
-            );
-            Ok(lazy_frame.collect()?)
+        Ok((
+            static_rewrite,
+            static_result_df,
+            time_series_queries,
+            iri_variables,
+        ))
+    }
+
+    //Service patterns are queried separately, and their solutions are joined with the static solutions
+    async fn execute_static_query(
+        &self,
+        static_rewrite: &Query,
+        endpoint: &str,
+        basic_time_series_queries: &mut Vec<BasicTimeSeriesQuery>,
+    ) -> Result<(DataFrame, HashSet<Variable>), Box<dyn Error>> {
+        let (local_query, service_queries) = split_service_queries(static_rewrite)?;
+        let local_solutions = execute_sparql_query(endpoint, &local_query).await?;
+        let mut iri_variables = find_iri_variables(&local_solutions);
+        if service_queries.is_empty() {
+            self.check_static_solution_limit(local_solutions.len())?;
+            complete_basic_time_series_queries(&local_solutions, basic_time_series_queries)?;
+            let static_result_df = create_static_query_result_df(static_rewrite, local_solutions);
+            return Ok((static_result_df, iri_variables));
+        }
+        debug!("Static query without services: {}", local_query);
+        let mut all_solutions = local_solutions.clone();
+        let local_df = create_static_query_result_df(&local_query, local_solutions);
+        let mut service_dfs = vec![];
+        for service_query in &service_queries {
+            if let Some(solutions) = self.execute_service_query(service_query).await? {
+                iri_variables.extend(find_iri_variables(&solutions));
+                all_solutions.extend(solutions.iter().cloned());
+                service_dfs.push(create_static_query_result_df(
+                    &service_query.query,
+                    solutions,
+                ));
+            }
+        }
+        let static_result_df = join_service_results(local_df, service_dfs, static_rewrite)?;
+        self.check_static_solution_limit(static_result_df.height())?;
+        complete_basic_time_series_queries(&all_solutions, basic_time_series_queries)?;
+        restrict_time_series_ids(&static_result_df, basic_time_series_queries);
+        Ok((static_result_df, iri_variables))
+    }
+
+    async fn execute_service_query(
+        &self,
+        service_query: &ServiceQuery,
+    ) -> Result<Option<Vec<QuerySolution>>, Box<dyn Error>> {
+        let endpoint = service_query.endpoint.as_str();
+        if !self.federation_endpoints.contains(endpoint) {
+            return Err(Box::new(OrchestrationError::ServiceEndpointNotAllowed(
+                endpoint.to_string(),
+            )));
+        }
+        debug!("Service query to {}: {}", endpoint, service_query.query);
+        match execute_sparql_query(endpoint, &service_query.query).await {
+            Ok(solutions) => Ok(Some(solutions)),
+            //Failing silent services do not restrict the solutions
+            Err(err) if service_query.silent => {
+                debug!("Ignoring error from silent service {}: {}", endpoint, err);
+                Ok(None)
+            }
+            Err(err) => Err(Box::new(err)),
         }
     }
 
+    fn check_static_solution_limit(&self, solutions: usize) -> Result<(), OrchestrationError> {
+        if let Some(limit) = self.limits.max_static_solutions {
+            if solutions > limit {
+                return Err(OrchestrationError::StaticSolutionLimitExceeded(
+                    solutions, limit,
+                ));
+            }
+        }
+        Ok(())
+    }
+
     async fn execute_time_series_queries(
         &mut self,
         time_series_queries: Vec<TimeSeriesQuery>,
     ) -> Result<Vec<(TimeSeriesQuery, DataFrame)>, Box<dyn Error>> {
         let mut out = vec![];
+        let mut rows = 0;
         for tsq in time_series_queries {
-            let df_res = self.time_series_database.execute(&tsq).await;
+            let df_res = self
+                .time_series_database
+                .execute(&tsq, &self.function_registry)
+                .await;
             match df_res {
                 Ok(df) => {
                     match tsq.validate(&df) {
                         Ok(_) => {}
                         Err(err) => return Err(Box::new(err)),
                     }
+                    rows += df.height();
+                    if let Some(limit) = self.limits.max_time_series_rows {
+                        if rows > limit {
+                            return Err(Box::new(OrchestrationError::TimeSeriesRowLimitExceeded(
+                                rows, limit,
+                            )));
+                        }
+                    }
                     out.push((tsq, df))
                 }
                 Err(err) => return Err(err),
@@ -134,6 +604,112 @@ impl Engine {
     }
 }
 
+//A single row of the time series query is a solution when the where clause is a basic graph pattern,
+//or a basic graph pattern with a filter that is pushed down in full
+fn deciding_time_series_query<'a>(
+    pattern: &GraphPattern,
+    time_series_queries: &'a Vec<TimeSeriesQuery>,
+) -> Option<&'a TimeSeriesQuery> {
+    if time_series_queries.len() != 1 {
+        return None;
+    }
+    let tsq = time_series_queries.get(0).unwrap();
+    match (pattern, tsq) {
+        (GraphPattern::Bgp { .. }, TimeSeriesQuery::Basic(_)) => Some(tsq),
+        (GraphPattern::Filter { expr, inner }, TimeSeriesQuery::Filtered(inner_tsq, tsq_expr)) => {
+            if let (GraphPattern::Bgp { .. }, TimeSeriesQuery::Basic(_)) =
+                (inner.as_ref(), inner_tsq.as_ref())
+            {
+                if expr == tsq_expr {
+                    return Some(tsq);
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+//A frame without solutions, with a column for each projected variable
+fn empty_solutions_df(parsed_query: &Query) -> Result<DataFrame, PolarsError> {
+    let mut variables = vec![];
+    if let Query::Select { pattern, .. } = parsed_query {
+        let mut pattern = pattern;
+        loop {
+            match pattern {
+                GraphPattern::Slice { inner, .. }
+                | GraphPattern::Distinct { inner }
+                | GraphPattern::Reduced { inner }
+                | GraphPattern::OrderBy { inner, .. } => pattern = inner.as_ref(),
+                GraphPattern::Project {
+                    variables: project_variables,
+                    ..
+                } => {
+                    variables = project_variables.clone();
+                    break;
+                }
+                _ => {
+                    let mut in_scope = HashSet::new();
+                    find_in_scope_variables(pattern, &mut in_scope);
+                    variables = in_scope.into_iter().collect();
+                    variables.sort();
+                    break;
+                }
+            }
+        }
+    }
+    DataFrame::new(
+        variables
+            .iter()
+            .map(|v| Series::new_empty(v.as_str(), &DataType::Utf8))
+            .collect(),
+    )
+}
+
+//Queries made on behalf of a query are made against the same dataset
+fn with_dataset(query: Query, dataset: &Option<QueryDataset>) -> Query {
+    if let Query::Select {
+        pattern, base_iri, ..
+    } = query
+    {
+        Query::Select {
+            dataset: dataset.clone(),
+            pattern,
+            base_iri,
+        }
+    } else {
+        query
+    }
+}
+
+//Time series ids found by the separate service queries may not be part of the joined solutions
+fn restrict_time_series_ids(
+    static_result_df: &DataFrame,
+    basic_time_series_queries: &mut Vec<BasicTimeSeriesQuery>,
+) {
+    for basic_query in basic_time_series_queries {
+        if let (Some(id_var), Some(ids)) = (&basic_query.identifier_variable, &mut basic_query.ids)
+        {
+            if let Ok(Ok(column)) = static_result_df.column(id_var.as_str()).map(|c| c.utf8()) {
+                let present: HashSet<&str> = column.into_iter().flatten().collect();
+                ids.retain(|id| present.contains(id.as_str()));
+            }
+        }
+    }
+}
+
+fn find_iri_variables(static_query_solutions: &Vec<QuerySolution>) -> HashSet<Variable> {
+    let mut iri_variables = HashSet::new();
+    for sqs in static_query_solutions {
+        for (v, t) in sqs.iter() {
+            if let Term::NamedNode(_) = t {
+                iri_variables.insert(v.clone());
+            }
+        }
+    }
+    iri_variables
+}
+
 pub(crate) fn complete_basic_time_series_queries(
     static_query_solutions: &Vec<QuerySolution>,
     basic_time_series_queries: &mut Vec<BasicTimeSeriesQuery>,
@@ -0,0 +1,301 @@
+use crate::construct::find_in_scope_variables;
+use crate::engine::OrchestrationError;
+use crate::find_query_variables::find_all_used_variables_in_expression;
+use crate::sparql_result_to_polars::find_projected_variables;
+use oxrdf::{NamedNode, Variable};
+use polars::frame::DataFrame;
+use polars::prelude::{col, Expr, IntoLazy, JoinType, PolarsError};
+use spargebra::algebra::{Expression, GraphPattern};
+use spargebra::term::NamedNodePattern;
+use spargebra::Query;
+use std::collections::HashSet;
+
+pub(crate) struct ServiceQuery {
+    pub(crate) endpoint: NamedNode,
+    pub(crate) silent: bool,
+    pub(crate) query: Query,
+}
+
+//Service patterns are replaced by empty patterns in the static query and queried separately.
+//This is only equivalent when the solutions of the services are joined with the other solutions,
+//so services below optional, union, minus and grouping patterns are not supported.
+pub(crate) fn split_service_queries(
+    static_query: &Query,
+) -> Result<(Query, Vec<ServiceQuery>), OrchestrationError> {
+    if let Query::Select {
+        dataset,
+        pattern,
+        base_iri,
+    } = static_query
+    {
+        let mut service_queries = vec![];
+        let pattern = split_top_level_services(pattern, &mut service_queries)?;
+        Ok((
+            Query::Select {
+                dataset: dataset.clone(),
+                pattern,
+                base_iri: base_iri.clone(),
+            },
+            service_queries,
+        ))
+    } else {
+        Err(OrchestrationError::UnsupportedService(
+            "services are only supported in select queries".to_string(),
+        ))
+    }
+}
+
+//Joins the solutions of the services with the solutions of the static query
+pub(crate) fn join_service_results(
+    static_df: DataFrame,
+    service_dfs: Vec<DataFrame>,
+    static_query: &Query,
+) -> Result<DataFrame, PolarsError> {
+    let mut lf = static_df.clone().lazy();
+    let mut columns: HashSet<String> = static_df
+        .get_column_names()
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    for df in service_dfs {
+        let mut join_on = vec![];
+        for c in df.get_column_names() {
+            if !columns.insert(c.to_string()) {
+                join_on.push(col(c));
+            }
+        }
+        if join_on.is_empty() {
+            lf = lf.cross_join(df.lazy());
+        } else {
+            lf = lf.join(
+                df.lazy(),
+                join_on.as_slice(),
+                join_on.as_slice(),
+                JoinType::Inner,
+            );
+        }
+    }
+    //Variables shared only to join the services are not part of the static result
+    let projections: Vec<Expr> = find_projected_variables(static_query)
+        .iter()
+        .filter(|v| columns.contains(v.as_str()))
+        .map(|v| col(v.as_str()))
+        .collect();
+    lf.select(projections.as_slice()).collect()
+}
+
+fn split_top_level_services(
+    pattern: &GraphPattern,
+    service_queries: &mut Vec<ServiceQuery>,
+) -> Result<GraphPattern, OrchestrationError> {
+    match pattern {
+        GraphPattern::Slice {
+            inner,
+            start,
+            length,
+        } => {
+            let inner = split_top_level_services(inner, service_queries)?;
+            //Slices are applied again when combining, so they are only an optimization here
+            if service_queries.is_empty() {
+                Ok(GraphPattern::Slice {
+                    inner: Box::new(inner),
+                    start: *start,
+                    length: *length,
+                })
+            } else {
+                Ok(inner)
+            }
+        }
+        GraphPattern::Distinct { inner } => Ok(GraphPattern::Distinct {
+            inner: Box::new(split_top_level_services(inner, service_queries)?),
+        }),
+        GraphPattern::Reduced { inner } => Ok(GraphPattern::Reduced {
+            inner: Box::new(split_top_level_services(inner, service_queries)?),
+        }),
+        GraphPattern::Project { inner, variables } => {
+            let inner = split_services(inner, true, service_queries)?;
+            if service_queries.is_empty() {
+                return Ok(GraphPattern::Project {
+                    inner: Box::new(inner),
+                    variables: variables.clone(),
+                });
+            }
+            let mut in_scope = HashSet::new();
+            find_in_scope_variables(&inner, &mut in_scope);
+            let mut service_variables = HashSet::new();
+            for s in service_queries.iter() {
+                service_variables.extend(find_projected_variables(&s.query));
+            }
+            //Variables bound only by the services are no longer bound by the static query,
+            //and variables shared with the services are needed to join them
+            let mut project_variables: Vec<Variable> = variables
+                .iter()
+                .filter(|v| in_scope.contains(*v))
+                .cloned()
+                .collect();
+            let mut shared: Vec<&Variable> = in_scope
+                .iter()
+                .filter(|v| service_variables.contains(*v) && !variables.contains(*v))
+                .collect();
+            //Sort to make rewrites deterministic
+            shared.sort_by_key(|v| v.to_string());
+            project_variables.extend(shared.into_iter().cloned());
+            Ok(GraphPattern::Project {
+                inner: Box::new(inner),
+                variables: project_variables,
+            })
+        }
+        _ => split_services(pattern, true, service_queries),
+    }
+}
+
+fn split_services(
+    pattern: &GraphPattern,
+    joined: bool,
+    service_queries: &mut Vec<ServiceQuery>,
+) -> Result<GraphPattern, OrchestrationError> {
+    match pattern {
+        GraphPattern::Service {
+            name,
+            inner,
+            silent,
+        } => {
+            if !joined {
+                return Err(OrchestrationError::UnsupportedService(
+                    "service patterns must be joined with the rest of the query".to_string(),
+                ));
+            }
+            if let NamedNodePattern::NamedNode(endpoint) = name {
+                let mut in_scope = HashSet::new();
+                find_in_scope_variables(inner, &mut in_scope);
+                let mut variables: Vec<Variable> = in_scope.into_iter().collect();
+                //Sort to make rewrites deterministic
+                variables.sort_by_key(|v| v.to_string());
+                service_queries.push(ServiceQuery {
+                    endpoint: endpoint.clone(),
+                    silent: *silent,
+                    query: Query::Select {
+                        dataset: None,
+                        pattern: GraphPattern::Project {
+                            inner: inner.clone(),
+                            variables,
+                        },
+                        base_iri: None,
+                    },
+                });
+                Ok(GraphPattern::Bgp { patterns: vec![] })
+            } else {
+                Err(OrchestrationError::UnsupportedService(format!(
+                    "service endpoint must be an IRI, not {}",
+                    name
+                )))
+            }
+        }
+        GraphPattern::Join { left, right } => Ok(GraphPattern::Join {
+            left: Box::new(split_services(left, joined, service_queries)?),
+            right: Box::new(split_services(right, joined, service_queries)?),
+        }),
+        GraphPattern::LeftJoin {
+            left,
+            right,
+            expression,
+        } => Ok(GraphPattern::LeftJoin {
+            left: Box::new(split_services(left, joined, service_queries)?),
+            right: Box::new(split_services(right, false, service_queries)?),
+            expression: expression.clone(),
+        }),
+        GraphPattern::Minus { left, right } => Ok(GraphPattern::Minus {
+            left: Box::new(split_services(left, joined, service_queries)?),
+            right: Box::new(split_services(right, false, service_queries)?),
+        }),
+        GraphPattern::Union { left, right } => Ok(GraphPattern::Union {
+            left: Box::new(split_services(left, false, service_queries)?),
+            right: Box::new(split_services(right, false, service_queries)?),
+        }),
+        GraphPattern::Filter { expr, inner } => {
+            let first_service = service_queries.len();
+            let inner = split_services(inner, joined, service_queries)?;
+            check_expression_is_local(expr, &inner, &service_queries[first_service..])?;
+            Ok(GraphPattern::Filter {
+                expr: expr.clone(),
+                inner: Box::new(inner),
+            })
+        }
+        GraphPattern::Extend {
+            inner,
+            variable,
+            expression,
+        } => {
+            let first_service = service_queries.len();
+            let inner = split_services(inner, joined, service_queries)?;
+            check_expression_is_local(expression, &inner, &service_queries[first_service..])?;
+            Ok(GraphPattern::Extend {
+                inner: Box::new(inner),
+                variable: variable.clone(),
+                expression: expression.clone(),
+            })
+        }
+        GraphPattern::Graph { name, inner } => Ok(GraphPattern::Graph {
+            name: name.clone(),
+            inner: Box::new(split_services(inner, joined, service_queries)?),
+        }),
+        GraphPattern::OrderBy { inner, expression } => Ok(GraphPattern::OrderBy {
+            inner: Box::new(split_services(inner, joined, service_queries)?),
+            expression: expression.clone(),
+        }),
+        GraphPattern::Project { inner, variables } => Ok(GraphPattern::Project {
+            inner: Box::new(split_services(inner, false, service_queries)?),
+            variables: variables.clone(),
+        }),
+        GraphPattern::Distinct { inner } => Ok(GraphPattern::Distinct {
+            inner: Box::new(split_services(inner, false, service_queries)?),
+        }),
+        GraphPattern::Reduced { inner } => Ok(GraphPattern::Reduced {
+            inner: Box::new(split_services(inner, false, service_queries)?),
+        }),
+        GraphPattern::Slice {
+            inner,
+            start,
+            length,
+        } => Ok(GraphPattern::Slice {
+            inner: Box::new(split_services(inner, false, service_queries)?),
+            start: *start,
+            length: *length,
+        }),
+        GraphPattern::Group {
+            inner,
+            variables,
+            aggregates,
+        } => Ok(GraphPattern::Group {
+            inner: Box::new(split_services(inner, false, service_queries)?),
+            variables: variables.clone(),
+            aggregates: aggregates.clone(),
+        }),
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } | GraphPattern::Values { .. } => {
+            Ok(pattern.clone())
+        }
+    }
+}
+
+//Expressions are evaluated by the static query, where variables bound only by the services are unbound
+fn check_expression_is_local(
+    expression: &Expression,
+    local_pattern: &GraphPattern,
+    service_queries: &[ServiceQuery],
+) -> Result<(), OrchestrationError> {
+    let mut used_variables = HashSet::new();
+    find_all_used_variables_in_expression(expression, &mut used_variables);
+    let mut in_scope = HashSet::new();
+    find_in_scope_variables(local_pattern, &mut in_scope);
+    for s in service_queries {
+        for v in find_projected_variables(&s.query) {
+            if used_variables.contains(&v) && !in_scope.contains(&v) {
+                return Err(OrchestrationError::UnsupportedService(format!(
+                    "variable {} from the service {} is used in an expression outside it",
+                    v, s.endpoint
+                )));
+            }
+        }
+    }
+    Ok(())
+}
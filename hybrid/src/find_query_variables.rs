@@ -1,6 +1,6 @@
 use oxrdf::Variable;
 use spargebra::algebra::{AggregateExpression, Expression, GraphPattern};
-use spargebra::term::TermPattern;
+use spargebra::term::{NamedNodePattern, TermPattern};
 use std::collections::HashSet;
 
 pub fn find_all_used_variables_in_graph_pattern(
@@ -51,7 +51,10 @@ pub fn find_all_used_variables_in_graph_pattern(
             find_all_used_variables_in_graph_pattern(left, used_vars);
             find_all_used_variables_in_graph_pattern(right, used_vars);
         }
-        GraphPattern::Graph { inner, .. } => {
+        GraphPattern::Graph { name, inner } => {
+            if let NamedNodePattern::Variable(v) = name {
+                used_vars.insert(v.clone());
+            }
             find_all_used_variables_in_graph_pattern(inner, used_vars);
         }
         GraphPattern::Extend {
@@ -0,0 +1,127 @@
+use crate::constants::{
+    DATETIME_AS_NANOS, DATETIME_AS_SECONDS, FILL_FORWARD, INTERPOLATE_LINEAR, NANOS_AS_DATETIME,
+    RESAMPLE, SECONDS_AS_DATETIME,
+};
+use oxrdf::vocab::xsd;
+use polars::prelude::Expr;
+use sea_query::{Alias, Function, SimpleExpr};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub type PolarsFunction = Arc<dyn Fn(Vec<Expr>) -> Expr + Send + Sync>;
+pub type SQLFunction = Arc<dyn Fn(Vec<SimpleExpr>) -> SimpleExpr + Send + Sync>;
+pub type PolarsAggregate = Arc<dyn Fn(Expr) -> Expr + Send + Sync>;
+pub type SQLAggregate = Arc<dyn Fn(SimpleExpr) -> SimpleExpr + Send + Sync>;
+
+#[derive(Clone)]
+pub struct CustomFunction {
+    pub polars_function: PolarsFunction,
+    pub sql_function: Option<SQLFunction>,
+}
+
+impl CustomFunction {
+    pub fn new(polars_function: PolarsFunction) -> CustomFunction {
+        CustomFunction {
+            polars_function,
+            sql_function: None,
+        }
+    }
+
+    pub fn with_sql_function(mut self, sql_function: SQLFunction) -> CustomFunction {
+        self.sql_function = Some(sql_function);
+        self
+    }
+
+    //Pushes the function down as a call to the SQL function with the same arguments
+    pub fn with_sql_function_name(self, name: &str) -> CustomFunction {
+        let name = name.to_string();
+        self.with_sql_function(Arc::new(move |args| {
+            SimpleExpr::FunctionCall(Function::Custom(Rc::new(Alias::new(&name))), args)
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct CustomAggregate {
+    pub polars_aggregate: PolarsAggregate,
+    pub sql_aggregate: Option<SQLAggregate>,
+    //Numeric part of the NodeId of an OPC UA Part 13 aggregate function in namespace 0
+    pub opcua_aggregate_function: Option<u32>,
+}
+
+impl CustomAggregate {
+    pub fn new(polars_aggregate: PolarsAggregate) -> CustomAggregate {
+        CustomAggregate {
+            polars_aggregate,
+            sql_aggregate: None,
+            opcua_aggregate_function: None,
+        }
+    }
+
+    pub fn with_sql_aggregate(mut self, sql_aggregate: SQLAggregate) -> CustomAggregate {
+        self.sql_aggregate = Some(sql_aggregate);
+        self
+    }
+
+    pub fn with_sql_aggregate_name(self, name: &str) -> CustomAggregate {
+        let name = name.to_string();
+        self.with_sql_aggregate(Arc::new(move |arg| {
+            SimpleExpr::FunctionCall(Function::Custom(Rc::new(Alias::new(&name))), vec![arg])
+        }))
+    }
+
+    pub fn with_opcua_aggregate_function(mut self, node_id: u32) -> CustomAggregate {
+        self.opcua_aggregate_function = Some(node_id);
+        self
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, CustomFunction>,
+    aggregates: HashMap<String, CustomAggregate>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> FunctionRegistry {
+        FunctionRegistry::default()
+    }
+
+    pub fn register_function(&mut self, iri: &str, function: CustomFunction) {
+        self.functions.insert(iri.to_string(), function);
+    }
+
+    pub fn register_aggregate(&mut self, iri: &str, aggregate: CustomAggregate) {
+        self.aggregates.insert(iri.to_string(), aggregate);
+    }
+
+    pub fn get_function(&self, iri: &str) -> Option<&CustomFunction> {
+        self.functions.get(iri)
+    }
+
+    pub fn get_aggregate(&self, iri: &str) -> Option<&CustomAggregate> {
+        self.aggregates.get(iri)
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = (&String, &CustomFunction)> {
+        self.functions.iter()
+    }
+
+    pub fn aggregates(&self) -> impl Iterator<Item = (&String, &CustomAggregate)> {
+        self.aggregates.iter()
+    }
+}
+
+//Custom functions with a built in implementation, these do not need to be registered
+pub(crate) fn is_builtin_function(iri: &str) -> bool {
+    iri == xsd::INTEGER.as_str()
+        || iri == xsd::STRING.as_str()
+        || iri == DATETIME_AS_NANOS
+        || iri == DATETIME_AS_SECONDS
+        || iri == NANOS_AS_DATETIME
+        || iri == SECONDS_AS_DATETIME
+        || iri == RESAMPLE
+        || iri == FILL_FORWARD
+        || iri == INTERPOLATE_LINEAR
+}
@@ -5,10 +5,16 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 pub mod change_types;
 pub mod combiner;
+pub mod config;
 pub mod constants;
 pub mod constraints;
+pub mod construct;
+pub mod continuous_query;
+mod describe;
 pub mod engine;
+mod federation;
 mod find_query_variables;
+pub mod function_registry;
 mod preparing;
 pub mod preprocessing;
 pub mod pushdown_setting;
@@ -2,11 +2,13 @@ mod expressions;
 mod graph_patterns;
 mod synchronization;
 
-use crate::pushdown_setting::PushdownSetting;
+use crate::constants::{FILL_FORWARD, INTERPOLATE_LINEAR};
+use crate::function_registry::is_builtin_function;
+use crate::pushdown_setting::{AggregatePushdown, PushdownSetting};
 use crate::query_context::Context;
 use crate::timeseries_query::{BasicTimeSeriesQuery, TimeSeriesQuery};
 use polars_core::frame::DataFrame;
-use spargebra::algebra::Expression;
+use spargebra::algebra::{Expression, Function};
 use spargebra::Query;
 use std::collections::{HashMap, HashSet};
 
@@ -14,6 +16,10 @@ use std::collections::{HashMap, HashSet};
 pub struct TimeSeriesQueryPrepper {
     pushdown_settings: HashSet<PushdownSetting>,
     allow_compound_timeseries_queries: bool,
+    function_pushdowns: HashSet<Function>,
+    custom_function_pushdowns: HashSet<String>,
+    custom_aggregate_pushdowns: HashSet<String>,
+    aggregate_pushdowns: HashSet<AggregatePushdown>,
     basic_time_series_queries: Vec<BasicTimeSeriesQuery>,
     pub static_result_df: DataFrame,
     grouping_counter: u16,
@@ -24,12 +30,20 @@ impl TimeSeriesQueryPrepper {
     pub fn new(
         pushdown_settings: HashSet<PushdownSetting>,
         allow_compound_timeseries_queries: bool,
+        function_pushdowns: HashSet<Function>,
+        custom_function_pushdowns: HashSet<String>,
+        custom_aggregate_pushdowns: HashSet<String>,
+        aggregate_pushdowns: HashSet<AggregatePushdown>,
         basic_time_series_queries: Vec<BasicTimeSeriesQuery>,
         static_result_df: DataFrame,
         rewritten_filters: HashMap<Context, Expression>,
     ) -> TimeSeriesQueryPrepper {
         TimeSeriesQueryPrepper {
             allow_compound_timeseries_queries,
+            function_pushdowns,
+            custom_function_pushdowns,
+            custom_aggregate_pushdowns,
+            aggregate_pushdowns,
             pushdown_settings,
             basic_time_series_queries,
             static_result_df,
@@ -47,3 +61,72 @@ impl TimeSeriesQueryPrepper {
         }
     }
 }
+
+//Window functions depend on neighbouring rows, so they are evaluated by the combiner
+//Built in and user defined functions are only pushed down if the database supports them
+pub(crate) fn function_allows_pushdown(
+    fun: &Function,
+    function_pushdowns: &HashSet<Function>,
+    custom_function_pushdowns: &HashSet<String>,
+) -> bool {
+    if let Function::Custom(nn) = fun {
+        let iri = nn.as_str();
+        if iri == FILL_FORWARD || iri == INTERPOLATE_LINEAR {
+            false
+        } else {
+            is_builtin_function(iri) || custom_function_pushdowns.contains(iri)
+        }
+    } else {
+        function_pushdowns.contains(fun)
+    }
+}
+
+pub(crate) fn expression_allows_pushdown(
+    expression: &Expression,
+    function_pushdowns: &HashSet<Function>,
+    custom_function_pushdowns: &HashSet<String>,
+) -> bool {
+    match expression {
+        //Time series values are literals, and exists patterns are evaluated by the combiner
+        Expression::NamedNode(_) | Expression::Exists(_) => false,
+        Expression::Literal(_) | Expression::Variable(_) | Expression::Bound(_) => true,
+        Expression::Or(left, right)
+        | Expression::And(left, right)
+        | Expression::Equal(left, right)
+        | Expression::SameTerm(left, right)
+        | Expression::Greater(left, right)
+        | Expression::GreaterOrEqual(left, right)
+        | Expression::Less(left, right)
+        | Expression::LessOrEqual(left, right)
+        | Expression::Add(left, right)
+        | Expression::Subtract(left, right)
+        | Expression::Multiply(left, right)
+        | Expression::Divide(left, right) => {
+            expression_allows_pushdown(left, function_pushdowns, custom_function_pushdowns)
+                && expression_allows_pushdown(right, function_pushdowns, custom_function_pushdowns)
+        }
+        Expression::In(left, right) => {
+            expression_allows_pushdown(left, function_pushdowns, custom_function_pushdowns)
+                && right.iter().all(|x| {
+                    expression_allows_pushdown(x, function_pushdowns, custom_function_pushdowns)
+                })
+        }
+        Expression::UnaryPlus(inner) | Expression::UnaryMinus(inner) | Expression::Not(inner) => {
+            expression_allows_pushdown(inner, function_pushdowns, custom_function_pushdowns)
+        }
+        Expression::If(left, middle, right) => {
+            expression_allows_pushdown(left, function_pushdowns, custom_function_pushdowns)
+                && expression_allows_pushdown(middle, function_pushdowns, custom_function_pushdowns)
+                && expression_allows_pushdown(right, function_pushdowns, custom_function_pushdowns)
+        }
+        Expression::Coalesce(inner) => inner
+            .iter()
+            .all(|x| expression_allows_pushdown(x, function_pushdowns, custom_function_pushdowns)),
+        Expression::FunctionCall(fun, args) => {
+            function_allows_pushdown(fun, function_pushdowns, custom_function_pushdowns)
+                && args.iter().all(|x| {
+                    expression_allows_pushdown(x, function_pushdowns, custom_function_pushdowns)
+                })
+        }
+    }
+}
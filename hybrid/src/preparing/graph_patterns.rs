@@ -134,7 +134,9 @@ impl TimeSeriesQueryPrepper {
                 try_groupby_complex_query,
                 context,
             ),
-            GraphPattern::Service { .. } => self.prepare_service(),
+            GraphPattern::Service { inner, .. } => {
+                self.prepare_service(inner, try_groupby_complex_query, context)
+            }
         }
     }
 }
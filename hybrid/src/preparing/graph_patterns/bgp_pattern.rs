@@ -1,6 +1,7 @@
 use super::TimeSeriesQueryPrepper;
 use crate::preparing::graph_patterns::GPPrepReturn;
 use crate::preparing::synchronization::create_identity_synchronized_queries;
+use crate::pushdown_setting::PushdownSetting;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_query::TimeSeriesQuery;
 
@@ -19,7 +20,11 @@ impl TimeSeriesQueryPrepper {
                 }
             }
         }
-        if try_groupby_complex_query {
+        if try_groupby_complex_query
+            || self
+                .pushdown_settings
+                .contains(&PushdownSetting::Synchronization)
+        {
             local_tsqs = create_identity_synchronized_queries(local_tsqs);
         }
         GPPrepReturn::new(local_tsqs)
@@ -1,5 +1,6 @@
 use super::TimeSeriesQueryPrepper;
 use crate::find_query_variables::find_all_used_variables_in_expression;
+use crate::preparing::expression_allows_pushdown;
 use crate::preparing::graph_patterns::GPPrepReturn;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_query::TimeSeriesQuery;
@@ -22,6 +23,13 @@ impl TimeSeriesQueryPrepper {
             &context.extension_with(PathEntry::ExtendInner),
         );
         if try_groupby_complex_query {
+            if !expression_allows_pushdown(
+                expr,
+                &self.function_pushdowns,
+                &self.custom_function_pushdowns,
+            ) {
+                return GPPrepReturn::fail_groupby_complex_query();
+            }
             let mut expression_vars = HashSet::new();
             find_all_used_variables_in_expression(expr, &mut expression_vars);
             let mut found_i = None;
@@ -33,6 +41,8 @@ impl TimeSeriesQueryPrepper {
                         found_some = true;
                     } else if tsq.has_equivalent_timestamp_variable(expression_var, context) {
                         found_some = true;
+                    } else if tsq.has_equivalent_quality_variable(expression_var, context) {
+                        found_some = true;
                     } else {
                         found_all = false;
                         break;
@@ -1,9 +1,10 @@
 use crate::change_types::ChangeType;
+use crate::preparing::function_allows_pushdown;
 use crate::pushdown_setting::PushdownSetting;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_query::TimeSeriesQuery;
 use oxrdf::Literal;
-use spargebra::algebra::Expression;
+use spargebra::algebra::{Expression, Function};
 use std::collections::HashSet;
 
 pub(crate) struct RecursiveRewriteReturn {
@@ -40,6 +41,8 @@ pub(crate) fn rewrite_filter_expression(
     context: &Context,
     static_rewrite_conjunction: &Option<Vec<&Expression>>,
     pushdown_settings: &HashSet<PushdownSetting>,
+    function_pushdowns: &HashSet<Function>,
+    custom_function_pushdowns: &HashSet<String>,
 ) -> (Option<Expression>, bool) {
     let mut rewrite = try_recursive_rewrite_expression(
         tsq,
@@ -48,6 +51,8 @@ pub(crate) fn rewrite_filter_expression(
         required_change_direction,
         context,
         pushdown_settings,
+        function_pushdowns,
+        custom_function_pushdowns,
     );
     return (rewrite.expression.take(), rewrite.lost_value);
 }
@@ -59,6 +64,8 @@ pub(crate) fn try_recursive_rewrite_expression(
     required_change_direction: &ChangeType,
     context: &Context,
     pushdown_settings: &HashSet<PushdownSetting>,
+    function_pushdowns: &HashSet<Function>,
+    custom_function_pushdowns: &HashSet<String>,
 ) -> RecursiveRewriteReturn {
     if static_rewrite_conjunction.is_some()
         && static_rewrite_conjunction
@@ -91,6 +98,7 @@ pub(crate) fn try_recursive_rewrite_expression(
             } else if tsq
                 .get_value_variables()
                 .into_iter()
+                .chain(tsq.get_quality_variables().into_iter())
                 .find(|x| &x.variable == v)
                 .is_some()
             {
@@ -115,6 +123,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::OrLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -123,6 +133,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::OrRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
             match required_change_direction {
@@ -244,6 +256,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::AndLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -252,6 +266,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::AndRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -372,6 +388,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::EqualLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -380,6 +398,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::EqualRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -407,6 +427,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::GreaterLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -415,6 +437,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::GreaterRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -442,6 +466,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::GreaterOrEqualLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -450,6 +476,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::GreaterOrEqualRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -477,6 +505,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::LessLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -485,6 +515,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::LessRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -512,6 +544,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::LessOrEqualLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -520,6 +554,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::LessOrEqualRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -547,6 +583,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 &ChangeType::NoChange,
                 &context.extension_with(PathEntry::InLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
 
             let mut right_rewrites = right
@@ -560,6 +598,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                         required_change_direction,
                         &context.extension_with(PathEntry::InRight(i as u16)),
                         pushdown_settings,
+                        function_pushdowns,
+                        custom_function_pushdowns,
                     )
                 })
                 .collect::<Vec<RecursiveRewriteReturn>>();
@@ -621,6 +661,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::AddLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -629,6 +671,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::AddRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -656,6 +700,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::SubtractLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -664,6 +710,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::SubtractRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -691,6 +739,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::MultiplyLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -699,6 +749,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::MultiplyRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -726,6 +778,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::DivideLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -734,6 +788,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::DivideRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value = or_lost_value(vec![&left_rewrite, &right_rewrite]);
 
@@ -761,6 +817,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::UnaryPlus),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             if inner_rewrite.change_type.is_some()
                 && inner_rewrite.change_type.as_ref().unwrap() == &ChangeType::NoChange
@@ -783,6 +841,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::UnaryMinus),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             if inner_rewrite.expression.is_some()
                 && inner_rewrite.change_type.as_ref().unwrap() == &ChangeType::NoChange
@@ -811,6 +871,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 &use_direction,
                 &context.extension_with(PathEntry::Not),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             if inner_rewrite.expression.is_some() {
                 match inner_rewrite.change_type.as_ref().unwrap() {
@@ -853,6 +915,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::IfLeft),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut middle_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -861,6 +925,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::IfMiddle),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let mut right_rewrite = try_recursive_rewrite_expression(
                 tsq,
@@ -869,6 +935,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                 required_change_direction,
                 &context.extension_with(PathEntry::IfRight),
                 pushdown_settings,
+                function_pushdowns,
+                custom_function_pushdowns,
             );
             let use_lost_value =
                 or_lost_value(vec![&left_rewrite, &middle_rewrite, &right_rewrite]);
@@ -904,6 +972,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                         required_change_direction,
                         &context.extension_with(PathEntry::Coalesce(i as u16)),
                         pushdown_settings,
+                        function_pushdowns,
+                        custom_function_pushdowns,
                     )
                 })
                 .collect::<Vec<RecursiveRewriteReturn>>();
@@ -928,6 +998,9 @@ pub(crate) fn try_recursive_rewrite_expression(
             RecursiveRewriteReturn::none(use_lost_value)
         }
         Expression::FunctionCall(left, right) => {
+            if !function_allows_pushdown(left, function_pushdowns, custom_function_pushdowns) {
+                return RecursiveRewriteReturn::none(false);
+            }
             let right_rewrites = right
                 .iter()
                 .enumerate()
@@ -939,6 +1012,8 @@ pub(crate) fn try_recursive_rewrite_expression(
                         required_change_direction,
                         &context.extension_with(PathEntry::FunctionCall(i as u16)),
                         pushdown_settings,
+                        function_pushdowns,
+                        custom_function_pushdowns,
                     )
                 })
                 .collect::<Vec<RecursiveRewriteReturn>>();
@@ -32,6 +32,14 @@ impl TimeSeriesQueryPrepper {
         let mut out_tsqs = vec![];
         out_tsqs.extend(expression_prepare.drained_time_series_queries());
         for t in inner_prepare.drained_time_series_queries() {
+            //Conditions must be applied after the latest data points are found
+            if t.has_latest() {
+                if try_groupby_complex_query {
+                    return GPPrepReturn::fail_groupby_complex_query();
+                }
+                out_tsqs.push(t);
+                continue;
+            }
             let use_change_type = if try_groupby_complex_query {
                 ChangeType::NoChange
             } else {
@@ -45,6 +53,8 @@ impl TimeSeriesQueryPrepper {
                 context,
                 &conj_vec,
                 &self.pushdown_settings,
+                &self.function_pushdowns,
+                &self.custom_function_pushdowns,
             );
             if try_groupby_complex_query && (lost_value || time_series_condition.is_none()) {
                 return GPPrepReturn::fail_groupby_complex_query();
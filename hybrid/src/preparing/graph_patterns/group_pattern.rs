@@ -3,16 +3,17 @@ use log::debug;
 use std::collections::HashSet;
 
 use super::TimeSeriesQueryPrepper;
-use crate::constants::GROUPING_COL;
+use crate::constants::{DURATION_BAD, DURATION_GOOD, GROUPING_COL, PERCENT_BAD, PERCENT_GOOD};
 use crate::find_query_variables::find_all_used_variables_in_aggregate_expression;
+use crate::preparing::expression_allows_pushdown;
 use crate::preparing::graph_patterns::GPPrepReturn;
-use crate::pushdown_setting::PushdownSetting;
+use crate::pushdown_setting::{AggregatePushdown, PushdownSetting};
 use crate::timeseries_query::{GroupedTimeSeriesQuery, TimeSeriesQuery};
 use oxrdf::Variable;
 use polars_core::frame::DataFrame;
 use polars_core::prelude::{JoinType, UniqueKeepStrategy};
 use polars_core::series::Series;
-use spargebra::algebra::{AggregateExpression, GraphPattern};
+use spargebra::algebra::{AggregateExpression, Expression, Function, GraphPattern};
 
 impl TimeSeriesQueryPrepper {
     pub fn prepare_group(
@@ -37,8 +38,22 @@ impl TimeSeriesQueryPrepper {
             if time_series_queries.len() == 1 {
                 let mut tsq = time_series_queries.remove(0);
                 let in_scope = check_aggregations_are_in_scope(&tsq, inner_context, aggregations);
+                //Databases without support for compound queries cannot aggregate synchronized time series
+                let compound_ok = self.allow_compound_timeseries_queries || !is_synchronized(&tsq);
+                let latest_ok = !tsq.has_latest();
+                //Events are not aggregated by the servers that store them
+                let events_ok = !tsq.has_events();
+                let supported = check_aggregations_are_supported(
+                    &tsq,
+                    inner_context,
+                    aggregations,
+                    &self.aggregate_pushdowns,
+                    &self.function_pushdowns,
+                    &self.custom_function_pushdowns,
+                    &self.custom_aggregate_pushdowns,
+                );
 
-                if in_scope {
+                if in_scope && supported && compound_ok && latest_ok && events_ok {
                     let grouping_col = self.add_grouping_col(by);
                     tsq = add_basic_groupby_mapping_values(
                         tsq,
@@ -122,6 +137,8 @@ fn check_aggregations_are_in_scope(
                 continue;
             } else if tsq.has_equivalent_value_variable(v, context) {
                 continue;
+            } else if tsq.has_equivalent_quality_variable(v, context) {
+                continue;
             } else {
                 debug!("Variable {:?} in aggregate expression not in scope", v);
                 return false;
@@ -131,6 +148,128 @@ fn check_aggregations_are_in_scope(
     true
 }
 
+fn check_aggregations_are_supported(
+    tsq: &TimeSeriesQuery,
+    context: &Context,
+    aggregations: &Vec<(Variable, AggregateExpression)>,
+    aggregate_pushdowns: &HashSet<AggregatePushdown>,
+    function_pushdowns: &HashSet<Function>,
+    custom_function_pushdowns: &HashSet<String>,
+    custom_aggregate_pushdowns: &HashSet<String>,
+) -> bool {
+    for (_, ae) in aggregations {
+        let (aggregate_pushdown, distinct) = match ae {
+            AggregateExpression::Count { distinct, .. } => {
+                (Some(AggregatePushdown::Count), distinct)
+            }
+            AggregateExpression::Sum { distinct, .. } => (Some(AggregatePushdown::Sum), distinct),
+            AggregateExpression::Avg { distinct, .. } => (Some(AggregatePushdown::Avg), distinct),
+            AggregateExpression::Min { distinct, .. } => (Some(AggregatePushdown::Min), distinct),
+            AggregateExpression::Max { distinct, .. } => (Some(AggregatePushdown::Max), distinct),
+            AggregateExpression::GroupConcat { distinct, .. } => {
+                (Some(AggregatePushdown::GroupConcat), distinct)
+            }
+            AggregateExpression::Sample { distinct, .. } => {
+                (Some(AggregatePushdown::Sample), distinct)
+            }
+            AggregateExpression::Custom { distinct, .. } => (None, distinct),
+        };
+        if let Some(aggregate_pushdown) = aggregate_pushdown {
+            if !aggregate_pushdowns.contains(&aggregate_pushdown) {
+                debug!("Aggregation {} cannot be pushed down", ae);
+                return false;
+            }
+        }
+        //Distinct solutions depend on all the variables in scope, which are only known when combining
+        if let AggregateExpression::Count {
+            expr: None,
+            distinct: true,
+        } = ae
+        {
+            debug!("Aggregation {} cannot be pushed down", ae);
+            return false;
+        }
+        if *distinct && !aggregate_pushdowns.contains(&AggregatePushdown::Distinct) {
+            debug!("Distinct aggregation {} cannot be pushed down", ae);
+            return false;
+        }
+        if let AggregateExpression::Custom { name, .. } = ae {
+            if !custom_aggregate_pushdowns.contains(name.as_str()) {
+                debug!("Custom aggregation {} cannot be pushed down", name);
+                return false;
+            }
+        }
+        if let Some(expr) = aggregated_expression(ae) {
+            if !expression_allows_pushdown(expr, function_pushdowns, custom_function_pushdowns) {
+                debug!("Aggregated expression {} cannot be pushed down", expr);
+                return false;
+            }
+            let required_pushdown = required_aggregated_expression_pushdown(tsq, context, expr);
+            if is_status_aggregate(ae) {
+                if required_pushdown != Some(AggregatePushdown::QualityVariables) {
+                    debug!("Aggregated expression {} is not a quality variable", expr);
+                    return false;
+                }
+            } else if let Some(required_pushdown) = required_pushdown {
+                if !aggregate_pushdowns.contains(&required_pushdown) {
+                    debug!("Aggregated expression {} cannot be pushed down", expr);
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+fn is_synchronized(tsq: &TimeSeriesQuery) -> bool {
+    match tsq {
+        TimeSeriesQuery::InnerSynchronized(..) => true,
+        TimeSeriesQuery::Filtered(inner, _) | TimeSeriesQuery::ExpressionAs(inner, ..) => {
+            is_synchronized(inner)
+        }
+        _ => false,
+    }
+}
+
+//Value variables can be aggregated by any database that supports the aggregation
+fn required_aggregated_expression_pushdown(
+    tsq: &TimeSeriesQuery,
+    context: &Context,
+    expr: &Expression,
+) -> Option<AggregatePushdown> {
+    if let Expression::Variable(v) = expr {
+        if tsq.has_equivalent_value_variable(v, context) {
+            return None;
+        } else if tsq.has_equivalent_timestamp_variable(v, context) {
+            return Some(AggregatePushdown::TimestampVariables);
+        } else if tsq.has_equivalent_quality_variable(v, context) {
+            return Some(AggregatePushdown::QualityVariables);
+        }
+    }
+    Some(AggregatePushdown::Expressions)
+}
+
+fn is_status_aggregate(ae: &AggregateExpression) -> bool {
+    if let AggregateExpression::Custom { name, .. } = ae {
+        [DURATION_GOOD, DURATION_BAD, PERCENT_GOOD, PERCENT_BAD].contains(&name.as_str())
+    } else {
+        false
+    }
+}
+
+fn aggregated_expression(ae: &AggregateExpression) -> Option<&Expression> {
+    match ae {
+        AggregateExpression::Count { expr, .. } => expr.as_deref(),
+        AggregateExpression::Sum { expr, .. }
+        | AggregateExpression::Avg { expr, .. }
+        | AggregateExpression::Min { expr, .. }
+        | AggregateExpression::Max { expr, .. }
+        | AggregateExpression::GroupConcat { expr, .. }
+        | AggregateExpression::Sample { expr, .. }
+        | AggregateExpression::Custom { expr, .. } => Some(expr.as_ref()),
+    }
+}
+
 fn add_basic_groupby_mapping_values(
     tsq: TimeSeriesQuery,
     static_result_df: &DataFrame,
@@ -1,10 +1,28 @@
 use super::TimeSeriesQueryPrepper;
 
 use crate::preparing::graph_patterns::GPPrepReturn;
+use crate::query_context::{Context, PathEntry};
+use log::debug;
+use spargebra::algebra::GraphPattern;
 
 impl TimeSeriesQueryPrepper {
-    pub fn prepare_service(&mut self) -> GPPrepReturn {
-        //Service pattern should not contain anything dynamic
-        GPPrepReturn::new(vec![])
+    pub fn prepare_service(
+        &mut self,
+        inner: &GraphPattern,
+        try_groupby_complex_query: bool,
+        context: &Context,
+    ) -> GPPrepReturn {
+        if try_groupby_complex_query {
+            debug!(
+                "Encountered service inside groupby, not supported for complex groupby pushdown"
+            );
+            return GPPrepReturn::fail_groupby_complex_query();
+        }
+        //Time series may be identified by the solutions of federated services
+        self.prepare_graph_pattern(
+            inner,
+            try_groupby_complex_query,
+            &context.extension_with(PathEntry::ServiceInner),
+        )
     }
 }
@@ -18,7 +18,7 @@ impl TimeSeriesQueryPrepper {
             let inner_prepare = self.prepare_graph_pattern(
                 inner,
                 try_groupby_complex_query,
-                &context.extension_with(PathEntry::ReducedInner),
+                &context.extension_with(PathEntry::SliceInner),
             );
             inner_prepare
         }
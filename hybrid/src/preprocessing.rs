@@ -1,4 +1,8 @@
-use crate::constants::{HAS_DATA_POINT, HAS_TIMESERIES, HAS_TIMESTAMP, HAS_VALUE};
+use crate::constants::{
+    HAS_DATA_POINT, HAS_EVENT, HAS_EVENT_SERIES, HAS_EVENT_TYPE, HAS_LATEST_TIMESTAMP,
+    HAS_LATEST_VALUE, HAS_MESSAGE, HAS_QUALITY, HAS_SEVERITY, HAS_SOURCE_NODE, HAS_TIMESERIES,
+    HAS_TIMESTAMP, HAS_VALUE,
+};
 use crate::constraints::{Constraint, VariableConstraints};
 use crate::find_query_variables::{
     find_all_used_variables_in_aggregate_expression, find_all_used_variables_in_expression,
@@ -7,7 +11,9 @@ use crate::query_context::{Context, PathEntry};
 use spargebra::algebra::{
     AggregateExpression, Expression, GraphPattern, OrderExpression, PropertyPathExpression,
 };
-use spargebra::term::{BlankNode, NamedNodePattern, TermPattern, TriplePattern, Variable};
+use spargebra::term::{
+    BlankNode, NamedNode, NamedNodePattern, TermPattern, TriplePattern, Variable,
+};
 use spargebra::Query;
 use std::collections::{HashMap, HashSet};
 
@@ -55,8 +61,17 @@ impl Preprocessor {
             GraphPattern::Bgp { patterns } => {
                 let bgp_context = context.extension_with(PathEntry::BGP);
                 let mut new_patterns = vec![];
+                let mut latest_data_points = HashMap::new();
                 for p in patterns {
-                    new_patterns.push(self.preprocess_triple_pattern(p, &bgp_context));
+                    if let Some(latest_patterns) = self.preprocess_latest_triple_pattern(
+                        p,
+                        &mut latest_data_points,
+                        &bgp_context,
+                    ) {
+                        new_patterns.extend(latest_patterns);
+                    } else {
+                        new_patterns.push(self.preprocess_triple_pattern(p, &bgp_context));
+                    }
                 }
                 GraphPattern::Bgp {
                     patterns: new_patterns,
@@ -159,6 +174,8 @@ impl Preprocessor {
                 for v in used_vars.drain() {
                     if let Some(ctr) = self.variable_constraints.get_constraint(&v, context) {
                         if ctr == &Constraint::ExternalDataValue
+                            || ctr == &Constraint::ExternalDataQuality
+                            || ctr == &Constraint::ExternalEventField
                             || ctr == &Constraint::ExternalTimestamp
                             || ctr == &Constraint::ExternallyDerived
                         {
@@ -280,6 +297,8 @@ impl Preprocessor {
                     for v in used_vars.drain() {
                         if let Some(ctr) = self.variable_constraints.get_constraint(&v, context) {
                             if ctr == &Constraint::ExternalDataValue
+                                || ctr == &Constraint::ExternalDataQuality
+                                || ctr == &Constraint::ExternalEventField
                                 || ctr == &Constraint::ExternalTimestamp
                                 || ctr == &Constraint::ExternallyDerived
                             {
@@ -339,7 +358,9 @@ impl Preprocessor {
                 TermPattern::Variable(new_object_variable),
             ) = (&new_subject, &new_object)
             {
-                if named_predicate_node == &HAS_TIMESERIES {
+                if named_predicate_node == &HAS_TIMESERIES
+                    || named_predicate_node == &HAS_EVENT_SERIES
+                {
                     self.variable_constraints.insert(
                         new_object_variable.clone(),
                         context.clone(),
@@ -370,7 +391,19 @@ impl Preprocessor {
                         Constraint::ExternalDataPoint,
                     );
                 }
-                if named_predicate_node == &HAS_DATA_POINT {
+                if named_predicate_node == &HAS_QUALITY {
+                    self.variable_constraints.insert(
+                        new_object_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalDataQuality,
+                    );
+                    self.variable_constraints.insert(
+                        new_subject_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalDataPoint,
+                    );
+                }
+                if named_predicate_node == &HAS_DATA_POINT || named_predicate_node == &HAS_EVENT {
                     self.variable_constraints.insert(
                         new_object_variable.clone(),
                         context.clone(),
@@ -382,6 +415,22 @@ impl Preprocessor {
                         Constraint::ExternalTimeseries,
                     );
                 }
+                if named_predicate_node == &HAS_EVENT_TYPE
+                    || named_predicate_node == &HAS_SEVERITY
+                    || named_predicate_node == &HAS_MESSAGE
+                    || named_predicate_node == &HAS_SOURCE_NODE
+                {
+                    self.variable_constraints.insert(
+                        new_object_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalEventField,
+                    );
+                    self.variable_constraints.insert(
+                        new_subject_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalDataPoint,
+                    );
+                }
             }
         }
         return TriplePattern {
@@ -391,6 +440,77 @@ impl Preprocessor {
         };
     }
 
+    //The latest value and timestamp of a time series are rewritten to belong to a data point,
+    //so that they are queried like any other data point
+    fn preprocess_latest_triple_pattern(
+        &mut self,
+        triple_pattern: &TriplePattern,
+        latest_data_points: &mut HashMap<Variable, Variable>,
+        context: &Context,
+    ) -> Option<Vec<TriplePattern>> {
+        let (predicate, constraint) =
+            if let NamedNodePattern::NamedNode(named_predicate_node) = &triple_pattern.predicate {
+                if named_predicate_node == &HAS_LATEST_VALUE {
+                    (HAS_VALUE, Constraint::ExternalDataValue)
+                } else if named_predicate_node == &HAS_LATEST_TIMESTAMP {
+                    (HAS_TIMESTAMP, Constraint::ExternalTimestamp)
+                } else {
+                    return None;
+                }
+            } else {
+                return None;
+            };
+        let new_subject = self.rename_if_blank(&triple_pattern.subject);
+        let new_object = self.rename_if_blank(&triple_pattern.object);
+        if let (
+            TermPattern::Variable(time_series_variable),
+            TermPattern::Variable(object_variable),
+        ) = (&new_subject, &new_object)
+        {
+            let mut new_patterns = vec![];
+            let data_point_variable =
+                if let Some(data_point_variable) = latest_data_points.get(time_series_variable) {
+                    data_point_variable.clone()
+                } else {
+                    let data_point_variable = Variable::new(
+                        "latest_data_point_".to_string() + self.counter.to_string().as_str(),
+                    )
+                    .expect("Name is ok");
+                    self.counter += 1;
+                    self.variable_constraints.insert(
+                        time_series_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalTimeseries,
+                    );
+                    self.variable_constraints.insert(
+                        data_point_variable.clone(),
+                        context.clone(),
+                        Constraint::ExternalLatestDataPoint,
+                    );
+                    latest_data_points
+                        .insert(time_series_variable.clone(), data_point_variable.clone());
+                    new_patterns.push(TriplePattern {
+                        subject: new_subject.clone(),
+                        predicate: NamedNodePattern::NamedNode(NamedNode::new_unchecked(
+                            HAS_DATA_POINT,
+                        )),
+                        object: TermPattern::Variable(data_point_variable.clone()),
+                    });
+                    data_point_variable
+                };
+            self.variable_constraints
+                .insert(object_variable.clone(), context.clone(), constraint);
+            new_patterns.push(TriplePattern {
+                subject: TermPattern::Variable(data_point_variable),
+                predicate: NamedNodePattern::NamedNode(NamedNode::new_unchecked(predicate)),
+                object: new_object,
+            });
+            Some(new_patterns)
+        } else {
+            None
+        }
+    }
+
     fn rename_if_blank(&mut self, term_pattern: &TermPattern) -> TermPattern {
         if let TermPattern::BlankNode(bn) = term_pattern {
             if let Some(var) = self.blank_node_rename.get(bn) {
@@ -1,11 +1,106 @@
+use serde::Deserialize;
+use spargebra::algebra::Function;
 use std::collections::HashSet;
 
 pub fn all_pushdowns() -> HashSet<PushdownSetting> {
     [PushdownSetting::GroupBy, PushdownSetting::ValueConditions].into()
 }
 
+//All built in SPARQL functions, custom functions are handled through the function registry
+pub fn all_function_pushdowns() -> HashSet<Function> {
+    [
+        Function::Str,
+        Function::Lang,
+        Function::LangMatches,
+        Function::Datatype,
+        Function::Iri,
+        Function::BNode,
+        Function::Rand,
+        Function::Abs,
+        Function::Ceil,
+        Function::Floor,
+        Function::Round,
+        Function::Concat,
+        Function::SubStr,
+        Function::StrLen,
+        Function::Replace,
+        Function::UCase,
+        Function::LCase,
+        Function::EncodeForUri,
+        Function::Contains,
+        Function::StrStarts,
+        Function::StrEnds,
+        Function::StrBefore,
+        Function::StrAfter,
+        Function::Year,
+        Function::Month,
+        Function::Day,
+        Function::Hours,
+        Function::Minutes,
+        Function::Seconds,
+        Function::Timezone,
+        Function::Tz,
+        Function::Now,
+        Function::Uuid,
+        Function::StrUuid,
+        Function::Md5,
+        Function::Sha1,
+        Function::Sha256,
+        Function::Sha384,
+        Function::Sha512,
+        Function::StrLang,
+        Function::StrDt,
+        Function::IsIri,
+        Function::IsBlank,
+        Function::IsLiteral,
+        Function::IsNumeric,
+        Function::Regex,
+    ]
+    .into()
+}
+
+pub fn all_aggregate_pushdowns() -> HashSet<AggregatePushdown> {
+    [
+        AggregatePushdown::Count,
+        AggregatePushdown::Sum,
+        AggregatePushdown::Avg,
+        AggregatePushdown::Min,
+        AggregatePushdown::Max,
+        AggregatePushdown::GroupConcat,
+        AggregatePushdown::Sample,
+        AggregatePushdown::Distinct,
+        AggregatePushdown::TimestampVariables,
+        AggregatePushdown::QualityVariables,
+        AggregatePushdown::Expressions,
+    ]
+    .into()
+}
+
+//Built in aggregates, custom aggregates are handled through the function registry
 #[derive(Hash, Clone, Eq, PartialEq, Debug)]
+pub enum AggregatePushdown {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    GroupConcat,
+    Sample,
+    //Aggregation over distinct values
+    Distinct,
+    //Aggregation of timestamp variables, value variables can always be aggregated
+    TimestampVariables,
+    //Aggregation of quality variables by other aggregates than those of StatusCodes
+    QualityVariables,
+    //Aggregation of anything else than a value, timestamp or quality variable
+    Expressions,
+}
+
+#[derive(Hash, Clone, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PushdownSetting {
     ValueConditions,
     GroupBy,
+    //Time series sharing a timestamp variable are queried together, also outside of aggregations
+    Synchronization,
 }
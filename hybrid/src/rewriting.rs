@@ -81,7 +81,10 @@ impl StaticQueryRewriter {
     fn rewrite_variable(&self, v: &Variable, context: &Context) -> Option<Variable> {
         if let Some(ctr) = self.variable_constraints.get_constraint(v, context) {
             if !(ctr == &Constraint::ExternalDataPoint
+                || ctr == &Constraint::ExternalLatestDataPoint
                 || ctr == &Constraint::ExternalDataValue
+                || ctr == &Constraint::ExternalDataQuality
+                || ctr == &Constraint::ExternalEventField
                 || ctr == &Constraint::ExternalTimestamp
                 || ctr == &Constraint::ExternallyDerived)
             {
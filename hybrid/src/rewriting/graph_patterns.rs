@@ -176,7 +176,7 @@ impl StaticQueryRewriter {
                 name,
                 inner,
                 silent,
-            } => self.rewrite_service(name, inner, silent, context),
+            } => self.rewrite_service(name, inner, silent, required_change_direction, context),
         }
     }
 }
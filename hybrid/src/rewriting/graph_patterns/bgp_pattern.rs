@@ -1,10 +1,13 @@
 use super::StaticQueryRewriter;
 use crate::change_types::ChangeType;
-use crate::constants::{HAS_DATATYPE, HAS_DATA_POINT, HAS_EXTERNAL_ID, HAS_TIMESTAMP, HAS_VALUE};
+use crate::constants::{
+    HAS_DATATYPE, HAS_DATA_POINT, HAS_EVENT, HAS_EVENT_TYPE, HAS_EXTERNAL_ID, HAS_MESSAGE,
+    HAS_QUALITY, HAS_SEVERITY, HAS_SOURCE_NODE, HAS_TIMESTAMP, HAS_VALUE,
+};
 use crate::constraints::{Constraint, VariableConstraints};
 use crate::query_context::{Context, PathEntry, VariableInContext};
 use crate::rewriting::graph_patterns::GPReturn;
-use crate::timeseries_query::BasicTimeSeriesQuery;
+use crate::timeseries_query::{BasicTimeSeriesQuery, EventField};
 use oxrdf::{NamedNode, Variable};
 use spargebra::algebra::GraphPattern;
 use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
@@ -84,8 +87,11 @@ impl StaticQueryRewriter {
                 if let TermPattern::Variable(var) = term_pattern {
                     if let Some(ctr) = variable_constraints.get_constraint(var, context) {
                         if ctr == &Constraint::ExternalDataPoint
+                            || ctr == &Constraint::ExternalLatestDataPoint
                             || ctr == &Constraint::ExternalTimestamp
                             || ctr == &Constraint::ExternalDataValue
+                            || ctr == &Constraint::ExternalDataQuality
+                            || ctr == &Constraint::ExternalEventField
                         {
                             return true;
                         }
@@ -113,7 +119,12 @@ impl StaticQueryRewriter {
         }
 
         //We wait until last to process the dynamic triples, making sure all relationships are known first.
-        process_dynamic_triples(&mut new_basic_tsqs, dynamic_triples, &context);
+        process_dynamic_triples(
+            &mut new_basic_tsqs,
+            dynamic_triples,
+            &self.variable_constraints,
+            &context,
+        );
         self.basic_time_series_queries.extend(new_basic_tsqs);
 
         if new_triples.is_empty() {
@@ -163,11 +174,12 @@ impl StaticQueryRewriter {
 fn process_dynamic_triples(
     local_basic_tsqs: &mut Vec<BasicTimeSeriesQuery>,
     dynamic_triples: Vec<&TriplePattern>,
+    variable_constraints: &VariableConstraints,
     context: &Context,
 ) {
     for t in &dynamic_triples {
         if let NamedNodePattern::NamedNode(named_predicate_node) = &t.predicate {
-            if named_predicate_node == HAS_DATA_POINT {
+            if named_predicate_node == HAS_DATA_POINT || named_predicate_node == HAS_EVENT {
                 for q in local_basic_tsqs.iter_mut() {
                     if let (Some(q_timeseries_variable), TermPattern::Variable(subject_variable)) =
                         (&q.timeseries_variable, &t.subject)
@@ -176,6 +188,9 @@ fn process_dynamic_triples(
                             if let TermPattern::Variable(ts_var) = &t.object {
                                 q.data_point_variable =
                                     Some(VariableInContext::new(ts_var.clone(), context.clone()));
+                                q.latest = variable_constraints.get_constraint(ts_var, context)
+                                    == Some(&Constraint::ExternalLatestDataPoint);
+                                q.events = named_predicate_node == HAS_EVENT;
                             }
                         }
                     }
@@ -224,7 +239,60 @@ fn process_dynamic_triples(
                         }
                     }
                 }
+            } else if named_predicate_node == HAS_QUALITY {
+                for q in local_basic_tsqs.iter_mut() {
+                    if q.quality_variable.is_none() {
+                        if let (
+                            Some(q_data_point_variable),
+                            TermPattern::Variable(subject_variable),
+                        ) = (&q.data_point_variable, &t.subject)
+                        {
+                            if q_data_point_variable.partial(subject_variable, context) {
+                                if let TermPattern::Variable(quality_var) = &t.object {
+                                    q.quality_variable = Some(VariableInContext::new(
+                                        quality_var.clone(),
+                                        context.clone(),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+            } else if let Some(event_field) = event_field(named_predicate_node.as_str()) {
+                for q in local_basic_tsqs.iter_mut() {
+                    if q.events
+                        && !q
+                            .event_field_variables
+                            .iter()
+                            .any(|(f, _)| f == &event_field)
+                    {
+                        if let (
+                            Some(q_data_point_variable),
+                            TermPattern::Variable(subject_variable),
+                        ) = (&q.data_point_variable, &t.subject)
+                        {
+                            if q_data_point_variable.partial(subject_variable, context) {
+                                if let TermPattern::Variable(field_var) = &t.object {
+                                    q.event_field_variables.push((
+                                        event_field.clone(),
+                                        VariableInContext::new(field_var.clone(), context.clone()),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
     }
 }
+
+fn event_field(predicate: &str) -> Option<EventField> {
+    match predicate {
+        HAS_EVENT_TYPE => Some(EventField::EventType),
+        HAS_SEVERITY => Some(EventField::Severity),
+        HAS_MESSAGE => Some(EventField::Message),
+        HAS_SOURCE_NODE => Some(EventField::SourceNode),
+        _ => None,
+    }
+}
@@ -1,6 +1,6 @@
 use super::StaticQueryRewriter;
 use crate::change_types::ChangeType;
-use crate::query_context::Context;
+use crate::query_context::{Context, PathEntry};
 use crate::rewriting::graph_patterns::GPReturn;
 use spargebra::algebra::GraphPattern;
 use spargebra::term::NamedNodePattern;
@@ -13,8 +13,16 @@ impl StaticQueryRewriter {
         required_change_direction: &ChangeType,
         context: &Context,
     ) -> GPReturn {
-        let mut inner_gpr = self.rewrite_graph_pattern(inner, required_change_direction, context);
+        let mut inner_gpr = self.rewrite_graph_pattern(
+            inner,
+            required_change_direction,
+            &context.extension_with(PathEntry::GraphInner),
+        );
         if inner_gpr.graph_pattern.is_some() {
+            //Graph variables are bound by the static query
+            if let NamedNodePattern::Variable(v) = name {
+                inner_gpr.variables_in_scope.insert(v.clone());
+            }
             let inner_rewrite = inner_gpr.graph_pattern.take().unwrap();
             inner_gpr.with_graph_pattern(GraphPattern::Graph {
                 name: name.clone(),
@@ -11,11 +11,12 @@ impl StaticQueryRewriter {
         name: &NamedNodePattern,
         inner: &GraphPattern,
         silent: &bool,
+        required_change_direction: &ChangeType,
         context: &Context,
     ) -> GPReturn {
         let mut inner_rewrite = self.rewrite_graph_pattern(
             inner,
-            &ChangeType::NoChange,
+            required_change_direction,
             &context.extension_with(PathEntry::ServiceInner),
         );
         if inner_rewrite.graph_pattern.is_some() {
@@ -27,6 +28,6 @@ impl StaticQueryRewriter {
             });
             return inner_rewrite;
         }
-        panic!("Should never happen")
+        GPReturn::none()
     }
 }
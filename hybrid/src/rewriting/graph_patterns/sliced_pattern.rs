@@ -19,12 +19,20 @@ impl StaticQueryRewriter {
             &context.extension_with(PathEntry::SliceInner),
         );
         if inner_rewrite.graph_pattern.is_some() {
-            let inner_graph_pattern = inner_rewrite.graph_pattern.take().unwrap();
-            inner_rewrite.with_graph_pattern(GraphPattern::Slice {
-                inner: Box::new(inner_graph_pattern),
-                start: start.clone(),
-                length: length.clone(),
-            });
+            //Time series may remove or multiply solutions, so only static patterns are limited.
+            //The offset is applied when combining, since the slice is applied again there.
+            if inner_rewrite.change_type == ChangeType::NoChange
+                && inner_rewrite.external_ids_in_scope.is_empty()
+            {
+                if let Some(length) = length {
+                    let inner_graph_pattern = inner_rewrite.graph_pattern.take().unwrap();
+                    inner_rewrite.with_graph_pattern(GraphPattern::Slice {
+                        inner: Box::new(inner_graph_pattern),
+                        start: 0,
+                        length: Some(start + length),
+                    });
+                }
+            }
             return inner_rewrite;
         }
         GPReturn::none()
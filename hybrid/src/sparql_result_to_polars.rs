@@ -1,5 +1,5 @@
 use oxrdf::vocab::xsd;
-use oxrdf::{Literal, NamedNode, Term};
+use oxrdf::{Literal, NamedNode, Term, Variable};
 use polars::export::chrono::{DateTime, NaiveDateTime, Utc};
 use polars::prelude::{DataFrame, LiteralValue, NamedFrom, Series, TimeUnit};
 use sparesults::QuerySolution;
@@ -11,27 +11,7 @@ pub(crate) fn create_static_query_result_df(
     static_query: &Query,
     static_query_solutions: Vec<QuerySolution>,
 ) -> DataFrame {
-    let column_variables;
-    if let Query::Select {
-        dataset: _,
-        pattern,
-        base_iri: _,
-    } = static_query
-    {
-        if let GraphPattern::Project { variables, .. } = pattern {
-            column_variables = variables.clone();
-        } else if let GraphPattern::Distinct { inner } = pattern {
-            if let GraphPattern::Project { variables, .. } = inner.as_ref() {
-                column_variables = variables.clone();
-            } else {
-                panic!("");
-            }
-        } else {
-            panic!("");
-        }
-    } else {
-        panic!("");
-    }
+    let column_variables = find_projected_variables(static_query);
 
     let mut series_vec = vec![];
     for c in &column_variables {
@@ -52,6 +32,24 @@ pub(crate) fn create_static_query_result_df(
     df
 }
 
+pub(crate) fn find_projected_variables(static_query: &Query) -> Vec<Variable> {
+    if let Query::Select { pattern, .. } = static_query {
+        find_projected_pattern_variables(pattern)
+    } else {
+        panic!("");
+    }
+}
+
+fn find_projected_pattern_variables(pattern: &GraphPattern) -> Vec<Variable> {
+    match pattern {
+        GraphPattern::Project { variables, .. } => variables.clone(),
+        GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. } => find_projected_pattern_variables(inner),
+        _ => panic!(""),
+    }
+}
+
 pub(crate) fn sparql_term_to_polars_literal_value(term: &Term) -> polars::prelude::LiteralValue {
     match term {
         Term::NamedNode(named_node) => sparql_named_node_to_polars_literal_value(named_node),
@@ -1,3 +1,5 @@
+use spargebra::algebra::{GraphPattern, QueryDataset};
+use spargebra::term::TriplePattern;
 use spargebra::{ParseError, Query};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
@@ -6,7 +8,9 @@ use std::fmt::{Debug, Display, Formatter};
 pub enum SelectQueryErrorKind {
     Parse(ParseError),
     NotSelectQuery,
-    Unsupported(String),
+    NotConstructQuery,
+    NotAskQuery,
+    NotDescribeQuery,
 }
 
 #[derive(Debug)]
@@ -21,8 +25,14 @@ impl Display for SelectQueryError {
             SelectQueryErrorKind::NotSelectQuery => {
                 write!(f, "Not a select query")
             }
-            SelectQueryErrorKind::Unsupported(s) => {
-                write!(f, "Unsupported construct: {}", s)
+            SelectQueryErrorKind::NotConstructQuery => {
+                write!(f, "Not a construct query")
+            }
+            SelectQueryErrorKind::NotAskQuery => {
+                write!(f, "Not an ask query")
+            }
+            SelectQueryErrorKind::NotDescribeQuery => {
+                write!(f, "Not a describe query")
             }
         }
     }
@@ -30,36 +40,38 @@ impl Display for SelectQueryError {
 
 impl Error for SelectQueryError {}
 
+//Dataset clauses and base IRIs are passed on to the static query
 pub fn parse_sparql_select_query(query_str: &str) -> Result<Query, SelectQueryError> {
     let q_res = Query::parse(query_str, None);
     match q_res {
         Ok(q) => match q {
-            Query::Select {
+            Query::Select { .. } => Ok(q),
+            _ => Err(SelectQueryError {
+                kind: SelectQueryErrorKind::NotSelectQuery,
+            }),
+        },
+        Err(e) => Err(SelectQueryError {
+            kind: SelectQueryErrorKind::Parse(e),
+        }),
+    }
+}
+
+//Splits a construct query into its template, its dataset clause and the graph pattern of its where clause.
+//Relative IRIs are resolved against the base IRI when parsing.
+pub fn parse_sparql_construct_query(
+    query_str: &str,
+) -> Result<(Vec<TriplePattern>, Option<QueryDataset>, GraphPattern), SelectQueryError> {
+    let q_res = Query::parse(query_str, None);
+    match q_res {
+        Ok(q) => match q {
+            Query::Construct {
+                template,
                 dataset,
                 pattern,
-                base_iri,
-            } => {
-                let mut unsupported_constructs = vec![];
-                if dataset.is_some() {
-                    unsupported_constructs.push("Dataset")
-                }
-                if base_iri.is_some() {
-                    unsupported_constructs.push("BaseIri")
-                }
-                if unsupported_constructs.len() > 0 {
-                    Err(SelectQueryError {
-                        kind: SelectQueryErrorKind::Unsupported(unsupported_constructs.join(",")),
-                    })
-                } else {
-                    Ok(Query::Select {
-                        dataset,
-                        pattern,
-                        base_iri,
-                    })
-                }
-            }
+                ..
+            } => Ok((template, dataset, pattern)),
             _ => Err(SelectQueryError {
-                kind: SelectQueryErrorKind::NotSelectQuery,
+                kind: SelectQueryErrorKind::NotConstructQuery,
             }),
         },
         Err(e) => Err(SelectQueryError {
@@ -67,3 +79,37 @@ pub fn parse_sparql_select_query(query_str: &str) -> Result<Query, SelectQueryEr
         }),
     }
 }
+
+//Returns the dataset clause and the graph pattern of the where clause of an ask query
+pub fn parse_sparql_ask_query(
+    query_str: &str,
+) -> Result<(Option<QueryDataset>, GraphPattern), SelectQueryError> {
+    match Query::parse(query_str, None) {
+        Ok(Query::Ask {
+            dataset, pattern, ..
+        }) => Ok((dataset, pattern)),
+        Ok(_) => Err(SelectQueryError {
+            kind: SelectQueryErrorKind::NotAskQuery,
+        }),
+        Err(e) => Err(SelectQueryError {
+            kind: SelectQueryErrorKind::Parse(e),
+        }),
+    }
+}
+
+//Returns the dataset clause and the graph pattern projecting the described resources
+pub fn parse_sparql_describe_query(
+    query_str: &str,
+) -> Result<(Option<QueryDataset>, GraphPattern), SelectQueryError> {
+    match Query::parse(query_str, None) {
+        Ok(Query::Describe {
+            dataset, pattern, ..
+        }) => Ok((dataset, pattern)),
+        Ok(_) => Err(SelectQueryError {
+            kind: SelectQueryErrorKind::NotDescribeQuery,
+        }),
+        Err(e) => Err(SelectQueryError {
+            kind: SelectQueryErrorKind::Parse(e),
+        }),
+    }
+}
@@ -1,15 +1,70 @@
 pub mod arrow_flight_sql_database;
 pub mod opcua_history_read;
+pub mod opcua_subscription;
 pub mod simple_in_memory_timeseries;
+pub mod table_discovery;
 pub mod timeseries_sql_rewrite;
 
+use crate::function_registry::FunctionRegistry;
+use crate::pushdown_setting::{all_aggregate_pushdowns, AggregatePushdown};
 use crate::timeseries_query::TimeSeriesQuery;
 use async_trait::async_trait;
 use polars::frame::DataFrame;
+use spargebra::algebra::Function;
+use std::collections::HashSet;
 use std::error::Error;
 
 #[async_trait]
-pub trait TimeSeriesQueryable {
-    async fn execute(&mut self, tsq: &TimeSeriesQuery) -> Result<DataFrame, Box<dyn Error>>;
+pub trait TimeSeriesQueryable: Send {
+    async fn execute(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>>;
+    //Executes the query, returning at most limit rows
+    async fn execute_with_limit(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: usize,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        Ok(self
+            .execute(tsq, function_registry)
+            .await?
+            .head(Some(limit)))
+    }
+    //Executes the query, returning the limit rows with the earliest timestamps
+    async fn execute_earliest(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: usize,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let mut df = self.execute(tsq, function_registry).await?;
+        if let Some(timestamp_variable) = tsq.get_timestamp_variables().get(0) {
+            df = df.sort(vec![timestamp_variable.variable.as_str()], false)?;
+        }
+        Ok(df.head(Some(limit)))
+    }
     fn allow_compound_timeseries_queries(&self) -> bool;
+    fn function_pushdowns(&self) -> HashSet<Function> {
+        HashSet::new()
+    }
+    fn custom_function_pushdowns(&self, _function_registry: &FunctionRegistry) -> HashSet<String> {
+        HashSet::new()
+    }
+    fn custom_aggregate_pushdowns(&self, _function_registry: &FunctionRegistry) -> HashSet<String> {
+        HashSet::new()
+    }
+    fn aggregate_pushdowns(&self) -> HashSet<AggregatePushdown> {
+        all_aggregate_pushdowns()
+    }
+    //The query that would be sent to the database, for databases queried with a query language
+    fn query_string(
+        &self,
+        _tsq: &TimeSeriesQuery,
+        _function_registry: &FunctionRegistry,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(None)
+    }
 }
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::function_registry::FunctionRegistry;
 use crate::timeseries_database::TimeSeriesQueryable;
 use crate::timeseries_query::TimeSeriesQuery;
 use arrow2::io::flight as flight2;
@@ -21,8 +22,12 @@ use async_trait::async_trait;
 use polars::frame::DataFrame;
 use polars_core::utils::accumulate_dataframes_vertical;
 
+use crate::timeseries_database::table_discovery::{
+    catalog_columns_from_df, information_schema_columns_query, TableDiscovery,
+};
 use crate::timeseries_database::timeseries_sql_rewrite::{
-    TimeSeriesQueryToSQLError, TimeSeriesQueryToSQLTransformer, TimeSeriesTable,
+    sql_function_pushdowns, SQLDialect, TimeSeriesQueryToSQLError, TimeSeriesQueryToSQLTransformer,
+    TimeSeriesTable,
 };
 use arrow_format::flight::service::flight_service_client::FlightServiceClient;
 use arrow_format::ipc::planus::ReadAsRoot;
@@ -30,7 +35,9 @@ use arrow_format::ipc::MessageHeaderRef;
 use log::{debug, warn};
 use polars_core::error::ArrowError;
 use polars_core::prelude::PolarsError;
-use sea_query::PostgresQueryBuilder;
+use sea_query::{Alias, Order, PostgresQueryBuilder};
+use spargebra::algebra::Function;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::time::Instant;
@@ -78,6 +85,7 @@ pub struct ArrowFlightSQLDatabase {
     token: Option<String>,
     cookies: Option<Vec<String>>,
     time_series_tables: Vec<TimeSeriesTable>,
+    dialect: SQLDialect,
 }
 
 impl ArrowFlightSQLDatabase {
@@ -94,11 +102,17 @@ impl ArrowFlightSQLDatabase {
             token: None,
             cookies: None,
             time_series_tables,
+            dialect: SQLDialect::Dremio,
         };
         db.init().await?;
         Ok(db)
     }
 
+    pub fn with_dialect(mut self, dialect: SQLDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
     async fn init(&mut self) -> Result<(), ArrowFlightSQLError> {
         let token = self.get_token().await?;
         self.token = Some(token);
@@ -118,6 +132,43 @@ impl ArrowFlightSQLDatabase {
         Ok(channel)
     }
 
+    //Proposes tables from the catalog of the database using the naming conventions of the discovery
+    pub async fn discover_tables(
+        &mut self,
+        discovery: &TableDiscovery,
+    ) -> Result<Vec<TimeSeriesTable>, ArrowFlightSQLError> {
+        let df = self
+            .execute_sql_query(information_schema_columns_query(&self.dialect))
+            .await?;
+        Ok(discovery.propose_tables(&catalog_columns_from_df(&df)))
+    }
+
+    fn create_sql_query(
+        &self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: Option<usize>,
+        order_by_timestamp: bool,
+    ) -> Result<String, TimeSeriesQueryToSQLError> {
+        let transformer =
+            TimeSeriesQueryToSQLTransformer::new(&self.time_series_tables, function_registry)
+                .with_dialect(self.dialect);
+        let (mut query, _) = transformer.create_query(tsq, false)?;
+        if order_by_timestamp {
+            if let Some(timestamp_variable) = tsq.get_timestamp_variables().get(0) {
+                query.order_by(Alias::new(timestamp_variable.variable.as_str()), Order::Asc);
+            }
+        }
+        if let Some(limit) = limit {
+            query.limit(limit as u64);
+        }
+        Ok(query.to_string(PostgresQueryBuilder))
+    }
+
+    pub fn add_time_series_tables(&mut self, time_series_tables: Vec<TimeSeriesTable>) {
+        self.time_series_tables.extend(time_series_tables);
+    }
+
     pub async fn execute_sql_query(
         &mut self,
         query: String,
@@ -222,20 +273,74 @@ impl ArrowFlightSQLDatabase {
 
 #[async_trait]
 impl TimeSeriesQueryable for ArrowFlightSQLDatabase {
-    async fn execute(&mut self, tsq: &TimeSeriesQuery) -> Result<DataFrame, Box<dyn Error>> {
-        let query_string;
-        {
-            let transformer = TimeSeriesQueryToSQLTransformer::new(&self.time_series_tables);
-            let (query, _) = transformer.create_query(tsq, false)?;
-            query_string = query.to_string(PostgresQueryBuilder);
-            debug!("SQL: {}", query_string);
-        }
+    async fn execute(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let query_string = self.create_sql_query(tsq, function_registry, None, false)?;
+        debug!("SQL: {}", query_string);
         Ok(self.execute_sql_query(query_string).await?)
     }
 
+    async fn execute_with_limit(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: usize,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let query_string = self.create_sql_query(tsq, function_registry, Some(limit), false)?;
+        debug!("SQL: {}", query_string);
+        Ok(self.execute_sql_query(query_string).await?)
+    }
+
+    async fn execute_earliest(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: usize,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let query_string = self.create_sql_query(tsq, function_registry, Some(limit), true)?;
+        debug!("SQL: {}", query_string);
+        Ok(self.execute_sql_query(query_string).await?)
+    }
+
+    fn query_string(
+        &self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(Some(self.create_sql_query(
+            tsq,
+            function_registry,
+            None,
+            false,
+        )?))
+    }
+
     fn allow_compound_timeseries_queries(&self) -> bool {
         true
     }
+
+    fn function_pushdowns(&self) -> HashSet<Function> {
+        sql_function_pushdowns(&self.dialect)
+    }
+
+    fn custom_function_pushdowns(&self, function_registry: &FunctionRegistry) -> HashSet<String> {
+        function_registry
+            .functions()
+            .filter(|(_, f)| f.sql_function.is_some())
+            .map(|(iri, _)| iri.to_string())
+            .collect()
+    }
+
+    fn custom_aggregate_pushdowns(&self, function_registry: &FunctionRegistry) -> HashSet<String> {
+        function_registry
+            .aggregates()
+            .filter(|(_, a)| a.sql_aggregate.is_some())
+            .map(|(iri, _)| iri.to_string())
+            .collect()
+    }
 }
 
 //Adapted from: https://github.com/apache/arrow-rs/blob/master/integration-testing/src/flight_client_scenarios/auth_basic_proto.rs
@@ -1,13 +1,21 @@
-use crate::constants::DATETIME_AS_SECONDS;
+use crate::constants::{
+    DATETIME_AS_SECONDS, DELTA, DURATION_BAD, DURATION_GOOD, FIRST, INTERPOLATIVE, LAST,
+    PERCENT_BAD, PERCENT_GOOD, RANGE, RESAMPLE, STANDARD_DEVIATION, TIME_WEIGHTED_AVERAGE,
+};
+use crate::function_registry::FunctionRegistry;
+use crate::pushdown_setting::AggregatePushdown;
 use crate::query_context::Context;
 use crate::timeseries_database::TimeSeriesQueryable;
-use crate::timeseries_query::TimeSeriesQuery;
+use crate::timeseries_query::{EventField, Synchronizer, TimeSeriesQuery};
 use async_trait::async_trait;
+use log::warn;
 use opcua_client::prelude::{
-    AggregateConfiguration, AttributeService, ByteString, Client, ClientBuilder, DateTime,
-    EndpointDescription, Guid, HistoryData, HistoryReadAction, HistoryReadResult,
-    HistoryReadValueId, Identifier, IdentityToken, MessageSecurityMode, NodeId, QualifiedName,
-    ReadProcessedDetails, ReadRawModifiedDetails, Session, TimestampsToReturn, UAString,
+    AggregateConfiguration, AttributeId, AttributeService, ByteString, Client, ClientBuilder,
+    ContentFilter, DateTime, EndpointDescription, EventFilter, ExtensionObject, Guid, HistoryData,
+    HistoryEvent, HistoryEventFieldList, HistoryReadAction, HistoryReadResult, HistoryReadValueId,
+    Identifier, IdentityToken, MessageSecurityMode, NodeId, ObjectTypeId, QualifiedName,
+    ReadAtTimeDetails, ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, ReadValueId,
+    SecurityPolicy, Session, SimpleAttributeOperand, StatusCode, TimestampsToReturn, UAString,
     UserTokenPolicy, Variant,
 };
 use oxrdf::vocab::xsd;
@@ -15,31 +23,74 @@ use oxrdf::{Literal, Variable};
 use polars::export::chrono::{DateTime as ChronoDateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use polars::prelude::{concat, IntoLazy};
 use polars_core::frame::DataFrame;
-use polars_core::prelude::{AnyValue, DataType, NamedFrom};
+use polars_core::prelude::{AnyValue, DataType, JoinType, NamedFrom, PolarsError, TimeUnit};
 use polars_core::series::Series;
+use serde::Deserialize;
 use spargebra::algebra::{AggregateExpression, Expression, Function};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 
 const OPCUA_AGG_FUNC_AVERAGE: u32 = 2342;
+const OPCUA_AGG_FUNC_TIME_AVERAGE: u32 = 2343;
 const OPCUA_AGG_FUNC_COUNT: u32 = 2352;
 const OPCUA_AGG_FUNC_MINIMUM: u32 = 2346;
 const OPCUA_AGG_FUNC_MAXIMUM: u32 = 2347;
 const OPCUA_AGG_FUNC_TOTAL: u32 = 2344;
+const OPCUA_AGG_FUNC_START: u32 = 2357;
+const OPCUA_AGG_FUNC_END: u32 = 2358;
+const OPCUA_AGG_FUNC_INTERPOLATIVE: u32 = 2341;
+const OPCUA_AGG_FUNC_RANGE: u32 = 2350;
+const OPCUA_AGG_FUNC_DELTA: u32 = 2359;
+const OPCUA_AGG_FUNC_DURATION_GOOD: u32 = 2360;
+const OPCUA_AGG_FUNC_DURATION_BAD: u32 = 2361;
+const OPCUA_AGG_FUNC_PERCENT_GOOD: u32 = 2362;
+const OPCUA_AGG_FUNC_PERCENT_BAD: u32 = 2363;
+const OPCUA_AGG_FUNC_STANDARD_DEVIATION_SAMPLE: u32 = 11426;
+
+//Upper bound on the number of values held in memory while reading pages from the server
+const DEFAULT_MAX_VALUES: usize = 100_000_000;
 
 pub struct OPCUAHistoryRead {
     client: Client,
     session: Arc<RwLock<Session>>,
     namespace: u16,
+    max_values: usize,
+    timestamps_to_return: TimestampsToReturn,
+    aggregate_configuration: OPCUAAggregateConfiguration,
+    synchronization: OPCUASynchronization,
+    //Browse paths of the event fields, relative to BaseEventType
+    event_fields: HashMap<EventField, Vec<QualifiedName>>,
+}
+
+//How time series that share timestamps are read together
+#[derive(Clone, Debug, PartialEq)]
+pub enum OPCUASynchronization {
+    //Read all series in full and join them on the timestamps
+    LocalJoin,
+    //Read the first series, and sample the others at its timestamps using ReadAtTime
+    ReadAtTime,
 }
 
 #[derive(Debug)]
 pub enum OPCUAHistoryReadError {
     InvalidNodeIdError(String),
+    InvalidBrowsePath(String),
+    InvalidSecurityConfiguration(String),
+    ConnectionError(StatusCode),
     TimeSeriesQueryTypeNotSupported,
+    HistoryReadError(StatusCode),
+    ReadError(StatusCode),
+    MaxValuesExceeded(usize),
+    VariantNotSupported(String),
+    MixedVariantTypes,
+    MissingHistoryData(String),
+    AggregationNotSupported(String),
+    WrongNumberOfSynchronizers(usize),
+    PolarsError(PolarsError),
 }
 
 impl Display for OPCUAHistoryReadError {
@@ -48,58 +99,375 @@ impl Display for OPCUAHistoryReadError {
             OPCUAHistoryReadError::InvalidNodeIdError(s) => {
                 write!(f, "Invalid NodeId {}", s)
             }
+            OPCUAHistoryReadError::InvalidBrowsePath(s) => {
+                write!(f, "Invalid browse path {}", s)
+            }
+            OPCUAHistoryReadError::InvalidSecurityConfiguration(s) => {
+                write!(f, "Invalid security configuration: {}", s)
+            }
+            OPCUAHistoryReadError::ConnectionError(status_code) => {
+                write!(f, "Could not connect to server: {}", status_code)
+            }
             OPCUAHistoryReadError::TimeSeriesQueryTypeNotSupported => {
                 write!(f, "Only grouped and basic query types are supported")
             }
+            OPCUAHistoryReadError::HistoryReadError(status_code) => {
+                write!(f, "History read failed with status {}", status_code)
+            }
+            OPCUAHistoryReadError::ReadError(status_code) => {
+                write!(
+                    f,
+                    "Read of current values failed with status {}",
+                    status_code
+                )
+            }
+            OPCUAHistoryReadError::MaxValuesExceeded(max_values) => {
+                write!(
+                    f,
+                    "History read returned more than the maximum of {} values",
+                    max_values
+                )
+            }
+            OPCUAHistoryReadError::VariantNotSupported(v) => {
+                write!(f, "History values of type {} are not supported", v)
+            }
+            OPCUAHistoryReadError::MixedVariantTypes => {
+                write!(f, "History values of a node must all have the same type")
+            }
+            OPCUAHistoryReadError::MissingHistoryData(id) => {
+                write!(f, "Server returned no history data for {}", id)
+            }
+            OPCUAHistoryReadError::AggregationNotSupported(a) => {
+                write!(f, "Aggregation {} is not supported by the server", a)
+            }
+            OPCUAHistoryReadError::WrongNumberOfSynchronizers(n) => {
+                write!(f, "Expected a single synchronizer, got {}", n)
+            }
+            OPCUAHistoryReadError::PolarsError(err) => {
+                write!(f, "Could not combine history results: {}", err)
+            }
         }
     }
 }
 
 impl Error for OPCUAHistoryReadError {}
 
-impl OPCUAHistoryRead {
-    pub fn new(endpoint: &str, namespace: u16) -> OPCUAHistoryRead {
-        //From: https://github.com/locka99/opcua/blob/master/docs/client.md
-        let mut client = ClientBuilder::new()
-            .application_name("My First Client")
-            .application_uri("urn:MyFirstClient")
-            .create_sample_keypair(true)
-            .trust_server_certs(true)
-            .session_retry_limit(3)
-            .client()
-            .unwrap();
+//How the server computes processed (aggregated) history, as in the AggregateConfiguration of the specification.
+//Percentages give the share of good or bad data needed for an interval to be good or bad.
+#[derive(Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OPCUAAggregateConfiguration {
+    #[serde(default)]
+    pub use_server_capabilities_defaults: bool,
+    #[serde(default)]
+    pub treat_uncertain_as_bad: bool,
+    #[serde(default)]
+    pub percent_data_bad: u8,
+    #[serde(default)]
+    pub percent_data_good: u8,
+    #[serde(default)]
+    pub use_sloped_extrapolation: bool,
+}
 
-        let endpoint: EndpointDescription = (
-            endpoint,
-            "None",
-            MessageSecurityMode::None,
-            UserTokenPolicy::anonymous(),
-        )
-            .into();
+impl OPCUAAggregateConfiguration {
+    fn to_aggregate_configuration(&self) -> AggregateConfiguration {
+        AggregateConfiguration {
+            use_server_capabilities_defaults: self.use_server_capabilities_defaults,
+            treat_uncertain_as_bad: self.treat_uncertain_as_bad,
+            percent_data_bad: self.percent_data_bad,
+            percent_data_good: self.percent_data_good,
+            use_sloped_extrapolation: self.use_sloped_extrapolation,
+        }
+    }
+}
 
-        let session = client
-            .connect_to_endpoint(endpoint, IdentityToken::Anonymous)
-            .unwrap();
+//How the client authenticates the user towards the server
+#[derive(Clone, Debug, PartialEq)]
+pub enum OPCUAIdentity {
+    Anonymous,
+    UserName(String, String),
+    //Paths to the user certificate and its private key
+    X509(PathBuf, PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct OPCUAClientConfig {
+    pub security_policy: SecurityPolicy,
+    pub security_mode: MessageSecurityMode,
+    pub identity: OPCUAIdentity,
+    //Directory with the client certificate and the trusted and rejected server certificates
+    pub pki_dir: Option<PathBuf>,
+    //Paths relative to the pki directory, a sample keypair is created when these are not set
+    pub certificate_path: Option<PathBuf>,
+    pub private_key_path: Option<PathBuf>,
+    pub trust_server_certs: bool,
+}
+
+impl Default for OPCUAClientConfig {
+    fn default() -> Self {
+        OPCUAClientConfig {
+            security_policy: SecurityPolicy::None,
+            security_mode: MessageSecurityMode::None,
+            identity: OPCUAIdentity::Anonymous,
+            pki_dir: None,
+            certificate_path: None,
+            private_key_path: None,
+            trust_server_certs: false,
+        }
+    }
+}
 
-        OPCUAHistoryRead {
+impl OPCUAClientConfig {
+    pub fn with_security(
+        mut self,
+        security_policy: SecurityPolicy,
+        security_mode: MessageSecurityMode,
+    ) -> OPCUAClientConfig {
+        self.security_policy = security_policy;
+        self.security_mode = security_mode;
+        self
+    }
+
+    //Policies are named as in the specification, e.g. Basic256Sha256, modes are None, Sign or SignAndEncrypt
+    pub fn with_security_names(
+        self,
+        security_policy: &str,
+        security_mode: &str,
+    ) -> Result<OPCUAClientConfig, OPCUAHistoryReadError> {
+        let security_policy = match SecurityPolicy::from_str(security_policy) {
+            Ok(SecurityPolicy::Unknown) | Err(_) => {
+                return Err(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+                    format!("Unknown security policy {}", security_policy),
+                ))
+            }
+            Ok(security_policy) => security_policy,
+        };
+        let security_mode = match security_mode {
+            "None" => MessageSecurityMode::None,
+            "Sign" => MessageSecurityMode::Sign,
+            "SignAndEncrypt" => MessageSecurityMode::SignAndEncrypt,
+            _ => {
+                return Err(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+                    format!("Unknown security mode {}", security_mode),
+                ))
+            }
+        };
+        Ok(self.with_security(security_policy, security_mode))
+    }
+
+    pub fn with_identity(mut self, identity: OPCUAIdentity) -> OPCUAClientConfig {
+        self.identity = identity;
+        self
+    }
+
+    pub fn with_pki_dir(mut self, pki_dir: PathBuf) -> OPCUAClientConfig {
+        self.pki_dir = Some(pki_dir);
+        self
+    }
+
+    pub fn with_certificate(
+        mut self,
+        certificate_path: PathBuf,
+        private_key_path: PathBuf,
+    ) -> OPCUAClientConfig {
+        self.certificate_path = Some(certificate_path);
+        self.private_key_path = Some(private_key_path);
+        self
+    }
+
+    pub fn with_trust_server_certs(mut self, trust_server_certs: bool) -> OPCUAClientConfig {
+        self.trust_server_certs = trust_server_certs;
+        self
+    }
+
+    fn validate(&self) -> Result<(), OPCUAHistoryReadError> {
+        if self.security_policy == SecurityPolicy::Unknown {
+            return Err(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+                "Unknown security policy".to_string(),
+            ));
+        }
+        if (self.security_policy == SecurityPolicy::None)
+            != (self.security_mode == MessageSecurityMode::None)
+        {
+            return Err(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+                format!(
+                    "Security policy {} can not be used with security mode {:?}",
+                    self.security_policy.to_str(),
+                    self.security_mode
+                ),
+            ));
+        }
+        if self.certificate_path.is_some() != self.private_key_path.is_some() {
+            return Err(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+                "Certificate and private key must be set together".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl OPCUAHistoryRead {
+    pub fn new(endpoint: &str, namespace: u16) -> Result<OPCUAHistoryRead, OPCUAHistoryReadError> {
+        OPCUAHistoryRead::from_config(endpoint, namespace, &OPCUAClientConfig::default())
+    }
+
+    //Ids without a namespace are looked up in the given namespace
+    pub fn from_config(
+        endpoint: &str,
+        namespace: u16,
+        config: &OPCUAClientConfig,
+    ) -> Result<OPCUAHistoryRead, OPCUAHistoryReadError> {
+        let (client, session) = connect(endpoint, config)?;
+        Ok(OPCUAHistoryRead {
             client,
             session,
             namespace,
+            max_values: DEFAULT_MAX_VALUES,
+            timestamps_to_return: TimestampsToReturn::Source,
+            aggregate_configuration: OPCUAAggregateConfiguration::default(),
+            synchronization: OPCUASynchronization::LocalJoin,
+            event_fields: default_event_fields(),
+        })
+    }
+
+    pub fn with_max_values(mut self, max_values: usize) -> OPCUAHistoryRead {
+        self.max_values = max_values;
+        self
+    }
+
+    //Use the timestamps assigned by the server rather than by the data source
+    pub fn with_server_timestamps(mut self, server_timestamps: bool) -> OPCUAHistoryRead {
+        self.timestamps_to_return = if server_timestamps {
+            TimestampsToReturn::Server
+        } else {
+            TimestampsToReturn::Source
+        };
+        self
+    }
+
+    //Used by the server when computing processed (aggregated) history reads
+    pub fn with_aggregate_configuration(
+        mut self,
+        aggregate_configuration: OPCUAAggregateConfiguration,
+    ) -> OPCUAHistoryRead {
+        self.aggregate_configuration = aggregate_configuration;
+        self
+    }
+
+    pub fn with_synchronization(
+        mut self,
+        synchronization: OPCUASynchronization,
+    ) -> OPCUAHistoryRead {
+        self.synchronization = synchronization;
+        self
+    }
+
+    //Reads the event field from another field than the standard one, e.g. a vendor specific message.
+    //Names are qualified with their namespace index as in 2:AlarmText, and nested names are separated by /
+    pub fn with_event_field(
+        mut self,
+        event_field: EventField,
+        browse_path: &str,
+    ) -> Result<OPCUAHistoryRead, OPCUAHistoryReadError> {
+        let mut qualified_names = vec![];
+        for name in browse_path.split('/') {
+            let qualified_name = match name.split_once(':') {
+                Some((namespace, name)) => {
+                    let namespace = namespace.parse::<u16>().map_err(|_| {
+                        OPCUAHistoryReadError::InvalidBrowsePath(browse_path.to_string())
+                    })?;
+                    QualifiedName::new(namespace, name)
+                }
+                None => QualifiedName::new(0, name),
+            };
+            qualified_names.push(qualified_name);
         }
+        self.event_fields.insert(event_field, qualified_names);
+        Ok(self)
     }
-}
 
-#[async_trait]
-impl TimeSeriesQueryable for OPCUAHistoryRead {
-    async fn execute(&mut self, tsq: &TimeSeriesQuery) -> Result<DataFrame, Box<dyn Error>> {
-        validate_tsq(tsq, true, false)?;
-        let session = self.session.write().unwrap();
-        let start_time = find_time(tsq, &FindTime::Start);
-        let end_time = find_time(tsq, &FindTime::End);
+    fn execute_synchronized(
+        &self,
+        inners: &Vec<Box<TimeSeriesQuery>>,
+        synchronizers: &Vec<Synchronizer>,
+        start_time: DateTime,
+        end_time: DateTime,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let timestamp_col =
+            if let [Synchronizer::Identity(timestamp_col)] = synchronizers.as_slice() {
+                timestamp_col
+            } else {
+                return Err(Box::new(OPCUAHistoryReadError::WrongNumberOfSynchronizers(
+                    synchronizers.len(),
+                )));
+            };
+        let mut inners_iter = inners.iter();
+        let mut first_df = self.history_read(
+            inners_iter.next().unwrap(),
+            start_time,
+            end_time,
+            None,
+            None,
+            function_registry,
+        )?;
+        let req_times = if let OPCUASynchronization::ReadAtTime = self.synchronization {
+            let timestamps = first_df
+                .column(timestamp_col)?
+                .unique()?
+                .sort(false)
+                .cast(&DataType::Int64)?;
+            let req_times: Vec<DateTime> = timestamps
+                .i64()?
+                .into_iter()
+                .flatten()
+                .map(nanos_to_datetime)
+                .collect();
+            //Without timestamps to sample at, the join below will be empty anyway
+            if req_times.is_empty() {
+                None
+            } else {
+                Some(req_times)
+            }
+        } else {
+            None
+        };
+        for inner in inners_iter {
+            let df = self.history_read(
+                inner,
+                start_time,
+                end_time,
+                req_times.clone(),
+                None,
+                function_registry,
+            )?;
+            first_df = first_df.join(
+                &df,
+                [timestamp_col.as_str()],
+                [timestamp_col.as_str()],
+                JoinType::Inner,
+                None,
+            )?;
+        }
+        Ok(first_df)
+    }
 
-        let mut processed_details = None;
+    //Reads either processed, at time or raw history, depending on the query and requested times
+    fn history_read(
+        &self,
+        tsq: &TimeSeriesQuery,
+        start_time: DateTime,
+        end_time: DateTime,
+        req_times: Option<Vec<DateTime>>,
+        values_per_node: Option<u32>,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        if tsq.has_events() {
+            return self.history_read_events(tsq, start_time, end_time);
+        }
+        let session = self.session.write().unwrap();
+        let action;
         let mut timestamp_grouping_colname = None;
-        let mut raw_modified_details = None;
 
         let mut colnames_identifiers = vec![];
         let mut grouping_col_lookup = HashMap::new();
@@ -110,12 +478,14 @@ impl TimeSeriesQueryable for OPCUAHistoryRead {
                 start_time,
                 end_time,
                 &grouped.graph_pattern_context,
-            );
-            processed_details = Some(processed_details_some);
+                function_registry,
+                &self.aggregate_configuration,
+            )?;
+            action = HistoryReadAction::ReadProcessedDetails(processed_details_some);
             timestamp_grouping_colname = colname;
             for c in grouped.tsq.get_ids() {
                 for (v, _) in &grouped.aggregations {
-                    colnames_identifiers.push((v.as_str().to_string(), c.clone()));
+                    colnames_identifiers.push((Some(v.as_str().to_string()), c.clone()));
                 }
             }
             let mapping_df = grouped.tsq.get_groupby_mapping_df().unwrap();
@@ -147,140 +517,577 @@ impl TimeSeriesQueryable for OPCUAHistoryRead {
                 grouping_col_lookup.insert(id_value, grouping_col_value);
             }
         } else {
-            raw_modified_details = Some(create_raw_details(start_time, end_time));
+            if let Some(req_times) = req_times {
+                action = HistoryReadAction::ReadAtTimeDetails(ReadAtTimeDetails {
+                    req_times: Some(req_times),
+                    use_simple_bounds: true,
+                });
+            } else {
+                action = HistoryReadAction::ReadRawModifiedDetails(create_raw_details(
+                    start_time,
+                    end_time,
+                    values_per_node.unwrap_or(0),
+                ));
+            }
+            //Values are read even when only the timestamp or quality is used in the query
+            let value_colname = tsq
+                .get_value_variables()
+                .get(0)
+                .map(|v| v.variable.as_str().to_string());
             for c in tsq.get_ids() {
-                colnames_identifiers.push((
-                    tsq.get_value_variables()
-                        .get(0)
-                        .as_ref()
-                        .unwrap()
-                        .variable
-                        .as_str()
-                        .to_string(),
-                    c.clone(),
-                ))
+                colnames_identifiers.push((value_colname.clone(), c.clone()))
             }
         }
 
         let mut nodes_to_read_vec = vec![];
         for (_, id) in &colnames_identifiers {
             let hrvi = HistoryReadValueId {
-                node_id: node_id_from_string(id)?,
+                node_id: node_id_from_string(id, self.namespace)?,
                 index_range: UAString::null(),
                 data_encoding: QualifiedName::null(),
                 continuation_point: ByteString::null(),
             };
             nodes_to_read_vec.push(hrvi);
         }
-        //let series = vec![];
-        let mut stopped = false;
-        let mut dfs = vec![];
-        while !stopped {
-            let action = if let Some(d) = &processed_details {
-                HistoryReadAction::ReadProcessedDetails(d.clone())
-            } else if let Some(d) = &raw_modified_details {
+        //The aggregate types of processed reads must line up with the nodes we read
+        let make_action = |node_indices: &[usize]| match &action {
+            HistoryReadAction::ReadProcessedDetails(d) => {
+                let mut d = d.clone();
+                d.aggregate_type = d.aggregate_type.map(|aggregate_types| {
+                    node_indices
+                        .iter()
+                        .map(|i| aggregate_types.get(*i).unwrap().clone())
+                        .collect()
+                });
+                HistoryReadAction::ReadProcessedDetails(d)
+            }
+            HistoryReadAction::ReadRawModifiedDetails(d) => {
                 HistoryReadAction::ReadRawModifiedDetails(d.clone())
+            }
+            HistoryReadAction::ReadAtTimeDetails(d) => {
+                HistoryReadAction::ReadAtTimeDetails(d.clone())
+            }
+            HistoryReadAction::ReadEventDetails(d) => {
+                HistoryReadAction::ReadEventDetails(d.clone())
+            }
+        };
+
+        let mut chunks: Vec<Vec<(Series, Series, Series)>> = vec![vec![]; nodes_to_read_vec.len()];
+        //The latest values are read with the Read service, which is not paged
+        let unfinished: Vec<usize> = if tsq.has_latest() {
+            let values_to_read: Vec<ReadValueId> = nodes_to_read_vec
+                .iter()
+                .map(|n| ReadValueId {
+                    node_id: n.node_id.clone(),
+                    attribute_id: AttributeId::Value as u32,
+                    index_range: UAString::null(),
+                    data_encoding: QualifiedName::null(),
+                })
+                .collect();
+            let data_values = session
+                .read(&values_to_read, self.timestamps_to_return, 0.0)
+                .map_err(OPCUAHistoryReadError::ReadError)?;
+            for (i, data_value) in data_values.into_iter().enumerate() {
+                let history_data = HistoryData {
+                    data_values: Some(vec![data_value]),
+                };
+                chunks
+                    .get_mut(i)
+                    .unwrap()
+                    .push(history_data_to_series_tuple(
+                        history_data,
+                        &self.timestamps_to_return,
+                    )?);
+            }
+            vec![]
+        } else {
+            (0..nodes_to_read_vec.len()).collect()
+        };
+        self.read_history_pages(
+            &session,
+            make_action,
+            &mut nodes_to_read_vec,
+            unfinished,
+            values_per_node.is_some(),
+            &mut chunks,
+            |history_data| {
+                let history_data = history_data
+                    .decode_inner::<HistoryData>(&Default::default())
+                    .map_err(OPCUAHistoryReadError::HistoryReadError)?;
+                let tuple = history_data_to_series_tuple(history_data, &self.timestamps_to_return)?;
+                let values_read = tuple.0.len();
+                Ok((tuple, values_read))
+            },
+        )?;
+
+        let mut series_map: HashMap<String, (Series, Vec<Series>)> = HashMap::new();
+        for (i, mut node_chunks) in chunks.into_iter().enumerate() {
+            //Empty pages may not have the right data type, so we skip them when we can
+            if node_chunks.iter().any(|(ts, ..)| !ts.is_empty()) {
+                node_chunks.retain(|(ts, ..)| !ts.is_empty());
+            }
+            let (colname, id) = colnames_identifiers.get(i).unwrap();
+            let mut node_chunks_iter = node_chunks.into_iter();
+            let (mut ts, mut val, mut quality) = node_chunks_iter
+                .next()
+                .ok_or_else(|| OPCUAHistoryReadError::MissingHistoryData(id.clone()))?;
+            //Pages of the same node may only differ in type if the values have different types
+            for (more_ts, more_val, more_quality) in node_chunks_iter {
+                ts.append(&more_ts)
+                    .map_err(|_| OPCUAHistoryReadError::MixedVariantTypes)?;
+                val.append(&more_val)
+                    .map_err(|_| OPCUAHistoryReadError::MixedVariantTypes)?;
+                quality
+                    .append(&more_quality)
+                    .map_err(|_| OPCUAHistoryReadError::MixedVariantTypes)?;
+            }
+            if let Some(grvar) = &timestamp_grouping_colname {
+                ts.rename(grvar);
             } else {
-                panic!("");
-            };
-            let resp = session
-                .history_read(
-                    action,
-                    TimestampsToReturn::Source,
-                    false,
-                    nodes_to_read_vec.as_slice(),
+                ts.rename(
+                    tsq.get_timestamp_variables()
+                        .get(0)
+                        .unwrap()
+                        .variable
+                        .as_str(),
+                );
+            }
+            let mut columns = vec![];
+            if let Some(colname) = colname {
+                val.rename(colname);
+                columns.push(val);
+            }
+            //Aggregated values have no quality variable in the query
+            if let (false, Some(quality_variable)) = (
+                matches!(action, HistoryReadAction::ReadProcessedDetails(_)),
+                tsq.get_quality_variables().get(0),
+            ) {
+                quality.rename(quality_variable.variable.as_str());
+                columns.push(quality);
+            }
+            if let Some((_, v)) = series_map.get_mut(id) {
+                v.extend(columns);
+            } else {
+                series_map.insert(id.clone(), (ts, columns));
+            }
+        }
+        let mut dfs = vec![];
+        let mut keys: Vec<String> = series_map.keys().map(|x| x.clone()).collect();
+        keys.sort();
+        for k in keys {
+            let (ts, mut value_vec) = series_map.remove(&k).unwrap();
+            let mut identifier_series = if let Some(grouping_col) = grouping_col_name {
+                Series::new_empty(grouping_col, &DataType::Int64)
+            } else {
+                Series::new_empty(
+                    tsq.get_identifier_variables().get(0).unwrap().as_str(),
+                    &DataType::Utf8,
                 )
-                .expect("");
-            //First we set the new continuation points:
-            for (i, h) in resp.iter().enumerate() {
-                if !h.continuation_point.is_null() {
-                    nodes_to_read_vec.get_mut(i).unwrap().continuation_point =
-                        h.continuation_point.clone();
-                    todo!("Continuation points are just halfway implemented...");
+            };
+            identifier_series = if let Some(_) = grouping_col_name {
+                identifier_series
+                    .extend_constant(
+                        AnyValue::Int64(*grouping_col_lookup.get(k.as_str()).unwrap()),
+                        ts.len(),
+                    )
+                    .unwrap()
+            } else {
+                identifier_series
+                    .extend_constant(AnyValue::Utf8(&k), ts.len())
+                    .unwrap()
+            };
+            value_vec.push(identifier_series);
+            value_vec.push(ts);
+            value_vec.sort_by_key(|x| x.name().to_string());
+            dfs.push(DataFrame::new(value_vec).unwrap().lazy())
+        }
+        let df = concat(dfs, true).unwrap().collect().unwrap();
+        Ok(df)
+    }
+
+    //Events are read from the history of the notifiers identified by the external ids
+    fn history_read_events(
+        &self,
+        tsq: &TimeSeriesQuery,
+        start_time: DateTime,
+        end_time: DateTime,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let session = self.session.write().unwrap();
+        //Only the fields used in the query are selected
+        let mut selected = vec![];
+        let timestamp_variable = tsq
+            .get_timestamp_variables()
+            .get(0)
+            .map(|v| v.variable.as_str().to_string());
+        if let Some(timestamp_variable) = &timestamp_variable {
+            let time_field = if let TimestampsToReturn::Server = self.timestamps_to_return {
+                "ReceiveTime"
+            } else {
+                "Time"
+            };
+            selected.push((
+                timestamp_variable.clone(),
+                vec![QualifiedName::new(0, time_field)],
+            ));
+        }
+        for (event_field, v) in tsq.get_event_field_variables() {
+            selected.push((
+                v.variable.as_str().to_string(),
+                self.event_fields.get(event_field).unwrap().clone(),
+            ));
+        }
+        let select_clauses = selected
+            .iter()
+            .map(|(_, browse_path)| SimpleAttributeOperand {
+                type_definition_id: ObjectTypeId::BaseEventType.into(),
+                browse_path: Some(browse_path.clone()),
+                attribute_id: AttributeId::Value as u32,
+                index_range: UAString::null(),
+            })
+            .collect();
+        let details = ReadEventDetails {
+            num_values_per_node: 0,
+            start_time,
+            end_time,
+            filter: EventFilter {
+                select_clauses: Some(select_clauses),
+                where_clause: ContentFilter { elements: None },
+            },
+        };
+
+        let mut ids: Vec<&String> = tsq.get_ids();
+        ids.sort();
+        let mut nodes_to_read_vec = vec![];
+        for id in &ids {
+            nodes_to_read_vec.push(HistoryReadValueId {
+                node_id: node_id_from_string(id, self.namespace)?,
+                index_range: UAString::null(),
+                data_encoding: QualifiedName::null(),
+                continuation_point: ByteString::null(),
+            });
+        }
+        let mut chunks: Vec<Vec<Vec<HistoryEventFieldList>>> = vec![vec![]; ids.len()];
+        self.read_history_pages(
+            &session,
+            |_| HistoryReadAction::ReadEventDetails(details.clone()),
+            &mut nodes_to_read_vec,
+            (0..ids.len()).collect(),
+            false,
+            &mut chunks,
+            |history_data| {
+                let history_event = history_data
+                    .decode_inner::<HistoryEvent>(&Default::default())
+                    .map_err(OPCUAHistoryReadError::HistoryReadError)?;
+                let events = history_event.events.unwrap_or_default();
+                let events_read = events.len();
+                Ok((events, events_read))
+            },
+        )?;
+
+        let identifier_variable = tsq.get_identifier_variables().get(0).unwrap().as_str();
+        let events_to_df = |id: &str,
+                            events: Vec<HistoryEventFieldList>|
+         -> Result<DataFrame, OPCUAHistoryReadError> {
+            let mut series_vec = vec![Series::new(identifier_variable, vec![id; events.len()])];
+            for (i, (name, _)) in selected.iter().enumerate() {
+                //Fields that do not exist for the type of an event are returned as empty
+                let variants: Vec<Option<Variant>> = events
+                    .iter()
+                    .map(|e| e.event_fields.as_ref().and_then(|f| f.get(i)).cloned())
+                    .collect();
+                if Some(name) == timestamp_variable.as_ref() {
+                    series_vec.push(
+                        Series::new(
+                            name,
+                            variants
+                                .iter()
+                                .map(|v| match v {
+                                    Some(Variant::DateTime(d)) => Some(datetime_to_nanos(d, None)),
+                                    _ => None,
+                                })
+                                .collect::<Vec<Option<i64>>>(),
+                        )
+                        .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+                        .map_err(OPCUAHistoryReadError::PolarsError)?,
+                    );
                 } else {
-                    stopped = true;
+                    series_vec.push(event_field_variants_to_series(name, variants)?);
                 }
             }
+            series_vec.sort_by_key(|x| x.name().to_string());
+            DataFrame::new(series_vec).map_err(OPCUAHistoryReadError::PolarsError)
+        };
+        let mut dfs = vec![];
+        for (id, node_chunks) in ids.iter().zip(chunks.into_iter()) {
+            dfs.push(events_to_df(
+                id,
+                node_chunks.into_iter().flatten().collect(),
+            )?);
+        }
+        //Without ids there are no events, but the columns are still expected
+        if dfs.is_empty() {
+            return Ok(events_to_df("", vec![])?);
+        }
+        //Empty results may not have the right data types, so we skip them when we can
+        if dfs.iter().any(|df| df.height() > 0) {
+            dfs.retain(|df| df.height() > 0);
+        }
+        let df = concat(
+            dfs.into_iter().map(|df| df.lazy()).collect::<Vec<_>>(),
+            true,
+        )
+        .and_then(|lf| lf.collect())
+        .map_err(OPCUAHistoryReadError::PolarsError)?;
+        Ok(df)
+    }
+
+    //Servers may page the results, so we read until all continuation points are exhausted.
+    //Each page is decoded into a chunk of the node, together with the number of values read.
+    fn read_history_pages<T, F, D>(
+        &self,
+        session: &Session,
+        make_action: F,
+        nodes_to_read_vec: &mut Vec<HistoryReadValueId>,
+        mut unfinished: Vec<usize>,
+        first_page_only: bool,
+        chunks: &mut Vec<Vec<T>>,
+        decode: D,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&[usize]) -> HistoryReadAction,
+        D: Fn(&ExtensionObject) -> Result<(T, usize), OPCUAHistoryReadError>,
+    {
+        let mut values_read = 0;
+        while !unfinished.is_empty() {
+            let nodes_to_read: Vec<HistoryReadValueId> = unfinished
+                .iter()
+                .map(|i| nodes_to_read_vec.get(*i).unwrap().clone())
+                .collect();
+            let resp = match session.history_read(
+                make_action(&unfinished),
+                self.timestamps_to_return,
+                false,
+                nodes_to_read.as_slice(),
+            ) {
+                Ok(resp) => resp,
+                Err(status_code) => {
+                    release_continuation_points(session, make_action(&unfinished), &nodes_to_read);
+                    return Err(Box::new(OPCUAHistoryReadError::HistoryReadError(
+                        status_code,
+                    )));
+                }
+            };
 
-            let mut series_map: HashMap<String, Vec<(Series, Series)>> = HashMap::new();
+            //First we set the new continuation points, so that they can be released on errors
+            let mut next_unfinished = vec![];
+            for (j, h) in resp.iter().enumerate() {
+                let i = *unfinished.get(j).unwrap();
+                nodes_to_read_vec.get_mut(i).unwrap().continuation_point =
+                    h.continuation_point.clone();
+                if !h.continuation_point.is_null() {
+                    next_unfinished.push(i);
+                }
+            }
+            let pending: Vec<HistoryReadValueId> = next_unfinished
+                .iter()
+                .map(|i| nodes_to_read_vec.get(*i).unwrap().clone())
+                .collect();
 
             //Now we process the data
-            for (i, h) in resp.into_iter().enumerate() {
+            for (j, h) in resp.into_iter().enumerate() {
+                let i = *unfinished.get(j).unwrap();
                 let HistoryReadResult {
-                    status_code: _,
+                    status_code,
                     continuation_point: _,
                     history_data,
                 } = h;
-                let (mut ts, mut val) = history_data_to_series_tuple(
-                    history_data
-                        .decode_inner::<HistoryData>(&Default::default())
-                        .unwrap(),
-                );
-                let (colname, id) = colnames_identifiers.get(i).unwrap();
-                if let Some(grvar) = &timestamp_grouping_colname {
-                    ts.rename(grvar);
-                } else {
-                    ts.rename(
-                        tsq.get_timestamp_variables()
-                            .get(0)
-                            .unwrap()
-                            .variable
-                            .as_str(),
-                    );
-                }
-                val.rename(colname);
-                if let Some(v) = series_map.get_mut(id) {
-                    v.push((ts, val));
-                } else {
-                    series_map.insert(id.clone(), vec![(ts, val)]);
+                if status_code.is_bad() {
+                    release_continuation_points(session, make_action(&next_unfinished), &pending);
+                    return Err(Box::new(OPCUAHistoryReadError::HistoryReadError(
+                        status_code,
+                    )));
                 }
-            }
-            let mut keys: Vec<String> = series_map.keys().map(|x| x.clone()).collect();
-            keys.sort();
-            for k in keys {
-                let series_vec = series_map.remove(&k).unwrap();
-                let mut first_ts = None;
-                let mut value_vec = vec![];
-                for (ts, val) in series_vec.into_iter() {
-                    if let Some(_) = &first_ts {
-                    } else {
-                        first_ts = Some(ts);
+                let (chunk, n) = match decode(&history_data) {
+                    Ok(decoded) => decoded,
+                    Err(err) => {
+                        release_continuation_points(
+                            session,
+                            make_action(&next_unfinished),
+                            &pending,
+                        );
+                        return Err(Box::new(err));
                     }
-                    value_vec.push(val);
-                }
-                let mut identifier_series = if let Some(grouping_col) = grouping_col_name {
-                    Series::new_empty(grouping_col, &DataType::Int64)
-                } else {
-                    Series::new_empty(
-                        tsq.get_identifier_variables().get(0).unwrap().as_str(),
-                        &DataType::Utf8,
-                    )
-                };
-                identifier_series = if let Some(_) = grouping_col_name {
-                    identifier_series
-                        .extend_constant(
-                            AnyValue::Int64(*grouping_col_lookup.get(k.as_str()).unwrap()),
-                            first_ts.as_ref().unwrap().len(),
-                        )
-                        .unwrap()
-                } else {
-                    identifier_series
-                        .extend_constant(AnyValue::Utf8(&k), first_ts.as_ref().unwrap().len())
-                        .unwrap()
                 };
-                value_vec.push(identifier_series);
-                value_vec.push(first_ts.unwrap());
-                value_vec.sort_by_key(|x| x.name().to_string());
-                dfs.push(DataFrame::new(value_vec).unwrap().lazy())
+                values_read += n;
+                chunks.get_mut(i).unwrap().push(chunk);
             }
+            if values_read > self.max_values {
+                release_continuation_points(session, make_action(&next_unfinished), &pending);
+                return Err(Box::new(OPCUAHistoryReadError::MaxValuesExceeded(
+                    self.max_values,
+                )));
+            }
+            if first_page_only {
+                release_continuation_points(session, make_action(&next_unfinished), &pending);
+                break;
+            }
+            unfinished = next_unfinished;
         }
-        let df = concat(dfs, true).unwrap().collect().unwrap();
-        Ok(df)
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TimeSeriesQueryable for OPCUAHistoryRead {
+    async fn execute(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        validate_tsq(tsq, true, false)?;
+        let start_time = find_time(tsq, &FindTime::Start);
+        let end_time = find_time(tsq, &FindTime::End);
+        if let Some((inners, synchronizers)) = find_synchronized(tsq) {
+            self.execute_synchronized(
+                inners,
+                synchronizers,
+                start_time,
+                end_time,
+                function_registry,
+            )
+        } else {
+            self.history_read(tsq, start_time, end_time, None, None, function_registry)
+        }
+    }
+
+    //Raw history is returned forward in time, so the earliest values of a node are
+    //the first page of a read from the start that is limited to that number of values
+    async fn execute_earliest(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+        limit: usize,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let mut df = match tsq {
+            TimeSeriesQuery::Basic(b) if !b.latest && !b.events => {
+                let start_time = nanos_to_datetime(i64::MIN);
+                self.history_read(
+                    tsq,
+                    start_time,
+                    DateTime::null(),
+                    None,
+                    Some(limit as u32),
+                    function_registry,
+                )?
+            }
+            _ => self.execute(tsq, function_registry).await?,
+        };
+        if let Some(timestamp_variable) = tsq.get_timestamp_variables().get(0) {
+            df = df.sort(vec![timestamp_variable.variable.as_str()], false)?;
+        }
+        Ok(df.head(Some(limit)))
     }
 
     fn allow_compound_timeseries_queries(&self) -> bool {
         false
     }
+
+    //Only used to find the processing interval of aggregations by time
+    fn function_pushdowns(&self) -> HashSet<Function> {
+        [Function::Floor].into()
+    }
+
+    fn custom_aggregate_pushdowns(&self, function_registry: &FunctionRegistry) -> HashSet<String> {
+        let mut pushdowns: HashSet<String> = [
+            FIRST,
+            LAST,
+            TIME_WEIGHTED_AVERAGE,
+            INTERPOLATIVE,
+            DELTA,
+            RANGE,
+            STANDARD_DEVIATION,
+            DURATION_GOOD,
+            DURATION_BAD,
+            PERCENT_GOOD,
+            PERCENT_BAD,
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+        pushdowns.extend(
+            function_registry
+                .aggregates()
+                .filter(|(_, a)| a.opcua_aggregate_function.is_some())
+                .map(|(iri, _)| iri.to_string()),
+        );
+        pushdowns
+    }
+
+    //Aggregations of other kinds are computed from raw history reads
+    fn aggregate_pushdowns(&self) -> HashSet<AggregatePushdown> {
+        [
+            AggregatePushdown::Count,
+            AggregatePushdown::Sum,
+            AggregatePushdown::Avg,
+            AggregatePushdown::Min,
+            AggregatePushdown::Max,
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+//Connects a client to the endpoint with the given security configuration
+pub(crate) fn connect(
+    endpoint: &str,
+    config: &OPCUAClientConfig,
+) -> Result<(Client, Arc<RwLock<Session>>), OPCUAHistoryReadError> {
+    config.validate()?;
+    //From: https://github.com/locka99/opcua/blob/master/docs/client.md
+    let mut builder = ClientBuilder::new()
+        .application_name("My First Client")
+        .application_uri("urn:MyFirstClient")
+        .trust_server_certs(config.trust_server_certs)
+        .session_retry_limit(3);
+    if let Some(pki_dir) = &config.pki_dir {
+        builder = builder.pki_dir(pki_dir.clone());
+    }
+    if let (Some(certificate_path), Some(private_key_path)) =
+        (&config.certificate_path, &config.private_key_path)
+    {
+        builder = builder
+            .certificate_path(certificate_path.clone())
+            .private_key_path(private_key_path.clone());
+    } else {
+        builder = builder.create_sample_keypair(true);
+    }
+    let mut client = builder.client().ok_or_else(|| {
+        OPCUAHistoryReadError::InvalidSecurityConfiguration(
+            "Could not create client from configuration".to_string(),
+        )
+    })?;
+
+    let endpoint: EndpointDescription = (
+        endpoint,
+        config.security_policy.to_str(),
+        config.security_mode,
+        UserTokenPolicy::anonymous(),
+    )
+        .into();
+    let identity_token = match &config.identity {
+        OPCUAIdentity::Anonymous => IdentityToken::Anonymous,
+        OPCUAIdentity::UserName(user, password) => {
+            IdentityToken::UserName(user.clone(), password.clone())
+        }
+        OPCUAIdentity::X509(certificate_path, private_key_path) => {
+            IdentityToken::X509(certificate_path.clone(), private_key_path.clone())
+        }
+    };
+
+    let session = client
+        .connect_to_endpoint(endpoint, identity_token)
+        .map_err(OPCUAHistoryReadError::ConnectionError)?;
+    Ok((client, session))
 }
 
 fn validate_tsq(
@@ -305,19 +1112,75 @@ fn validate_tsq(
                 Err(OPCUAHistoryReadError::TimeSeriesQueryTypeNotSupported)
             }
         }
-        TimeSeriesQuery::InnerSynchronized(_, _) => {
-            Err(OPCUAHistoryReadError::TimeSeriesQueryTypeNotSupported)
+        TimeSeriesQuery::InnerSynchronized(inners, _) => {
+            //Synchronized time series are read separately, so they cannot be aggregated by the server
+            if inside_grouping {
+                return Err(OPCUAHistoryReadError::TimeSeriesQueryTypeNotSupported);
+            }
+            for inner in inners {
+                if find_synchronized(inner).is_some() {
+                    return Err(OPCUAHistoryReadError::TimeSeriesQueryTypeNotSupported);
+                }
+                validate_tsq(inner, false, inside_grouping)?;
+            }
+            Ok(())
         }
         TimeSeriesQuery::ExpressionAs(t, _, _) => validate_tsq(t, false, inside_grouping),
     }
 }
 
-fn create_raw_details(start_time: DateTime, end_time: DateTime) -> ReadRawModifiedDetails {
+fn find_synchronized(
+    tsq: &TimeSeriesQuery,
+) -> Option<(&Vec<Box<TimeSeriesQuery>>, &Vec<Synchronizer>)> {
+    match tsq {
+        TimeSeriesQuery::InnerSynchronized(inners, synchronizers) => Some((inners, synchronizers)),
+        TimeSeriesQuery::Filtered(inner, _) => find_synchronized(inner),
+        _ => None,
+    }
+}
+
+//Lets the server free resources held for reads that will not be completed
+fn release_continuation_points(
+    session: &Session,
+    action: HistoryReadAction,
+    nodes_to_read: &[HistoryReadValueId],
+) {
+    let to_release: Vec<HistoryReadValueId> = nodes_to_read
+        .iter()
+        .filter(|x| !x.continuation_point.is_null())
+        .cloned()
+        .collect();
+    if !to_release.is_empty() {
+        if let Err(status_code) =
+            session.history_read(action, TimestampsToReturn::Source, true, &to_release)
+        {
+            warn!("Failed to release continuation points: {}", status_code);
+        }
+    }
+}
+
+fn default_event_fields() -> HashMap<EventField, Vec<QualifiedName>> {
+    [
+        (EventField::EventType, "EventType"),
+        (EventField::Severity, "Severity"),
+        (EventField::Message, "Message"),
+        (EventField::SourceNode, "SourceNode"),
+    ]
+    .into_iter()
+    .map(|(event_field, name)| (event_field, vec![QualifiedName::new(0, name)]))
+    .collect()
+}
+
+fn create_raw_details(
+    start_time: DateTime,
+    end_time: DateTime,
+    num_values_per_node: u32,
+) -> ReadRawModifiedDetails {
     ReadRawModifiedDetails {
         is_read_modified: false,
         start_time,
         end_time,
-        num_values_per_node: 0,
+        num_values_per_node,
         return_bounds: false,
     }
 }
@@ -327,16 +1190,11 @@ fn create_read_processed_details(
     start_time: DateTime,
     end_time: DateTime,
     context: &Context,
-) -> (Option<String>, ReadProcessedDetails) {
-    let aggregate_type = find_aggregate_types(tsq);
-
-    let config = AggregateConfiguration {
-        use_server_capabilities_defaults: false,
-        treat_uncertain_as_bad: false,
-        percent_data_bad: 0,
-        percent_data_good: 0,
-        use_sloped_extrapolation: false,
-    };
+    function_registry: &FunctionRegistry,
+    aggregate_configuration: &OPCUAAggregateConfiguration,
+) -> Result<(Option<String>, ReadProcessedDetails), OPCUAHistoryReadError> {
+    let aggregate_type = find_aggregate_types(tsq, function_registry)?;
+
     let interval_opt = find_grouping_interval(tsq, context);
     let (out_string, processing_interval) = if let Some((s, interval)) = interval_opt {
         (Some(s), interval)
@@ -349,105 +1207,275 @@ fn create_read_processed_details(
         end_time,
         processing_interval,
         aggregate_type,
-        aggregate_configuration: config,
+        aggregate_configuration: aggregate_configuration.to_aggregate_configuration(),
     };
-    (out_string, details)
+    Ok((out_string, details))
 }
 
-fn history_data_to_series_tuple(hd: HistoryData) -> (Series, Series) {
+pub(crate) fn history_data_to_series_tuple(
+    hd: HistoryData,
+    timestamps_to_return: &TimestampsToReturn,
+) -> Result<(Series, Series, Series), OPCUAHistoryReadError> {
     let HistoryData { data_values } = hd;
-    let data_values_vec = data_values.unwrap();
-    let mut any_value_vec = vec![];
+    let data_values_vec = data_values.unwrap_or_default();
+    let mut variants = vec![];
     let mut ts_value_vec = vec![];
+    let mut quality_vec = vec![];
     for data_value in data_values_vec {
-        if let Some(ts) = data_value.source_timestamp {
-            let polars_datetime = NaiveDateTime::from_timestamp(ts.as_chrono().timestamp(), 0);
-            ts_value_vec.push(polars_datetime);
-        }
-        if let Some(val) = data_value.value {
-            let any_value = match val {
-                Variant::Double(d) => AnyValue::Float64(d),
-                Variant::Int64(i) => AnyValue::Int64(i),
-                _ => {
-                    todo!("Implement: {}", val)
-                }
-            };
-            any_value_vec.push(any_value);
-        }
+        //Missing timestamps become nulls so that timestamps and values stay aligned
+        let (ts, picoseconds) = match timestamps_to_return {
+            TimestampsToReturn::Server => {
+                (data_value.server_timestamp, data_value.server_picoseconds)
+            }
+            _ => (data_value.source_timestamp, data_value.source_picoseconds),
+        };
+        ts_value_vec.push(ts.map(|ts| datetime_to_nanos(&ts, picoseconds)));
+        //A missing status means that the value is good
+        quality_vec.push(data_value.status.map(|s| s.bits()).unwrap_or(0));
+        variants.push(data_value.value);
     }
-    let timestamps = Series::new("timestamp", ts_value_vec.as_slice());
-    let values = Series::from_any_values("value", any_value_vec.as_slice()).unwrap();
-    (timestamps, values)
+    let timestamps = Series::new("timestamp", ts_value_vec)
+        .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+        .unwrap();
+    let values = variants_to_series("value", variants)?;
+    let qualities = Series::new("quality", quality_vec);
+    Ok((timestamps, values, qualities))
+}
+
+//Picoseconds are given in units of 10 picoseconds, below the 100ns resolution of DateTime
+fn nanos_to_datetime(nanos: i64) -> DateTime {
+    DateTime::from(Utc.timestamp_nanos(nanos))
 }
 
-fn find_aggregate_types(tsq: &TimeSeriesQuery) -> Option<Vec<NodeId>> {
+fn datetime_to_nanos(datetime: &DateTime, picoseconds: Option<u16>) -> i64 {
+    datetime.as_chrono().timestamp_nanos() + (picoseconds.unwrap_or(0) as i64) / 100
+}
+
+macro_rules! variant_series {
+    ($name:expr, $variants:expr, $variant:path, $t:ty) => {
+        Series::new(
+            $name,
+            $variants
+                .iter()
+                .map(|v| match v {
+                    Some($variant(x)) => Some(*x as $t),
+                    _ => None,
+                })
+                .collect::<Vec<Option<$t>>>(),
+        )
+    };
+}
+
+//Small integer types are widened to 32 bit, as we do not enable the small polars dtypes
+fn variants_to_series(
+    name: &str,
+    variants: Vec<Option<Variant>>,
+) -> Result<Series, OPCUAHistoryReadError> {
+    let first = variants
+        .iter()
+        .find(|v| !matches!(v, None | Some(Variant::Empty)));
+    let first = if let Some(Some(first)) = first {
+        first
+    } else {
+        return Ok(Series::full_null(name, variants.len(), &DataType::Float64));
+    };
+    //Values of a different type than the first value would otherwise silently become null
+    if variants.iter().flatten().any(|v| {
+        !matches!(v, Variant::Empty) && std::mem::discriminant(v) != std::mem::discriminant(first)
+    }) {
+        return Err(OPCUAHistoryReadError::MixedVariantTypes);
+    }
+    let series = match first {
+        Variant::Boolean(_) => variant_series!(name, variants, Variant::Boolean, bool),
+        Variant::SByte(_) => variant_series!(name, variants, Variant::SByte, i32),
+        Variant::Int16(_) => variant_series!(name, variants, Variant::Int16, i32),
+        Variant::Int32(_) => variant_series!(name, variants, Variant::Int32, i32),
+        Variant::Int64(_) => variant_series!(name, variants, Variant::Int64, i64),
+        Variant::Byte(_) => variant_series!(name, variants, Variant::Byte, u32),
+        Variant::UInt16(_) => variant_series!(name, variants, Variant::UInt16, u32),
+        Variant::UInt32(_) => variant_series!(name, variants, Variant::UInt32, u32),
+        Variant::UInt64(_) => variant_series!(name, variants, Variant::UInt64, u64),
+        Variant::Float(_) => variant_series!(name, variants, Variant::Float, f32),
+        Variant::Double(_) => variant_series!(name, variants, Variant::Double, f64),
+        Variant::StatusCode(_) => Series::new(
+            name,
+            variants
+                .iter()
+                .map(|v| match v {
+                    Some(Variant::StatusCode(s)) => Some(s.bits()),
+                    _ => None,
+                })
+                .collect::<Vec<Option<u32>>>(),
+        ),
+        Variant::DateTime(_) => Series::new(
+            name,
+            variants
+                .iter()
+                .map(|v| match v {
+                    Some(Variant::DateTime(d)) => Some(datetime_to_nanos(d, None)),
+                    _ => None,
+                })
+                .collect::<Vec<Option<i64>>>(),
+        )
+        .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+        .unwrap(),
+        Variant::String(_)
+        | Variant::LocalizedText(_)
+        | Variant::ByteString(_)
+        | Variant::Guid(_)
+        | Variant::XmlElement(_) => Series::new(
+            name,
+            variants
+                .iter()
+                .map(|v| match v {
+                    Some(Variant::String(s)) | Some(Variant::XmlElement(s)) => s.value().clone(),
+                    Some(Variant::LocalizedText(l)) => l.text.value().clone(),
+                    Some(Variant::ByteString(b)) => {
+                        if b.is_null() {
+                            None
+                        } else {
+                            Some(b.as_base64())
+                        }
+                    }
+                    Some(Variant::Guid(g)) => Some(g.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<Option<String>>>(),
+        ),
+        v => {
+            return Err(OPCUAHistoryReadError::VariantNotSupported(format!(
+                "{:?}",
+                v.type_id()
+            )))
+        }
+    };
+    Ok(series)
+}
+
+//Node ids, such as the event type and source node, are given as strings
+fn event_field_variants_to_series(
+    name: &str,
+    variants: Vec<Option<Variant>>,
+) -> Result<Series, OPCUAHistoryReadError> {
+    let variants = variants
+        .into_iter()
+        .map(|v| match v {
+            Some(Variant::NodeId(n)) => Some(Variant::String(n.to_string().into())),
+            v => v,
+        })
+        .collect();
+    variants_to_series(name, variants)
+}
+
+//Status aggregates aggregate the StatusCodes of the values, other aggregates aggregate the values
+fn find_aggregate_types(
+    tsq: &TimeSeriesQuery,
+    function_registry: &FunctionRegistry,
+) -> Result<Option<Vec<NodeId>>, OPCUAHistoryReadError> {
     if let TimeSeriesQuery::Grouped(grouped) = tsq {
+        let value_var_str = tsq
+            .get_value_variables()
+            .get(0)
+            .map(|v| v.variable.as_str().to_string());
+        let quality_var_str = tsq
+            .get_quality_variables()
+            .get(0)
+            .map(|q| q.variable.as_str().to_string());
         let mut nodes = vec![];
         for (_, agg) in &grouped.aggregations {
-            let value_var_str = tsq.get_value_variables().get(0).unwrap().variable.as_str();
-            let expr_is_ok = |expr: &Expression| -> bool {
-                if let Expression::Variable(v) = expr {
-                    v.as_str() == value_var_str
-                } else {
-                    false
-                }
-            };
-            let aggfunc = match agg {
+            let (aggfunc_id, expr, distinct, status_aggregate) = match agg {
                 AggregateExpression::Count { expr, distinct } => {
-                    assert!(!distinct);
-                    if let Some(e) = expr {
-                        assert!(expr_is_ok(e));
-                    }
-                    NodeId {
-                        namespace: 0,
-                        identifier: Identifier::Numeric(OPCUA_AGG_FUNC_COUNT),
-                    }
+                    (OPCUA_AGG_FUNC_COUNT, expr.as_deref(), distinct, false)
                 }
                 AggregateExpression::Sum { expr, distinct } => {
-                    assert!(!distinct);
-                    assert!(expr_is_ok(expr));
-                    NodeId {
-                        namespace: 0,
-                        identifier: Identifier::Numeric(OPCUA_AGG_FUNC_TOTAL),
-                    }
+                    (OPCUA_AGG_FUNC_TOTAL, Some(expr.as_ref()), distinct, false)
                 }
                 AggregateExpression::Avg { expr, distinct } => {
-                    assert!(!distinct);
-                    assert!(expr_is_ok(expr));
-                    NodeId {
-                        namespace: 0,
-                        identifier: Identifier::Numeric(OPCUA_AGG_FUNC_AVERAGE),
-                    }
+                    (OPCUA_AGG_FUNC_AVERAGE, Some(expr.as_ref()), distinct, false)
                 }
                 AggregateExpression::Min { expr, distinct } => {
-                    assert!(!distinct);
-                    assert!(expr_is_ok(expr));
-                    NodeId {
-                        namespace: 0,
-                        identifier: Identifier::Numeric(OPCUA_AGG_FUNC_MINIMUM),
-                    }
+                    (OPCUA_AGG_FUNC_MINIMUM, Some(expr.as_ref()), distinct, false)
                 }
                 AggregateExpression::Max { expr, distinct } => {
-                    assert!(!distinct);
-                    assert!(expr_is_ok(expr));
-                    NodeId {
-                        namespace: 0,
-                        identifier: Identifier::Numeric(OPCUA_AGG_FUNC_MAXIMUM),
-                    }
+                    (OPCUA_AGG_FUNC_MAXIMUM, Some(expr.as_ref()), distinct, false)
+                }
+                AggregateExpression::Custom {
+                    name,
+                    expr,
+                    distinct,
+                } => {
+                    let iri = name.as_str();
+                    let aggfunc_id = if iri == FIRST {
+                        OPCUA_AGG_FUNC_START
+                    } else if iri == LAST {
+                        OPCUA_AGG_FUNC_END
+                    } else if iri == TIME_WEIGHTED_AVERAGE {
+                        OPCUA_AGG_FUNC_TIME_AVERAGE
+                    } else if iri == INTERPOLATIVE {
+                        OPCUA_AGG_FUNC_INTERPOLATIVE
+                    } else if iri == DELTA {
+                        OPCUA_AGG_FUNC_DELTA
+                    } else if iri == RANGE {
+                        OPCUA_AGG_FUNC_RANGE
+                    } else if iri == STANDARD_DEVIATION {
+                        OPCUA_AGG_FUNC_STANDARD_DEVIATION_SAMPLE
+                    } else if iri == DURATION_GOOD {
+                        OPCUA_AGG_FUNC_DURATION_GOOD
+                    } else if iri == DURATION_BAD {
+                        OPCUA_AGG_FUNC_DURATION_BAD
+                    } else if iri == PERCENT_GOOD {
+                        OPCUA_AGG_FUNC_PERCENT_GOOD
+                    } else if iri == PERCENT_BAD {
+                        OPCUA_AGG_FUNC_PERCENT_BAD
+                    } else if let Some(aggfunc_id) = function_registry
+                        .get_aggregate(iri)
+                        .and_then(|x| x.opcua_aggregate_function)
+                    {
+                        aggfunc_id
+                    } else {
+                        return Err(OPCUAHistoryReadError::AggregationNotSupported(
+                            agg.to_string(),
+                        ));
+                    };
+                    let status_aggregate = iri == DURATION_GOOD
+                        || iri == DURATION_BAD
+                        || iri == PERCENT_GOOD
+                        || iri == PERCENT_BAD;
+                    (aggfunc_id, Some(expr.as_ref()), distinct, status_aggregate)
                 }
                 _ => {
-                    panic!("Not supported {:?}, should not happen", agg)
+                    return Err(OPCUAHistoryReadError::AggregationNotSupported(
+                        agg.to_string(),
+                    ))
                 }
             };
-            nodes.push(aggfunc);
+            let aggregated_var_str = if status_aggregate {
+                &quality_var_str
+            } else {
+                &value_var_str
+            };
+            let expr_is_ok = match expr {
+                Some(Expression::Variable(v)) => Some(v.as_str()) == aggregated_var_str.as_deref(),
+                Some(_) => false,
+                None => true,
+            };
+            if *distinct || !expr_is_ok {
+                return Err(OPCUAHistoryReadError::AggregationNotSupported(
+                    agg.to_string(),
+                ));
+            }
+            nodes.push(NodeId {
+                namespace: 0,
+                identifier: Identifier::Numeric(aggfunc_id),
+            });
         }
         let mut outnodes = vec![];
         for _ in tsq.get_ids() {
             outnodes.extend_from_slice(nodes.as_slice())
         }
-        Some(outnodes)
+        Ok(Some(outnodes))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -698,6 +1726,15 @@ fn find_grouping_interval(tsq: &TimeSeriesQuery, context: &Context) -> Option<(S
             }
         }
         if let Some((_, e)) = tsf {
+            if let Expression::FunctionCall(Function::Custom(nn), args) = e {
+                if nn.as_str() == RESAMPLE && args.len() == 2 {
+                    if let Expression::Literal(lit) = args.get(1).unwrap() {
+                        if let Some(f) = from_numeric_datatype(lit) {
+                            return Some((grvar.unwrap().as_str().to_string(), f * 1000.0));
+                        }
+                    }
+                }
+            }
             if let Expression::Multiply(left, right) = e {
                 let n = find_grouping_interval_multiplication(left, right);
                 let out = if n.is_some() {
@@ -755,58 +1792,180 @@ fn from_numeric_datatype(lit: &Literal) -> Option<f64> {
     }
 }
 
-fn node_id_from_string(s: &str) -> Result<NodeId, OPCUAHistoryReadError> {
-    let mut splitstring = s.split(";");
-    let ns_str = if let Some(ns_str) = splitstring.next() {
-        ns_str
-    } else {
-        return Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()));
-    };
-    let identifier_string = splitstring.collect::<Vec<&str>>().join(";");
-    let namespace: u16 = if let Some(namespace_str) = ns_str.strip_prefix("ns=") {
-        namespace_str
+//Parses ids on the form ns=<namespace>;<type>=<identifier>, ids without ns= are in the default namespace
+pub(crate) fn node_id_from_string(
+    s: &str,
+    default_namespace: u16,
+) -> Result<NodeId, OPCUAHistoryReadError> {
+    let (namespace, identifier_string) = if let Some(rest) = s.strip_prefix("ns=") {
+        let (namespace_str, identifier_string) =
+            if let Some((namespace_str, identifier_string)) = rest.split_once(";") {
+                (namespace_str, identifier_string)
+            } else {
+                return Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()));
+            };
+        let namespace: u16 = namespace_str
             .parse()
-            .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?
+            .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?;
+        (namespace, identifier_string)
     } else {
-        return Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()));
+        (default_namespace, s)
     };
-    if identifier_string.starts_with("s=") {
-        let identifier = identifier_string.strip_prefix("s=").unwrap();
-        Ok(NodeId {
-            namespace,
-            identifier: Identifier::String(UAString::from(identifier.to_string())),
-        })
-    } else if identifier_string.starts_with("i=") {
-        let identifier: u32 = identifier_string
-            .strip_prefix("i=")
-            .unwrap()
-            .parse()
-            .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?;
-        Ok(NodeId {
-            namespace,
-            identifier: Identifier::Numeric(identifier),
-        })
-    } else if identifier_string.starts_with("g=") {
-        let identifier = identifier_string.strip_prefix("g=").unwrap();
-        Ok(NodeId {
-            namespace,
-            identifier: Identifier::Guid(
-                Guid::from_str(identifier)
-                    .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?,
-            ),
-        })
-    } else if identifier_string.starts_with("b=") {
-        let identifier = identifier_string.strip_prefix("g=").unwrap();
-        let byte_string = if let Some(byte_string) = ByteString::from_base64(identifier) {
-            byte_string
+    let identifier = if let Some(identifier) = identifier_string.strip_prefix("s=") {
+        Identifier::String(UAString::from(identifier.to_string()))
+    } else if let Some(identifier) = identifier_string.strip_prefix("i=") {
+        Identifier::Numeric(
+            identifier
+                .parse()
+                .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?,
+        )
+    } else if let Some(identifier) = identifier_string.strip_prefix("g=") {
+        Identifier::Guid(
+            Guid::from_str(identifier)
+                .map_err(|_| OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))?,
+        )
+    } else if let Some(identifier) = identifier_string.strip_prefix("b=") {
+        if let Some(byte_string) = ByteString::from_base64(identifier) {
+            Identifier::ByteString(byte_string)
         } else {
             return Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()));
-        };
-        Ok(NodeId {
-            namespace,
-            identifier: Identifier::ByteString(byte_string),
-        })
+        }
     } else {
-        Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()))
+        return Err(OPCUAHistoryReadError::InvalidNodeIdError(s.to_string()));
+    };
+    Ok(NodeId {
+        namespace,
+        identifier,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcua_client::prelude::{DataValue, LocalizedText};
+
+    fn data_value(value: Variant, nanos: i64) -> DataValue {
+        let datetime = DateTime::from(Utc.timestamp_nanos(nanos));
+        DataValue {
+            value: Some(value),
+            status: None,
+            source_timestamp: Some(datetime),
+            source_picoseconds: None,
+            server_timestamp: None,
+            server_picoseconds: None,
+        }
+    }
+
+    #[test]
+    fn test_timestamps_keep_sub_second_precision() {
+        let hd = HistoryData {
+            data_values: Some(vec![
+                data_value(Variant::Float(1.5), 1_654_073_213_123_456_700),
+                DataValue {
+                    source_timestamp: None,
+                    ..data_value(Variant::Float(2.5), 0)
+                },
+            ]),
+        };
+        let (ts, val, quality) =
+            history_data_to_series_tuple(hd, &TimestampsToReturn::Source).unwrap();
+        assert_eq!(ts.dtype(), &DataType::Datetime(TimeUnit::Nanoseconds, None));
+        assert_eq!(
+            ts.cast(&DataType::Int64).unwrap().i64().unwrap().get(0),
+            Some(1_654_073_213_123_456_700)
+        );
+        assert_eq!(ts.null_count(), 1);
+        assert_eq!(val.dtype(), &DataType::Float32);
+        assert_eq!(val.len(), 2);
+        assert_eq!(quality.u32().unwrap().get(0), Some(0));
+    }
+
+    #[test]
+    fn test_node_id_from_string() {
+        assert_eq!(
+            node_id_from_string("ns=2;s=a;b", 1).unwrap(),
+            NodeId::new(2, "a;b")
+        );
+        assert_eq!(
+            node_id_from_string("s=ts1", 1).unwrap(),
+            NodeId::new(1, "ts1")
+        );
+        assert_eq!(
+            node_id_from_string("ns=3;i=42", 1).unwrap(),
+            NodeId::new(3, 42u32)
+        );
+        assert_eq!(
+            node_id_from_string("b=AQI=", 0).unwrap(),
+            NodeId::new(0, ByteString::from(vec![1u8, 2]))
+        );
+        assert!(node_id_from_string("ns=x;s=a", 1).is_err());
+        assert!(node_id_from_string("ns=1", 1).is_err());
+        assert!(node_id_from_string("ts1", 1).is_err());
+    }
+
+    #[test]
+    fn test_invalid_security_configuration() {
+        let config = OPCUAClientConfig::default()
+            .with_security(SecurityPolicy::None, MessageSecurityMode::Sign);
+        assert!(config.validate().is_err());
+        let config = OPCUAClientConfig::default()
+            .with_security_names("Basic256Sha256", "SignAndEncrypt")
+            .unwrap();
+        assert_eq!(config.security_policy, SecurityPolicy::Basic256Sha256);
+        assert!(config.validate().is_ok());
+        assert!(OPCUAClientConfig::default()
+            .with_security_names("Basic257", "Sign")
+            .is_err());
+        let config = OPCUAClientConfig::default()
+            .with_security(SecurityPolicy::Unknown, MessageSecurityMode::Sign);
+        assert!(config.validate().is_err());
+        assert!(OPCUAClientConfig::default()
+            .with_security_names("None", "Encrypt")
+            .is_err());
+    }
+
+    #[test]
+    fn test_variant_types() {
+        let cases = vec![
+            (vec![Variant::Boolean(true)], DataType::Boolean),
+            (vec![Variant::SByte(-1)], DataType::Int32),
+            (vec![Variant::UInt16(1)], DataType::UInt32),
+            (vec![Variant::UInt64(1)], DataType::UInt64),
+            (vec![Variant::from("a")], DataType::Utf8),
+            (
+                vec![Variant::LocalizedText(Box::new(LocalizedText::new(
+                    "en", "a",
+                )))],
+                DataType::Utf8,
+            ),
+            (
+                vec![Variant::ByteString(ByteString::from(vec![1u8, 2]))],
+                DataType::Utf8,
+            ),
+            (
+                vec![Variant::DateTime(Box::new(DateTime::now()))],
+                DataType::Datetime(TimeUnit::Nanoseconds, None),
+            ),
+        ];
+        for (variants, dtype) in cases {
+            let series =
+                variants_to_series("value", variants.into_iter().map(Some).collect()).unwrap();
+            assert_eq!(series.dtype(), &dtype);
+        }
+        assert!(variants_to_series(
+            "value",
+            vec![Some(Variant::Int32(1)), Some(Variant::Double(1.0))]
+        )
+        .is_err());
+        let series = variants_to_series(
+            "value",
+            vec![
+                Some(Variant::from("a")),
+                Some(Variant::String(UAString::null())),
+                Some(Variant::Empty),
+            ],
+        )
+        .unwrap();
+        assert_eq!(series.null_count(), 2);
     }
 }
@@ -0,0 +1,299 @@
+use crate::timeseries_database::opcua_history_read::{
+    connect, history_data_to_series_tuple, node_id_from_string, OPCUAClientConfig,
+    OPCUAHistoryReadError,
+};
+use crate::timeseries_query::TimeSeriesQuery;
+use log::warn;
+use opcua_client::prelude::{
+    Client, DataChangeCallback, DataValue, HistoryData, MonitoredItem, MonitoredItemCreateRequest,
+    MonitoredItemService, NodeId, Session, SessionCommand, StatusCode, SubscriptionService,
+    TimestampsToReturn,
+};
+use polars::prelude::{concat, IntoLazy};
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{AnyValue, DataType};
+use polars_core::series::Series;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+//Publishing interval in milliseconds
+const DEFAULT_PUBLISHING_INTERVAL: f64 = 1000.0;
+
+//Creates subscriptions with monitored items for the ids found by the static query
+pub struct OPCUASubscriber {
+    client: Client,
+    session: Arc<RwLock<Session>>,
+    namespace: u16,
+    publishing_interval: f64,
+    timestamps_to_return: TimestampsToReturn,
+}
+
+#[derive(Debug)]
+pub enum OPCUASubscriptionError {
+    ClientError(OPCUAHistoryReadError),
+    CreateSubscriptionError(StatusCode),
+    CreateMonitoredItemError(String, StatusCode),
+    TimeSeriesQueryTypeNotSupported,
+    SubscriptionClosed,
+}
+
+impl Display for OPCUASubscriptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OPCUASubscriptionError::ClientError(e) => {
+                write!(f, "{}", e)
+            }
+            OPCUASubscriptionError::CreateSubscriptionError(status_code) => {
+                write!(f, "Could not create subscription: {}", status_code)
+            }
+            OPCUASubscriptionError::CreateMonitoredItemError(node_id, status_code) => {
+                write!(
+                    f,
+                    "Could not create monitored item for {}: {}",
+                    node_id, status_code
+                )
+            }
+            OPCUASubscriptionError::TimeSeriesQueryTypeNotSupported => {
+                write!(f, "Only basic time series queries can be subscribed to")
+            }
+            OPCUASubscriptionError::SubscriptionClosed => {
+                write!(f, "The subscription was closed by the client")
+            }
+        }
+    }
+}
+
+impl Error for OPCUASubscriptionError {}
+
+impl OPCUASubscriber {
+    pub fn new(endpoint: &str, namespace: u16) -> Result<OPCUASubscriber, OPCUASubscriptionError> {
+        OPCUASubscriber::from_config(endpoint, namespace, &OPCUAClientConfig::default())
+    }
+
+    pub fn from_config(
+        endpoint: &str,
+        namespace: u16,
+        config: &OPCUAClientConfig,
+    ) -> Result<OPCUASubscriber, OPCUASubscriptionError> {
+        let (client, session) =
+            connect(endpoint, config).map_err(OPCUASubscriptionError::ClientError)?;
+        Ok(OPCUASubscriber {
+            client,
+            session,
+            namespace,
+            publishing_interval: DEFAULT_PUBLISHING_INTERVAL,
+            timestamps_to_return: TimestampsToReturn::Source,
+        })
+    }
+
+    pub fn with_publishing_interval(mut self, publishing_interval: f64) -> OPCUASubscriber {
+        self.publishing_interval = publishing_interval;
+        self
+    }
+
+    pub fn with_server_timestamps(mut self, server_timestamps: bool) -> OPCUASubscriber {
+        self.timestamps_to_return = if server_timestamps {
+            TimestampsToReturn::Server
+        } else {
+            TimestampsToReturn::Source
+        };
+        self
+    }
+
+    pub(crate) fn subscribe(
+        self,
+        time_series_queries: Vec<TimeSeriesQuery>,
+    ) -> Result<OPCUASubscription, OPCUASubscriptionError> {
+        //Several queries may refer to the same node, so each node maps to all its uses
+        let mut node_lookup: HashMap<NodeId, Vec<(usize, String)>> = HashMap::new();
+        for (i, tsq) in time_series_queries.iter().enumerate() {
+            if !matches!(tsq, TimeSeriesQuery::Basic(_)) || tsq.has_events() {
+                return Err(OPCUASubscriptionError::TimeSeriesQueryTypeNotSupported);
+            }
+            for id in tsq.get_ids() {
+                let node_id = node_id_from_string(id, self.namespace)
+                    .map_err(OPCUASubscriptionError::ClientError)?;
+                let uses = node_lookup.entry(node_id).or_insert_with(Vec::new);
+                uses.push((i, id.clone()));
+            }
+        }
+        let mut node_ids: Vec<NodeId> = node_lookup.keys().cloned().collect();
+        node_ids.sort_by_key(|n| n.to_string());
+
+        let (sender, receiver) = channel();
+        //The callback is called from the session thread, the channel is read by the query
+        let sender = Mutex::new(sender);
+        let callback = DataChangeCallback::new(move |items: Vec<&MonitoredItem>| {
+            let changes: Vec<(NodeId, DataValue)> = items
+                .iter()
+                .map(|i| (i.item_to_monitor().node_id.clone(), i.last_value().clone()))
+                .collect();
+            //The receiver is gone when the subscription is dropped
+            let _ = sender.lock().unwrap().send(changes);
+        });
+        let subscription_id = {
+            let session = self.session.read().unwrap();
+            let subscription_id = session
+                .create_subscription(self.publishing_interval, 10, 30, 0, 0, true, callback)
+                .map_err(OPCUASubscriptionError::CreateSubscriptionError)?;
+            let items_to_create: Vec<MonitoredItemCreateRequest> =
+                node_ids.iter().map(|n| n.clone().into()).collect();
+            let results = session
+                .create_monitored_items(
+                    subscription_id,
+                    self.timestamps_to_return,
+                    &items_to_create,
+                )
+                .map_err(OPCUASubscriptionError::CreateSubscriptionError)?;
+            for (node_id, result) in node_ids.iter().zip(results.iter()) {
+                if result.status_code.is_bad() {
+                    return Err(OPCUASubscriptionError::CreateMonitoredItemError(
+                        node_id.to_string(),
+                        result.status_code,
+                    ));
+                }
+            }
+            subscription_id
+        };
+        //Publish requests are only sent while the session is running
+        let session_stop = Session::run_async(self.session.clone());
+        let latest_dfs = vec![None; time_series_queries.len()];
+        Ok(OPCUASubscription {
+            _client: self.client,
+            session: self.session,
+            subscription_id,
+            session_stop: Some(session_stop),
+            receiver,
+            time_series_queries,
+            node_lookup,
+            timestamps_to_return: self.timestamps_to_return,
+            latest_dfs,
+        })
+    }
+}
+
+pub(crate) struct OPCUASubscription {
+    //The client is kept for as long as its session is used
+    _client: Client,
+    session: Arc<RwLock<Session>>,
+    subscription_id: u32,
+    session_stop: Option<oneshot::Sender<SessionCommand>>,
+    receiver: Receiver<Vec<(NodeId, DataValue)>>,
+    time_series_queries: Vec<TimeSeriesQuery>,
+    node_lookup: HashMap<NodeId, Vec<(usize, String)>>,
+    timestamps_to_return: TimestampsToReturn,
+    //Queries without changes in a notification keep their most recent values
+    latest_dfs: Vec<Option<DataFrame>>,
+}
+
+impl OPCUASubscription {
+    //Waits until every query has received values and then returns the changed values of each query.
+    //Returns None if the timeout is reached first.
+    pub(crate) fn next_changes(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Vec<(TimeSeriesQuery, DataFrame)>>, OPCUASubscriptionError> {
+        loop {
+            let changes = if let Some(timeout) = timeout {
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(changes) => changes,
+                    Err(RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err(OPCUASubscriptionError::SubscriptionClosed)
+                    }
+                }
+            } else {
+                self.receiver
+                    .recv()
+                    .map_err(|_| OPCUASubscriptionError::SubscriptionClosed)?
+            };
+            let mut values: Vec<HashMap<String, Vec<DataValue>>> =
+                vec![HashMap::new(); self.time_series_queries.len()];
+            for (node_id, data_value) in changes {
+                if let Some(uses) = self.node_lookup.get(&node_id) {
+                    for (i, id) in uses {
+                        let id_values = values.get_mut(*i).unwrap().entry(id.clone());
+                        id_values.or_insert_with(Vec::new).push(data_value.clone());
+                    }
+                }
+            }
+            for (i, id_values) in values.into_iter().enumerate() {
+                if !id_values.is_empty() {
+                    let df =
+                        self.changes_to_df(self.time_series_queries.get(i).unwrap(), id_values)?;
+                    *self.latest_dfs.get_mut(i).unwrap() = Some(df);
+                }
+            }
+            if self.latest_dfs.iter().all(|df| df.is_some()) {
+                let out = self
+                    .time_series_queries
+                    .iter()
+                    .zip(self.latest_dfs.iter())
+                    .map(|(tsq, df)| (tsq.clone(), df.as_ref().unwrap().clone()))
+                    .collect();
+                return Ok(Some(out));
+            }
+        }
+    }
+
+    fn changes_to_df(
+        &self,
+        tsq: &TimeSeriesQuery,
+        id_values: HashMap<String, Vec<DataValue>>,
+    ) -> Result<DataFrame, OPCUASubscriptionError> {
+        let mut ids: Vec<&String> = id_values.keys().collect();
+        ids.sort();
+        let mut dfs = vec![];
+        for id in ids {
+            let history_data = HistoryData {
+                data_values: Some(id_values.get(id).unwrap().clone()),
+            };
+            let (mut ts, mut val, mut quality) =
+                history_data_to_series_tuple(history_data, &self.timestamps_to_return)
+                    .map_err(OPCUASubscriptionError::ClientError)?;
+            let mut columns = vec![];
+            let identifier_series = Series::new_empty(
+                tsq.get_identifier_variables().get(0).unwrap().as_str(),
+                &DataType::Utf8,
+            )
+            .extend_constant(AnyValue::Utf8(id), ts.len())
+            .unwrap();
+            columns.push(identifier_series);
+            if let Some(timestamp_variable) = tsq.get_timestamp_variables().get(0) {
+                ts.rename(timestamp_variable.variable.as_str());
+                columns.push(ts);
+            }
+            if let Some(value_variable) = tsq.get_value_variables().get(0) {
+                val.rename(value_variable.variable.as_str());
+                columns.push(val);
+            }
+            if let Some(quality_variable) = tsq.get_quality_variables().get(0) {
+                quality.rename(quality_variable.variable.as_str());
+                columns.push(quality);
+            }
+            dfs.push(DataFrame::new(columns).unwrap().lazy());
+        }
+        Ok(concat(dfs, true).unwrap().collect().unwrap())
+    }
+}
+
+impl Drop for OPCUASubscription {
+    fn drop(&mut self) {
+        if let Err(status_code) = self
+            .session
+            .read()
+            .unwrap()
+            .delete_subscription(self.subscription_id)
+        {
+            warn!("Could not delete subscription: {}", status_code);
+        }
+        if let Some(session_stop) = self.session_stop.take() {
+            let _ = session_stop.send(SessionCommand::Stop);
+        }
+    }
+}
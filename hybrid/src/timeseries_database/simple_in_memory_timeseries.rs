@@ -1,6 +1,8 @@
 use crate::combiner::lazy_aggregate::sparql_aggregate_expression_as_lazy_column_and_expression;
 use crate::combiner::lazy_expressions::lazy_expression;
-use crate::constants::GROUPING_COL;
+use crate::constants::{FIRST, GROUPING_COL, LAST, NEST, TIME_WEIGHTED_AVERAGE};
+use crate::function_registry::FunctionRegistry;
+use crate::pushdown_setting::all_function_pushdowns;
 use crate::query_context::{Context, PathEntry};
 use crate::timeseries_database::TimeSeriesQueryable;
 use crate::timeseries_query::{
@@ -10,8 +12,8 @@ use async_trait::async_trait;
 use polars::frame::DataFrame;
 use polars::prelude::{col, concat, lit, IntoLazy};
 use polars_core::prelude::JoinType;
-use spargebra::algebra::Expression;
-use std::collections::HashMap;
+use spargebra::algebra::{Expression, Function};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 
 pub struct InMemoryTimeseriesDatabase {
@@ -20,24 +22,58 @@ pub struct InMemoryTimeseriesDatabase {
 
 #[async_trait]
 impl TimeSeriesQueryable for InMemoryTimeseriesDatabase {
-    async fn execute(&mut self, tsq: &TimeSeriesQuery) -> Result<DataFrame, Box<dyn Error>> {
-        self.execute_query(tsq)
+    async fn execute(
+        &mut self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        self.execute_query(tsq, function_registry)
     }
 
     fn allow_compound_timeseries_queries(&self) -> bool {
         true
     }
+
+    fn function_pushdowns(&self) -> HashSet<Function> {
+        all_function_pushdowns()
+    }
+
+    fn custom_function_pushdowns(&self, function_registry: &FunctionRegistry) -> HashSet<String> {
+        function_registry
+            .functions()
+            .map(|(iri, _)| iri.to_string())
+            .collect()
+    }
+
+    fn custom_aggregate_pushdowns(&self, function_registry: &FunctionRegistry) -> HashSet<String> {
+        let mut pushdowns: HashSet<String> = [NEST, FIRST, LAST, TIME_WEIGHTED_AVERAGE]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        pushdowns.extend(
+            function_registry
+                .aggregates()
+                .map(|(iri, _)| iri.to_string()),
+        );
+        pushdowns
+    }
 }
 
 impl InMemoryTimeseriesDatabase {
-    fn execute_query(&self, tsq: &TimeSeriesQuery) -> Result<DataFrame, Box<dyn Error>> {
+    fn execute_query(
+        &self,
+        tsq: &TimeSeriesQuery,
+        function_registry: &FunctionRegistry,
+    ) -> Result<DataFrame, Box<dyn Error>> {
         match tsq {
             TimeSeriesQuery::Basic(b) => self.execute_basic(b),
-            TimeSeriesQuery::Filtered(inner, filter) => self.execute_filtered(inner, filter),
+            TimeSeriesQuery::Filtered(inner, filter) => {
+                self.execute_filtered(inner, filter, function_registry)
+            }
             TimeSeriesQuery::InnerSynchronized(inners, synchronizers) => {
-                self.execute_inner_synchronized(inners, synchronizers)
+                self.execute_inner_synchronized(inners, synchronizers, function_registry)
             }
-            TimeSeriesQuery::Grouped(grouped) => self.execute_grouped(grouped),
+            TimeSeriesQuery::Grouped(grouped) => self.execute_grouped(grouped, function_registry),
             TimeSeriesQuery::GroupedBasic(btsq, df, ..) => {
                 let mut basic_df = self.execute_basic(btsq)?;
                 basic_df = basic_df
@@ -55,15 +91,22 @@ impl InMemoryTimeseriesDatabase {
                 Ok(basic_df)
             }
             TimeSeriesQuery::ExpressionAs(tsq, v, e) => {
-                let mut df = self.execute_query(tsq)?;
+                let mut df = self.execute_query(tsq, function_registry)?;
                 let tmp_context = Context::from_path(vec![PathEntry::Coalesce(13)]);
                 let columns = df
                     .get_column_names()
                     .into_iter()
                     .map(|x| x.to_string())
                     .collect();
-                let out_lf = lazy_expression(e, df.lazy(), &columns, &mut vec![], &tmp_context)
-                    .rename([tmp_context.as_str()], [v.as_str()]);
+                let out_lf = lazy_expression(
+                    e,
+                    df.lazy(),
+                    &columns,
+                    &mut vec![],
+                    function_registry,
+                    &tmp_context,
+                )?
+                .rename([tmp_context.as_str()], [v.as_str()]);
                 df = out_lf.collect().unwrap();
                 Ok(df)
             }
@@ -76,18 +119,24 @@ impl InMemoryTimeseriesDatabase {
             if let Some(df) = self.frames.get(id) {
                 assert!(btsq.identifier_variable.is_some());
                 let mut df = df.clone();
+                if btsq.latest {
+                    df = df.sort(vec!["timestamp"], true)?.head(Some(1));
+                }
 
                 if let Some(value_variable) = &btsq.value_variable {
-                    df.rename("value", value_variable.variable.as_str())
-                        .expect("Rename problem");
+                    df.rename("value", value_variable.variable.as_str())?;
                 } else {
-                    df = df.drop("value").expect("Drop value problem");
+                    df = df.drop("value")?;
                 }
                 if let Some(timestamp_variable) = &btsq.timestamp_variable {
-                    df.rename("timestamp", timestamp_variable.variable.as_str())
-                        .expect("Rename problem");
+                    df.rename("timestamp", timestamp_variable.variable.as_str())?;
                 } else {
-                    df = df.drop("timestamp").expect("Drop timestamp problem");
+                    df = df.drop("timestamp")?;
+                }
+                if let Some(quality_variable) = &btsq.quality_variable {
+                    df.rename("quality", quality_variable.variable.as_str())?;
+                } else if df.get_column_names().contains(&"quality") {
+                    df = df.drop("quality")?;
                 }
                 let mut lf = df.lazy();
                 lf = lf.with_column(
@@ -107,15 +156,23 @@ impl InMemoryTimeseriesDatabase {
         &self,
         tsq: &TimeSeriesQuery,
         filter: &Expression,
+        function_registry: &FunctionRegistry,
     ) -> Result<DataFrame, Box<dyn Error>> {
-        let df = self.execute_query(tsq)?;
+        let df = self.execute_query(tsq, function_registry)?;
         let columns = df
             .get_column_names()
             .into_iter()
             .map(|x| x.to_string())
             .collect();
         let tmp_context = Context::from_path(vec![PathEntry::Coalesce(12)]);
-        let mut lf = lazy_expression(filter, df.lazy(), &columns, &mut vec![], &tmp_context);
+        let mut lf = lazy_expression(
+            filter,
+            df.lazy(),
+            &columns,
+            &mut vec![],
+            function_registry,
+            &tmp_context,
+        )?;
         lf = lf
             .filter(col(tmp_context.as_str()))
             .drop_columns([tmp_context.as_str()]);
@@ -125,8 +182,9 @@ impl InMemoryTimeseriesDatabase {
     fn execute_grouped(
         &self,
         grouped: &GroupedTimeSeriesQuery,
+        function_registry: &FunctionRegistry,
     ) -> Result<DataFrame, Box<dyn Error>> {
-        let df = self.execute_query(&grouped.tsq)?;
+        let df = self.execute_query(&grouped.tsq, function_registry)?;
         let columns = df
             .get_column_names()
             .into_iter()
@@ -152,6 +210,8 @@ impl InMemoryTimeseriesDatabase {
                     &columns,
                     out_lf,
                     &mut vec![],
+                    function_registry,
+                    timestamp_names.get(0).map(|x| x.as_str()),
                     &grouped
                         .graph_pattern_context
                         .extension_with(PathEntry::GroupAggregation(i as u16)),
@@ -191,6 +251,7 @@ impl InMemoryTimeseriesDatabase {
         &self,
         inners: &Vec<Box<TimeSeriesQuery>>,
         synchronizers: &Vec<Synchronizer>,
+        function_registry: &FunctionRegistry,
     ) -> Result<DataFrame, Box<dyn Error>> {
         assert_eq!(synchronizers.len(), 1);
         #[allow(irrefutable_let_patterns)]
@@ -198,7 +259,7 @@ impl InMemoryTimeseriesDatabase {
             let mut on = vec![timestamp_col.clone()];
             let mut dfs = vec![];
             for q in inners {
-                let df = self.execute_query(q)?;
+                let df = self.execute_query(q, function_registry)?;
                 for c in df.get_column_names() {
                     if c.starts_with(GROUPING_COL) {
                         let c_string = c.to_string();
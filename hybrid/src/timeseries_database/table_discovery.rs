@@ -0,0 +1,270 @@
+use crate::timeseries_database::timeseries_sql_rewrite::{SQLDialect, TimeSeriesTable};
+use log::{debug, warn};
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNode;
+use polars::frame::DataFrame;
+use polars_core::datatypes::AnyValue;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+//Query for the schema, table, column and type of all columns in the catalog
+pub fn information_schema_columns_query(dialect: &SQLDialect) -> String {
+    match dialect {
+        //COLUMNS is a reserved word in Dremio
+        SQLDialect::Dremio => {
+            r#"SELECT TABLE_SCHEMA, TABLE_NAME, COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA."COLUMNS""#
+        }
+        SQLDialect::Postgres => {
+            "SELECT table_schema, table_name, column_name, data_type FROM information_schema.columns"
+        }
+    }
+    .to_string()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatalogColumn {
+    pub schema: Option<String>,
+    pub table: String,
+    pub column: String,
+    //None when the type of the column has no xsd counterpart
+    pub datatype: Option<NamedNode>,
+}
+
+impl CatalogColumn {
+    pub fn from_sql_type(
+        schema: Option<String>,
+        table: String,
+        column: String,
+        sql_type: &str,
+    ) -> CatalogColumn {
+        CatalogColumn {
+            schema,
+            table,
+            column,
+            datatype: sql_type_to_xsd(sql_type),
+        }
+    }
+}
+
+//Column naming conventions used to recognize time series tables.
+//The default patterns match whole names, ignoring case. Other patterns are used as given,
+//so they must be anchored with ^ and $ to match whole names.
+#[derive(Clone, Debug)]
+pub struct TableDiscovery {
+    pub schema_pattern: Option<Regex>,
+    pub table_pattern: Option<Regex>,
+    pub identifier_column_pattern: Regex,
+    pub timestamp_column_pattern: Regex,
+    pub value_column_pattern: Regex,
+    pub quality_column_pattern: Option<Regex>,
+    pub year_column_pattern: Option<Regex>,
+    pub month_column_pattern: Option<Regex>,
+    pub day_column_pattern: Option<Regex>,
+}
+
+impl Default for TableDiscovery {
+    fn default() -> Self {
+        TableDiscovery {
+            schema_pattern: None,
+            table_pattern: None,
+            identifier_column_pattern: Regex::new("(?i)^(id|identifier|external_id|tag)$").unwrap(),
+            timestamp_column_pattern: Regex::new("(?i)^(timestamp|ts|time)$").unwrap(),
+            value_column_pattern: Regex::new("(?i)^(value|v|val)$").unwrap(),
+            quality_column_pattern: Some(Regex::new("(?i)^(quality|status)$").unwrap()),
+            year_column_pattern: Some(Regex::new("(?i)^year$").unwrap()),
+            month_column_pattern: Some(Regex::new("(?i)^month$").unwrap()),
+            day_column_pattern: Some(Regex::new("(?i)^day$").unwrap()),
+        }
+    }
+}
+
+impl TableDiscovery {
+    pub fn new() -> TableDiscovery {
+        TableDiscovery::default()
+    }
+
+    pub fn with_schema_pattern(mut self, pattern: Regex) -> Self {
+        self.schema_pattern = Some(pattern);
+        self
+    }
+
+    pub fn with_table_pattern(mut self, pattern: Regex) -> Self {
+        self.table_pattern = Some(pattern);
+        self
+    }
+
+    pub fn with_identifier_column_pattern(mut self, pattern: Regex) -> Self {
+        self.identifier_column_pattern = pattern;
+        self
+    }
+
+    pub fn with_timestamp_column_pattern(mut self, pattern: Regex) -> Self {
+        self.timestamp_column_pattern = pattern;
+        self
+    }
+
+    pub fn with_value_column_pattern(mut self, pattern: Regex) -> Self {
+        self.value_column_pattern = pattern;
+        self
+    }
+
+    pub fn with_quality_column_pattern(mut self, pattern: Option<Regex>) -> Self {
+        self.quality_column_pattern = pattern;
+        self
+    }
+
+    pub fn with_date_partition_column_patterns(
+        mut self,
+        year: Option<Regex>,
+        month: Option<Regex>,
+        day: Option<Regex>,
+    ) -> Self {
+        self.year_column_pattern = year;
+        self.month_column_pattern = month;
+        self.day_column_pattern = day;
+        self
+    }
+
+    //Proposes a table definition for each table with identifier, timestamp and value columns.
+    //Narrow tables are chosen by the datatype of their values, so tables with the same datatype
+    //as an earlier table are skipped.
+    pub fn propose_tables(&self, columns: &Vec<CatalogColumn>) -> Vec<TimeSeriesTable> {
+        let mut table_columns: BTreeMap<(Option<String>, String), Vec<&CatalogColumn>> =
+            BTreeMap::new();
+        for c in columns {
+            if let Some(p) = &self.schema_pattern {
+                if !p.is_match(c.schema.as_deref().unwrap_or("")) {
+                    continue;
+                }
+            }
+            if let Some(p) = &self.table_pattern {
+                if !p.is_match(&c.table) {
+                    continue;
+                }
+            }
+            table_columns
+                .entry((c.schema.clone(), c.table.clone()))
+                .or_default()
+                .push(c);
+        }
+
+        let mut tables = vec![];
+        let mut datatype_tables: HashMap<NamedNode, String> = HashMap::new();
+        for ((schema, table), cs) in table_columns {
+            let find = |pattern: &Regex| cs.iter().find(|c| pattern.is_match(&c.column));
+            let find_opt = |pattern: &Option<Regex>| pattern.as_ref().and_then(|p| find(p));
+            let (identifier, timestamp, value) = match (
+                find(&self.identifier_column_pattern),
+                find(&self.timestamp_column_pattern),
+                find(&self.value_column_pattern),
+            ) {
+                (Some(i), Some(t), Some(v)) => (i, t, v),
+                _ => {
+                    debug!("Table {} does not have the time series columns", table);
+                    continue;
+                }
+            };
+            if timestamp.datatype.as_ref() != Some(&xsd::DATE_TIME.into_owned()) {
+                debug!("Table {} does not have a timestamp column", table);
+                continue;
+            }
+            let value_datatype = if let Some(dt) = &value.datatype {
+                dt.clone()
+            } else {
+                debug!("Table {} has values without an xsd datatype", table);
+                continue;
+            };
+            if let Some(previous) = datatype_tables.get(&value_datatype) {
+                warn!(
+                    "Table {} is skipped, as table {} already has values of datatype {}",
+                    table, previous, value_datatype
+                );
+                continue;
+            }
+            datatype_tables.insert(value_datatype.clone(), table.clone());
+            let date_columns = [
+                find_opt(&self.year_column_pattern),
+                find_opt(&self.month_column_pattern),
+                find_opt(&self.day_column_pattern),
+            ];
+            //Date partitions are only used when all of them are present
+            let (year_column, month_column, day_column) =
+                if let [Some(y), Some(m), Some(d)] = date_columns {
+                    (
+                        Some(y.column.clone()),
+                        Some(m.column.clone()),
+                        Some(d.column.clone()),
+                    )
+                } else {
+                    (None, None, None)
+                };
+            tables.push(TimeSeriesTable {
+                schema,
+                time_series_table: table,
+                value_column: value.column.clone(),
+                quality_column: find_opt(&self.quality_column_pattern).map(|c| c.column.clone()),
+                timestamp_column: timestamp.column.clone(),
+                identifier_column: identifier.column.clone(),
+                value_datatype,
+                year_column,
+                month_column,
+                day_column,
+                partitions: vec![],
+                wide_value_columns: None,
+            });
+        }
+        tables
+    }
+}
+
+//Reads the result of the information_schema_columns_query
+pub fn catalog_columns_from_df(df: &DataFrame) -> Vec<CatalogColumn> {
+    let columns = df.get_columns();
+    let mut iters: Vec<_> = columns.iter().take(4).map(|s| s.iter()).collect();
+    let mut catalog_columns = vec![];
+    if iters.len() < 4 {
+        return catalog_columns;
+    }
+    for _ in 0..df.height() {
+        let mut values = vec![];
+        for it in iters.iter_mut() {
+            values.push(match it.next().unwrap() {
+                AnyValue::Utf8(s) => Some(s.to_string()),
+                _ => None,
+            });
+        }
+        let sql_type = values.pop().unwrap();
+        let column = values.pop().unwrap();
+        let table = values.pop().unwrap();
+        let schema = values.pop().unwrap();
+        if let (Some(table), Some(column), Some(sql_type)) = (table, column, sql_type) {
+            catalog_columns.push(CatalogColumn::from_sql_type(
+                schema, table, column, &sql_type,
+            ));
+        }
+    }
+    catalog_columns
+}
+
+pub fn sql_type_to_xsd(sql_type: &str) -> Option<NamedNode> {
+    //Parameters such as in DECIMAL(10,2) and VARCHAR(255) do not matter
+    let name = sql_type.split('(').next().unwrap().trim().to_uppercase();
+    let dt = match name.as_str() {
+        "DOUBLE" | "DOUBLE PRECISION" | "FLOAT8" => xsd::DOUBLE,
+        "FLOAT" | "REAL" | "FLOAT4" => xsd::FLOAT,
+        "DECIMAL" | "NUMERIC" => xsd::DECIMAL,
+        "TINYINT" => xsd::BYTE,
+        "SMALLINT" | "INT2" => xsd::SHORT,
+        "INTEGER" | "INT" | "INT4" => xsd::INT,
+        "BIGINT" | "INT8" => xsd::LONG,
+        "BOOLEAN" | "BOOL" => xsd::BOOLEAN,
+        "VARCHAR" | "CHARACTER VARYING" | "CHAR" | "CHARACTER" | "TEXT" => xsd::STRING,
+        "DATE" => xsd::DATE,
+        "TIMESTAMP"
+        | "TIMESTAMP WITHOUT TIME ZONE"
+        | "TIMESTAMP WITH TIME ZONE"
+        | "TIMESTAMPTZ" => xsd::DATE_TIME,
+        _ => return None,
+    };
+    Some(dt.into_owned())
+}
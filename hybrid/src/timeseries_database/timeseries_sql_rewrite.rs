@@ -1,8 +1,12 @@
 mod expression_rewrite;
 mod partitioning_support;
+mod wide_tables;
 
+use crate::function_registry::FunctionRegistry;
 use crate::timeseries_database::timeseries_sql_rewrite::expression_rewrite::SPARQLToSQLExpressionTransformer;
-use crate::timeseries_database::timeseries_sql_rewrite::partitioning_support::add_partitioned_timestamp_conditions;
+use crate::timeseries_database::timeseries_sql_rewrite::partitioning_support::{
+    add_partitioned_timestamp_conditions, partition_hash_function, partition_pruning_conditions,
+};
 use crate::timeseries_query::{BasicTimeSeriesQuery, Synchronizer, TimeSeriesQuery};
 use oxrdf::{NamedNode, Variable};
 use polars_core::datatypes::AnyValue;
@@ -11,15 +15,18 @@ use sea_query::{
     Alias, BinOper, ColumnRef, JoinType, Query, SelectStatement, SimpleExpr, TableRef,
 };
 use sea_query::{Expr as SeaExpr, Iden, Value};
-use spargebra::algebra::{AggregateExpression, Expression};
+use spargebra::algebra::{AggregateExpression, Expression, Function};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::{Display, Formatter, Write};
 use std::rc::Rc;
+use std::sync::Arc;
 
 const YEAR_PARTITION_COLUMN_NAME: &str = "year_partition_column_name";
 const MONTH_PARTITION_COLUMN_NAME: &str = "month_partition_column_name";
 const DAY_PARTITION_COLUMN_NAME: &str = "day_partition_column_name";
+const LATEST_IDENTIFIER_COLUMN_NAME: &str = "latest_identifier";
+const LATEST_TIMESTAMP_COLUMN_NAME: &str = "latest_timestamp";
 
 #[derive(Debug)]
 pub enum TimeSeriesQueryToSQLError {
@@ -28,6 +35,15 @@ pub enum TimeSeriesQueryToSQLError {
     FoundNonValueInInExpression,
     DatatypeNotSupported(String),
     MissingTimeseriesQueryDatatype,
+    FunctionNotSupported(String),
+    AggregateNotSupported(String),
+    ExpressionNotSupported(String),
+    MissingQualityColumn(String),
+    EventsNotSupported,
+    MissingWideTableColumn(String, String),
+    LatestNotSupportedForWideTable(String),
+    UnexpectedGroupingValue(String),
+    InvalidLiteral(String, String),
 }
 
 impl Display for TimeSeriesQueryToSQLError {
@@ -48,6 +64,45 @@ impl Display for TimeSeriesQueryToSQLError {
             TimeSeriesQueryToSQLError::MissingTimeseriesQueryDatatype => {
                 write!(f, "Timeseries value datatype missing")
             }
+            TimeSeriesQueryToSQLError::FunctionNotSupported(fun) => {
+                write!(f, "Function not supported in SQL: {}", fun)
+            }
+            TimeSeriesQueryToSQLError::AggregateNotSupported(agg) => {
+                write!(f, "Aggregate not supported in SQL: {}", agg)
+            }
+            TimeSeriesQueryToSQLError::MissingQualityColumn(table) => {
+                write!(
+                    f,
+                    "Quality was requested but table {} has no quality column",
+                    table
+                )
+            }
+            TimeSeriesQueryToSQLError::ExpressionNotSupported(e) => {
+                write!(f, "Expression not supported in SQL: {}", e)
+            }
+            TimeSeriesQueryToSQLError::EventsNotSupported => {
+                write!(f, "Event series are not supported in SQL")
+            }
+            TimeSeriesQueryToSQLError::MissingWideTableColumn(table, id) => {
+                write!(
+                    f,
+                    "Wide table {} has no column for identifier {}",
+                    table, id
+                )
+            }
+            TimeSeriesQueryToSQLError::LatestNotSupportedForWideTable(table) => {
+                write!(
+                    f,
+                    "The latest values can not be found in wide table {}",
+                    table
+                )
+            }
+            TimeSeriesQueryToSQLError::UnexpectedGroupingValue(v) => {
+                write!(f, "Unexpected identifier or group {} in grouping", v)
+            }
+            TimeSeriesQueryToSQLError::InvalidLiteral(v, dt) => {
+                write!(f, "Invalid literal {} of datatype {}", v, dt)
+            }
         }
     }
 }
@@ -86,39 +141,117 @@ impl Iden for Name {
     }
 }
 
+//Functions such as regular expressions are named and parameterized differently across databases
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SQLDialect {
+    Dremio,
+    Postgres,
+}
+
 #[derive(Clone)]
 pub struct TimeSeriesTable {
     pub schema: Option<String>,
     pub time_series_table: String,
     pub value_column: String,
+    //Column holding the quality of each value, exposed through otit_swt:hasQuality
+    pub quality_column: Option<String>,
     pub timestamp_column: String,
     pub identifier_column: String,
     pub value_datatype: NamedNode,
     pub year_column: Option<String>,
     pub month_column: Option<String>,
     pub day_column: Option<String>,
+    //Other partition columns, with timestamp partitions listed from the coarsest to the finest
+    pub partitions: Vec<TimeSeriesPartition>,
+    //Identifiers mapped to the columns of a wide table with one row per timestamp.
+    //The identifier and value columns are not used for wide tables.
+    pub wide_value_columns: Option<HashMap<String, String>>,
+}
+
+#[derive(Clone)]
+pub struct TimeSeriesPartition {
+    pub column: String,
+    pub scheme: PartitionScheme,
+}
+
+//How the value of a partition column is derived from the rows in the partition
+#[derive(Clone)]
+pub enum PartitionScheme {
+    //An integer part of the timestamp given by a chrono format, such as %H for hour=13 folders
+    TimestampInteger(String),
+    //The timestamp formatted by a chrono format, such as %Y-%m-%d for dt=2022-06-01 folders.
+    //The formatted timestamps must sort in the same order as the timestamps.
+    TimestampString(String),
+    //The partition of each identifier, such as the site of the sensor
+    IdentifierMapping(HashMap<String, String>),
+    //The partition computed from the identifier, such as a hash bucket
+    IdentifierFunction(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl PartitionScheme {
+    pub fn is_timestamp_partition(&self) -> bool {
+        match self {
+            PartitionScheme::TimestampInteger(_) | PartitionScheme::TimestampString(_) => true,
+            PartitionScheme::IdentifierMapping(_) | PartitionScheme::IdentifierFunction(_) => false,
+        }
+    }
+
+    //The partition is a named hash of the identifier modulo n, such as crc32 for bucket=7 folders
+    pub fn identifier_hash(hash: &str, modulo: u64) -> Option<PartitionScheme> {
+        let hash_function = partition_hash_function(hash)?;
+        if modulo == 0 {
+            return None;
+        }
+        Some(PartitionScheme::IdentifierFunction(Arc::new(
+            move |id: &str| (hash_function(id.as_bytes()) % modulo).to_string(),
+        )))
+    }
 }
 
 pub struct TimeSeriesQueryToSQLTransformer<'a> {
     pub partition_support: bool,
     pub tables: &'a Vec<TimeSeriesTable>,
+    pub function_registry: &'a FunctionRegistry,
+    pub dialect: SQLDialect,
 }
 
 impl TimeSeriesQueryToSQLTransformer<'_> {
-    pub fn new(tables: &Vec<TimeSeriesTable>) -> TimeSeriesQueryToSQLTransformer {
+    pub fn new<'a>(
+        tables: &'a Vec<TimeSeriesTable>,
+        function_registry: &'a FunctionRegistry,
+    ) -> TimeSeriesQueryToSQLTransformer<'a> {
         TimeSeriesQueryToSQLTransformer {
             partition_support: check_partitioning_support(tables),
             tables,
+            function_registry,
+            dialect: SQLDialect::Dremio,
         }
     }
 
+    pub fn with_dialect(mut self, dialect: SQLDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
     pub fn create_query(
         &self,
         tsq: &TimeSeriesQuery,
         project_date_partition: bool,
+    ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
+        self.create_query_with_filters(tsq, project_date_partition, &vec![])
+    }
+
+    //The filters applying to the rows of the query are used to prune the partitions of the tables
+    fn create_query_with_filters(
+        &self,
+        tsq: &TimeSeriesQuery,
+        project_date_partition: bool,
+        filters: &Vec<&Expression>,
     ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
         match tsq {
-            TimeSeriesQuery::Basic(b) => self.create_basic_select(b, project_date_partition),
+            TimeSeriesQuery::Basic(b) => {
+                self.create_basic_select(b, project_date_partition, filters)
+            }
             TimeSeriesQuery::Filtered(tsq, filter) => {
                 let (se, need_partition_columns) = self.create_filter_expressions(
                     filter,
@@ -132,8 +265,13 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
                     ),
                 )?;
 
-                let (select, mut columns) =
-                    self.create_query(tsq, need_partition_columns || project_date_partition)?;
+                let mut inner_filters = filters.clone();
+                inner_filters.push(filter);
+                let (select, mut columns) = self.create_query_with_filters(
+                    tsq,
+                    need_partition_columns || project_date_partition,
+                    &inner_filters,
+                )?;
 
                 let wraps_inner = if let TimeSeriesQuery::Basic(_) = **tsq {
                     true
@@ -175,11 +313,26 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
                         false
                     }
                 }) {
+                    let groupby_col = tsq.get_groupby_column().unwrap();
+                    if let Some(Synchronizer::Identity(timestamp_col)) = &synchronizers.get(0) {
+                        //Columns of the same wide table are read in a single scan
+                        if let Some(wide) = self.create_wide_synchronized_query(
+                            inner,
+                            timestamp_col,
+                            groupby_col,
+                            filters,
+                        )? {
+                            return Ok(wide);
+                        }
+                    }
                     let mut selects = vec![];
                     for s in inner {
-                        selects.push(self.create_query(s, true)?);
+                        selects.push(self.create_query_with_filters(
+                            s,
+                            self.partition_support,
+                            filters,
+                        )?);
                     }
-                    let groupby_col = tsq.get_groupby_column().unwrap();
                     if let Some(Synchronizer::Identity(timestamp_col)) = &synchronizers.get(0) {
                         Ok(self.inner_join_selects(selects, timestamp_col, groupby_col))
                     } else {
@@ -196,10 +349,10 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
                 project_date_partition,
             ),
             TimeSeriesQuery::GroupedBasic(btsq, df, col) => {
-                self.create_grouped_basic(btsq, project_date_partition, df, col)
+                self.create_grouped_basic(btsq, project_date_partition, df, col, filters)
             }
             TimeSeriesQuery::ExpressionAs(tsq, v, e) => {
-                self.create_expression_as(tsq, project_date_partition, v, e)
+                self.create_expression_as(tsq, project_date_partition, v, e, filters)
             }
         }
     }
@@ -210,15 +363,17 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         project_date_partition: bool,
         v: &Variable,
         e: &Expression,
+        filters: &Vec<&Expression>,
     ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
         let subquery_alias = "subquery";
         let subquery_name = Name::Table(subquery_alias.to_string());
         let mut expr_transformer = self.create_transformer(Some(&subquery_name));
         let se = expr_transformer.sparql_expression_to_sql_expression(e)?;
 
-        let (select, mut columns) = self.create_query(
+        let (select, mut columns) = self.create_query_with_filters(
             tsq,
             project_date_partition || expr_transformer.used_partitioning,
+            filters,
         )?;
         if !project_date_partition && expr_transformer.used_partitioning {
             columns.remove(YEAR_PARTITION_COLUMN_NAME);
@@ -253,26 +408,10 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         project_date_partition: bool,
         df: &DataFrame,
         column_name: &String,
+        filters: &Vec<&Expression>,
     ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
-        let mut value_tuples = vec![];
+        let value_tuples = grouping_tuples(btsq, df, column_name)?;
         let identifier_colname = btsq.identifier_variable.as_ref().unwrap().as_str();
-        let mut identifier_iter = df.column(identifier_colname).unwrap().iter();
-        let mut groupcol_iter = df.column(&column_name).unwrap().iter();
-        for _ in 0..df.height() {
-            let id = identifier_iter.next().unwrap();
-            let grp = groupcol_iter.next().unwrap();
-            let id_value = if let AnyValue::Utf8(id_value) = id {
-                id_value.to_string()
-            } else {
-                panic!("Should never happen");
-            };
-            let grp_value = if let AnyValue::Int64(grp_value) = grp {
-                grp_value
-            } else {
-                panic!("Should never happen");
-            };
-            value_tuples.push((id_value, grp_value));
-        }
 
         let mut static_select = Query::select();
         let mapping_values_alias = "mapping";
@@ -293,7 +432,8 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         );
         let static_alias = "static_query";
 
-        let (basic_select, mut columns) = self.create_basic_select(btsq, project_date_partition)?;
+        let (basic_select, mut columns) =
+            self.create_basic_select(btsq, project_date_partition, filters)?;
 
         let mut joined_select = Query::select();
         let basic_alias = "basic_query";
@@ -346,13 +486,35 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         &self,
         btsq: &BasicTimeSeriesQuery,
         project_date_partition: bool,
+        filters: &Vec<&Expression>,
     ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
+        if btsq.events {
+            return Err(TimeSeriesQueryToSQLError::EventsNotSupported);
+        }
         let table = self.find_right_table(btsq)?;
-        let (select, columns) = table.create_basic_query(btsq, project_date_partition)?;
-
+        let (mut select, columns) = table.create_basic_query(btsq, project_date_partition)?;
+        self.add_partition_pruning(&mut select, table, btsq, filters);
         Ok((select, columns))
     }
 
+    fn add_partition_pruning(
+        &self,
+        select: &mut SelectStatement,
+        table: &TimeSeriesTable,
+        btsq: &BasicTimeSeriesQuery,
+        filters: &Vec<&Expression>,
+    ) {
+        //When all tables have date partitions, these are instead used in the filters themselves
+        let mut partitions = vec![];
+        if !self.partition_support {
+            partitions.extend(table.date_partitions());
+        }
+        partitions.extend(table.partitions.iter().cloned());
+        for c in partition_pruning_conditions(&partitions, btsq, filters) {
+            select.and_where(c);
+        }
+    }
+
     fn inner_join_selects(
         &self,
         mut selects_and_timestamp_cols: Vec<(SelectStatement, HashSet<String>)>,
@@ -379,13 +541,15 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         for (i, (s, cols)) in selects_and_timestamp_cols.into_iter().enumerate() {
             let select_name = format!("other_{}", i);
             let mut conditions = vec![];
-            let col_conditions = [
-                groupby_col.clone(),
-                timestamp_col.clone(),
-                YEAR_PARTITION_COLUMN_NAME.to_string(),
-                MONTH_PARTITION_COLUMN_NAME.to_string(),
-                DAY_PARTITION_COLUMN_NAME.to_string(),
-            ];
+            let mut col_conditions = vec![groupby_col.clone(), timestamp_col.clone()];
+            //The date partitions are only projected when all tables have them
+            if self.partition_support {
+                col_conditions.extend([
+                    YEAR_PARTITION_COLUMN_NAME.to_string(),
+                    MONTH_PARTITION_COLUMN_NAME.to_string(),
+                    DAY_PARTITION_COLUMN_NAME.to_string(),
+                ]);
+            }
             for c in col_conditions {
                 conditions.push(
                     SimpleExpr::Column(ColumnRef::TableColumn(
@@ -431,18 +595,35 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
         &'a self,
         btsq: &BasicTimeSeriesQuery,
     ) -> Result<&'a TimeSeriesTable, TimeSeriesQueryToSQLError> {
-        if let Some(b_datatype) = &btsq.datatype {
-            for table in self.tables {
-                if table.value_datatype.as_str() == b_datatype.as_str() {
-                    return Ok(table);
+        let b_datatype = if let Some(b_datatype) = &btsq.datatype {
+            b_datatype
+        } else {
+            return Err(TimeSeriesQueryToSQLError::MissingTimeseriesQueryDatatype);
+        };
+        let candidates: Vec<&TimeSeriesTable> = self
+            .tables
+            .iter()
+            .filter(|t| t.value_datatype.as_str() == b_datatype.as_str())
+            .collect();
+        //A wide table is used when it has a column for each of the identifiers,
+        //otherwise the identifiers are looked up in a narrow table
+        if let Some(ids) = &btsq.ids {
+            for table in &candidates {
+                if let Some(wide_value_columns) = &table.wide_value_columns {
+                    if ids.iter().all(|id| wide_value_columns.contains_key(id)) {
+                        return Ok(table);
+                    }
                 }
             }
-            Err(TimeSeriesQueryToSQLError::DatatypeNotSupported(
-                b_datatype.as_str().to_string(),
-            ))
-        } else {
-            Err(TimeSeriesQueryToSQLError::MissingTimeseriesQueryDatatype)
         }
+        candidates
+            .iter()
+            .find(|t| t.wide_value_columns.is_none())
+            .or_else(|| candidates.first())
+            .copied()
+            .ok_or_else(|| {
+                TimeSeriesQueryToSQLError::DatatypeNotSupported(b_datatype.as_str().to_string())
+            })
     }
 
     fn create_filter_expressions(
@@ -554,9 +735,18 @@ impl TimeSeriesQueryToSQLTransformer<'_> {
                 Some(YEAR_PARTITION_COLUMN_NAME),
                 Some(MONTH_PARTITION_COLUMN_NAME),
                 Some(DAY_PARTITION_COLUMN_NAME),
+                self.function_registry,
+                &self.dialect,
             )
         } else {
-            SPARQLToSQLExpressionTransformer::new(table_name, None, None, None)
+            SPARQLToSQLExpressionTransformer::new(
+                table_name,
+                None,
+                None,
+                None,
+                self.function_registry,
+                &self.dialect,
+            )
         }
     }
 }
@@ -567,6 +757,9 @@ impl TimeSeriesTable {
         btsq: &BasicTimeSeriesQuery,
         project_date_partition: bool,
     ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
+        if let Some(wide_value_columns) = &self.wide_value_columns {
+            return self.create_wide_basic_query(btsq, wide_value_columns, project_date_partition);
+        }
         let mut basic_query = Query::select();
         let mut variable_column_name_map = HashMap::new();
         variable_column_name_map.insert(
@@ -595,20 +788,17 @@ impl TimeSeriesTable {
                 .to_string(),
             self.timestamp_column.clone(),
         );
-        let mut projection_column_name_map = HashMap::new();
-        if project_date_partition {
-            projection_column_name_map.insert(
-                YEAR_PARTITION_COLUMN_NAME.to_string(),
-                self.year_column.as_ref().unwrap().clone(),
-            );
-            projection_column_name_map.insert(
-                MONTH_PARTITION_COLUMN_NAME.to_string(),
-                self.month_column.as_ref().unwrap().clone(),
-            );
-            projection_column_name_map.insert(
-                DAY_PARTITION_COLUMN_NAME.to_string(),
-                self.day_column.as_ref().unwrap().clone(),
-            );
+        if let Some(quality_variable) = &btsq.quality_variable {
+            if let Some(quality_column) = &self.quality_column {
+                variable_column_name_map.insert(
+                    quality_variable.variable.as_str().to_string(),
+                    quality_column.clone(),
+                );
+            } else {
+                return Err(TimeSeriesQueryToSQLError::MissingQualityColumn(
+                    self.time_series_table.clone(),
+                ));
+            }
         }
         let mut columns = HashSet::new();
 
@@ -619,48 +809,211 @@ impl TimeSeriesTable {
             columns.insert(k.clone());
         }
 
+        if project_date_partition {
+            self.project_date_partitions(&mut basic_query, &mut columns);
+        }
+
+        self.from_table_with_ids(&mut basic_query, btsq);
+
+        //Only the rows with the latest timestamp of each identifier are kept
+        if btsq.latest {
+            let latest_query_name = "latest_query";
+            let mut latest_query = Query::select();
+            latest_query.expr_as(
+                SeaExpr::col(Name::Column(self.identifier_column.clone())),
+                Alias::new(LATEST_IDENTIFIER_COLUMN_NAME),
+            );
+            latest_query.expr_as(
+                SeaExpr::col(Name::Column(self.timestamp_column.clone())).max(),
+                Alias::new(LATEST_TIMESTAMP_COLUMN_NAME),
+            );
+            self.from_table_with_ids(&mut latest_query, btsq);
+            latest_query.group_by_columns(vec![ColumnRef::Column(Rc::new(Name::Column(
+                self.identifier_column.clone(),
+            )))]);
+
+            let mut conditions = vec![];
+            for (c, latest_c) in [
+                (&self.identifier_column, LATEST_IDENTIFIER_COLUMN_NAME),
+                (&self.timestamp_column, LATEST_TIMESTAMP_COLUMN_NAME),
+            ] {
+                conditions.push(
+                    SimpleExpr::Column(ColumnRef::Column(Rc::new(Name::Column(c.clone())))).equals(
+                        SimpleExpr::Column(ColumnRef::TableColumn(
+                            Rc::new(Name::Table(latest_query_name.to_string())),
+                            Rc::new(Name::Column(latest_c.to_string())),
+                        )),
+                    ),
+                );
+            }
+            let second_condition = conditions.pop().unwrap();
+            let first_condition = conditions.pop().unwrap();
+            basic_query.join(
+                JoinType::InnerJoin,
+                TableRef::SubQuery(latest_query, Rc::new(Alias::new(latest_query_name))),
+                SimpleExpr::Binary(
+                    Box::new(first_condition),
+                    BinOper::And,
+                    Box::new(second_condition),
+                ),
+            );
+        }
+
+        Ok((basic_query, columns))
+    }
+
+    fn project_date_partitions(&self, select: &mut SelectStatement, columns: &mut HashSet<String>) {
+        let mut projection_column_name_map = HashMap::new();
+        projection_column_name_map.insert(
+            YEAR_PARTITION_COLUMN_NAME.to_string(),
+            self.year_column.as_ref().unwrap().clone(),
+        );
+        projection_column_name_map.insert(
+            MONTH_PARTITION_COLUMN_NAME.to_string(),
+            self.month_column.as_ref().unwrap().clone(),
+        );
+        projection_column_name_map.insert(
+            DAY_PARTITION_COLUMN_NAME.to_string(),
+            self.day_column.as_ref().unwrap().clone(),
+        );
         let mut kvs: Vec<_> = projection_column_name_map.iter().collect();
         kvs.sort();
         for (k, v) in kvs {
-            basic_query.expr_as(
+            select.expr_as(
                 SeaExpr::col(Name::Column(v.clone())).as_enum(Alias::new("INTEGER")),
                 Alias::new(k),
             );
             columns.insert(k.clone());
         }
+    }
+
+    //The year, month and day columns as timestamp partitions
+    fn date_partitions(&self) -> Vec<TimeSeriesPartition> {
+        let mut partitions = vec![];
+        for (column, format) in [
+            (&self.year_column, "%Y"),
+            (&self.month_column, "%m"),
+            (&self.day_column, "%d"),
+        ] {
+            //A finer partition can only be used together with the coarser ones
+            if let Some(column) = column {
+                partitions.push(TimeSeriesPartition {
+                    column: column.clone(),
+                    scheme: PartitionScheme::TimestampInteger(format.to_string()),
+                });
+            } else {
+                break;
+            }
+        }
+        partitions
+    }
 
+    fn from_table(&self, select: &mut SelectStatement) {
         if let Some(schema) = &self.schema {
-            basic_query.from((
+            select.from((
                 Name::Schema(schema.clone()),
                 Name::Table(self.time_series_table.clone()),
             ));
         } else {
-            basic_query.from(Name::Table(self.time_series_table.clone()));
+            select.from(Name::Table(self.time_series_table.clone()));
         }
+    }
 
+    fn from_table_with_ids(&self, select: &mut SelectStatement, btsq: &BasicTimeSeriesQuery) {
+        self.from_table(select);
         if let Some(ids) = &btsq.ids {
-            basic_query.and_where(
+            select.and_where(
                 SeaExpr::col(Name::Column(self.identifier_column.clone())).is_in(
                     ids.iter()
                         .map(|x| Value::String(Some(Box::new(x.to_string())))),
                 ),
             );
         }
+    }
+}
 
-        Ok((basic_query, columns))
+//The identifiers and groups of a grouped basic query
+fn grouping_tuples(
+    btsq: &BasicTimeSeriesQuery,
+    df: &DataFrame,
+    column_name: &String,
+) -> Result<Vec<(String, i64)>, TimeSeriesQueryToSQLError> {
+    let mut value_tuples = vec![];
+    let identifier_colname = btsq.identifier_variable.as_ref().unwrap().as_str();
+    let mut identifier_iter = df.column(identifier_colname).unwrap().iter();
+    let mut groupcol_iter = df.column(column_name).unwrap().iter();
+    for _ in 0..df.height() {
+        let id = identifier_iter.next().unwrap();
+        let grp = groupcol_iter.next().unwrap();
+        let id_value = if let AnyValue::Utf8(id_value) = id {
+            id_value.to_string()
+        } else {
+            return Err(TimeSeriesQueryToSQLError::UnexpectedGroupingValue(
+                id.to_string(),
+            ));
+        };
+        let grp_value = if let AnyValue::Int64(grp_value) = grp {
+            grp_value
+        } else {
+            return Err(TimeSeriesQueryToSQLError::UnexpectedGroupingValue(
+                grp.to_string(),
+            ));
+        };
+        value_tuples.push((id_value, grp_value));
+    }
+    Ok(value_tuples)
+}
+
+//The built in functions that have a translation to SQL in the given dialect
+pub fn sql_function_pushdowns(dialect: &SQLDialect) -> HashSet<Function> {
+    let mut functions: HashSet<Function> = [
+        Function::Abs,
+        Function::Ceil,
+        Function::Floor,
+        Function::Round,
+        Function::Concat,
+        Function::SubStr,
+        Function::StrLen,
+        Function::Replace,
+        Function::UCase,
+        Function::LCase,
+        Function::Contains,
+        Function::StrStarts,
+        Function::StrEnds,
+        Function::StrBefore,
+        Function::StrAfter,
+        Function::Year,
+        Function::Month,
+        Function::Day,
+        Function::Hours,
+        Function::Minutes,
+        Function::Seconds,
+        Function::Md5,
+        Function::Regex,
+    ]
+    .into();
+    if dialect == &SQLDialect::Dremio {
+        functions.insert(Function::Sha1);
+        functions.insert(Function::Sha256);
+        functions.insert(Function::Sha512);
     }
+    functions
 }
 
 fn check_partitioning_support(tables: &Vec<TimeSeriesTable>) -> bool {
     tables
         .iter()
-        .all(|x| x.day_column.is_some() && x.month_column.is_some() && x.day_column.is_some())
+        .all(|x| x.year_column.is_some() && x.month_column.is_some() && x.day_column.is_some())
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::constants::RESAMPLE;
+    use crate::function_registry::FunctionRegistry;
     use crate::query_context::{Context, VariableInContext};
+    use crate::timeseries_database::timeseries_sql_rewrite::expression_rewrite::SPARQLToSQLExpressionTransformer;
     use crate::timeseries_database::timeseries_sql_rewrite::{
+        PartitionScheme, SQLDialect, TimeSeriesPartition, TimeSeriesQueryToSQLError,
         TimeSeriesQueryToSQLTransformer, TimeSeriesTable,
     };
     use crate::timeseries_query::{
@@ -671,8 +1024,10 @@ mod tests {
     use polars_core::frame::DataFrame;
     use polars_core::prelude::NamedFrom;
     use polars_core::series::Series;
-    use sea_query::PostgresQueryBuilder;
+    use sea_query::{PostgresQueryBuilder, Query};
     use spargebra::algebra::{AggregateExpression, Expression, Function};
+    use std::collections::HashMap;
+    use std::sync::Arc;
     use std::vec;
 
     #[test]
@@ -697,7 +1052,11 @@ mod tests {
                 Variable::new_unchecked("t"),
                 Context::new(),
             )),
+            quality_variable: None,
             ids: Some(vec!["A".to_string(), "B".to_string()]),
+            latest: false,
+            events: false,
+            event_field_variables: vec![],
         };
         let tsq = TimeSeriesQuery::Filtered(
             Box::new(TimeSeriesQuery::Basic(basic_tsq)),
@@ -714,15 +1073,19 @@ mod tests {
             schema: Some("s3.otit-benchmark".into()),
             time_series_table: "timeseries_double".into(),
             value_column: "value".into(),
+            quality_column: None,
             timestamp_column: "timestamp".into(),
             identifier_column: "dir3".into(),
             value_datatype: NamedNode::new_unchecked("http://www.w3.org/2001/XMLSchema#double"),
             year_column: Some("dir0".to_string()),
             month_column: Some("dir1".to_string()),
             day_column: Some("dir2".to_string()),
+            partitions: vec![],
+            wide_value_columns: None,
         };
         let tables = vec![table];
-        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables);
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
         let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
         //println!("{}", sql_query)
         assert_eq!(
@@ -773,7 +1136,11 @@ mod tests {
                                                             Context::new(),
                                                         ),
                                                     ),
+                                                    quality_variable: None,
                                                     ids: Some(vec!["id1".to_string()]),
+                                                    latest: false,
+                                                    events: false,
+                                                    event_field_variables: vec![],
                                                 },
                                                 DataFrame::new(vec![
                                                     Series::new("ts_external_id_1", ["id1"]),
@@ -813,7 +1180,11 @@ mod tests {
                                                             Context::new(),
                                                         ),
                                                     ),
+                                                    quality_variable: None,
                                                     ids: Some(vec!["id2".to_string()]),
+                                                    latest: false,
+                                                    events: false,
+                                                    event_field_variables: vec![],
                                                 },
                                                 DataFrame::new(vec![
                                                     Series::new("ts_external_id_2", ["id2"]),
@@ -925,19 +1296,548 @@ mod tests {
             schema: Some("s3.otit-benchmark".into()),
             time_series_table: "timeseries_double".into(),
             value_column: "value".into(),
+            quality_column: None,
             timestamp_column: "timestamp".into(),
             identifier_column: "dir3".into(),
             value_datatype: NamedNode::new_unchecked("http://www.w3.org/2001/XMLSchema#double"),
             year_column: Some("dir0".to_string()),
             month_column: Some("dir1".to_string()),
             day_column: Some("dir2".to_string()),
+            partitions: vec![],
+            wide_value_columns: None,
         };
         let tables = vec![table];
-        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables);
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
         let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
         println!("Select structure {:?}", sql_query);
 
         let expected_str = r#"SELECT AVG("outer_query"."val_dir") AS "f7ca5ee9058effba8691ac9c642fbe95", AVG("outer_query"."val_speed") AS "990362f372e4019bc151c13baf0b50d5", "outer_query"."year" AS "year", "outer_query"."month" AS "month", "outer_query"."day" AS "day", "outer_query"."hour" AS "hour", "outer_query"."minute_10" AS "minute_10", "outer_query"."grouping_col_0" AS "grouping_col_0" FROM (SELECT "inner_query"."day" AS "day", "inner_query"."grouping_col_0" AS "grouping_col_0", "inner_query"."hour" AS "hour", "inner_query"."minute_10" AS "minute_10", "inner_query"."month" AS "month", "inner_query"."t" AS "t", "inner_query"."val_dir" AS "val_dir", "inner_query"."val_speed" AS "val_speed", "inner_query"."year" AS "year" FROM (SELECT "day" AS "day", "grouping_col_0" AS "grouping_col_0", "hour" AS "hour", "minute_10" AS "minute_10", "month" AS "month", "t" AS "t", "val_dir" AS "val_dir", "val_speed" AS "val_speed", "subquery"."year_partition_column_name" AS "year" FROM (SELECT "day" AS "day", "day_partition_column_name" AS "day_partition_column_name", "grouping_col_0" AS "grouping_col_0", "hour" AS "hour", "minute_10" AS "minute_10", "month_partition_column_name" AS "month_partition_column_name", "t" AS "t", "val_dir" AS "val_dir", "val_speed" AS "val_speed", "year_partition_column_name" AS "year_partition_column_name", "subquery"."month_partition_column_name" AS "month" FROM (SELECT "day_partition_column_name" AS "day_partition_column_name", "grouping_col_0" AS "grouping_col_0", "hour" AS "hour", "minute_10" AS "minute_10", "month_partition_column_name" AS "month_partition_column_name", "t" AS "t", "val_dir" AS "val_dir", "val_speed" AS "val_speed", "year_partition_column_name" AS "year_partition_column_name", "subquery"."day_partition_column_name" AS "day" FROM (SELECT "day_partition_column_name" AS "day_partition_column_name", "grouping_col_0" AS "grouping_col_0", "minute_10" AS "minute_10", "month_partition_column_name" AS "month_partition_column_name", "t" AS "t", "val_dir" AS "val_dir", "val_speed" AS "val_speed", "year_partition_column_name" AS "year_partition_column_name", date_part('hour', "subquery"."t") AS "hour" FROM (SELECT "day_partition_column_name" AS "day_partition_column_name", "grouping_col_0" AS "grouping_col_0", "month_partition_column_name" AS "month_partition_column_name", "t" AS "t", "val_dir" AS "val_dir", "val_speed" AS "val_speed", "year_partition_column_name" AS "year_partition_column_name", CAST(FLOOR(date_part('minute', "subquery"."t") / 10) AS INTEGER) AS "minute_10" FROM (SELECT "first_query"."day_partition_column_name" AS "day_partition_column_name", "first_query"."grouping_col_0" AS "grouping_col_0", "first_query"."month_partition_column_name" AS "month_partition_column_name", "first_query"."t" AS "t", "first_query"."val_speed" AS "val_speed", "first_query"."year_partition_column_name" AS "year_partition_column_name", "other_0"."day_partition_column_name" AS "day_partition_column_name", "other_0"."grouping_col_0" AS "grouping_col_0", "other_0"."month_partition_column_name" AS "month_partition_column_name", "other_0"."val_dir" AS "val_dir", "other_0"."year_partition_column_name" AS "year_partition_column_name" FROM (SELECT "basic_query"."day_partition_column_name" AS "day_partition_column_name", "basic_query"."month_partition_column_name" AS "month_partition_column_name", "basic_query"."t" AS "t", "basic_query"."val_speed" AS "val_speed", "basic_query"."year_partition_column_name" AS "year_partition_column_name", "static_query"."grouping_col_0" AS "grouping_col_0" FROM (SELECT "timestamp" AS "t", "dir3" AS "ts_external_id_1", "value" AS "val_speed", CAST("dir2" AS INTEGER) AS "day_partition_column_name", CAST("dir1" AS INTEGER) AS "month_partition_column_name", CAST("dir0" AS INTEGER) AS "year_partition_column_name" FROM "s3.otit-benchmark"."timeseries_double" WHERE "dir3" IN ('id1')) AS "basic_query" INNER JOIN (SELECT "mapping"."EXPR$0" AS "ts_external_id_1", "mapping"."EXPR$1" AS "grouping_col_0" FROM (VALUES ('id1', 0)) AS "mapping") AS "static_query" ON "static_query"."ts_external_id_1" = "basic_query"."ts_external_id_1") AS "first_query" INNER JOIN (SELECT "basic_query"."day_partition_column_name" AS "day_partition_column_name", "basic_query"."month_partition_column_name" AS "month_partition_column_name", "basic_query"."t" AS "t", "basic_query"."val_dir" AS "val_dir", "basic_query"."year_partition_column_name" AS "year_partition_column_name", "static_query"."grouping_col_0" AS "grouping_col_0" FROM (SELECT "timestamp" AS "t", "dir3" AS "ts_external_id_2", "value" AS "val_dir", CAST("dir2" AS INTEGER) AS "day_partition_column_name", CAST("dir1" AS INTEGER) AS "month_partition_column_name", CAST("dir0" AS INTEGER) AS "year_partition_column_name" FROM "s3.otit-benchmark"."timeseries_double" WHERE "dir3" IN ('id2')) AS "basic_query" INNER JOIN (SELECT "mapping"."EXPR$0" AS "ts_external_id_2", "mapping"."EXPR$1" AS "grouping_col_0" FROM (VALUES ('id2', 1)) AS "mapping") AS "static_query" ON "static_query"."ts_external_id_2" = "basic_query"."ts_external_id_2") AS "other_0" ON ("first_query"."grouping_col_0" = "other_0"."grouping_col_0") AND ("first_query"."t" = "other_0"."t") AND ("first_query"."year_partition_column_name" = "other_0"."year_partition_column_name") AND ("first_query"."month_partition_column_name" = "other_0"."month_partition_column_name") AND ("first_query"."day_partition_column_name" = "other_0"."day_partition_column_name") WHERE (("year_partition_column_name" > 2022) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" > 8)) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" = 8) AND ("day_partition_column_name" > 30)) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" = 8) AND ("day_partition_column_name" = 30) AND ("t" >= '2022-08-30 08:46:53'))) AND (("year_partition_column_name" < 2022) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" < 8)) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" = 8) AND ("day_partition_column_name" < 30)) OR (("year_partition_column_name" = 2022) AND ("month_partition_column_name" = 8) AND ("day_partition_column_name" = 30) AND ("t" <= '2022-08-30 21:46:53')))) AS "subquery") AS "subquery") AS "subquery") AS "subquery") AS "subquery") AS "inner_query") AS "outer_query" GROUP BY "outer_query"."year", "outer_query"."month", "outer_query"."day", "outer_query"."hour", "outer_query"."minute_10", "outer_query"."grouping_col_0""#;
         assert_eq!(sql_query.to_string(PostgresQueryBuilder), expected_str);
     }
+
+    fn filtered_string_function_query() -> TimeSeriesQuery {
+        let basic_tsq = BasicTimeSeriesQuery {
+            identifier_variable: Some(Variable::new_unchecked("id")),
+            timeseries_variable: None,
+            data_point_variable: None,
+            value_variable: Some(VariableInContext::new(
+                Variable::new_unchecked("v"),
+                Context::new(),
+            )),
+            datatype_variable: None,
+            datatype: Some(xsd::STRING.into_owned()),
+            timestamp_variable: Some(VariableInContext::new(
+                Variable::new_unchecked("t"),
+                Context::new(),
+            )),
+            quality_variable: None,
+            ids: Some(vec!["A".to_string()]),
+            latest: false,
+            events: false,
+            event_field_variables: vec![],
+        };
+        TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::Basic(basic_tsq)),
+            Expression::And(
+                Box::new(Expression::FunctionCall(
+                    Function::Regex,
+                    vec![
+                        Expression::FunctionCall(
+                            Function::UCase,
+                            vec![Expression::Variable(Variable::new_unchecked("v"))],
+                        ),
+                        Expression::Literal(Literal::new_simple_literal("^open")),
+                        Expression::Literal(Literal::new_simple_literal("i")),
+                    ],
+                )),
+                Box::new(Expression::FunctionCall(
+                    Function::StrStarts,
+                    vec![
+                        Expression::Variable(Variable::new_unchecked("v")),
+                        Expression::Literal(Literal::new_simple_literal("OP")),
+                    ],
+                )),
+            ),
+        )
+    }
+
+    fn string_table() -> TimeSeriesTable {
+        TimeSeriesTable {
+            schema: None,
+            time_series_table: "timeseries_string".into(),
+            value_column: "value".into(),
+            quality_column: None,
+            timestamp_column: "timestamp".into(),
+            identifier_column: "id".into(),
+            value_datatype: xsd::STRING.into_owned(),
+            year_column: None,
+            month_column: None,
+            day_column: None,
+            partitions: vec![],
+            wide_value_columns: None,
+        }
+    }
+
+    #[test]
+    fn test_quality_filter() {
+        let mut basic_tsq = BasicTimeSeriesQuery::new_empty();
+        basic_tsq.identifier_variable = Some(Variable::new_unchecked("id"));
+        basic_tsq.value_variable = Some(VariableInContext::new(
+            Variable::new_unchecked("v"),
+            Context::new(),
+        ));
+        basic_tsq.timestamp_variable = Some(VariableInContext::new(
+            Variable::new_unchecked("t"),
+            Context::new(),
+        ));
+        basic_tsq.quality_variable = Some(VariableInContext::new(
+            Variable::new_unchecked("q"),
+            Context::new(),
+        ));
+        basic_tsq.datatype = Some(xsd::STRING.into_owned());
+        basic_tsq.ids = Some(vec!["A".to_string()]);
+        let tsq = TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::Basic(basic_tsq)),
+            Expression::Equal(
+                Box::new(Expression::Variable(Variable::new_unchecked("q"))),
+                Box::new(Expression::Literal(Literal::from(0))),
+            ),
+        );
+        let function_registry = FunctionRegistry::new();
+
+        let tables = vec![string_table()];
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        assert!(transformer.create_query(&tsq, false).is_err());
+
+        let mut table = string_table();
+        table.quality_column = Some("status".to_string());
+        let tables = vec![table];
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, columns) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#""status" AS "q""#));
+        assert!(sql.contains(r#""q" = 0"#));
+        assert!(columns.contains("q"));
+    }
+
+    #[test]
+    fn test_string_functions_dremio() {
+        let tables = vec![string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, _) = transformer
+            .create_query(&filtered_string_function_query(), false)
+            .unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#"REGEXP_LIKE(UPPER("v"), '(?i)^open')"#));
+        assert!(sql.contains(r#"SUBSTR("v", 1, CHAR_LENGTH('OP'))"#));
+    }
+
+    #[test]
+    fn test_string_functions_postgres() {
+        let tables = vec![string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry)
+            .with_dialect(SQLDialect::Postgres);
+        let (sql_query, _) = transformer
+            .create_query(&filtered_string_function_query(), false)
+            .unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#"REGEXP_MATCH(UPPER("v"), '^open', 'pi') IS NOT NULL"#));
+    }
+
+    #[test]
+    fn test_untranslatable_function_is_an_error() {
+        let tables = vec![string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let tsq = if let TimeSeriesQuery::Filtered(inner, _) = filtered_string_function_query() {
+            TimeSeriesQuery::Filtered(
+                inner,
+                Expression::FunctionCall(
+                    Function::IsIri,
+                    vec![Expression::Variable(Variable::new_unchecked("v"))],
+                ),
+            )
+        } else {
+            panic!()
+        };
+        assert!(transformer.create_query(&tsq, false).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_translations() {
+        let function_registry = FunctionRegistry::new();
+        let v = Box::new(Expression::Variable(Variable::new_unchecked("v")));
+        let aggregations = vec![
+            AggregateExpression::Count {
+                expr: Some(v.clone()),
+                distinct: true,
+            },
+            AggregateExpression::Count {
+                expr: None,
+                distinct: false,
+            },
+            AggregateExpression::GroupConcat {
+                expr: v.clone(),
+                distinct: false,
+                separator: Some(",".to_string()),
+            },
+        ];
+        let render = |dialect: SQLDialect| {
+            let mut transformer = SPARQLToSQLExpressionTransformer::new(
+                None,
+                None,
+                None,
+                None,
+                &function_registry,
+                &dialect,
+            );
+            let mut select = Query::select();
+            for agg in &aggregations {
+                select.expr(
+                    transformer
+                        .sparql_aggregate_expression_to_sql_expression(agg)
+                        .unwrap(),
+                );
+            }
+            select.to_string(PostgresQueryBuilder)
+        };
+        assert_eq!(
+            render(SQLDialect::Dremio),
+            r#"SELECT COUNT(DISTINCT("v")), COUNT(*), LISTAGG(CAST("v" AS VARCHAR), ',')"#
+        );
+        assert_eq!(
+            render(SQLDialect::Postgres),
+            r#"SELECT COUNT(DISTINCT("v")), COUNT(*), STRING_AGG(CAST("v" AS VARCHAR), ',')"#
+        );
+    }
+
+    #[test]
+    fn test_resample_translations() {
+        let function_registry = FunctionRegistry::new();
+        let resample = Expression::FunctionCall(
+            Function::Custom(NamedNode::new_unchecked(RESAMPLE)),
+            vec![
+                Expression::Variable(Variable::new_unchecked("t")),
+                Expression::Literal(Literal::new_typed_literal("60", xsd::INTEGER)),
+            ],
+        );
+        let render = |dialect: SQLDialect| {
+            let mut transformer = SPARQLToSQLExpressionTransformer::new(
+                None,
+                None,
+                None,
+                None,
+                &function_registry,
+                &dialect,
+            );
+            let mut select = Query::select();
+            select.expr(
+                transformer
+                    .sparql_expression_to_sql_expression(&resample)
+                    .unwrap(),
+            );
+            select.to_string(PostgresQueryBuilder)
+        };
+        let dremio = render(SQLDialect::Dremio);
+        assert!(dremio.contains(r#"UNIX_TIMESTAMP("t", 'YYYY-MM-DD HH:MI:SS.FFF')"#));
+        let postgres = render(SQLDialect::Postgres);
+        assert!(postgres.contains(r#"TO_TIMESTAMP(FLOOR(date_part('epoch', "t")"#));
+        assert!(!postgres.contains("UNIX_TIMESTAMP"));
+    }
+
+    #[test]
+    fn test_invalid_literals_are_errors() {
+        let function_registry = FunctionRegistry::new();
+        let dialect = SQLDialect::Dremio;
+        let mut transformer = SPARQLToSQLExpressionTransformer::new(
+            None,
+            None,
+            None,
+            None,
+            &function_registry,
+            &dialect,
+        );
+        for (value, datatype) in [
+            ("abc", xsd::DOUBLE),
+            ("99999999999", xsd::INT),
+            ("2022-13-45", xsd::DATE_TIME),
+        ] {
+            let literal = Expression::Literal(Literal::new_typed_literal(value, datatype));
+            assert!(matches!(
+                transformer.sparql_expression_to_sql_expression(&literal),
+                Err(TimeSeriesQueryToSQLError::InvalidLiteral(..))
+            ));
+        }
+    }
+
+    fn string_basic_tsq(suffix: &str, ids: Vec<&str>) -> BasicTimeSeriesQuery {
+        let mut basic_tsq = BasicTimeSeriesQuery::new_empty();
+        basic_tsq.identifier_variable = Some(Variable::new_unchecked(format!("id{}", suffix)));
+        basic_tsq.value_variable = Some(VariableInContext::new(
+            Variable::new_unchecked(format!("v{}", suffix)),
+            Context::new(),
+        ));
+        basic_tsq.timestamp_variable = Some(VariableInContext::new(
+            Variable::new_unchecked("t"),
+            Context::new(),
+        ));
+        basic_tsq.datatype = Some(xsd::STRING.into_owned());
+        basic_tsq.ids = Some(ids.into_iter().map(|x| x.to_string()).collect());
+        basic_tsq
+    }
+
+    fn timestamp_literal(s: &str) -> Box<Expression> {
+        Box::new(Expression::Literal(Literal::new_typed_literal(
+            s,
+            xsd::DATE_TIME,
+        )))
+    }
+
+    #[test]
+    fn test_partition_pruning() {
+        let mut table = string_table();
+        table.partitions = vec![
+            TimeSeriesPartition {
+                column: "dt".to_string(),
+                scheme: PartitionScheme::TimestampString("%Y-%m-%d".to_string()),
+            },
+            TimeSeriesPartition {
+                column: "hour".to_string(),
+                scheme: PartitionScheme::TimestampInteger("%H".to_string()),
+            },
+            TimeSeriesPartition {
+                column: "site".to_string(),
+                scheme: PartitionScheme::IdentifierMapping(HashMap::from([
+                    ("A".to_string(), "north".to_string()),
+                    ("B".to_string(), "south".to_string()),
+                ])),
+            },
+        ];
+        let t = Box::new(Expression::Variable(Variable::new_unchecked("t")));
+        let tsq = TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::Basic(string_basic_tsq("", vec!["A", "B"]))),
+            Expression::And(
+                Box::new(Expression::GreaterOrEqual(
+                    t.clone(),
+                    timestamp_literal("2022-06-01T08:30:00"),
+                )),
+                Box::new(Expression::Less(
+                    t.clone(),
+                    timestamp_literal("2022-06-02T00:00:00"),
+                )),
+            ),
+        );
+        let tables = vec![table];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#""site" IN ('north', 'south')"#));
+        assert!(sql.contains(r#"("dt" > '2022-06-01')"#));
+        assert!(sql.contains(r#"("dt" = '2022-06-01') AND (CAST("hour" AS INTEGER) > 8)"#));
+        assert!(sql.contains(r#"("dt" < '2022-06-02')"#));
+        assert!(sql.contains(r#"("dt" = '2022-06-02') AND (CAST("hour" AS INTEGER) < 0)"#));
+
+        //Alternatives that do not restrict the timestamp can not be pruned
+        let tsq = TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::Basic(string_basic_tsq("", vec!["A", "C"]))),
+            Expression::Or(
+                Box::new(Expression::Equal(
+                    t.clone(),
+                    timestamp_literal("2022-06-01T08:30:00"),
+                )),
+                Box::new(Expression::Equal(
+                    Box::new(Expression::Variable(Variable::new_unchecked("v"))),
+                    Box::new(Expression::Literal(Literal::from("open"))),
+                )),
+            ),
+        );
+        let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(!sql.contains(r#""dt""#));
+        assert!(!sql.contains(r#""site""#));
+    }
+
+    #[test]
+    fn test_partition_pruning_in_and_identifier_function() {
+        let mut table = string_table();
+        table.partitions = vec![
+            TimeSeriesPartition {
+                column: "dt".to_string(),
+                scheme: PartitionScheme::TimestampString("%Y-%m-%d".to_string()),
+            },
+            TimeSeriesPartition {
+                column: "bucket".to_string(),
+                scheme: PartitionScheme::IdentifierFunction(Arc::new(|id: &str| {
+                    (id.len() % 4).to_string()
+                })),
+            },
+        ];
+        let tsq = TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::Basic(string_basic_tsq(
+                "",
+                vec!["A", "BB"],
+            ))),
+            Expression::In(
+                Box::new(Expression::Variable(Variable::new_unchecked("t"))),
+                vec![
+                    *timestamp_literal("2022-06-01T08:30:00"),
+                    *timestamp_literal("2022-06-03T08:30:00Z"),
+                ],
+            ),
+        );
+        let tables = vec![table];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#""bucket" IN ('1', '2')"#));
+        assert!(sql.contains(r#"("dt" = '2022-06-01') OR ("dt" = '2022-06-03')"#));
+    }
+
+    #[test]
+    fn test_identifier_hash_partitions() {
+        let partition = |scheme: Option<PartitionScheme>, id: &str| match scheme {
+            Some(PartitionScheme::IdentifierFunction(f)) => f(id),
+            _ => panic!("Expected an identifier function"),
+        };
+        assert_eq!(
+            partition(PartitionScheme::identifier_hash("crc32", 16), "123456789"),
+            (0xCBF43926u64 % 16).to_string()
+        );
+        assert_eq!(
+            partition(PartitionScheme::identifier_hash("fnv1a_64", 10), "a"),
+            (0xaf63dc4c8601ec8cu64 % 10).to_string()
+        );
+        assert!(PartitionScheme::identifier_hash("md5", 16).is_none());
+        assert!(PartitionScheme::identifier_hash("crc32", 0).is_none());
+    }
+
+    fn grouped_string_basic(suffix: &str, id: &str, group: i64) -> Box<TimeSeriesQuery> {
+        let basic_tsq = string_basic_tsq(suffix, vec![id]);
+        let df = DataFrame::new(vec![
+            Series::new(&format!("id{}", suffix), [id]),
+            Series::new("grouping_col_0", [group]),
+        ])
+        .unwrap();
+        Box::new(TimeSeriesQuery::GroupedBasic(
+            basic_tsq,
+            df,
+            "grouping_col_0".to_string(),
+        ))
+    }
+
+    fn synchronized_string_query(second_group: i64) -> TimeSeriesQuery {
+        TimeSeriesQuery::Filtered(
+            Box::new(TimeSeriesQuery::InnerSynchronized(
+                vec![
+                    grouped_string_basic("1", "A", 0),
+                    grouped_string_basic("2", "B", second_group),
+                ],
+                vec![Synchronizer::Identity("t".to_string())],
+            )),
+            Expression::Greater(
+                Box::new(Expression::Variable(Variable::new_unchecked("t"))),
+                timestamp_literal("2022-06-01T08:30:00"),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_synchronized_without_partitions() {
+        let tsq = synchronized_string_query(1);
+        let tables = vec![string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, columns) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(!sql.contains("partition_column_name"));
+        assert!(sql.contains(r#"("first_query"."t" = "other_0"."t")"#));
+        assert!(columns.contains("v1") && columns.contains("v2"));
+    }
+
+    fn wide_string_table() -> TimeSeriesTable {
+        let mut table = string_table();
+        table.wide_value_columns = Some(HashMap::from([
+            ("A".to_string(), "tag_a".to_string()),
+            ("B".to_string(), "tag_b".to_string()),
+        ]));
+        table
+    }
+
+    #[test]
+    fn test_wide_table_unpivot() {
+        let tsq = TimeSeriesQuery::Basic(string_basic_tsq("", vec!["A", "B"]));
+        let tables = vec![wide_string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, columns) = transformer.create_query(&tsq, false).unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(sql.contains(r#""wide_identifiers"."wide_identifier" AS "id""#));
+        assert!(sql.contains(
+            r#"CASE "wide_identifiers"."wide_index" WHEN 0 THEN "tag_a" WHEN 1 THEN "tag_b" END AS "v""#
+        ));
+        assert!(sql.contains(r#"VALUES ('A', 0), ('B', 1)"#));
+        assert!(!sql.contains(r#""id" IN"#));
+        assert_eq!(
+            columns,
+            ["id", "t", "v"].iter().map(|x| x.to_string()).collect()
+        );
+
+        let tsq = TimeSeriesQuery::Basic(string_basic_tsq("", vec!["A", "C"]));
+        assert!(transformer.create_query(&tsq, false).is_err());
+    }
+
+    #[test]
+    fn test_table_with_identifiers_is_chosen() {
+        let mut other_wide_table = wide_string_table();
+        other_wide_table.time_series_table = "timeseries_other_wide".into();
+        other_wide_table.wide_value_columns =
+            Some(HashMap::from([("C".to_string(), "tag_c".to_string())]));
+        let tables = vec![string_table(), wide_string_table(), other_wide_table];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let sql_for = |ids: Vec<&str>| {
+            let tsq = TimeSeriesQuery::Basic(string_basic_tsq("", ids));
+            let (sql_query, _) = transformer.create_query(&tsq, false).unwrap();
+            sql_query.to_string(PostgresQueryBuilder)
+        };
+        let sql = sql_for(vec!["C"]);
+        assert!(sql.contains(r#"FROM "timeseries_other_wide""#));
+        assert!(sql.contains(r#"WHEN 0 THEN "tag_c""#));
+        let sql = sql_for(vec!["A", "B"]);
+        assert!(sql.contains(r#"WHEN 1 THEN "tag_b""#));
+        assert!(!sql.contains("timeseries_other_wide"));
+        //Identifiers that are not all in one wide table are read from the narrow table
+        let sql = sql_for(vec!["A", "C"]);
+        assert!(!sql.contains("CASE"));
+        assert!(sql.contains(r#""id" IN ('A', 'C')"#));
+    }
+
+    #[test]
+    fn test_wide_table_synchronized_single_scan() {
+        let tables = vec![wide_string_table()];
+        let function_registry = FunctionRegistry::new();
+        let transformer = TimeSeriesQueryToSQLTransformer::new(&tables, &function_registry);
+        let (sql_query, columns) = transformer
+            .create_query(&synchronized_string_query(0), false)
+            .unwrap();
+        let sql = sql_query.to_string(PostgresQueryBuilder);
+        assert!(!sql.contains("first_query"));
+        assert!(sql.contains(
+            r#"CASE "wide_identifiers"."grouping_col_0" WHEN 0 THEN "tag_a" END AS "v1""#
+        ));
+        assert!(sql
+            .contains(r#"CASE "wide_identifiers"."grouping_col_0" WHEN 0 THEN 'B' END AS "id2""#));
+        assert_eq!(
+            columns,
+            ["grouping_col_0", "id1", "id2", "t", "v1", "v2"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        );
+    }
 }
@@ -1,12 +1,17 @@
 use oxrdf::vocab::xsd;
+use oxrdf::Literal;
 use polars::export::chrono::{DateTime, NaiveDateTime, Utc};
 use sea_query::Expr as SeaExpr;
-use sea_query::{BinOper, ColumnRef, Function, SimpleExpr, UnOper, Value};
+use sea_query::{BinOper, CaseStatement, ColumnRef, Function, SimpleExpr, UnOper, Value};
 use spargebra::algebra::Expression;
 use std::rc::Rc;
+use std::str::FromStr;
 
-use crate::constants::DATETIME_AS_SECONDS;
-use crate::timeseries_database::timeseries_sql_rewrite::{Name, TimeSeriesQueryToSQLError};
+use crate::constants::{DATETIME_AS_SECONDS, RESAMPLE};
+use crate::function_registry::FunctionRegistry;
+use crate::timeseries_database::timeseries_sql_rewrite::{
+    Name, SQLDialect, TimeSeriesQueryToSQLError,
+};
 
 pub mod aggregate_expressions;
 
@@ -15,6 +20,8 @@ pub(crate) struct SPARQLToSQLExpressionTransformer<'a> {
     year_col: Option<&'a str>,
     month_col: Option<&'a str>,
     day_col: Option<&'a str>,
+    function_registry: &'a FunctionRegistry,
+    dialect: &'a SQLDialect,
     pub used_partitioning: bool,
 }
 
@@ -24,12 +31,16 @@ impl SPARQLToSQLExpressionTransformer<'_> {
         year_col: Option<&'a str>,
         month_col: Option<&'a str>,
         day_col: Option<&'a str>,
-    ) -> SPARQLToSQLExpressionTransformer {
+        function_registry: &'a FunctionRegistry,
+        dialect: &'a SQLDialect,
+    ) -> SPARQLToSQLExpressionTransformer<'a> {
         SPARQLToSQLExpressionTransformer {
             table_name,
             year_col,
             month_col,
             day_col,
+            function_registry,
+            dialect,
             used_partitioning: false,
         }
     }
@@ -45,15 +56,15 @@ impl SPARQLToSQLExpressionTransformer<'_> {
             Expression::Literal(l) => {
                 let v = l.value();
                 let value = match l.datatype() {
-                    xsd::BOOLEAN => Value::Bool(Some(v.parse().unwrap())),
-                    xsd::DOUBLE => Value::Double(Some(v.parse().unwrap())),
-                    xsd::DECIMAL => Value::Double(Some(v.parse().unwrap())),
-                    xsd::FLOAT => Value::Float(Some(v.parse().unwrap())),
-                    xsd::INTEGER => Value::BigInt(Some(v.parse().unwrap())),
-                    xsd::LONG => Value::BigInt(Some(v.parse().unwrap())),
-                    xsd::INT => Value::Int(Some(v.parse().unwrap())),
-                    xsd::UNSIGNED_INT => Value::Unsigned(Some(v.parse().unwrap())),
-                    xsd::UNSIGNED_LONG => Value::BigUnsigned(Some(v.parse().unwrap())),
+                    xsd::BOOLEAN => Value::Bool(Some(parse_literal(l)?)),
+                    xsd::DOUBLE => Value::Double(Some(parse_literal(l)?)),
+                    xsd::DECIMAL => Value::Double(Some(parse_literal(l)?)),
+                    xsd::FLOAT => Value::Float(Some(parse_literal(l)?)),
+                    xsd::INTEGER => Value::BigInt(Some(parse_literal(l)?)),
+                    xsd::LONG => Value::BigInt(Some(parse_literal(l)?)),
+                    xsd::INT => Value::Int(Some(parse_literal(l)?)),
+                    xsd::UNSIGNED_INT => Value::Unsigned(Some(parse_literal(l)?)),
+                    xsd::UNSIGNED_LONG => Value::BigUnsigned(Some(parse_literal(l)?)),
                     xsd::STRING => Value::String(Some(Box::new(v.to_string()))),
                     xsd::DATE_TIME => {
                         if let Ok(dt) = v.parse::<NaiveDateTime>() {
@@ -61,7 +72,10 @@ impl SPARQLToSQLExpressionTransformer<'_> {
                         } else if let Ok(dt) = v.parse::<DateTime<Utc>>() {
                             Value::ChronoDateTimeUtc(Some(Box::new(dt)))
                         } else {
-                            todo!("Could not parse {}", v);
+                            return Err(TimeSeriesQueryToSQLError::InvalidLiteral(
+                                v.to_string(),
+                                l.datatype().as_str().to_string(),
+                            ));
                         }
                     }
                     _ => {
@@ -204,35 +218,341 @@ impl SPARQLToSQLExpressionTransformer<'_> {
                     let e = expressions.first().unwrap();
                     let mapped_e = self.sparql_expression_to_sql_expression(e)?;
                     if c.as_str() == DATETIME_AS_SECONDS {
+                        self.epoch_seconds(mapped_e)
+                    } else if c.as_str() == RESAMPLE {
+                        let interval =
+                            self.sparql_expression_to_sql_expression(expressions.get(1).unwrap())?;
+                        let seconds = self.epoch_seconds(mapped_e);
+                        let floored = SimpleExpr::FunctionCall(
+                            Function::Custom(Rc::new(Name::Function("FLOOR".to_string()))),
+                            vec![SimpleExpr::Binary(
+                                Box::new(seconds),
+                                BinOper::Div,
+                                Box::new(interval.clone()),
+                            )],
+                        );
                         SimpleExpr::FunctionCall(
-                            Function::Custom(Rc::new(Name::Function("UNIX_TIMESTAMP".to_string()))),
-                            vec![
-                                mapped_e,
-                                SimpleExpr::Value(Value::String(Some(Box::new(
-                                    "YYYY-MM-DD HH:MI:SS.FFF".to_string(),
-                                )))),
-                            ],
+                            Function::Custom(Rc::new(Name::Function("TO_TIMESTAMP".to_string()))),
+                            vec![SimpleExpr::Binary(
+                                Box::new(floored),
+                                BinOper::Mul,
+                                Box::new(interval),
+                            )],
                         )
                     } else if c.as_str() == xsd::INTEGER.as_str() {
                         SimpleExpr::AsEnum(
                             Rc::new(Name::Table("INTEGER".to_string())),
                             Box::new(mapped_e),
                         )
+                    } else if let Some(sql_function) = self
+                        .function_registry
+                        .get_function(c.as_str())
+                        .and_then(|x| x.sql_function.as_ref())
+                    {
+                        let mut mapped_args = vec![mapped_e];
+                        for e in &expressions[1..] {
+                            mapped_args.push(self.sparql_expression_to_sql_expression(e)?);
+                        }
+                        sql_function(mapped_args)
                     } else {
-                        todo!("Fix custom {}", c)
+                        return Err(TimeSeriesQueryToSQLError::FunctionNotSupported(
+                            c.as_str().to_string(),
+                        ));
                     }
                 }
-                _ => {
-                    todo!("{}", f)
-                }
+                _ => self.sparql_function_to_sql_expression(f, expressions)?,
             },
+            Expression::SameTerm(left, right) => self
+                .sparql_expression_to_sql_expression(left)?
+                .equals(self.sparql_expression_to_sql_expression(right)?),
+            Expression::Bound(v) => {
+                SeaExpr::expr(simple_expr_from_column_name(&self.table_name, v.as_str()))
+                    .is_not_null()
+            }
+            Expression::If(left, middle, right) => SimpleExpr::Case(Box::new(
+                CaseStatement::new()
+                    .case(
+                        self.sparql_expression_to_sql_expression(left)?,
+                        SeaExpr::expr(self.sparql_expression_to_sql_expression(middle)?),
+                    )
+                    .finally(SeaExpr::expr(
+                        self.sparql_expression_to_sql_expression(right)?,
+                    )),
+            )),
+            Expression::Coalesce(inner) => {
+                let mut mapped = vec![];
+                for e in inner {
+                    mapped.push(self.sparql_expression_to_sql_expression(e)?);
+                }
+                sql_function("COALESCE", mapped)
+            }
+            Expression::NamedNode(_) | Expression::Exists(_) => {
+                return Err(TimeSeriesQueryToSQLError::ExpressionNotSupported(
+                    e.to_string(),
+                ));
+            }
+        })
+    }
+
+    fn sparql_function_to_sql_expression(
+        &mut self,
+        f: &spargebra::algebra::Function,
+        expressions: &Vec<Expression>,
+    ) -> Result<SimpleExpr, TimeSeriesQueryToSQLError> {
+        let mut mapped = vec![];
+        for e in expressions {
+            mapped.push(self.sparql_expression_to_sql_expression(e)?);
+        }
+        Ok(match f {
+            spargebra::algebra::Function::Abs => sql_function("ABS", mapped),
+            spargebra::algebra::Function::Ceil => sql_function("CEIL", mapped),
+            //Halves are rounded towards positive infinity in SPARQL
+            spargebra::algebra::Function::Round => sql_function(
+                "FLOOR",
+                vec![mapped
+                    .remove(0)
+                    .add(SimpleExpr::Value(Value::Double(Some(0.5))))],
+            ),
+            spargebra::algebra::Function::StrLen => sql_function("CHAR_LENGTH", mapped),
+            spargebra::algebra::Function::SubStr => sql_function("SUBSTR", mapped),
+            spargebra::algebra::Function::UCase => sql_function("UPPER", mapped),
+            spargebra::algebra::Function::LCase => sql_function("LOWER", mapped),
+            spargebra::algebra::Function::Concat => {
+                if mapped.is_empty() {
+                    SimpleExpr::Value(Value::String(Some(Box::new("".to_string()))))
+                } else {
+                    sql_function("CONCAT", mapped)
+                }
+            }
+            spargebra::algebra::Function::Contains => SimpleExpr::Binary(
+                Box::new(sql_function("STRPOS", mapped)),
+                BinOper::GreaterThan,
+                Box::new(SimpleExpr::Value(Value::Int(Some(0)))),
+            ),
+            spargebra::algebra::Function::StrStarts => {
+                let (string, prefix) = (mapped.remove(0), mapped.remove(0));
+                sql_function(
+                    "SUBSTR",
+                    vec![
+                        string,
+                        SimpleExpr::Value(Value::Int(Some(1))),
+                        sql_function("CHAR_LENGTH", vec![prefix.clone()]),
+                    ],
+                )
+                .equals(prefix)
+            }
+            spargebra::algebra::Function::StrEnds => {
+                let (string, suffix) = (mapped.remove(0), mapped.remove(0));
+                sql_function(
+                    "RIGHT",
+                    vec![string, sql_function("CHAR_LENGTH", vec![suffix.clone()])],
+                )
+                .equals(suffix)
+            }
+            spargebra::algebra::Function::StrBefore | spargebra::algebra::Function::StrAfter => {
+                let (string, search) = (mapped.remove(0), mapped.remove(0));
+                let position = sql_function("STRPOS", vec![string.clone(), search.clone()]);
+                let found = if f == &spargebra::algebra::Function::StrBefore {
+                    sql_function(
+                        "SUBSTR",
+                        vec![
+                            string,
+                            SimpleExpr::Value(Value::Int(Some(1))),
+                            position.clone().sub(SimpleExpr::Value(Value::Int(Some(1)))),
+                        ],
+                    )
+                } else {
+                    sql_function(
+                        "SUBSTR",
+                        vec![
+                            string,
+                            position
+                                .clone()
+                                .add(sql_function("CHAR_LENGTH", vec![search])),
+                        ],
+                    )
+                };
+                SimpleExpr::Case(Box::new(
+                    CaseStatement::new()
+                        .case(
+                            SimpleExpr::Binary(
+                                Box::new(position),
+                                BinOper::GreaterThan,
+                                Box::new(SimpleExpr::Value(Value::Int(Some(0)))),
+                            ),
+                            SeaExpr::expr(found),
+                        )
+                        .finally(SeaExpr::val("")),
+                ))
+            }
+            spargebra::algebra::Function::Regex => {
+                let flags = literal_flags(f, expressions.get(2))?;
+                let (string, pattern) = (mapped.remove(0), mapped.remove(0));
+                match self.dialect {
+                    SQLDialect::Dremio => sql_function(
+                        "REGEXP_LIKE",
+                        vec![string, java_pattern_with_flags(pattern, &flags)],
+                    ),
+                    SQLDialect::Postgres => SeaExpr::expr(sql_function(
+                        "REGEXP_MATCH",
+                        vec![string, pattern, postgres_flags(&flags)],
+                    ))
+                    .is_not_null(),
+                }
+            }
+            spargebra::algebra::Function::Replace => {
+                let flags = literal_flags(f, expressions.get(3))?;
+                let (string, pattern, replacement) =
+                    (mapped.remove(0), mapped.remove(0), mapped.remove(0));
+                match self.dialect {
+                    SQLDialect::Dremio => sql_function(
+                        "REGEXP_REPLACE",
+                        vec![
+                            string,
+                            java_pattern_with_flags(pattern, &flags),
+                            replacement,
+                        ],
+                    ),
+                    SQLDialect::Postgres => {
+                        //Postgres refers to groups with backslashes instead of dollar signs
+                        let replacement = if let Some(Expression::Literal(l)) = expressions.get(2) {
+                            let mut postgres_replacement = l.value().to_string();
+                            for i in (0..10).rev() {
+                                postgres_replacement = postgres_replacement
+                                    .replace(&format!("${}", i), &format!("\\{}", i));
+                            }
+                            SimpleExpr::Value(Value::String(Some(Box::new(postgres_replacement))))
+                        } else {
+                            return Err(TimeSeriesQueryToSQLError::FunctionNotSupported(format!(
+                                "{} with non-literal replacement",
+                                f
+                            )));
+                        };
+                        let mut flags = postgres_flags_string(&flags);
+                        flags.push('g');
+                        sql_function(
+                            "REGEXP_REPLACE",
+                            vec![
+                                string,
+                                pattern,
+                                replacement,
+                                SimpleExpr::Value(Value::String(Some(Box::new(flags)))),
+                            ],
+                        )
+                    }
+                }
+            }
+            spargebra::algebra::Function::Md5 => sql_function("MD5", mapped),
+            spargebra::algebra::Function::Sha1
+            | spargebra::algebra::Function::Sha256
+            | spargebra::algebra::Function::Sha512
+                if self.dialect == &SQLDialect::Dremio =>
+            {
+                sql_function(&f.to_string(), mapped)
+            }
             _ => {
-                unimplemented!("")
+                return Err(TimeSeriesQueryToSQLError::FunctionNotSupported(
+                    f.to_string(),
+                ));
             }
         })
     }
 }
 
+impl SPARQLToSQLExpressionTransformer<'_> {
+    //Seconds since the epoch of a timestamp
+    fn epoch_seconds(&self, timestamp: SimpleExpr) -> SimpleExpr {
+        match self.dialect {
+            SQLDialect::Dremio => sql_function(
+                "UNIX_TIMESTAMP",
+                vec![
+                    timestamp,
+                    SimpleExpr::Value(Value::String(Some(Box::new(
+                        "YYYY-MM-DD HH:MI:SS.FFF".to_string(),
+                    )))),
+                ],
+            ),
+            SQLDialect::Postgres => sql_function(
+                "date_part",
+                vec![
+                    SimpleExpr::Value(Value::String(Some(Box::new("epoch".to_string())))),
+                    timestamp,
+                ],
+            ),
+        }
+    }
+}
+
+fn parse_literal<T: FromStr>(literal: &Literal) -> Result<T, TimeSeriesQueryToSQLError> {
+    literal.value().parse().map_err(|_| {
+        TimeSeriesQueryToSQLError::InvalidLiteral(
+            literal.value().to_string(),
+            literal.datatype().as_str().to_string(),
+        )
+    })
+}
+
+fn sql_function(name: &str, args: Vec<SimpleExpr>) -> SimpleExpr {
+    SimpleExpr::FunctionCall(
+        Function::Custom(Rc::new(Name::Function(name.to_string()))),
+        args,
+    )
+}
+
+//Regular expression flags must be known when the query is created
+fn literal_flags(
+    f: &spargebra::algebra::Function,
+    flags: Option<&Expression>,
+) -> Result<String, TimeSeriesQueryToSQLError> {
+    match flags {
+        None => Ok("".to_string()),
+        Some(Expression::Literal(l))
+            if l.value().chars().all(|c| ['i', 's', 'm', 'x'].contains(&c)) =>
+        {
+            Ok(l.value().to_string())
+        }
+        Some(e) => Err(TimeSeriesQueryToSQLError::FunctionNotSupported(format!(
+            "{} with flags {}",
+            f, e
+        ))),
+    }
+}
+
+fn java_pattern_with_flags(pattern: SimpleExpr, flags: &str) -> SimpleExpr {
+    if flags.is_empty() {
+        pattern
+    } else {
+        let flags_value = SimpleExpr::Value(Value::String(Some(Box::new(format!("(?{})", flags)))));
+        if let SimpleExpr::Value(Value::String(Some(p))) = &pattern {
+            SimpleExpr::Value(Value::String(Some(Box::new(format!("(?{}){}", flags, p)))))
+        } else {
+            sql_function("CONCAT", vec![flags_value, pattern])
+        }
+    }
+}
+
+//By default, a dot matches newlines in Postgres but not in SPARQL, and vice versa for anchors
+fn postgres_flags_string(flags: &str) -> String {
+    let mut postgres_flags = match (flags.contains('s'), flags.contains('m')) {
+        (true, true) => "w",
+        (true, false) => "",
+        (false, true) => "n",
+        (false, false) => "p",
+    }
+    .to_string();
+    if flags.contains('i') {
+        postgres_flags.push('i');
+    }
+    if flags.contains('x') {
+        postgres_flags.push('x');
+    }
+    postgres_flags
+}
+
+fn postgres_flags(flags: &str) -> SimpleExpr {
+    SimpleExpr::Value(Value::String(Some(Box::new(postgres_flags_string(flags)))))
+}
+
 fn simple_expr_from_column_name(table_name: &Option<&Name>, column_name: &str) -> SimpleExpr {
     if let Some(name) = table_name {
         SimpleExpr::Column(ColumnRef::TableColumn(
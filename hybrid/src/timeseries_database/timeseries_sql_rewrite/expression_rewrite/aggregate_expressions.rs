@@ -1,33 +1,43 @@
-use super::SPARQLToSQLExpressionTransformer;
-use crate::timeseries_database::timeseries_sql_rewrite::TimeSeriesQueryToSQLError;
-use sea_query::{Function, SimpleExpr};
+use super::{sql_function, SPARQLToSQLExpressionTransformer};
+use crate::timeseries_database::timeseries_sql_rewrite::{
+    Name, SQLDialect, TimeSeriesQueryToSQLError,
+};
+use sea_query::{Function, SimpleExpr, Value};
 use spargebra::algebra::AggregateExpression;
+use std::rc::Rc;
 
 impl SPARQLToSQLExpressionTransformer<'_> {
-    //TODO: Support distinct in aggregates.. how???
     pub(crate) fn sparql_aggregate_expression_to_sql_expression(
         &mut self,
         agg: &AggregateExpression,
     ) -> Result<SimpleExpr, TimeSeriesQueryToSQLError> {
         Ok(match agg {
-            AggregateExpression::Count { expr, distinct: _ } => {
+            AggregateExpression::Count { expr, distinct } => {
                 if let Some(some_expr) = expr {
                     SimpleExpr::FunctionCall(
                         Function::Count,
-                        vec![self.sparql_expression_to_sql_expression(some_expr)?],
+                        vec![self.aggregated_sql_expression(some_expr, *distinct)?],
                     )
+                } else if *distinct {
+                    return Err(TimeSeriesQueryToSQLError::AggregateNotSupported(
+                        "COUNT(DISTINCT *)".to_string(),
+                    ));
                 } else {
-                    todo!("")
+                    SimpleExpr::FunctionCall(
+                        Function::Count,
+                        vec![SimpleExpr::Custom("*".to_string())],
+                    )
                 }
             }
-            AggregateExpression::Sum { expr, distinct: _ } => SimpleExpr::FunctionCall(
+            AggregateExpression::Sum { expr, distinct } => SimpleExpr::FunctionCall(
                 Function::Sum,
-                vec![self.sparql_expression_to_sql_expression(expr)?],
+                vec![self.aggregated_sql_expression(expr, *distinct)?],
             ),
-            AggregateExpression::Avg { expr, distinct: _ } => SimpleExpr::FunctionCall(
+            AggregateExpression::Avg { expr, distinct } => SimpleExpr::FunctionCall(
                 Function::Avg,
-                vec![self.sparql_expression_to_sql_expression(expr)?],
+                vec![self.aggregated_sql_expression(expr, *distinct)?],
             ),
+            //Distinct does not change the minimum or maximum
             AggregateExpression::Min { expr, distinct: _ } => SimpleExpr::FunctionCall(
                 Function::Min,
                 vec![self.sparql_expression_to_sql_expression(expr)?],
@@ -37,25 +47,68 @@ impl SPARQLToSQLExpressionTransformer<'_> {
                 vec![self.sparql_expression_to_sql_expression(expr)?],
             ),
             AggregateExpression::GroupConcat {
-                expr: _,
-                distinct: _,
-                separator: _,
+                expr,
+                distinct,
+                separator,
             } => {
-                todo!("")
-            }
-            AggregateExpression::Sample {
-                expr: _,
-                distinct: _,
-            } => {
-                todo!("")
+                let function_name = match self.dialect {
+                    SQLDialect::Dremio => "LISTAGG",
+                    SQLDialect::Postgres => "STRING_AGG",
+                };
+                let mut mapped = self.sparql_expression_to_sql_expression(expr)?;
+                mapped = SimpleExpr::AsEnum(
+                    Rc::new(Name::Table("VARCHAR".to_string())),
+                    Box::new(mapped),
+                );
+                if *distinct {
+                    mapped = sql_function("DISTINCT", vec![mapped]);
+                }
+                //The default separator in SPARQL is a single space
+                let separator = separator.as_deref().unwrap_or(" ").to_string();
+                sql_function(
+                    function_name,
+                    vec![
+                        mapped,
+                        SimpleExpr::Value(Value::String(Some(Box::new(separator)))),
+                    ],
+                )
             }
+            //Any value in the group is a valid sample, the minimum is supported by all databases
+            AggregateExpression::Sample { expr, distinct: _ } => SimpleExpr::FunctionCall(
+                Function::Min,
+                vec![self.sparql_expression_to_sql_expression(expr)?],
+            ),
             AggregateExpression::Custom {
-                expr: _,
-                distinct: _,
-                name: _,
+                expr,
+                distinct,
+                name,
             } => {
-                todo!("")
+                if let Some(sql_aggregate) = self
+                    .function_registry
+                    .get_aggregate(name.as_str())
+                    .and_then(|x| x.sql_aggregate.as_ref())
+                {
+                    sql_aggregate(self.aggregated_sql_expression(expr, *distinct)?)
+                } else {
+                    return Err(TimeSeriesQueryToSQLError::AggregateNotSupported(
+                        name.as_str().to_string(),
+                    ));
+                }
             }
         })
     }
+
+    //Renders as e.g. COUNT(DISTINCT("v")), which is valid SQL
+    fn aggregated_sql_expression(
+        &mut self,
+        expr: &spargebra::algebra::Expression,
+        distinct: bool,
+    ) -> Result<SimpleExpr, TimeSeriesQueryToSQLError> {
+        let mapped = self.sparql_expression_to_sql_expression(expr)?;
+        if distinct {
+            Ok(sql_function("DISTINCT", vec![mapped]))
+        } else {
+            Ok(mapped)
+        }
+    }
 }
@@ -1,7 +1,13 @@
-use super::Name;
+use super::{Name, PartitionScheme, TimeSeriesPartition};
+use crate::timeseries_query::BasicTimeSeriesQuery;
 use log::debug;
-use polars_core::export::chrono::Datelike;
-use sea_query::{BinOper, ColumnRef, SimpleExpr, Value};
+use oxrdf::vocab::xsd;
+use oxrdf::Variable;
+use polars_core::export::chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use sea_query::{Alias, BinOper, ColumnRef, Expr as SeaExpr, SimpleExpr, Value};
+use spargebra::algebra::Expression;
+use std::collections::BTreeSet;
+use std::fmt::Write;
 use std::rc::Rc;
 
 pub fn add_partitioned_timestamp_conditions(
@@ -73,11 +79,13 @@ fn rewrite_binary_expression(
             debug!("Binary not_in expression partition rewriting not supported yet")
         }
         BinOper::Equal => {
+            //Equal timestamps are in the same partition
             if let Some(e) = oper_or_original(
                 &original,
                 &left,
                 &right,
-                BinOper::NotEqual,
+                BinOper::Equal,
+                BinOper::And,
                 timestamp_col,
                 year_col,
                 month_col,
@@ -92,6 +100,7 @@ fn rewrite_binary_expression(
                 &left,
                 &right,
                 BinOper::NotEqual,
+                BinOper::Or,
                 timestamp_col,
                 year_col,
                 month_col,
@@ -316,6 +325,7 @@ fn oper_or_original(
     left: &SimpleExpr,
     right: &SimpleExpr,
     oper: BinOper,
+    combine: BinOper,
     timestamp_col: &str,
     year_col: &str,
     month_col: &str,
@@ -349,7 +359,7 @@ fn oper_or_original(
                                 day_not_equal,
                                 original.clone(),
                             ],
-                            BinOper::Or,
+                            combine,
                         ));
                     }
                 }
@@ -383,7 +393,7 @@ fn oper_or_original(
                                 day_not_equal,
                                 original.clone(),
                             ],
-                            BinOper::Or,
+                            combine,
                         ));
                     }
                 }
@@ -608,3 +618,258 @@ fn iterated_binoper(mut exprs: Vec<SimpleExpr>, oper: BinOper) -> SimpleExpr {
     }
     expr
 }
+
+//Conditions on the partition columns of a table that hold for every row matching the filters and ids,
+//so that the database can skip the partitions that can not contain matching rows
+pub(crate) fn partition_pruning_conditions(
+    partitions: &Vec<TimeSeriesPartition>,
+    btsq: &BasicTimeSeriesQuery,
+    filters: &Vec<&Expression>,
+) -> Vec<SimpleExpr> {
+    let mut conditions = vec![];
+    //Timestamp partitions are listed from the coarsest to the finest
+    let timestamp_partitions: Vec<&TimeSeriesPartition> = partitions
+        .iter()
+        .filter(|p| p.scheme.is_timestamp_partition())
+        .collect();
+    if let Some(timestamp_variable) = &btsq.timestamp_variable {
+        if !timestamp_partitions.is_empty() {
+            for f in filters {
+                if let Some(c) = timestamp_pruning_condition(
+                    f,
+                    &timestamp_variable.variable,
+                    &timestamp_partitions,
+                ) {
+                    conditions.push(c);
+                }
+            }
+        }
+    }
+    if let Some(ids) = &btsq.ids {
+        for p in partitions {
+            if let Some(values) = identifier_partition_values(&p.scheme, ids) {
+                conditions.push(
+                    SeaExpr::col(Name::Column(p.column.clone()))
+                        .is_in(values.into_iter().map(|x| Value::String(Some(Box::new(x))))),
+                );
+            }
+        }
+    }
+    conditions
+}
+
+fn identifier_partition_values(
+    scheme: &PartitionScheme,
+    ids: &Vec<String>,
+) -> Option<BTreeSet<String>> {
+    match scheme {
+        PartitionScheme::IdentifierMapping(mapping) => {
+            //Identifiers without a known partition could be in any partition
+            ids.iter().map(|id| mapping.get(id).cloned()).collect()
+        }
+        PartitionScheme::IdentifierFunction(f) => {
+            Some(ids.iter().map(|id| f(id.as_str())).collect())
+        }
+        _ => None,
+    }
+}
+
+//Hashes of the bytes of an identifier that hash partitions may be computed with
+pub(crate) fn partition_hash_function(name: &str) -> Option<fn(&[u8]) -> u64> {
+    match name {
+        "crc32" => Some(crc32),
+        "fnv1a_64" => Some(fnv1a_64),
+        _ => None,
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u64 {
+    let mut crc = 0xFFFFFFFFu32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    (!crc) as u64
+}
+
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn timestamp_pruning_condition(
+    expression: &Expression,
+    timestamp_variable: &Variable,
+    partitions: &Vec<&TimeSeriesPartition>,
+) -> Option<SimpleExpr> {
+    match expression {
+        Expression::And(left, right) => {
+            let left_condition = timestamp_pruning_condition(left, timestamp_variable, partitions);
+            let right_condition =
+                timestamp_pruning_condition(right, timestamp_variable, partitions);
+            match (left_condition, right_condition) {
+                (Some(l), Some(r)) => Some(iterated_binoper(vec![l, r], BinOper::And)),
+                (Some(c), None) | (None, Some(c)) => Some(c),
+                (None, None) => None,
+            }
+        }
+        Expression::Or(left, right) => {
+            //Both alternatives must restrict the partitions
+            let left_condition = timestamp_pruning_condition(left, timestamp_variable, partitions)?;
+            let right_condition =
+                timestamp_pruning_condition(right, timestamp_variable, partitions)?;
+            Some(iterated_binoper(
+                vec![left_condition, right_condition],
+                BinOper::Or,
+            ))
+        }
+        Expression::Equal(left, right) => {
+            let (dt, _) = compared_timestamp(left, right, timestamp_variable)?;
+            partitions_equal(&partition_values(partitions, &dt)?)
+        }
+        Expression::Greater(left, right) | Expression::GreaterOrEqual(left, right) => {
+            let (dt, flipped) = compared_timestamp(left, right, timestamp_variable)?;
+            let oper = if flipped {
+                BinOper::SmallerThan
+            } else {
+                BinOper::GreaterThan
+            };
+            partitions_bound(&partition_values(partitions, &dt)?, oper)
+        }
+        Expression::Less(left, right) | Expression::LessOrEqual(left, right) => {
+            let (dt, flipped) = compared_timestamp(left, right, timestamp_variable)?;
+            let oper = if flipped {
+                BinOper::GreaterThan
+            } else {
+                BinOper::SmallerThan
+            };
+            partitions_bound(&partition_values(partitions, &dt)?, oper)
+        }
+        Expression::In(left, alternatives) => {
+            if let Expression::Variable(v) = left.as_ref() {
+                if v != timestamp_variable || alternatives.is_empty() {
+                    return None;
+                }
+                let mut equalities = vec![];
+                for a in alternatives {
+                    let dt = timestamp_literal(a)?;
+                    equalities.push(partitions_equal(&partition_values(partitions, &dt)?)?);
+                }
+                Some(iterated_binoper(equalities, BinOper::Or))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+//The timestamp compared to the timestamp variable, and whether it is on the left side
+fn compared_timestamp(
+    left: &Expression,
+    right: &Expression,
+    timestamp_variable: &Variable,
+) -> Option<(NaiveDateTime, bool)> {
+    match (left, right) {
+        (Expression::Variable(v), e) if v == timestamp_variable => {
+            Some((timestamp_literal(e)?, false))
+        }
+        (e, Expression::Variable(v)) if v == timestamp_variable => {
+            Some((timestamp_literal(e)?, true))
+        }
+        _ => None,
+    }
+}
+
+//Partitions are assumed to be in UTC
+fn timestamp_literal(expression: &Expression) -> Option<NaiveDateTime> {
+    if let Expression::Literal(l) = expression {
+        if l.datatype() == xsd::DATE_TIME {
+            let v = l.value();
+            if let Ok(dt) = v.parse::<NaiveDateTime>() {
+                return Some(dt);
+            } else if let Ok(dt) = v.parse::<DateTime<Utc>>() {
+                return Some(dt.naive_utc());
+            }
+        }
+    }
+    None
+}
+
+//The partition column and the value it has for the timestamp, for each timestamp partition
+fn partition_values(
+    partitions: &Vec<&TimeSeriesPartition>,
+    dt: &NaiveDateTime,
+) -> Option<Vec<(SimpleExpr, SimpleExpr)>> {
+    let mut values = vec![];
+    for p in partitions {
+        match &p.scheme {
+            PartitionScheme::TimestampInteger(format) => {
+                let num: i32 = format_timestamp(dt, format)?.parse().ok()?;
+                values.push((
+                    SeaExpr::col(Name::Column(p.column.clone())).as_enum(Alias::new("INTEGER")),
+                    SimpleExpr::Value(Value::Int(Some(num))),
+                ));
+            }
+            PartitionScheme::TimestampString(format) => {
+                let s = format_timestamp(dt, format)?;
+                values.push((
+                    *named_column_box_simple_expression(p.column.clone()),
+                    SimpleExpr::Value(Value::String(Some(Box::new(s)))),
+                ));
+            }
+            _ => {}
+        }
+    }
+    Some(values)
+}
+
+fn format_timestamp(dt: &NaiveDateTime, format: &str) -> Option<String> {
+    let mut s = String::new();
+    write!(s, "{}", dt.format(format)).ok()?;
+    Some(s)
+}
+
+fn partitions_equal(values: &Vec<(SimpleExpr, SimpleExpr)>) -> Option<SimpleExpr> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(iterated_binoper(
+        values
+            .iter()
+            .map(|(c, v)| {
+                SimpleExpr::Binary(Box::new(c.clone()), BinOper::Equal, Box::new(v.clone()))
+            })
+            .collect(),
+        BinOper::And,
+    ))
+}
+
+//Compares the partitions lexicographically, so the partition of the timestamp itself is kept
+fn partitions_bound(values: &Vec<(SimpleExpr, SimpleExpr)>, oper: BinOper) -> Option<SimpleExpr> {
+    let mut alternatives = vec![];
+    for i in 0..values.len() {
+        let mut conjunction: Vec<SimpleExpr> = values[..i]
+            .iter()
+            .map(|(c, v)| {
+                SimpleExpr::Binary(Box::new(c.clone()), BinOper::Equal, Box::new(v.clone()))
+            })
+            .collect();
+        let (c, v) = values.get(i).unwrap();
+        conjunction.push(SimpleExpr::Binary(
+            Box::new(c.clone()),
+            oper.clone(),
+            Box::new(v.clone()),
+        ));
+        alternatives.push(iterated_binoper(conjunction, BinOper::And));
+    }
+    alternatives.push(partitions_equal(values)?);
+    Some(iterated_binoper(alternatives, BinOper::Or))
+}
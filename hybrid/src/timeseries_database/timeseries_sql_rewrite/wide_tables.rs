@@ -0,0 +1,313 @@
+use super::{
+    grouping_tuples, Name, TimeSeriesQueryToSQLError, TimeSeriesQueryToSQLTransformer,
+    TimeSeriesTable,
+};
+use crate::timeseries_query::{BasicTimeSeriesQuery, TimeSeriesQuery};
+use sea_query::{Alias, ColumnRef, JoinType, Query, SelectStatement, SimpleExpr, TableRef, Value};
+use spargebra::algebra::Expression;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+const WIDE_IDENTIFIERS_NAME: &str = "wide_identifiers";
+const WIDE_IDENTIFIER_COLUMN_NAME: &str = "wide_identifier";
+const WIDE_INDEX_COLUMN_NAME: &str = "wide_index";
+
+impl TimeSeriesTable {
+    //Unpivots the columns of the identifiers into rows of identifier, timestamp and value
+    pub(super) fn create_wide_basic_query(
+        &self,
+        btsq: &BasicTimeSeriesQuery,
+        wide_value_columns: &HashMap<String, String>,
+        project_date_partition: bool,
+    ) -> Result<(SelectStatement, HashSet<String>), TimeSeriesQueryToSQLError> {
+        if btsq.latest {
+            return Err(TimeSeriesQueryToSQLError::LatestNotSupportedForWideTable(
+                self.time_series_table.clone(),
+            ));
+        }
+        if btsq.quality_variable.is_some() {
+            return Err(TimeSeriesQueryToSQLError::MissingQualityColumn(
+                self.time_series_table.clone(),
+            ));
+        }
+        let ids = if let Some(ids) = &btsq.ids {
+            ids.clone()
+        } else {
+            let mut ids: Vec<String> = wide_value_columns.keys().cloned().collect();
+            ids.sort();
+            ids
+        };
+        let mut identifier_tuples = vec![];
+        let mut value_cases = vec![];
+        for (i, id) in ids.iter().enumerate() {
+            let column = self.wide_value_column(wide_value_columns, id)?;
+            identifier_tuples.push((id.clone(), i as i64));
+            value_cases.push((i as i64, quote_identifier(column)));
+        }
+        let value_case = case_sql(WIDE_INDEX_COLUMN_NAME, value_cases);
+
+        let mut projections = vec![
+            (
+                btsq.identifier_variable
+                    .as_ref()
+                    .unwrap()
+                    .as_str()
+                    .to_string(),
+                wide_identifiers_column(WIDE_IDENTIFIER_COLUMN_NAME),
+            ),
+            (
+                btsq.value_variable
+                    .as_ref()
+                    .unwrap()
+                    .variable
+                    .as_str()
+                    .to_string(),
+                SimpleExpr::Custom(value_case.clone()),
+            ),
+            (
+                btsq.timestamp_variable
+                    .as_ref()
+                    .unwrap()
+                    .variable
+                    .as_str()
+                    .to_string(),
+                SimpleExpr::Column(ColumnRef::Column(Rc::new(Name::Column(
+                    self.timestamp_column.clone(),
+                )))),
+            ),
+        ];
+        let mut basic_query = Query::select();
+        let mut columns = project_sorted(&mut basic_query, &mut projections);
+        if project_date_partition {
+            self.project_date_partitions(&mut basic_query, &mut columns);
+        }
+        self.from_table(&mut basic_query);
+        join_wide_identifiers(&mut basic_query, identifier_tuples, WIDE_INDEX_COLUMN_NAME);
+        //Wide tables have missing values where a tag has no value at the timestamp
+        basic_query.and_where(is_not_null(&value_case));
+        Ok((basic_query, columns))
+    }
+
+    fn wide_value_column<'a>(
+        &self,
+        wide_value_columns: &'a HashMap<String, String>,
+        id: &String,
+    ) -> Result<&'a String, TimeSeriesQueryToSQLError> {
+        wide_value_columns.get(id).ok_or_else(|| {
+            TimeSeriesQueryToSQLError::MissingWideTableColumn(
+                self.time_series_table.clone(),
+                id.clone(),
+            )
+        })
+    }
+}
+
+impl TimeSeriesQueryToSQLTransformer<'_> {
+    //Synchronized grouped queries on the same wide table are read in a single scan,
+    //provided that each query has a single identifier in each group
+    pub(super) fn create_wide_synchronized_query(
+        &self,
+        inner: &Vec<Box<TimeSeriesQuery>>,
+        timestamp_col: &String,
+        groupby_col: &String,
+        filters: &Vec<&Expression>,
+    ) -> Result<Option<(SelectStatement, HashSet<String>)>, TimeSeriesQueryToSQLError> {
+        let mut table: Option<&TimeSeriesTable> = None;
+        let mut btsqs = vec![];
+        let mut group_identifiers = vec![];
+        for tsq in inner {
+            if let TimeSeriesQuery::GroupedBasic(btsq, df, col) = tsq.as_ref() {
+                let btsq_table = self.find_right_table(btsq)?;
+                if btsq_table.wide_value_columns.is_none()
+                    || btsq.latest
+                    || btsq.events
+                    || btsq.quality_variable.is_some()
+                    || btsq.timestamp_variable.as_ref().unwrap().variable.as_str()
+                        != timestamp_col.as_str()
+                {
+                    return Ok(None);
+                }
+                if let Some(table) = table {
+                    if !std::ptr::eq(table, btsq_table) {
+                        return Ok(None);
+                    }
+                }
+                table = Some(btsq_table);
+                let mut identifiers = BTreeMap::new();
+                for (id, group) in grouping_tuples(btsq, df, col)? {
+                    if identifiers.insert(group, id).is_some() {
+                        return Ok(None);
+                    }
+                }
+                btsqs.push(btsq);
+                group_identifiers.push(identifiers);
+            } else {
+                return Ok(None);
+            }
+        }
+        let table = if let Some(table) = table {
+            table
+        } else {
+            return Ok(None);
+        };
+        let wide_value_columns = table.wide_value_columns.as_ref().unwrap();
+        //Only the groups of all the queries are kept by the inner join
+        let groups: Vec<i64> = group_identifiers[0]
+            .keys()
+            .filter(|g| group_identifiers.iter().all(|x| x.contains_key(g)))
+            .cloned()
+            .collect();
+        if groups.is_empty() {
+            return Ok(None);
+        }
+
+        let mut select = Query::select();
+        let mut projections = vec![];
+        let mut value_cases = vec![];
+        for (btsq, identifiers) in btsqs.iter().zip(group_identifiers.iter()) {
+            let mut value_case = vec![];
+            let mut identifier_case = vec![];
+            for g in &groups {
+                let id = identifiers.get(g).unwrap();
+                let column = table.wide_value_column(wide_value_columns, id)?;
+                value_case.push((*g, quote_identifier(column)));
+                identifier_case.push((*g, quote_string(id)));
+            }
+            let value_case = case_sql(groupby_col, value_case);
+            projections.push((
+                btsq.value_variable
+                    .as_ref()
+                    .unwrap()
+                    .variable
+                    .as_str()
+                    .to_string(),
+                SimpleExpr::Custom(value_case.clone()),
+            ));
+            projections.push((
+                btsq.identifier_variable
+                    .as_ref()
+                    .unwrap()
+                    .as_str()
+                    .to_string(),
+                SimpleExpr::Custom(case_sql(groupby_col, identifier_case)),
+            ));
+            value_cases.push(value_case);
+        }
+        projections.push((groupby_col.clone(), wide_identifiers_column(groupby_col)));
+        projections.push((
+            timestamp_col.clone(),
+            SimpleExpr::Column(ColumnRef::Column(Rc::new(Name::Column(
+                table.timestamp_column.clone(),
+            )))),
+        ));
+        let mut columns = project_sorted(&mut select, &mut projections);
+        //The date partitions are projected as in the joined queries
+        if self.partition_support {
+            table.project_date_partitions(&mut select, &mut columns);
+        }
+        table.from_table(&mut select);
+        let identifier_tuples = groups
+            .iter()
+            .map(|g| (group_identifiers[0].get(g).unwrap().clone(), *g))
+            .collect();
+        join_wide_identifiers(&mut select, identifier_tuples, groupby_col);
+        for value_case in &value_cases {
+            select.and_where(is_not_null(value_case));
+        }
+        self.add_partition_pruning(&mut select, table, btsqs.get(0).unwrap(), filters);
+
+        //Filters on the synchronized query refer to the projected columns
+        let wide_query_name = "wide_query";
+        let mut outer_select = Query::select();
+        outer_select.from_subquery(select, Alias::new(wide_query_name));
+        let mut sorted_cols: Vec<&String> = columns.iter().collect();
+        sorted_cols.sort();
+        for c in sorted_cols {
+            outer_select.expr_as(
+                SimpleExpr::Column(ColumnRef::TableColumn(
+                    Rc::new(Name::Table(wide_query_name.to_string())),
+                    Rc::new(Name::Column(c.clone())),
+                )),
+                Alias::new(c),
+            );
+        }
+        Ok(Some((outer_select, columns)))
+    }
+}
+
+fn project_sorted(
+    select: &mut SelectStatement,
+    projections: &mut Vec<(String, SimpleExpr)>,
+) -> HashSet<String> {
+    projections.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut columns = HashSet::new();
+    for (c, e) in projections.drain(..) {
+        select.expr_as(e, Alias::new(&c));
+        columns.insert(c);
+    }
+    columns
+}
+
+//Joins each row of the table with the identifiers and their indices
+fn join_wide_identifiers(
+    select: &mut SelectStatement,
+    identifier_tuples: Vec<(String, i64)>,
+    index_column: &str,
+) {
+    let mut identifiers_select = Query::select();
+    let mapping_values_alias = "mapping";
+    identifiers_select.from_values(identifier_tuples, Alias::new(mapping_values_alias));
+    for (values_column, c) in [
+        ("EXPR$0", WIDE_IDENTIFIER_COLUMN_NAME),
+        ("EXPR$1", index_column),
+    ] {
+        identifiers_select.expr_as(
+            SimpleExpr::Column(ColumnRef::TableColumn(
+                Rc::new(Name::Table(mapping_values_alias.to_string())),
+                Rc::new(Name::Column(values_column.to_string())),
+            )),
+            Alias::new(c),
+        );
+    }
+    select.join(
+        JoinType::InnerJoin,
+        TableRef::SubQuery(
+            identifiers_select,
+            Rc::new(Name::Table(WIDE_IDENTIFIERS_NAME.to_string())),
+        ),
+        SimpleExpr::Value(Value::Bool(Some(true))),
+    );
+}
+
+fn wide_identifiers_column(c: &str) -> SimpleExpr {
+    SimpleExpr::Column(ColumnRef::TableColumn(
+        Rc::new(Name::Table(WIDE_IDENTIFIERS_NAME.to_string())),
+        Rc::new(Name::Column(c.to_string())),
+    ))
+}
+
+//Chooses between the SQL expressions using the index of the identifier
+fn case_sql(index_column: &str, cases: Vec<(i64, String)>) -> String {
+    let mut case = format!(
+        "CASE {}.{}",
+        quote_identifier(WIDE_IDENTIFIERS_NAME),
+        quote_identifier(index_column)
+    );
+    for (i, then) in cases {
+        case.push_str(&format!(" WHEN {} THEN {}", i, then));
+    }
+    case.push_str(" END");
+    case
+}
+
+fn is_not_null(sql: &str) -> SimpleExpr {
+    SimpleExpr::Custom(format!("({}) IS NOT NULL", sql))
+}
+
+fn quote_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -40,7 +40,21 @@ pub struct BasicTimeSeriesQuery {
     pub datatype_variable: Option<Variable>,
     pub datatype: Option<NamedNode>,
     pub timestamp_variable: Option<VariableInContext>,
+    pub quality_variable: Option<VariableInContext>,
     pub ids: Option<Vec<String>>,
+    //Only the latest data point of each time series is requested
+    pub latest: bool,
+    //The data points are events, such as alarms, rather than values
+    pub events: bool,
+    pub event_field_variables: Vec<(EventField, VariableInContext)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EventField {
+    EventType,
+    Severity,
+    Message,
+    SourceNode,
 }
 
 impl BasicTimeSeriesQuery {
@@ -53,6 +67,12 @@ impl BasicTimeSeriesQuery {
         if let Some(tsv) = &self.timestamp_variable {
             expected_columns.insert(tsv.variable.as_str());
         }
+        if let Some(qv) = &self.quality_variable {
+            expected_columns.insert(qv.variable.as_str());
+        }
+        for (_, v) in &self.event_field_variables {
+            expected_columns.insert(v.variable.as_str());
+        }
         expected_columns
     }
 }
@@ -148,6 +168,28 @@ impl TimeSeriesQuery {
         false
     }
 
+    pub(crate) fn has_latest(&self) -> bool {
+        match self {
+            TimeSeriesQuery::Basic(b) | TimeSeriesQuery::GroupedBasic(b, ..) => b.latest,
+            TimeSeriesQuery::Filtered(inner, _) | TimeSeriesQuery::ExpressionAs(inner, ..) => {
+                inner.has_latest()
+            }
+            TimeSeriesQuery::InnerSynchronized(inners, _) => inners.iter().any(|x| x.has_latest()),
+            TimeSeriesQuery::Grouped(grouped) => grouped.tsq.has_latest(),
+        }
+    }
+
+    pub(crate) fn has_events(&self) -> bool {
+        match self {
+            TimeSeriesQuery::Basic(b) | TimeSeriesQuery::GroupedBasic(b, ..) => b.events,
+            TimeSeriesQuery::Filtered(inner, _) | TimeSeriesQuery::ExpressionAs(inner, ..) => {
+                inner.has_events()
+            }
+            TimeSeriesQuery::InnerSynchronized(inners, _) => inners.iter().any(|x| x.has_events()),
+            TimeSeriesQuery::Grouped(grouped) => grouped.tsq.has_events(),
+        }
+    }
+
     pub(crate) fn get_ids(&self) -> Vec<&String> {
         match self {
             TimeSeriesQuery::Basic(b) => {
@@ -276,6 +318,72 @@ impl TimeSeriesQuery {
             TimeSeriesQuery::ExpressionAs(t, ..) => t.get_timestamp_variables(),
         }
     }
+
+    pub(crate) fn has_equivalent_quality_variable(
+        &self,
+        variable: &Variable,
+        context: &Context,
+    ) -> bool {
+        for q in self.get_quality_variables() {
+            if q.equivalent(variable, context) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub(crate) fn get_quality_variables(&self) -> Vec<&VariableInContext> {
+        match self {
+            TimeSeriesQuery::Basic(b) | TimeSeriesQuery::GroupedBasic(b, ..) => {
+                if let Some(v) = &b.quality_variable {
+                    vec![v]
+                } else {
+                    vec![]
+                }
+            }
+            TimeSeriesQuery::Filtered(t, _) => t.get_quality_variables(),
+            TimeSeriesQuery::InnerSynchronized(ts, _) => {
+                let mut vs = vec![];
+                for t in ts {
+                    vs.extend(t.get_quality_variables())
+                }
+                vs
+            }
+            TimeSeriesQuery::Grouped(grouped) => grouped.tsq.get_quality_variables(),
+            TimeSeriesQuery::ExpressionAs(t, ..) => t.get_quality_variables(),
+        }
+    }
+
+    pub(crate) fn has_equivalent_event_field_variable(
+        &self,
+        variable: &Variable,
+        context: &Context,
+    ) -> bool {
+        for (_, v) in self.get_event_field_variables() {
+            if v.equivalent(variable, context) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub(crate) fn get_event_field_variables(&self) -> Vec<&(EventField, VariableInContext)> {
+        match self {
+            TimeSeriesQuery::Basic(b) | TimeSeriesQuery::GroupedBasic(b, ..) => {
+                b.event_field_variables.iter().collect()
+            }
+            TimeSeriesQuery::Filtered(t, _) => t.get_event_field_variables(),
+            TimeSeriesQuery::InnerSynchronized(ts, _) => {
+                let mut vs = vec![];
+                for t in ts {
+                    vs.extend(t.get_event_field_variables())
+                }
+                vs
+            }
+            TimeSeriesQuery::Grouped(grouped) => grouped.tsq.get_event_field_variables(),
+            TimeSeriesQuery::ExpressionAs(t, ..) => t.get_event_field_variables(),
+        }
+    }
 }
 
 impl BasicTimeSeriesQuery {
@@ -288,7 +396,11 @@ impl BasicTimeSeriesQuery {
             datatype_variable: None,
             datatype: None,
             timestamp_variable: None,
+            quality_variable: None,
             ids: None,
+            latest: false,
+            events: false,
+            event_field_variables: vec![],
         }
     }
 }
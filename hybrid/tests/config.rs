@@ -0,0 +1,235 @@
+use hybrid::config::{
+    ConfigError, EngineConfig, PartitionSchemeConfig, Secret, TimeSeriesDatabaseConfig,
+};
+use hybrid::pushdown_setting::PushdownSetting;
+use hybrid::timeseries_database::opcua_history_read::OPCUAHistoryReadError;
+use hybrid::timeseries_database::timeseries_sql_rewrite::PartitionScheme;
+use oxrdf::vocab::xsd;
+
+#[test]
+fn test_arrow_flight_sql_yaml_config() {
+    let yaml = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: arrow_flight_sql
+  endpoint: http://127.0.0.1:32010
+  username: dremio
+  password:
+    env: OTIT_TEST_DREMIO_PASSWORD
+  discover_tables: true
+  tables:
+    - schema: my_nas
+      time_series_table: ts.parquet
+      value_column: v
+      timestamp_column: ts
+      identifier_column: id
+      value_datatype: http://www.w3.org/2001/XMLSchema#unsignedInt
+      partitions:
+        - column: dt
+          timestamp_string: "%Y-%m-%d"
+        - column: site
+          identifier_mapping:
+            ts1: a
+            ts2: b
+        - column: bucket
+          identifier_hash:
+            hash: crc32
+            modulo: 16
+pushdowns: [group_by, value_conditions]
+vocabulary:
+  name_predicate: https://github.com/magbak/otit_swt_query#hasName
+  connective_mapping:
+    ".": "http://example.org/hasChild"
+limits:
+  max_static_solutions: 1000
+federation_endpoints:
+  - http://maintenance.example.org/sparql
+"#;
+    let config = EngineConfig::from_yaml_str(yaml).unwrap();
+    assert_eq!(config.sparql_endpoint, "http://localhost:7878/query");
+    assert_eq!(
+        config.pushdowns,
+        Some([PushdownSetting::GroupBy, PushdownSetting::ValueConditions].into())
+    );
+    assert_eq!(
+        config.vocabulary.connective_mapping.get("."),
+        Some(&"http://example.org/hasChild".to_string())
+    );
+    assert_eq!(config.limits.max_static_solutions, Some(1000));
+    assert_eq!(config.limits.max_time_series_rows, None);
+    assert!(config
+        .federation_endpoints
+        .contains("http://maintenance.example.org/sparql"));
+    if let TimeSeriesDatabaseConfig::ArrowFlightSql {
+        password,
+        tables,
+        discover_tables,
+        ..
+    } = &config.time_series_database
+    {
+        assert!(discover_tables);
+        assert!(matches!(password, Secret::Environment { .. }));
+        assert!(matches!(
+            tables[0].partitions[0].scheme,
+            PartitionSchemeConfig::TimestampString(_)
+        ));
+        let table = tables[0].to_time_series_table().unwrap();
+        assert_eq!(table.value_datatype, xsd::UNSIGNED_INT.into_owned());
+        assert_eq!(table.partitions.len(), 3);
+        assert!(matches!(
+            table.partitions[1].scheme,
+            PartitionScheme::IdentifierMapping(_)
+        ));
+        if let PartitionScheme::IdentifierFunction(f) = &table.partitions[2].scheme {
+            assert_eq!(f("123456789"), "6");
+        } else {
+            panic!("Expected a hash partition");
+        }
+    } else {
+        panic!("Expected an Arrow Flight SQL database");
+    }
+}
+
+#[test]
+fn test_opcua_toml_config() {
+    let toml = r#"
+sparql_endpoint = "http://localhost:7878/query"
+
+[time_series_database]
+type = "opcua_history_read"
+endpoint = "opc.tcp://localhost:4855"
+namespace = 1
+username = "operator"
+password = "secret"
+read_at_time = true
+"#;
+    let config = EngineConfig::from_toml_str(toml).unwrap();
+    assert_eq!(config.pushdowns, None);
+    assert!(config.federation_endpoints.is_empty());
+    assert_eq!(
+        config.time_series_database.default_pushdowns(),
+        [PushdownSetting::GroupBy, PushdownSetting::Synchronization].into()
+    );
+    if let TimeSeriesDatabaseConfig::OpcuaHistoryRead {
+        namespace,
+        security_policy,
+        trust_server_certs,
+        read_at_time,
+        password,
+        ..
+    } = &config.time_series_database
+    {
+        assert_eq!(*namespace, 1);
+        assert_eq!(security_policy, "None");
+        assert!(!trust_server_certs);
+        assert!(read_at_time);
+        assert_eq!(password.as_ref().unwrap().resolve().unwrap(), "secret");
+    } else {
+        panic!("Expected an OPC UA history read database");
+    }
+}
+
+#[test]
+fn test_invalid_configs() {
+    let unknown_database = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: influx
+  endpoint: http://localhost:8086
+"#;
+    assert!(matches!(
+        EngineConfig::from_yaml_str(unknown_database),
+        Err(ConfigError::YAMLError(_))
+    ));
+    let invalid_name_predicate = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: opcua_history_read
+  endpoint: opc.tcp://localhost:4855
+  namespace: 1
+vocabulary:
+  name_predicate: hasName
+"#;
+    assert!(matches!(
+        EngineConfig::from_yaml_str(invalid_name_predicate),
+        Err(ConfigError::InvalidNamePredicateIRI(_))
+    ));
+    let invalid_partition_hash = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: arrow_flight_sql
+  endpoint: http://127.0.0.1:32010
+  username: dremio
+  password: dremio123
+  tables:
+    - time_series_table: ts.parquet
+      value_column: v
+      timestamp_column: ts
+      identifier_column: id
+      value_datatype: http://www.w3.org/2001/XMLSchema#double
+      partitions:
+        - column: bucket
+          identifier_hash:
+            hash: md5
+            modulo: 16
+"#;
+    let config = EngineConfig::from_yaml_str(invalid_partition_hash).unwrap();
+    if let TimeSeriesDatabaseConfig::ArrowFlightSql { tables, .. } = &config.time_series_database {
+        assert!(matches!(
+            tables[0].to_time_series_table(),
+            Err(ConfigError::InvalidPartitionHash(_, 16))
+        ));
+    } else {
+        panic!("Expected an Arrow Flight SQL database");
+    }
+    let narrow_table_without_identifier = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: arrow_flight_sql
+  endpoint: http://127.0.0.1:32010
+  username: dremio
+  password: dremio123
+  tables:
+    - time_series_table: ts.parquet
+      value_column: v
+      timestamp_column: ts
+      value_datatype: http://www.w3.org/2001/XMLSchema#double
+"#;
+    let config = EngineConfig::from_yaml_str(narrow_table_without_identifier).unwrap();
+    if let TimeSeriesDatabaseConfig::ArrowFlightSql { tables, .. } = &config.time_series_database {
+        assert!(matches!(
+            tables[0].to_time_series_table(),
+            Err(ConfigError::MissingTableColumn(_, _))
+        ));
+    } else {
+        panic!("Expected an Arrow Flight SQL database");
+    }
+    let missing_secret = Secret::Environment {
+        env: "OTIT_TEST_UNSET_VARIABLE".to_string(),
+    };
+    assert!(matches!(
+        missing_secret.resolve(),
+        Err(ConfigError::MissingEnvironmentVariable(_))
+    ));
+}
+
+#[test]
+fn test_half_specified_opcua_config() {
+    let yaml = r#"
+sparql_endpoint: http://localhost:7878/query
+time_series_database:
+  type: opcua_history_read
+  endpoint: opc.tcp://localhost:4855
+  namespace: 1
+  username: operator
+"#;
+    let config = EngineConfig::from_yaml_str(yaml).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    //The configuration is rejected before connecting
+    assert!(matches!(
+        runtime.block_on(config.time_series_database.connect()),
+        Err(ConfigError::OPCUAHistoryReadError(
+            OPCUAHistoryReadError::InvalidSecurityConfiguration(_)
+        ))
+    ));
+}
@@ -0,0 +1,94 @@
+use hybrid::construct::{ConstructTemplate, RDFFormat};
+use hybrid::splitter::parse_sparql_construct_query;
+use oxrdf::Variable;
+use polars::prelude::{DataFrame, NamedFrom, Series};
+use std::collections::HashSet;
+
+const QUERY: &str = r#"
+PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+PREFIX types:<http://example.org/types#>
+CONSTRUCT {
+    ?dp types:ofSensor ?s .
+    ?dp types:hasValue ?v .
+    ?dp types:unit ?unknown .
+} WHERE {
+    ?s otit_swt:hasTimeseries ?ts .
+    ?ts otit_swt:hasDataPoint ?dp .
+    ?dp otit_swt:hasTimestamp ?t .
+    ?dp otit_swt:hasValue ?v .
+}
+"#;
+
+#[test]
+fn test_construct_template_projection() {
+    let (template, dataset, where_pattern) = parse_sparql_construct_query(QUERY).unwrap();
+    let construct_template = ConstructTemplate::new(template, &where_pattern);
+    let select_query = construct_template.select_query(dataset, where_pattern);
+    let expected = [
+        "SELECT ?s ?t ?ts ?v WHERE {",
+        "?s <https://github.com/magbak/otit_swt#hasTimeseries> ?ts .",
+    ];
+    let select_string = select_query.to_string();
+    for e in expected {
+        assert!(select_string.contains(e), "{}", select_string);
+    }
+}
+
+#[test]
+fn test_construct_writes_minted_data_points() {
+    let (template, _, where_pattern) = parse_sparql_construct_query(QUERY).unwrap();
+    let construct_template = ConstructTemplate::new(template, &where_pattern);
+    let t = Series::new("t", [1654073213000i64, 1654073214000i64])
+        .cast(&polars::prelude::DataType::Datetime(
+            polars::prelude::TimeUnit::Milliseconds,
+            None,
+        ))
+        .unwrap();
+    let df = DataFrame::new(vec![
+        Series::new("s", ["http://example.org/s1", "http://example.org/s1"]),
+        t,
+        Series::new("ts", ["http://example.org/ts1", "http://example.org/ts1"]),
+        Series::new("v", [1.5f64, 2.0f64]),
+    ])
+    .unwrap();
+    let iri_variables: HashSet<Variable> =
+        [Variable::new_unchecked("s"), Variable::new_unchecked("ts")].into();
+    let mut out = vec![];
+    let count = construct_template
+        .write_triples(&df, &iri_variables, RDFFormat::NTriples, &mut out)
+        .unwrap();
+    assert_eq!(count, 4);
+    let expected = r#"<http://example.org/ts1/dataPoint/20220601T084653> <http://example.org/types#ofSensor> <http://example.org/s1> .
+<http://example.org/ts1/dataPoint/20220601T084653> <http://example.org/types#hasValue> "1.5"^^<http://www.w3.org/2001/XMLSchema#double> .
+<http://example.org/ts1/dataPoint/20220601T084654> <http://example.org/types#ofSensor> <http://example.org/s1> .
+<http://example.org/ts1/dataPoint/20220601T084654> <http://example.org/types#hasValue> "2"^^<http://www.w3.org/2001/XMLSchema#double> .
+"#;
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn test_construct_turtle_groups_subjects() {
+    let (template, _, where_pattern) = parse_sparql_construct_query(QUERY).unwrap();
+    let construct_template = ConstructTemplate::new(template, &where_pattern);
+    let t = Series::new("t", [1654073213000i64])
+        .cast(&polars::prelude::DataType::Datetime(
+            polars::prelude::TimeUnit::Milliseconds,
+            None,
+        ))
+        .unwrap();
+    let df = DataFrame::new(vec![
+        Series::new("s", ["http://example.org/s1"]),
+        t,
+        Series::new("ts", ["http://example.org/ts1"]),
+        Series::new("v", [1.5f64]),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    construct_template
+        .write_triples(&df, &HashSet::new(), RDFFormat::Turtle, &mut out)
+        .unwrap();
+    let expected = r#"<http://example.org/ts1/dataPoint/20220601T084653> <http://example.org/types#ofSensor> "http://example.org/s1" ;
+    <http://example.org/types#hasValue> "1.5"^^<http://www.w3.org/2001/XMLSchema#double> .
+"#;
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
@@ -1,7 +1,7 @@
 use opcua_server::prelude::*;
 use polars::export::chrono::{DateTime as PolarsDateTime, Utc as PolarsUtc};
 use polars::export::chrono::{NaiveDateTime, Utc};
-use polars::prelude::{col, lit, DataType as PolarsDataType, IntoLazy};
+use polars::prelude::{col, concat, lit, DataType as PolarsDataType, IntoLazy};
 use polars_core::datatypes::AnyValue;
 use polars_core::frame::DataFrame;
 use polars_core::prelude::TimeUnit;
@@ -17,11 +17,26 @@ const OPCUA_AGG_FUNC_TOTAL: u32 = 2344;
 
 pub struct OPCUADataProvider {
     pub frames: HashMap<String, DataFrame>,
+    //When set, results are split into pages of this size using continuation points
+    pub page_size: Option<usize>,
 }
 
 impl OPCUADataProvider {
     pub fn new(frames: HashMap<String, DataFrame>) -> OPCUADataProvider {
-        OPCUADataProvider { frames }
+        OPCUADataProvider {
+            frames,
+            page_size: None,
+        }
+    }
+
+    pub fn with_page_size(
+        frames: HashMap<String, DataFrame>,
+        page_size: usize,
+    ) -> OPCUADataProvider {
+        OPCUADataProvider {
+            frames,
+            page_size: Some(page_size),
+        }
     }
 
     fn read(
@@ -38,30 +53,13 @@ impl OPCUADataProvider {
                 namespace: _,
                 identifier,
             } = &n.node_id;
-            let idstring = if let Identifier::String(uas) = identifier {
-                uas.to_string()
-            } else {
-                panic!("")
-            };
-            let mut df = self.frames.get(&idstring).unwrap().clone();
+            let mut df = self.frames.get(&id_string(identifier)).unwrap().clone();
             let mut lf = df.lazy();
             if !start_time.is_null() {
-                let start = start_time
-                    .as_chrono()
-                    .to_string()
-                    .parse::<PolarsDateTime<PolarsUtc>>()
-                    .unwrap()
-                    .naive_utc();
-                lf = lf.filter(col("timestamp").gt_eq(lit(start)));
+                lf = lf.filter(col("timestamp").gt_eq(lit(to_naive(start_time))));
             }
             if !end_time.is_null() {
-                let stop = end_time
-                    .as_chrono()
-                    .to_string()
-                    .parse::<PolarsDateTime<PolarsUtc>>()
-                    .unwrap()
-                    .naive_utc();
-                lf = lf.filter(col("timestamp").lt_eq(lit(stop)));
+                lf = lf.filter(col("timestamp").lt_eq(lit(to_naive(end_time))));
             }
             if let Some(aggregation_types) = &aggregation_types {
                 assert!(interval.unwrap() > 0.0);
@@ -95,58 +93,24 @@ impl OPCUADataProvider {
                 lf = lfgr.agg([agg_col.unwrap().alias("value")]);
             }
             df = lf.collect().unwrap();
-            let mut ts_iter = df.column("timestamp").unwrap().iter();
-            let mut v_iter = df.column("value").unwrap().iter();
-            let mut data_values = vec![];
-            for _ in 0..df.height() {
-                let value_variant = match v_iter.next().unwrap() {
-                    AnyValue::Float64(f) => Variant::Double(f),
-                    AnyValue::Int64(i) => Variant::Int64(i),
-                    _ => {
-                        todo!("Very rudimentary value type support!")
-                    }
-                };
-
-                let naive_date_time = match ts_iter.next().unwrap() {
-                    AnyValue::Datetime(number, timeunit, _) => match timeunit {
-                        TimeUnit::Nanoseconds => NaiveDateTime::from_timestamp(
-                            number / 1_000_000_000,
-                            (number % 1_000_000_000) as u32,
-                        ),
-                        TimeUnit::Microseconds => NaiveDateTime::from_timestamp(
-                            number / 1_000_000,
-                            (number % 1_000_000) as u32,
-                        ),
-                        TimeUnit::Milliseconds => {
-                            NaiveDateTime::from_timestamp(number / 1_000, (number % 1_000) as u32)
-                        }
-                    },
-                    v => {
-                        panic!("Something is not right! {}", v)
-                    }
-                };
-
-                let timestamp = DateTime::from(DateTimeUtc::from_utc(naive_date_time, Utc));
-
-                let dv = DataValue {
-                    value: Some(value_variant),
-                    status: None,
-                    source_timestamp: Some(timestamp),
-                    source_picoseconds: None,
-                    server_timestamp: None,
-                    server_picoseconds: None,
-                };
-                data_values.push(dv);
-            }
-            let h = HistoryData {
-                data_values: Some(data_values),
-            };
-            let r = HistoryReadResult {
-                status_code: StatusCode::Good,
-                continuation_point: Default::default(),
-                history_data: ExtensionObject::from_encodable(h.object_id(), &h),
+            //The continuation point holds the offset of the next page
+            let offset = if n.continuation_point.is_null() {
+                0
+            } else {
+                String::from_utf8(n.continuation_point.value.clone().unwrap())
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap()
             };
-            results.push(r);
+            let mut continuation_point = ByteString::null();
+            if let Some(page_size) = self.page_size {
+                if offset + page_size < df.height() {
+                    continuation_point =
+                        ByteString::from((offset + page_size).to_string().into_bytes());
+                }
+                df = df.slice(offset as i64, page_size);
+            }
+            results.push(history_read_result(df, continuation_point));
         }
         Ok(results)
     }
@@ -158,9 +122,12 @@ impl HistoricalDataProvider for OPCUADataProvider {
         _address_space: Arc<RwLock<AddressSpace>>,
         request: ReadRawModifiedDetails,
         _timestamps_to_return: TimestampsToReturn,
-        _release_continuation_points: bool,
+        release_continuation_points: bool,
         nodes_to_read: &[HistoryReadValueId],
     ) -> Result<Vec<HistoryReadResult>, StatusCode> {
+        if release_continuation_points {
+            return Ok(released(nodes_to_read));
+        }
         self.read(
             nodes_to_read,
             None,
@@ -170,14 +137,62 @@ impl HistoricalDataProvider for OPCUADataProvider {
         )
     }
 
+    //Values are sampled at the requested times using stepped interpolation, frames are sorted by time
+    fn read_at_time_details(
+        &self,
+        _address_space: Arc<RwLock<AddressSpace>>,
+        request: ReadAtTimeDetails,
+        _timestamps_to_return: TimestampsToReturn,
+        release_continuation_points: bool,
+        nodes_to_read: &[HistoryReadValueId],
+    ) -> Result<Vec<HistoryReadResult>, StatusCode> {
+        if release_continuation_points {
+            return Ok(released(nodes_to_read));
+        }
+        let mut results = vec![];
+        for n in nodes_to_read {
+            let df = self.frames.get(&id_string(&n.node_id.identifier)).unwrap();
+            let mut sampled_dfs = vec![];
+            for req_time in request.req_times.as_ref().unwrap() {
+                let req_time = to_naive(req_time);
+                let sampled = df
+                    .clone()
+                    .lazy()
+                    .filter(col("timestamp").lt_eq(lit(req_time)))
+                    .collect()
+                    .unwrap()
+                    .tail(Some(1));
+                if sampled.height() > 0 {
+                    sampled_dfs.push(
+                        sampled.lazy().with_column(
+                            lit(req_time)
+                                .cast(df.column("timestamp").unwrap().dtype().clone())
+                                .alias("timestamp"),
+                        ),
+                    );
+                }
+            }
+            let sampled_df = if sampled_dfs.is_empty() {
+                df.head(Some(0))
+            } else {
+                concat(sampled_dfs, true).unwrap().collect().unwrap()
+            };
+            results.push(history_read_result(sampled_df, ByteString::null()));
+        }
+        Ok(results)
+    }
+
     fn read_processed_details(
         &self,
         _address_space: Arc<RwLock<AddressSpace>>,
         request: ReadProcessedDetails,
         _timestamps_to_return: TimestampsToReturn,
-        _release_continuation_points: bool,
+        release_continuation_points: bool,
         nodes_to_read: &[HistoryReadValueId],
     ) -> Result<Vec<HistoryReadResult>, StatusCode> {
+        if release_continuation_points {
+            return Ok(released(nodes_to_read));
+        }
         self.read(
             nodes_to_read,
             Some(request.aggregate_type.unwrap()),
@@ -187,3 +202,155 @@ impl HistoricalDataProvider for OPCUADataProvider {
         )
     }
 }
+
+//Events of each notifier as time, event type, severity, message and source node
+pub struct OPCUAEventProvider {
+    pub events: HashMap<String, Vec<(DateTime, NodeId, u16, String, NodeId)>>,
+}
+
+impl HistoricalEventProvider for OPCUAEventProvider {
+    fn read_event_details(
+        &self,
+        _address_space: Arc<RwLock<AddressSpace>>,
+        request: ReadEventDetails,
+        _timestamps_to_return: TimestampsToReturn,
+        release_continuation_points: bool,
+        nodes_to_read: &[HistoryReadValueId],
+    ) -> Result<Vec<HistoryReadResult>, StatusCode> {
+        if release_continuation_points {
+            return Ok(released(nodes_to_read));
+        }
+        let select_clauses = request.filter.select_clauses.unwrap_or_default();
+        let mut results = vec![];
+        for n in nodes_to_read {
+            let mut events = vec![];
+            for (time, event_type, severity, message, source_node) in
+                self.events.get(&id_string(&n.node_id.identifier)).unwrap()
+            {
+                if (!request.start_time.is_null()
+                    && time.as_chrono() < request.start_time.as_chrono())
+                    || (!request.end_time.is_null()
+                        && time.as_chrono() > request.end_time.as_chrono())
+                {
+                    continue;
+                }
+                let event_fields = select_clauses
+                    .iter()
+                    .map(|s| {
+                        let name = s
+                            .browse_path
+                            .as_ref()
+                            .unwrap()
+                            .last()
+                            .unwrap()
+                            .name
+                            .to_string();
+                        match name.as_str() {
+                            "Time" => Variant::from(time.clone()),
+                            "EventType" => Variant::from(event_type.clone()),
+                            "Severity" => Variant::from(*severity),
+                            "Message" => Variant::from(LocalizedText::new("", message)),
+                            "SourceNode" => Variant::from(source_node.clone()),
+                            _ => Variant::Empty,
+                        }
+                    })
+                    .collect();
+                events.push(HistoryEventFieldList {
+                    event_fields: Some(event_fields),
+                });
+            }
+            let h = HistoryEvent {
+                events: Some(events),
+            };
+            results.push(HistoryReadResult {
+                status_code: StatusCode::Good,
+                continuation_point: ByteString::null(),
+                history_data: ExtensionObject::from_encodable(h.object_id(), &h),
+            });
+        }
+        Ok(results)
+    }
+}
+
+fn released(nodes_to_read: &[HistoryReadValueId]) -> Vec<HistoryReadResult> {
+    nodes_to_read
+        .iter()
+        .map(|_| HistoryReadResult {
+            status_code: StatusCode::Good,
+            continuation_point: ByteString::null(),
+            history_data: ExtensionObject::null(),
+        })
+        .collect()
+}
+
+fn id_string(identifier: &Identifier) -> String {
+    if let Identifier::String(uas) = identifier {
+        uas.to_string()
+    } else {
+        panic!("")
+    }
+}
+
+fn to_naive(datetime: &DateTime) -> NaiveDateTime {
+    datetime
+        .as_chrono()
+        .to_string()
+        .parse::<PolarsDateTime<PolarsUtc>>()
+        .unwrap()
+        .naive_utc()
+}
+
+fn history_read_result(df: DataFrame, continuation_point: ByteString) -> HistoryReadResult {
+    let mut ts_iter = df.column("timestamp").unwrap().iter();
+    let mut v_iter = df.column("value").unwrap().iter();
+    let mut data_values = vec![];
+    for _ in 0..df.height() {
+        let value_variant = match v_iter.next().unwrap() {
+            AnyValue::Float64(f) => Variant::Double(f),
+            AnyValue::Int64(i) => Variant::Int64(i),
+            _ => {
+                todo!("Very rudimentary value type support!")
+            }
+        };
+
+        let naive_date_time = match ts_iter.next().unwrap() {
+            AnyValue::Datetime(number, timeunit, _) => match timeunit {
+                TimeUnit::Nanoseconds => NaiveDateTime::from_timestamp(
+                    number / 1_000_000_000,
+                    (number % 1_000_000_000) as u32,
+                ),
+                TimeUnit::Microseconds => NaiveDateTime::from_timestamp(
+                    number / 1_000_000,
+                    (number % 1_000_000) as u32 * 1_000,
+                ),
+                TimeUnit::Milliseconds => NaiveDateTime::from_timestamp(
+                    number / 1_000,
+                    (number % 1_000) as u32 * 1_000_000,
+                ),
+            },
+            v => {
+                panic!("Something is not right! {}", v)
+            }
+        };
+
+        let timestamp = DateTime::from(DateTimeUtc::from_utc(naive_date_time, Utc));
+
+        let dv = DataValue {
+            value: Some(value_variant),
+            status: None,
+            source_timestamp: Some(timestamp),
+            source_picoseconds: None,
+            server_timestamp: None,
+            server_picoseconds: None,
+        };
+        data_values.push(dv);
+    }
+    let h = HistoryData {
+        data_values: Some(data_values),
+    };
+    HistoryReadResult {
+        status_code: StatusCode::Good,
+        continuation_point,
+        history_data: ExtensionObject::from_encodable(h.object_id(), &h),
+    }
+}
@@ -1,19 +1,22 @@
 mod common;
 
-use hybrid::engine::Engine;
+use hybrid::construct::RDFFormat;
+use hybrid::engine::{Engine, OrchestrationError};
+use hybrid::function_registry::{CustomAggregate, CustomFunction};
 use hybrid::pushdown_setting::all_pushdowns;
 use hybrid::splitter::parse_sparql_select_query;
 use hybrid::static_sparql::execute_sparql_query;
 use hybrid::timeseries_database::simple_in_memory_timeseries::InMemoryTimeseriesDatabase;
 use log::debug;
 use oxrdf::{NamedNode, Term, Variable};
-use polars::prelude::{CsvReader, SerReader};
+use polars::prelude::{lit, CsvReader, DataFrame, Expr, SerReader, Series};
 use rstest::*;
 use serial_test::serial;
 use sparesults::QuerySolution;
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::common::{
     add_sparql_testdata, compare_all_solutions, start_sparql_container, QUERY_ENDPOINT,
@@ -164,6 +167,77 @@ async fn test_simple_hybrid_query(
     // println!("{}", df);
 }
 
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_quality_hybrid_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    testdata_path: PathBuf,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?s ?t ?v ?q WHERE {
+        ?w a types:BigWidget .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasTimestamp ?t .
+        ?dp otit_swt:hasValue ?v .
+        ?dp otit_swt:hasQuality ?q .
+        FILTER(?t > "2022-06-01T08:46:53"^^xsd:dateTime && ?v < 200 && ?q = 0) .
+    }
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_quality_hybrid.csv");
+
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error");
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_latest_value_query(#[future] with_testdata: (), mut engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w a types:BigWidget .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasLatestValue ?v .
+    }
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget1"]),
+        Series::new("v", [105i64]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
+
 #[rstest]
 #[tokio::test]
 #[serial]
@@ -1046,3 +1120,512 @@ async fn test_coalesce_query(
     // writer.finish(&mut df).expect("writeok");
     // println!("{}", df);
 }
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_first_last_time_weighted_average_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    testdata_path: PathBuf,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w (otit_swt:first(?v) as ?first_v) (otit_swt:last(?v) as ?last_v) (otit_swt:timeWeightedAverage(?v) as ?twa_v) WHERE {
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasTimestamp ?t .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?t > "2022-06-01T08:46:53"^^xsd:dateTime) .
+    } GROUP BY ?w
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_first_last_time_weighted_average_query.csv");
+
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_delta_range_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    testdata_path: PathBuf,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w (otit_swt:delta(?v) as ?delta_v) (otit_swt:range(?v) as ?range_v) WHERE {
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasTimestamp ?t .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?t > "2022-06-01T08:46:53"^^xsd:dateTime) .
+    } GROUP BY ?w
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_delta_range_query.csv");
+
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_registered_function_and_aggregate_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    testdata_path: PathBuf,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    engine.register_function(
+        "http://example.org/functions#double",
+        CustomFunction::new(Arc::new(|args: Vec<Expr>| {
+            args.get(0).unwrap().clone() * lit(2)
+        })),
+    );
+    engine.register_aggregate(
+        "http://example.org/functions#median",
+        CustomAggregate::new(Arc::new(|expr: Expr| expr.median())),
+    );
+    let query = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    PREFIX fn:<http://example.org/functions#>
+    SELECT ?w (fn:median(?v2) as ?median_v2) WHERE {
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasTimestamp ?t .
+        ?dp otit_swt:hasValue ?v .
+        BIND(fn:double(?v) AS ?v2)
+        FILTER(?t > "2022-06-01T08:46:53"^^xsd:dateTime) .
+    } GROUP BY ?w
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_registered_function_and_aggregate_query.csv");
+
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_ask_query(#[future] with_testdata: (), mut engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    ASK WHERE {
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?v > LIMIT_VALUE) .
+    }
+    "#;
+    let exists = engine
+        .execute_ask_query(&query.replace("LIMIT_VALUE", "100"), QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    assert!(exists);
+    let not_exists = engine
+        .execute_ask_query(&query.replace("LIMIT_VALUE", "100000"), QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    assert!(!not_exists);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_construct_query(#[future] with_testdata: (), mut engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    CONSTRUCT { ?w types:hasSensorValue ?v } WHERE {
+        ?w a WIDGET_TYPE .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?v > 300) .
+    }
+    "#;
+    let mut out = vec![];
+    let triples = engine
+        .execute_construct_query(
+            &query.replace("WIDGET_TYPE", "types:BigWidget"),
+            QUERY_ENDPOINT,
+            RDFFormat::NTriples,
+            &mut out,
+        )
+        .await
+        .expect("Hybrid error");
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(triples, 3);
+    assert_eq!(out.lines().count(), 3);
+    for value in ["301", "303", "304"] {
+        assert!(out.contains(&format!(
+            "<http://example.org/case#myWidget1> <http://example.org/types#hasSensorValue> \"{}\"^^",
+            value
+        )));
+    }
+
+    //No static solutions
+    let mut out = vec![];
+    let triples = engine
+        .execute_construct_query(
+            &query.replace("WIDGET_TYPE", "types:MissingWidget"),
+            QUERY_ENDPOINT,
+            RDFFormat::NTriples,
+            &mut out,
+        )
+        .await
+        .expect("Hybrid error");
+    assert_eq!(triples, 0);
+    assert!(out.is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_describe_query(#[future] with_testdata: (), mut engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX case:<http://example.org/case#>
+    DESCRIBE case:myTimeseries1
+    "#;
+    let mut out = vec![];
+    let triples = engine
+        .execute_describe_query(query, QUERY_ENDPOINT, RDFFormat::NTriples, &mut out)
+        .await
+        .expect("Hybrid error");
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(triples, out.lines().count());
+    for predicate in [
+        "hasExternalId",
+        "hasFirstTimestamp",
+        "hasLastTimestamp",
+        "hasLatestValue",
+    ] {
+        assert!(out.contains(&format!(
+            "<https://github.com/magbak/otit_swt#{}>",
+            predicate
+        )));
+    }
+    assert!(out.contains(
+        "<http://example.org/case#myTimeseries1> <https://github.com/magbak/otit_swt#hasLatestValue> \"105\"^^"
+    ));
+    assert!(out.contains(
+        "<http://example.org/case#myTimeseries1> <https://github.com/magbak/otit_swt#hasFirstTimestamp> \"2022-06-01T08:46:52\"^^"
+    ));
+    assert!(out.contains(
+        "<http://example.org/case#myTimeseries1> <https://github.com/magbak/otit_swt#hasLastTimestamp> \"2022-06-01T08:46:59\"^^"
+    ));
+
+    //Resources without triples or time series are described by nothing
+    let mut out = vec![];
+    let triples = engine
+        .execute_describe_query(
+            "DESCRIBE <http://example.org/case#myMissingResource>",
+            QUERY_ENDPOINT,
+            RDFFormat::NTriples,
+            &mut out,
+        )
+        .await
+        .expect("Hybrid error");
+    assert_eq!(triples, 0);
+    assert!(out.is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_nested_select_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    testdata_path: PathBuf,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w (MAX(?sum_v) AS ?max_sum_v) WHERE {
+        {
+        SELECT ?w ?second (SUM(?v) AS ?sum_v) WHERE {
+            ?w types:hasSensor ?s .
+            ?s otit_swt:hasTimeseries ?ts .
+            ?ts otit_swt:hasDataPoint ?dp .
+            ?dp otit_swt:hasTimestamp ?t .
+            ?dp otit_swt:hasValue ?v .
+            BIND(seconds(?t) AS ?second)
+        } GROUP BY ?w ?second
+        }
+    } GROUP BY ?w
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_nested_select_query.csv");
+
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_sliced_sub_select_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w types:hasSensor ?s .
+        {
+        SELECT ?v WHERE {
+            ?sensor otit_swt:hasTimeseries ?ts .
+            ?ts otit_swt:hasDataPoint ?dp .
+            ?dp otit_swt:hasValue ?v .
+        } ORDER BY DESC(?v) LIMIT 1
+        }
+    }
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["w"], vec![false])
+        .expect("Sort error");
+    //The limit applies to the solutions of the sub-select, not to the joined solutions
+    let expected_df = DataFrame::new(vec![
+        Series::new(
+            "w",
+            [
+                "http://example.org/case#myWidget1",
+                "http://example.org/case#myWidget2",
+            ],
+        ),
+        Series::new("v", [304i64, 304i64]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_sub_select_keeps_duplicate_solutions(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    PREFIX case:<http://example.org/case#>
+    SELECT ?w ?v WHERE {
+        VALUES ?w { case:myWidget1 case:myWidget1 }
+        ?w types:hasSensor ?s .
+        {
+        SELECT ?s ?v WHERE {
+            ?s otit_swt:hasTimeseries ?ts .
+            ?ts otit_swt:hasDataPoint ?dp .
+            ?dp otit_swt:hasValue ?v .
+            FILTER(?v > 300)
+        }
+        }
+    }
+    "#;
+    let df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["v"], vec![false])
+        .expect("Sort error");
+    //Each of the duplicate outer solutions is joined with the solutions of the sub-select
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget1"; 6]),
+        Series::new("v", [301i64, 301, 303, 303, 304, 304]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
+
+const SERVICE_QUERY: &str = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w a types:BigWidget .
+        SERVICE <http://localhost:7878/query> {
+            ?w types:hasSensor ?s .
+            ?s otit_swt:hasTimeseries ?ts .
+            ?ts otit_swt:hasDataPoint ?dp .
+            ?dp otit_swt:hasValue ?v .
+        }
+    }
+    "#;
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_service_query(#[future] with_testdata: (), engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let mut engine = engine.with_federation_endpoints([QUERY_ENDPOINT.to_string()].into());
+    let df = engine
+        .execute_hybrid_query(SERVICE_QUERY, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["v"], vec![false])
+        .expect("Sort error");
+    //The service solutions are joined with the static solutions as if they were queried together
+    let local_query = SERVICE_QUERY.replace("SERVICE <http://localhost:7878/query> {", "{");
+    let expected_df = engine
+        .execute_hybrid_query(&local_query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error")
+        .sort(&["v"], vec![false])
+        .expect("Sort error");
+    assert_eq!(df.height(), 8);
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_service_endpoint_not_allowed_query(
+    #[future] with_testdata: (),
+    mut engine: Engine,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let err = engine
+        .execute_hybrid_query(SERVICE_QUERY, QUERY_ENDPOINT)
+        .await
+        .expect_err("Service endpoint should not be allowed");
+    assert!(matches!(
+        err.downcast_ref::<OrchestrationError>(),
+        Some(OrchestrationError::ServiceEndpointNotAllowed(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_failing_service_query(#[future] with_testdata: (), engine: Engine, use_logger: ()) {
+    let _ = use_logger;
+    let _ = with_testdata.await;
+    let mut engine =
+        engine.with_federation_endpoints(["http://localhost:7879/query".to_string()].into());
+    let query = r#"
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w WHERE {
+        ?w a types:BigWidget .
+        SERVICE SILENT_KEYWORD <http://localhost:7879/query> {
+            ?w types:hasOwner ?o .
+        }
+    }
+    "#;
+    //Failing silent services do not restrict the solutions
+    let df = engine
+        .execute_hybrid_query(&query.replace("SILENT_KEYWORD", "SILENT"), QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    let expected_df = DataFrame::new(vec![Series::new(
+        "w",
+        ["http://example.org/case#myWidget1"],
+    )])
+    .unwrap();
+    assert_eq!(expected_df, df);
+    let res = engine
+        .execute_hybrid_query(&query.replace("SILENT_KEYWORD", ""), QUERY_ENDPOINT)
+        .await;
+    assert!(res.is_err());
+}
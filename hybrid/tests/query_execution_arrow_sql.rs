@@ -14,7 +14,7 @@ use hybrid::timeseries_database::arrow_flight_sql_database::ArrowFlightSQLDataba
 use hybrid::timeseries_database::timeseries_sql_rewrite::TimeSeriesTable;
 use log::debug;
 use oxrdf::vocab::xsd;
-use polars::prelude::{CsvReader, SerReader};
+use polars::prelude::{CsvReader, DataFrame, SerReader, Series};
 use polars_core::datatypes::DataType;
 use polars_core::prelude::TimeUnit;
 use reqwest::header::CONTENT_TYPE;
@@ -193,12 +193,15 @@ fn timeseries_table() -> TimeSeriesTable {
         schema: Some("my_nas".to_string()),
         time_series_table: "ts.parquet".to_string(),
         value_column: "v".to_string(),
+        quality_column: None,
         timestamp_column: "ts".to_string(),
         identifier_column: "id".to_string(),
         value_datatype: xsd::UNSIGNED_INT.into_owned(),
         year_column: None,
         month_column: None,
         day_column: None,
+        partitions: vec![],
+        wide_value_columns: None,
     }
 }
 
@@ -357,3 +360,41 @@ async fn test_simple_hybrid_query(
         .expect("DF read error");
     assert_eq!(expected_df, df);
 }
+
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_latest_value_query(
+    #[future] with_sparql_testdata: (),
+    #[future] with_timeseries_testdata: (),
+    timeseries_table: TimeSeriesTable,
+    use_logger: (),
+) {
+    let _ = use_logger;
+    let _ = with_sparql_testdata.await;
+    let _ = with_timeseries_testdata.await;
+    let db = ts_sql_db(timeseries_table).await;
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w a types:BigWidget .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasLatestValue ?v .
+    }
+    "#;
+    let mut engine = Engine::new(all_pushdowns(), Box::new(db));
+    let mut df = engine
+        .execute_hybrid_query(query, QUERY_ENDPOINT)
+        .await
+        .expect("Hybrid error");
+    df.with_column(df.column("v").unwrap().cast(&DataType::Int64).unwrap())
+        .unwrap();
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget1"]),
+        Series::new("v", [105i64]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
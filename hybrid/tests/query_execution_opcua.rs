@@ -3,23 +3,27 @@ mod opcua_data_provider;
 
 use hybrid::engine::Engine;
 use hybrid::pushdown_setting::PushdownSetting;
-use hybrid::timeseries_database::opcua_history_read::OPCUAHistoryRead;
+use hybrid::timeseries_database::opcua_history_read::{OPCUAHistoryRead, OPCUASynchronization};
+use hybrid::timeseries_database::opcua_subscription::OPCUASubscriber;
 use log::debug;
 use opcua_server::prelude::*;
 use polars::io::SerReader;
 use polars::prelude::CsvReader;
 use polars_core::frame::DataFrame;
+use polars_core::prelude::DataType as PolarsDataType;
+use polars_core::series::Series;
 use rstest::*;
 use serial_test::serial;
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::PathBuf;
 use std::thread::{sleep, JoinHandle};
+use std::time::Duration;
 use std::{thread, time};
 use tokio::runtime::Builder;
 
 use crate::common::{add_sparql_testdata, start_sparql_container, QUERY_ENDPOINT};
-use crate::opcua_data_provider::OPCUADataProvider;
+use crate::opcua_data_provider::{OPCUADataProvider, OPCUAEventProvider};
 
 #[fixture]
 fn use_logger() {
@@ -82,10 +86,17 @@ fn frames(testdata_path: PathBuf) -> HashMap<String, DataFrame> {
 
 #[fixture]
 fn opcua_server_fixture(frames: HashMap<String, DataFrame>) -> JoinHandle<()> {
-    let port = 1234;
+    start_opcua_server(OPCUADataProvider::new(frames), 1234)
+}
+
+fn start_opcua_server(data_provider: OPCUADataProvider, port: u16) -> JoinHandle<()> {
+    run_opcua_server(opcua_server(port), data_provider)
+}
+
+fn opcua_server(port: u16) -> Server {
     let path = "/";
     //From https://github.com/locka99/opcua/blob/master/docs/server.md
-    let server = ServerBuilder::new()
+    ServerBuilder::new()
         .application_name("Server Name")
         .application_uri("urn:server_uri")
         .discovery_urls(vec![format!(
@@ -117,11 +128,14 @@ fn opcua_server_fixture(frames: HashMap<String, DataFrame>) -> JoinHandle<()> {
             .collect(),
         )
         .server()
-        .unwrap();
+        .unwrap()
+}
+
+fn run_opcua_server(server: Server, data_provider: OPCUADataProvider) -> JoinHandle<()> {
     {
         let server_state = server.server_state();
         let mut server_state = server_state.write().unwrap();
-        server_state.set_historical_data_provider(Box::new(OPCUADataProvider { frames }))
+        server_state.set_historical_data_provider(Box::new(data_provider))
     }
     let handle = thread::spawn(move || server.run());
     sleep(time::Duration::from_secs(2));
@@ -133,11 +147,75 @@ fn engine() -> Engine {
     let port = 1234;
     let path = "/";
     let endpoint = format!("opc.tcp://{}:{}{}", hostname().unwrap(), port, path);
-    let opcua_tsdb = OPCUAHistoryRead::new(&endpoint, 1);
+    let opcua_tsdb = OPCUAHistoryRead::new(&endpoint, 1).unwrap();
     let engine = Engine::new([PushdownSetting::GroupBy].into(), Box::new(opcua_tsdb));
     engine
 }
 
+//The paged server runs on its own port, as servers from previous tests keep running
+fn paged_engine(max_values: usize) -> Engine {
+    let port = 1235;
+    let path = "/";
+    let endpoint = format!("opc.tcp://{}:{}{}", hostname().unwrap(), port, path);
+    let opcua_tsdb = OPCUAHistoryRead::new(&endpoint, 1)
+        .unwrap()
+        .with_max_values(max_values);
+    Engine::new([PushdownSetting::GroupBy].into(), Box::new(opcua_tsdb))
+}
+
+fn synchronized_engine(synchronization: OPCUASynchronization) -> Engine {
+    let port = 1234;
+    let path = "/";
+    let endpoint = format!("opc.tcp://{}:{}{}", hostname().unwrap(), port, path);
+    let opcua_tsdb = OPCUAHistoryRead::new(&endpoint, 1)
+        .unwrap()
+        .with_synchronization(synchronization);
+    Engine::new(
+        [PushdownSetting::GroupBy, PushdownSetting::Synchronization].into(),
+        Box::new(opcua_tsdb),
+    )
+}
+
+const BASIC_QUERY: &str = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?s ?t ?v WHERE {
+        ?w a types:BigWidget .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasTimestamp ?t .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?t >= "2022-06-01T08:46:53"^^xsd:dateTime && ?t <= "2022-06-01T08:46:58"^^xsd:dateTime) .
+    }
+    "#;
+
+fn expected_basic_query_df(testdata_path: &PathBuf) -> DataFrame {
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_basic_query.csv");
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let mut expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error");
+    expected_df
+        .with_column(
+            expected_df
+                .column("t")
+                .unwrap()
+                .cast(&polars::prelude::DataType::Datetime(
+                    polars::prelude::TimeUnit::Nanoseconds,
+                    None,
+                ))
+                .unwrap(),
+        )
+        .unwrap();
+    expected_df
+}
+
 #[rstest]
 #[serial]
 fn test_basic_query(
@@ -186,7 +264,7 @@ fn test_basic_query(
                 .column("t")
                 .unwrap()
                 .cast(&polars::prelude::DataType::Datetime(
-                    polars::prelude::TimeUnit::Milliseconds,
+                    polars::prelude::TimeUnit::Nanoseconds,
                     None,
                 ))
                 .unwrap(),
@@ -200,6 +278,103 @@ fn test_basic_query(
     //println!("{}", df);
 }
 
+#[rstest]
+#[serial]
+fn test_basic_paged_query(
+    with_testdata: (),
+    use_logger: (),
+    frames: HashMap<String, DataFrame>,
+    testdata_path: PathBuf,
+) {
+    let _ = with_testdata;
+    let _ = use_logger;
+    let _server = start_opcua_server(OPCUADataProvider::with_page_size(frames, 2), 1235);
+
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let runtime = builder.build().unwrap();
+    let mut engine = paged_engine(1000);
+    let df = runtime
+        .block_on(engine.execute_hybrid_query(BASIC_QUERY, QUERY_ENDPOINT))
+        .expect("Hybrid error");
+    assert_eq!(expected_basic_query_df(&testdata_path), df);
+
+    let mut engine = paged_engine(3);
+    let res = runtime.block_on(engine.execute_hybrid_query(BASIC_QUERY, QUERY_ENDPOINT));
+    assert!(res.is_err());
+}
+
+#[rstest]
+#[serial]
+fn test_synchronized_query(
+    with_testdata: (),
+    use_logger: (),
+    opcua_server_fixture: JoinHandle<()>,
+    testdata_path: PathBuf,
+) {
+    let _ = with_testdata;
+    let _ = use_logger;
+    let _ = opcua_server_fixture;
+
+    let query = r#"
+    PREFIX xsd:<http://www.w3.org/2001/XMLSchema#>
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w1 ?w2 ?t ?v1 ?v2 WHERE {
+        ?w1 a types:BigWidget .
+        ?w2 a types:SmallWidget .
+        ?w1 types:hasSensor ?s1 .
+        ?w2 types:hasSensor ?s2 .
+        ?s1 otit_swt:hasTimeseries ?ts1 .
+        ?s2 otit_swt:hasTimeseries ?ts2 .
+        ?ts1 otit_swt:hasDataPoint ?dp1 .
+        ?ts2 otit_swt:hasDataPoint ?dp2 .
+        ?dp1 otit_swt:hasTimestamp ?t .
+        ?dp2 otit_swt:hasTimestamp ?t .
+        ?dp1 otit_swt:hasValue ?v1 .
+        ?dp2 otit_swt:hasValue ?v2 .
+        FILTER(?t >= "2022-06-01T08:46:53"^^xsd:dateTime && ?t <= "2022-06-01T08:46:58"^^xsd:dateTime) .
+    }
+    "#;
+    let mut file_path = testdata_path.clone();
+    file_path.push("expected_synchronized_query.csv");
+    let file = File::open(file_path.as_path()).expect("Read file problem");
+    let mut expected_df = CsvReader::new(file)
+        .infer_schema(None)
+        .has_header(true)
+        .with_parse_dates(true)
+        .finish()
+        .expect("DF read error");
+    expected_df
+        .with_column(
+            expected_df
+                .column("t")
+                .unwrap()
+                .cast(&polars::prelude::DataType::Datetime(
+                    polars::prelude::TimeUnit::Nanoseconds,
+                    None,
+                ))
+                .unwrap(),
+        )
+        .unwrap();
+    expected_df = expected_df.sort(vec!["t"], false).unwrap();
+
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let runtime = builder.build().unwrap();
+    for synchronization in [
+        OPCUASynchronization::LocalJoin,
+        OPCUASynchronization::ReadAtTime,
+    ] {
+        let mut engine = synchronized_engine(synchronization);
+        let mut df = runtime
+            .block_on(engine.execute_hybrid_query(query, QUERY_ENDPOINT))
+            .expect("Hybrid error");
+        df = df.sort(vec!["t"], false).unwrap();
+        assert_eq!(expected_df, df);
+    }
+}
+
 #[rstest]
 #[serial]
 fn test_basic_no_end_time_query(
@@ -248,7 +423,7 @@ fn test_basic_no_end_time_query(
                 .column("t")
                 .unwrap()
                 .cast(&polars::prelude::DataType::Datetime(
-                    polars::prelude::TimeUnit::Milliseconds,
+                    polars::prelude::TimeUnit::Nanoseconds,
                     None,
                 ))
                 .unwrap(),
@@ -311,7 +486,7 @@ fn test_pushdown_group_by_five_second_hybrid_query(
                 .column("datetime_seconds")
                 .unwrap()
                 .cast(&polars::prelude::DataType::Datetime(
-                    polars::prelude::TimeUnit::Milliseconds,
+                    polars::prelude::TimeUnit::Nanoseconds,
                     None,
                 ))
                 .unwrap(),
@@ -384,3 +559,206 @@ fn test_no_pushdown_because_of_filter_query(
 //     writer.finish(&mut df).expect("writeok");
 //     println!("{}", df);
 //
+
+#[rstest]
+#[serial]
+fn test_subscription_query(with_testdata: (), use_logger: (), frames: HashMap<String, DataFrame>) {
+    let _ = with_testdata;
+    let _ = use_logger;
+    let port = 1236;
+    let server = opcua_server(port);
+    //Subscriptions monitor the current values of the variables, not their history
+    let address_space = server.address_space();
+    {
+        let mut address_space = address_space.write().unwrap();
+        let folder_id = address_space
+            .add_folder("Sensors", "Sensors", &NodeId::objects_folder_id())
+            .unwrap();
+        for t in ["ts1", "ts2"] {
+            VariableBuilder::new(&NodeId::new(1, t), t, t)
+                .data_type(DataTypeId::Double)
+                .value(0.0f64)
+                .organized_by(&folder_id)
+                .insert(&mut address_space);
+        }
+    }
+    let _server = run_opcua_server(server, OPCUADataProvider::new(frames));
+
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasDataPoint ?dp .
+        ?dp otit_swt:hasValue ?v .
+        FILTER(?v > 100.0) .
+    }
+    "#;
+    let endpoint = format!("opc.tcp://{}:{}/", hostname().unwrap(), port);
+    let engine = Engine::new(
+        [].into(),
+        Box::new(OPCUAHistoryRead::new(&endpoint, 1).unwrap()),
+    );
+    let subscriber = OPCUASubscriber::new(&endpoint, 1)
+        .unwrap()
+        .with_publishing_interval(100.0);
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let runtime = builder.build().unwrap();
+    let mut continuous_query = runtime
+        .block_on(engine.subscribe_hybrid_query(query, QUERY_ENDPOINT, subscriber))
+        .expect("Subscription error");
+
+    //The initial values do not pass the filter
+    let df = continuous_query
+        .next_result_timeout(Duration::from_secs(10))
+        .expect("Hybrid error")
+        .expect("No initial values");
+    assert_eq!(df.height(), 0);
+
+    {
+        let now = DateTime::now();
+        let mut address_space = address_space.write().unwrap();
+        address_space.set_variable_value(NodeId::new(1, "ts2"), 150.0f64, &now, &now);
+    }
+    let df = continuous_query
+        .next_result_timeout(Duration::from_secs(10))
+        .expect("Hybrid error")
+        .expect("No changed values");
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget2"]),
+        Series::new("v", [150.0f64]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
+
+//The latest values are read from the current values of the variables, not their history
+#[rstest]
+#[serial]
+fn test_latest_value_query(with_testdata: (), use_logger: (), frames: HashMap<String, DataFrame>) {
+    let _ = with_testdata;
+    let _ = use_logger;
+    let port = 1238;
+    let server = opcua_server(port);
+    let address_space = server.address_space();
+    {
+        let mut address_space = address_space.write().unwrap();
+        let folder_id = address_space
+            .add_folder("Sensors", "Sensors", &NodeId::objects_folder_id())
+            .unwrap();
+        for (t, v) in [("ts1", 42.0f64), ("ts2", 43.0f64)] {
+            VariableBuilder::new(&NodeId::new(1, t), t, t)
+                .data_type(DataTypeId::Double)
+                .value(v)
+                .organized_by(&folder_id)
+                .insert(&mut address_space);
+        }
+    }
+    let _server = run_opcua_server(server, OPCUADataProvider::new(frames));
+
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?v WHERE {
+        ?w a types:BigWidget .
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasLatestValue ?v .
+    }
+    "#;
+    let endpoint = format!("opc.tcp://{}:{}/", hostname().unwrap(), port);
+    let mut engine = Engine::new(
+        [].into(),
+        Box::new(OPCUAHistoryRead::new(&endpoint, 1).unwrap()),
+    );
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let runtime = builder.build().unwrap();
+    let df = runtime
+        .block_on(engine.execute_hybrid_query(query, QUERY_ENDPOINT))
+        .expect("Hybrid error");
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget1"]),
+        Series::new("v", [42.0f64]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
+
+#[rstest]
+#[serial]
+fn test_event_query(with_testdata: (), use_logger: (), frames: HashMap<String, DataFrame>) {
+    let _ = with_testdata;
+    let _ = use_logger;
+    let port = 1237;
+    let server = opcua_server(port);
+    let alarm_type = NodeId::new(1, "HighTemperatureAlarm");
+    let events = vec![
+        (
+            DateTime::ymd_hms(2022, 6, 1, 8, 46, 53),
+            alarm_type.clone(),
+            800,
+            "Temperature high".to_string(),
+            NodeId::new(1, "ts1"),
+        ),
+        (
+            DateTime::ymd_hms(2022, 6, 1, 8, 46, 58),
+            alarm_type.clone(),
+            200,
+            "Temperature normal".to_string(),
+            NodeId::new(1, "ts1"),
+        ),
+    ];
+    {
+        let server_state = server.server_state();
+        let mut server_state = server_state.write().unwrap();
+        server_state.set_historical_event_provider(Box::new(OPCUAEventProvider {
+            events: [("events1".to_string(), events)].into(),
+        }));
+    }
+    let _server = run_opcua_server(server, OPCUADataProvider::new(frames));
+
+    let query = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    SELECT ?w ?t ?type ?severity ?message ?source WHERE {
+        ?w otit_swt:hasEventSeries ?es .
+        ?es otit_swt:hasEvent ?e .
+        ?e otit_swt:hasTimestamp ?t .
+        ?e otit_swt:hasEventType ?type .
+        ?e otit_swt:hasSeverity ?severity .
+        ?e otit_swt:hasMessage ?message .
+        ?e otit_swt:hasSourceNode ?source .
+        FILTER(?severity > 500) .
+    }
+    "#;
+    let endpoint = format!("opc.tcp://{}:{}/", hostname().unwrap(), port);
+    let mut engine = Engine::new(
+        [PushdownSetting::GroupBy].into(),
+        Box::new(OPCUAHistoryRead::new(&endpoint, 1).unwrap()),
+    );
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let runtime = builder.build().unwrap();
+    let mut df = runtime
+        .block_on(engine.execute_hybrid_query(query, QUERY_ENDPOINT))
+        .expect("Hybrid error");
+    //Event times are compared as nanoseconds
+    let t = df
+        .column("t")
+        .unwrap()
+        .cast(&PolarsDataType::Int64)
+        .unwrap();
+    df.with_column(t).unwrap();
+    let expected_df = DataFrame::new(vec![
+        Series::new("w", ["http://example.org/case#myWidget1"]),
+        Series::new("t", [1654073213000000000i64]),
+        Series::new("type", ["ns=1;s=HighTemperatureAlarm"]),
+        Series::new("severity", [800u32]),
+        Series::new("message", ["Temperature high"]),
+        Series::new("source", ["ns=1;s=ts1"]),
+    ])
+    .unwrap();
+    assert_eq!(expected_df, df);
+}
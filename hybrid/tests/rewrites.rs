@@ -366,7 +366,11 @@ fn test_fix_dropped_triple() {
                 PathEntry::BGP,
             ]),
         )),
+        quality_variable: None,
         ids: None,
+        latest: false,
+        events: false,
+        event_field_variables: vec![],
     }];
     assert_eq!(time_series_queries, expected_time_series_queries);
 }
@@ -441,7 +445,11 @@ fn test_property_path_expression() {
                     PathEntry::BGP,
                 ]),
             )),
+            quality_variable: None,
             ids: None,
+            latest: false,
+            events: false,
+            event_field_variables: vec![],
         },
         BasicTimeSeriesQuery {
             identifier_variable: Some(Variable::new_unchecked("ts_external_id_1")),
@@ -479,7 +487,11 @@ fn test_property_path_expression() {
                     PathEntry::BGP,
                 ]),
             )),
+            quality_variable: None,
             ids: None,
+            latest: false,
+            events: false,
+            event_field_variables: vec![],
         },
     ];
     assert_eq!(time_series_queries, expected_time_series_queries);
@@ -607,3 +619,126 @@ fn test_filter_lost_bug() {
     let expected_query = Query::parse(expected_str, None).unwrap();
     assert_eq!(expected_query, static_rewrite);
 }
+
+#[test]
+fn test_latest_value_query() {
+    let sparql = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?s ?t ?v WHERE {
+        ?w types:hasSensor ?s .
+        ?s otit_swt:hasTimeseries ?ts .
+        ?ts otit_swt:hasLatestValue ?v .
+        ?ts otit_swt:hasLatestTimestamp ?t .
+    }"#;
+    let parsed = parse_sparql_select_query(sparql).unwrap();
+    let mut preprocessor = Preprocessor::new();
+    let (preprocessed_query, has_constraint) = preprocessor.preprocess(&parsed);
+    let mut rewriter = StaticQueryRewriter::new(&has_constraint);
+    let (static_rewrite, time_series_queries) = rewriter.rewrite_query(preprocessed_query).unwrap();
+    let expected_str = r#"
+    PREFIX otit_swt:<https://github.com/magbak/otit_swt#>
+    PREFIX types:<http://example.org/types#>
+    SELECT ?w ?s ?ts_datatype_0 ?ts_external_id_0 WHERE {
+        ?w types:hasSensor ?s .
+        ?ts otit_swt:hasExternalId ?ts_external_id_0 .
+        ?ts otit_swt:hasDatatype ?ts_datatype_0 .
+        ?s otit_swt:hasTimeseries ?ts .
+    }"#;
+    let expected_query = Query::parse(expected_str, None).unwrap();
+    assert_eq!(static_rewrite, expected_query);
+
+    let context = Context::from_path(vec![PathEntry::ProjectInner, PathEntry::BGP]);
+    let expected_time_series_queries = vec![BasicTimeSeriesQuery {
+        identifier_variable: Some(Variable::new_unchecked("ts_external_id_0")),
+        timeseries_variable: Some(VariableInContext::new(
+            Variable::new_unchecked("ts"),
+            context.clone(),
+        )),
+        data_point_variable: Some(VariableInContext::new(
+            Variable::new_unchecked("latest_data_point_0"),
+            context.clone(),
+        )),
+        value_variable: Some(VariableInContext::new(
+            Variable::new_unchecked("v"),
+            context.clone(),
+        )),
+        datatype_variable: Some(Variable::new_unchecked("ts_datatype_0")),
+        datatype: None,
+        timestamp_variable: Some(VariableInContext::new(
+            Variable::new_unchecked("t"),
+            context.clone(),
+        )),
+        quality_variable: None,
+        ids: None,
+        latest: true,
+        events: false,
+        event_field_variables: vec![],
+    }];
+    assert_eq!(time_series_queries, expected_time_series_queries);
+}
+
+#[test]
+fn test_service_query() {
+    let sparql = r#"
+    PREFIX qry:<https://github.com/magbak/otit_swt#>
+    SELECT ?var1 ?var2 WHERE {
+        ?var1 a ?var2 .
+        SERVICE <http://maintenance.example.org/sparql> {
+            ?var2 qry:hasTimeseries ?ts .
+            ?ts qry:hasDataPoint ?dp .
+            ?dp qry:hasValue ?val .
+        }
+    }
+    "#;
+    let parsed = parse_sparql_select_query(sparql).unwrap();
+    let mut preprocessor = Preprocessor::new();
+    let (preprocessed_query, has_constraint) = preprocessor.preprocess(&parsed);
+    let mut rewriter = StaticQueryRewriter::new(&has_constraint);
+    let (static_rewrite, _) = rewriter.rewrite_query(preprocessed_query).unwrap();
+
+    let expected_str = r#"
+    SELECT ?var1 ?var2 ?ts_datatype_0 ?ts_external_id_0 WHERE {
+     ?var1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> ?var2 .
+     SERVICE <http://maintenance.example.org/sparql> {
+      ?ts <https://github.com/magbak/otit_swt#hasExternalId> ?ts_external_id_0 .
+      ?ts <https://github.com/magbak/otit_swt#hasDatatype> ?ts_datatype_0 .
+      ?var2 <https://github.com/magbak/otit_swt#hasTimeseries> ?ts .
+      }
+     }"#;
+    let expected_query = Query::parse(expected_str, None).unwrap();
+    assert_eq!(static_rewrite, expected_query);
+}
+
+#[test]
+fn test_named_graph_query() {
+    let sparql = r#"
+    BASE <http://example.org/>
+    PREFIX qry:<https://github.com/magbak/otit_swt#>
+    SELECT ?g ?var1 FROM NAMED <site1> WHERE {
+        GRAPH ?g {
+            ?var1 qry:hasTimeseries ?ts .
+            ?ts qry:hasDataPoint ?dp .
+            ?dp qry:hasValue ?val .
+        }
+    }
+    "#;
+    let parsed = parse_sparql_select_query(sparql).unwrap();
+    let mut preprocessor = Preprocessor::new();
+    let (preprocessed_query, has_constraint) = preprocessor.preprocess(&parsed);
+    let mut rewriter = StaticQueryRewriter::new(&has_constraint);
+    let (static_rewrite, time_series_queries) = rewriter.rewrite_query(preprocessed_query).unwrap();
+    assert_eq!(time_series_queries.len(), 1);
+
+    let expected_str = r#"
+    BASE <http://example.org/>
+    SELECT ?g ?var1 ?ts_datatype_0 ?ts_external_id_0 FROM NAMED <http://example.org/site1> WHERE {
+     GRAPH ?g {
+      ?ts <https://github.com/magbak/otit_swt#hasExternalId> ?ts_external_id_0 .
+      ?ts <https://github.com/magbak/otit_swt#hasDatatype> ?ts_datatype_0 .
+      ?var1 <https://github.com/magbak/otit_swt#hasTimeseries> ?ts .
+      }
+     }"#;
+    let expected_query = Query::parse(expected_str, None).unwrap();
+    assert_eq!(static_rewrite, expected_query);
+}
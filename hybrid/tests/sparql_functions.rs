@@ -0,0 +1,258 @@
+//Cases derived from the W3C SPARQL 1.1 function tests (data-sparql11/functions),
+//evaluated by the combiner directly on a static result without time series.
+use hybrid::combiner::{Combiner, CombinerError};
+use hybrid::function_registry::FunctionRegistry;
+use polars::prelude::{DataFrame, DataType, NamedFrom, Series, TimeUnit};
+use rstest::*;
+use spargebra::Query;
+
+#[fixture]
+fn strings_df() -> DataFrame {
+    let x = Series::new(
+        "x",
+        &[
+            "http://example.org/s1",
+            "http://example.org/s2",
+            "http://example.org/s3",
+            "http://example.org/s4",
+            "http://example.org/s5",
+            "http://example.org/s6",
+        ],
+    );
+    let s = Series::new(
+        "s",
+        &[
+            Some("foo"),
+            Some("bar"),
+            Some("BAZ"),
+            Some("食べ物"),
+            Some("100%"),
+            None,
+        ],
+    );
+    DataFrame::new(vec![x, s]).unwrap()
+}
+
+#[fixture]
+fn numbers_df() -> DataFrame {
+    let x = Series::new(
+        "x",
+        &[
+            "http://example.org/n1",
+            "http://example.org/n2",
+            "http://example.org/n3",
+            "http://example.org/n4",
+            "http://example.org/n5",
+        ],
+    );
+    let n = Series::new("n", &[Some(-1i64), Some(2), Some(-3), Some(0), None]);
+    let d = Series::new(
+        "d",
+        &[Some(-1.6f64), Some(2.5), Some(-2.5), Some(1.4), None],
+    );
+    DataFrame::new(vec![x, n, d]).unwrap()
+}
+
+fn evaluate_bind(expression: &str, df: DataFrame) -> Series {
+    let query_str = format!(
+        "SELECT ?x ?r WHERE {{ ?x <http://example.org/p> ?s . BIND({} AS ?r) }}",
+        expression
+    );
+    let query = Query::parse(&query_str, None).unwrap();
+    let function_registry = FunctionRegistry::new();
+    let mut combiner = Combiner::new(&function_registry);
+    let mut df = combiner
+        .combine_static_and_time_series_results(&query, df, &mut vec![])
+        .unwrap()
+        .collect()
+        .unwrap();
+    let mut r = df.drop_in_place("r").unwrap();
+    r.rename("r");
+    r
+}
+
+fn assert_series_equal(actual: Series, expected: Series) {
+    assert!(
+        actual.series_equal_missing(&expected),
+        "Expected {} but got {}",
+        expected,
+        actual
+    );
+}
+
+#[rstest]
+#[case("STRLEN(?s)", Series::new("r", &[Some(3i64), Some(3), Some(3), Some(3), Some(4), None]))]
+#[case("UCASE(?s)", Series::new("r", &[Some("FOO"), Some("BAR"), Some("BAZ"), Some("食べ物"), Some("100%"), None]))]
+#[case("LCASE(?s)", Series::new("r", &[Some("foo"), Some("bar"), Some("baz"), Some("食べ物"), Some("100%"), None]))]
+#[case("SUBSTR(?s, 1, 1)", Series::new("r", &[Some("f"), Some("b"), Some("B"), Some("食"), Some("1"), None]))]
+#[case("SUBSTR(?s, 2)", Series::new("r", &[Some("oo"), Some("ar"), Some("AZ"), Some("べ物"), Some("00%"), None]))]
+#[case("CONTAINS(?s, \"a\")", Series::new("r", &[Some(false), Some(true), Some(false), Some(false), Some(false), None]))]
+#[case("STRSTARTS(?s, \"b\")", Series::new("r", &[Some(false), Some(true), Some(false), Some(false), Some(false), None]))]
+#[case("STRENDS(?s, \"z\")", Series::new("r", &[Some(false), Some(false), Some(false), Some(false), Some(false), None]))]
+#[case("STRBEFORE(?s, \"o\")", Series::new("r", &[Some("f"), Some(""), Some(""), Some(""), Some(""), None]))]
+#[case("STRAFTER(?s, \"b\")", Series::new("r", &[Some(""), Some("ar"), Some(""), Some(""), Some(""), None]))]
+#[case("ENCODE_FOR_URI(?s)", Series::new("r", &[Some("foo"), Some("bar"), Some("BAZ"), Some("%E9%A3%9F%E3%81%B9%E7%89%A9"), Some("100%25"), None]))]
+#[case("CONCAT(?s, \"-\", ?s)", Series::new("r", &[Some("foo-foo"), Some("bar-bar"), Some("BAZ-BAZ"), Some("食べ物-食べ物"), Some("100%-100%"), None]))]
+#[case("REGEX(?s, \"^b\", \"i\")", Series::new("r", &[Some(false), Some(true), Some(true), Some(false), Some(false), None]))]
+#[case("REGEX(?s, \"(\")", Series::new("r", &[None::<bool>, None, None, None, None, None]))]
+#[case("REPLACE(?s, \"[^a-z0-9]\", \"-\")", Series::new("r", &[Some("foo"), Some("bar"), Some("---"), Some("---"), Some("100-"), None]))]
+#[case("REPLACE(?s, \"O\", \"0\", \"i\")", Series::new("r", &[Some("f00"), Some("bar"), Some("BAZ"), Some("食べ物"), Some("100%"), None]))]
+#[case("MD5(?s)", Series::new("r", &[Some("acbd18db4cc2f85cedef654fccc4a4d8"), Some("37b51d194a7513e45b56f6524f2d51f2"), Some("f5aedf92178e1396cd4181962c8a9979"), Some("e7ada485d13b1decf628c9211bc3a97b"), Some("30bd7ce7de206924302499f197c7a966"), None]))]
+#[case("SHA1(?s)", Series::new("r", &[Some("0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33"), Some("62cdb7020ff920e5aa642c3d4066950dd1f01f4d"), Some("8324feb44eda347289ca80c2cbf964a214ccd719"), Some("caf1f189d7064f8987e972525091c29f7e462c74"), Some("fae31ecec0fc6f77b09e2dad840d052ca7f87f0d"), None]))]
+#[case("BOUND(?s)", Series::new("r", &[true, true, true, true, true, false]))]
+#[case("isIRI(?x)", Series::new("r", &[true, true, true, true, true, true]))]
+#[case("isIRI(?s)", Series::new("r", &[Some(false), Some(false), Some(false), Some(false), Some(false), None]))]
+#[case("isLITERAL(?s)", Series::new("r", &[Some(true), Some(true), Some(true), Some(true), Some(true), None]))]
+#[case("isNUMERIC(?s)", Series::new("r", &[Some(false), Some(false), Some(false), Some(false), Some(false), None]))]
+#[case("sameTerm(?s, \"foo\")", Series::new("r", &[Some(true), Some(false), Some(false), Some(false), Some(false), None]))]
+#[case("STR(?x)", Series::new("r", &["http://example.org/s1", "http://example.org/s2", "http://example.org/s3", "http://example.org/s4", "http://example.org/s5", "http://example.org/s6"]))]
+#[case("DATATYPE(?s)", Series::new("r", &[Some("http://www.w3.org/2001/XMLSchema#string"), Some("http://www.w3.org/2001/XMLSchema#string"), Some("http://www.w3.org/2001/XMLSchema#string"), Some("http://www.w3.org/2001/XMLSchema#string"), Some("http://www.w3.org/2001/XMLSchema#string"), None]))]
+#[case("LANG(?s)", Series::new("r", &[Some(""), Some(""), Some(""), Some(""), Some(""), None]))]
+fn test_string_functions(
+    strings_df: DataFrame,
+    #[case] expression: &str,
+    #[case] expected: Series,
+) {
+    assert_series_equal(evaluate_bind(expression, strings_df), expected);
+}
+
+#[rstest]
+#[case("ABS(?n)", Series::new("r", &[Some(1i64), Some(2), Some(3), Some(0), None]))]
+#[case("ABS(?d)", Series::new("r", &[Some(1.6f64), Some(2.5), Some(2.5), Some(1.4), None]))]
+#[case("ROUND(?d)", Series::new("r", &[Some(-2.0f64), Some(3.0), Some(-2.0), Some(1.0), None]))]
+#[case("CEIL(?d)", Series::new("r", &[Some(-1.0f64), Some(3.0), Some(-2.0), Some(2.0), None]))]
+#[case("FLOOR(?d)", Series::new("r", &[Some(-2.0f64), Some(2.0), Some(-3.0), Some(1.0), None]))]
+#[case("ROUND(?n)", Series::new("r", &[Some(-1i64), Some(2), Some(-3), Some(0), None]))]
+#[case("isNUMERIC(?n)", Series::new("r", &[Some(true), Some(true), Some(true), Some(true), None]))]
+#[case("STRLEN(?n)", Series::new("r", &[None::<i64>, None, None, None, None]))]
+#[case("UCASE(?d)", Series::new("r", &[None::<&str>, None, None, None, None]))]
+#[case("STR(?n)", Series::new("r", &[Some("-1"), Some("2"), Some("-3"), Some("0"), None]))]
+#[case("DATATYPE(?n)", Series::new("r", &[Some("http://www.w3.org/2001/XMLSchema#integer"), Some("http://www.w3.org/2001/XMLSchema#integer"), Some("http://www.w3.org/2001/XMLSchema#integer"), Some("http://www.w3.org/2001/XMLSchema#integer"), None]))]
+#[case("sameTerm(?n, 2)", Series::new("r", &[Some(false), Some(true), Some(false), Some(false), None]))]
+#[case("sameTerm(?n, \"2\")", Series::new("r", &[Some(false), Some(false), Some(false), Some(false), None]))]
+#[case("STRDT(STR(?n), <http://www.w3.org/2001/XMLSchema#integer>)", Series::new("r", &[Some(-1i64), Some(2), Some(-3), Some(0), None]))]
+fn test_numeric_functions(
+    numbers_df: DataFrame,
+    #[case] expression: &str,
+    #[case] expected: Series,
+) {
+    assert_series_equal(evaluate_bind(expression, numbers_df), expected);
+}
+
+#[rstest]
+fn test_generated_values(strings_df: DataFrame) {
+    let uuids = evaluate_bind("STRUUID()", strings_df.clone());
+    let uuids = uuids.utf8().unwrap();
+    assert_eq!(uuids.len(), 6);
+    assert_eq!(uuids.null_count(), 0);
+    assert!(uuids.into_iter().all(|u| u.unwrap().len() == 36));
+
+    let rand = evaluate_bind("RAND()", strings_df.clone());
+    assert!(rand
+        .f64()
+        .unwrap()
+        .into_iter()
+        .all(|r| (0.0..1.0).contains(&r.unwrap())));
+
+    let bnodes = evaluate_bind("BNODE(?s)", strings_df);
+    assert!(bnodes
+        .utf8()
+        .unwrap()
+        .into_iter()
+        .take(5)
+        .all(|b| b.unwrap().starts_with("_:")));
+}
+
+#[rstest]
+fn test_combination_errors(numbers_df: DataFrame) {
+    let function_registry = FunctionRegistry::new();
+    let mut combiner = Combiner::new(&function_registry);
+    let wrong_arity = Query::parse(
+        "SELECT ?x ?r WHERE { ?x <http://example.org/p> ?s . BIND(<https://github.com/magbak/otit_swt#DateTimeAsSeconds>(?n, ?n) AS ?r) }",
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        combiner.combine_static_and_time_series_results(
+            &wrong_arity,
+            numbers_df.clone(),
+            &mut vec![]
+        ),
+        Err(CombinerError::WrongNumberOfArguments(_, 2))
+    ));
+
+    let unregistered = Query::parse(
+        "SELECT ?x ?r WHERE { ?x <http://example.org/p> ?s . BIND(<http://example.org/f>(?n) AS ?r) }",
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        combiner.combine_static_and_time_series_results(
+            &unregistered,
+            numbers_df.clone(),
+            &mut vec![]
+        ),
+        Err(CombinerError::FunctionNotRegistered(_))
+    ));
+
+    let without_timestamp = Query::parse(
+        "SELECT ?x (<https://github.com/magbak/otit_swt#first>(?n) AS ?r) WHERE { ?x <http://example.org/p> ?s } GROUP BY ?x",
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        combiner.combine_static_and_time_series_results(
+            &without_timestamp,
+            numbers_df,
+            &mut vec![]
+        ),
+        Err(CombinerError::TimestampNotInScope(_))
+    ));
+}
+
+#[fixture]
+fn gaps_df() -> DataFrame {
+    //Rows are out of time order, with a gap in each series
+    let x = Series::new(
+        "x",
+        &[
+            "http://example.org/p1",
+            "http://example.org/p2",
+            "http://example.org/p3",
+            "http://example.org/p4",
+            "http://example.org/p5",
+        ],
+    );
+    let g = Series::new("g", &["ts1", "ts1", "ts1", "ts2", "ts2"]);
+    let t = Series::new("t", &[3000i64, 1000, 2000, 2000, 1000])
+        .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+        .unwrap();
+    let v = Series::new("v", &[Some(3.0f64), Some(1.0), None, None, Some(10.0)]);
+    DataFrame::new(vec![x, g, t, v]).unwrap()
+}
+
+#[rstest]
+#[case("fillForward", Series::new("r", &[Some(3.0f64), Some(1.0), Some(1.0), Some(10.0), Some(10.0)]))]
+#[case("interpolateLinear", Series::new("r", &[Some(3.0f64), Some(1.0), Some(2.0), None, Some(10.0)]))]
+fn test_fill_functions(gaps_df: DataFrame, #[case] function: &str, #[case] expected: Series) {
+    let query = Query::parse(
+        &format!(
+            "SELECT ?x ?r WHERE {{ ?x <http://example.org/p> ?v . BIND(<https://github.com/magbak/otit_swt#{}>(?v, ?t, ?g) AS ?r) }}",
+            function
+        ),
+        None,
+    )
+    .unwrap();
+    let function_registry = FunctionRegistry::new();
+    let mut combiner = Combiner::new(&function_registry);
+    let mut df = combiner
+        .combine_static_and_time_series_results(&query, gaps_df, &mut vec![])
+        .unwrap()
+        .collect()
+        .unwrap()
+        .sort(&["x"], vec![false])
+        .unwrap();
+    let mut r = df.drop_in_place("r").unwrap();
+    r.rename("r");
+    assert_series_equal(r, expected);
+}
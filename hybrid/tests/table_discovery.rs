@@ -0,0 +1,90 @@
+use hybrid::timeseries_database::table_discovery::{
+    catalog_columns_from_df, information_schema_columns_query, CatalogColumn, TableDiscovery,
+};
+use hybrid::timeseries_database::timeseries_sql_rewrite::SQLDialect;
+use oxrdf::vocab::xsd;
+use polars::prelude::{DataFrame, NamedFrom, Series};
+use regex::Regex;
+
+fn sql_column(schema: &str, table: &str, column: &str, sql_type: &str) -> CatalogColumn {
+    CatalogColumn::from_sql_type(
+        Some(schema.to_string()),
+        table.to_string(),
+        column.to_string(),
+        sql_type,
+    )
+}
+
+#[test]
+fn test_propose_tables_from_sql_catalog() {
+    let columns = vec![
+        sql_column("lake", "double_values", "id", "CHARACTER VARYING"),
+        sql_column("lake", "double_values", "timestamp", "TIMESTAMP"),
+        sql_column("lake", "double_values", "value", "DOUBLE"),
+        sql_column("lake", "double_values", "quality", "INTEGER"),
+        sql_column("lake", "double_values", "year", "INTEGER"),
+        sql_column("lake", "double_values", "month", "INTEGER"),
+        sql_column("lake", "double_values", "day", "INTEGER"),
+        sql_column("lake", "string_values", "ID", "VARCHAR(255)"),
+        sql_column("lake", "string_values", "TS", "TIMESTAMP"),
+        sql_column("lake", "string_values", "V", "VARCHAR"),
+        //Skipped, as the values have the same datatype as those of double_values
+        sql_column("lake", "more_doubles", "id", "VARCHAR"),
+        sql_column("lake", "more_doubles", "ts", "TIMESTAMP"),
+        sql_column("lake", "more_doubles", "v", "DOUBLE"),
+        //No timestamp column
+        sql_column("lake", "assets", "id", "VARCHAR"),
+        sql_column("lake", "assets", "value", "VARCHAR"),
+        sql_column("other", "values", "id", "VARCHAR"),
+        sql_column("other", "values", "ts", "TIMESTAMP"),
+        sql_column("other", "values", "v", "BIGINT"),
+    ];
+    let tables = TableDiscovery::new()
+        .with_schema_pattern(Regex::new("^lake$").unwrap())
+        .propose_tables(&columns);
+    assert_eq!(tables.len(), 2);
+
+    let double_table = &tables[0];
+    assert_eq!(double_table.time_series_table, "double_values");
+    assert_eq!(double_table.schema, Some("lake".to_string()));
+    assert_eq!(double_table.identifier_column, "id");
+    assert_eq!(double_table.timestamp_column, "timestamp");
+    assert_eq!(double_table.value_column, "value");
+    assert_eq!(double_table.quality_column, Some("quality".to_string()));
+    assert_eq!(double_table.value_datatype, xsd::DOUBLE.into_owned());
+    assert_eq!(double_table.year_column, Some("year".to_string()));
+    assert_eq!(double_table.day_column, Some("day".to_string()));
+
+    let string_table = &tables[1];
+    assert_eq!(string_table.time_series_table, "string_values");
+    assert_eq!(string_table.identifier_column, "ID");
+    assert_eq!(string_table.value_datatype, xsd::STRING.into_owned());
+    assert_eq!(string_table.quality_column, None);
+    assert_eq!(string_table.year_column, None);
+}
+
+#[test]
+fn test_catalog_columns_from_information_schema() {
+    let df = DataFrame::new(vec![
+        Series::new("TABLE_SCHEMA", ["my_nas", "my_nas", "my_nas"]),
+        Series::new("TABLE_NAME", ["ts.parquet", "ts.parquet", "ts.parquet"]),
+        Series::new("COLUMN_NAME", ["id", "ts", "v"]),
+        Series::new(
+            "DATA_TYPE",
+            ["CHARACTER VARYING", "TIMESTAMP", "DECIMAL(10,2)"],
+        ),
+    ])
+    .unwrap();
+    let tables = TableDiscovery::new().propose_tables(&catalog_columns_from_df(&df));
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].time_series_table, "ts.parquet");
+    assert_eq!(tables[0].value_datatype, xsd::DECIMAL.into_owned());
+}
+
+#[test]
+fn test_information_schema_query_per_dialect() {
+    assert!(information_schema_columns_query(&SQLDialect::Dremio)
+        .ends_with(r#"FROM INFORMATION_SCHEMA."COLUMNS""#));
+    assert!(information_schema_columns_query(&SQLDialect::Postgres)
+        .ends_with("FROM information_schema.columns"));
+}
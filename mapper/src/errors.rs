@@ -1,4 +1,5 @@
 use crate::mapping::errors::MappingError;
+use crate::opcua_import::OPCUAImportError;
 use crate::parsing::errors::ParsingError;
 use crate::resolver::ResolutionError;
 use crate::templates::TypingError;
@@ -16,4 +17,6 @@ pub enum MapperError {
     TypingError(#[from] TypingError),
     #[error(transparent)]
     MappingError(#[from] MappingError),
+    #[error(transparent)]
+    OPCUAImportError(#[from] OPCUAImportError),
 }
@@ -19,4 +19,5 @@ mod resolver;
 pub mod document;
 pub mod errors;
 pub mod mapping;
+pub mod opcua_import;
 pub mod templates;
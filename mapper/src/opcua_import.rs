@@ -0,0 +1,401 @@
+pub mod browse;
+pub mod nodeset;
+
+use crate::mapping::errors::MappingError;
+use crate::mapping::Mapping;
+use opcua_client::prelude::StatusCode;
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNodeRef;
+use polars::prelude::{DataFrame, Series};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+pub const OPCUA_PREFIX_IRI: &str = "https://github.com/magbak/otit_swt/opcua#";
+pub const OPCUA_NODE_TEMPLATE: &str = "https://github.com/magbak/otit_swt/opcua#NodeTemplate";
+pub const OPCUA_TYPE_DEFINITION_TEMPLATE: &str =
+    "https://github.com/magbak/otit_swt/opcua#TypeDefinitionTemplate";
+pub const OPCUA_SUBTYPE_TEMPLATE: &str = "https://github.com/magbak/otit_swt/opcua#SubtypeTemplate";
+pub const OPCUA_REFERENCE_TEMPLATE: &str =
+    "https://github.com/magbak/otit_swt/opcua#ReferenceTemplate";
+pub const OPCUA_TIMESERIES_TEMPLATE: &str =
+    "https://github.com/magbak/otit_swt/opcua#TimeseriesTemplate";
+
+//The default modelling of the address space. Custom templates must have the same signatures.
+pub const OPCUA_TEMPLATES: &str = r#"
+@prefix opcua:<https://github.com/magbak/otit_swt/opcua#>.
+@prefix otit_swt:<https://github.com/magbak/otit_swt#>.
+
+opcua:NodeTemplate [xsd:anyURI ?node, xsd:anyURI ?nodeClass, xsd:string ?browseName, xsd:string ?displayName, xsd:string ?nodeId]
+  :: {
+    ottr:Triple(?node, rdf:type, ?nodeClass) ,
+    ottr:Triple(?node, opcua:browseName, ?browseName) ,
+    ottr:Triple(?node, rdfs:label, ?displayName) ,
+    ottr:Triple(?node, opcua:nodeId, ?nodeId)
+  } .
+
+opcua:TypeDefinitionTemplate [xsd:anyURI ?node, xsd:anyURI ?typeDefinition]
+  :: {
+    ottr:Triple(?node, rdf:type, ?typeDefinition)
+  } .
+
+opcua:SubtypeTemplate [xsd:anyURI ?type, xsd:anyURI ?superType]
+  :: {
+    ottr:Triple(?type, rdfs:subClassOf, ?superType)
+  } .
+
+opcua:ReferenceTemplate [xsd:anyURI ?source, xsd:anyURI ?referenceType, xsd:anyURI ?target]
+  :: {
+    ottr:Triple(?source, ?referenceType, ?target)
+  } .
+
+opcua:TimeseriesTemplate [xsd:anyURI ?node, xsd:anyURI ?timeseries, xsd:string ?externalId, xsd:anyURI ?datatype]
+  :: {
+    ottr:Triple(?node, otit_swt:hasTimeseries, ?timeseries) ,
+    ottr:Triple(?timeseries, otit_swt:hasExternalId, ?externalId) ,
+    ottr:Triple(?timeseries, otit_swt:hasDatatype, ?datatype)
+  } .
+"#;
+
+const STANDARD_NAMESPACE_URI: &str = "http://opcfoundation.org/UA/";
+const HAS_TYPE_DEFINITION: &str = "i=40";
+const HAS_SUBTYPE: &str = "i=45";
+const BASE_DATA_TYPE: &str = "i=24";
+
+//Reference types of namespace 0 are named after their browse names
+const STANDARD_REFERENCE_TYPES: [(&str, &str); 15] = [
+    ("i=31", "References"),
+    ("i=33", "HierarchicalReferences"),
+    ("i=35", "Organizes"),
+    ("i=36", "HasEventSource"),
+    ("i=37", "HasModellingRule"),
+    ("i=38", "HasEncoding"),
+    ("i=39", "HasDescription"),
+    ("i=40", "HasTypeDefinition"),
+    ("i=41", "GeneratesEvent"),
+    ("i=44", "Aggregates"),
+    ("i=45", "HasSubtype"),
+    ("i=46", "HasProperty"),
+    ("i=47", "HasComponent"),
+    ("i=48", "HasNotifier"),
+    ("i=49", "HasOrderedComponent"),
+];
+
+//Built in data types with a corresponding xsd datatype
+const STANDARD_DATA_TYPES: [(&str, &str, NamedNodeRef); 13] = [
+    ("i=1", "Boolean", xsd::BOOLEAN),
+    ("i=2", "SByte", xsd::BYTE),
+    ("i=3", "Byte", xsd::UNSIGNED_BYTE),
+    ("i=4", "Int16", xsd::SHORT),
+    ("i=5", "UInt16", xsd::UNSIGNED_SHORT),
+    ("i=6", "Int32", xsd::INT),
+    ("i=7", "UInt32", xsd::UNSIGNED_INT),
+    ("i=8", "Int64", xsd::LONG),
+    ("i=9", "UInt64", xsd::UNSIGNED_LONG),
+    ("i=10", "Float", xsd::FLOAT),
+    ("i=11", "Double", xsd::DOUBLE),
+    ("i=12", "String", xsd::STRING),
+    ("i=13", "DateTime", xsd::DATE_TIME),
+];
+
+#[derive(Error, Debug)]
+pub enum OPCUAImportError {
+    #[error("Browse failed with status {0}")]
+    BrowseError(StatusCode),
+    #[error("Read of node attributes failed with status {0}")]
+    ReadError(StatusCode),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    XMLError(#[from] roxmltree::Error),
+    #[error("Invalid NodeSet: {0}")]
+    InvalidNodeSet(String),
+    #[error("Invalid NodeId {0}")]
+    InvalidNodeId(String),
+    #[error("Namespace {0} is not in the namespace array")]
+    MissingNamespace(String),
+}
+
+//Node ids are written as in the OPC UA specification, e.g. ns=1;s=MyVariable or i=85 in namespace 0
+#[derive(Clone, Debug, PartialEq)]
+pub struct OPCUANode {
+    pub node_id: String,
+    //The name of the node class, e.g. Object or Variable
+    pub node_class: String,
+    pub browse_name: String,
+    pub display_name: String,
+    pub type_definition: Option<String>,
+    pub data_type: Option<String>,
+    pub historizing: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OPCUAReference {
+    pub source: String,
+    pub reference_type: String,
+    pub target: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OPCUAAddressSpace {
+    pub nodes: Vec<OPCUANode>,
+    pub references: Vec<OPCUAReference>,
+    //The namespace indices of the node ids index this array, the first uri is that of the standard
+    pub namespace_uris: Vec<String>,
+}
+
+pub struct OPCUAImportOptions {
+    //Nodes outside of namespace 0 get IRIs in this namespace
+    pub base_iri: String,
+    pub node_template: String,
+    pub type_definition_template: String,
+    pub subtype_template: String,
+    pub reference_template: String,
+    pub timeseries_template: String,
+}
+
+impl OPCUAImportOptions {
+    pub fn new(base_iri: &str) -> OPCUAImportOptions {
+        OPCUAImportOptions {
+            base_iri: base_iri.to_string(),
+            node_template: OPCUA_NODE_TEMPLATE.to_string(),
+            type_definition_template: OPCUA_TYPE_DEFINITION_TEMPLATE.to_string(),
+            subtype_template: OPCUA_SUBTYPE_TEMPLATE.to_string(),
+            reference_template: OPCUA_REFERENCE_TEMPLATE.to_string(),
+            timeseries_template: OPCUA_TIMESERIES_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl OPCUAAddressSpace {
+    //Changes the namespace indices to index the given array, e.g. the namespace array of the server
+    //that the time series are read from
+    pub fn remap_namespaces(&mut self, namespace_uris: &[String]) -> Result<(), OPCUAImportError> {
+        let mut index_map = HashMap::new();
+        for (i, uri) in self.namespace_uris.iter().enumerate() {
+            if let Some(j) = namespace_uris.iter().position(|u| u == uri) {
+                index_map.insert(i as u16, j as u16);
+            }
+        }
+        let remap = |node_id: &mut String| -> Result<(), OPCUAImportError> {
+            *node_id = remap_node_id(node_id, &index_map, &self.namespace_uris)?;
+            Ok(())
+        };
+        for n in &mut self.nodes {
+            remap(&mut n.node_id)?;
+            if let Some(type_definition) = &mut n.type_definition {
+                remap(type_definition)?;
+            }
+            if let Some(data_type) = &mut n.data_type {
+                remap(data_type)?;
+            }
+        }
+        for r in &mut self.references {
+            remap(&mut r.source)?;
+            remap(&mut r.reference_type)?;
+            remap(&mut r.target)?;
+        }
+        self.namespace_uris = namespace_uris.to_vec();
+        Ok(())
+    }
+}
+
+fn remap_node_id(
+    node_id: &str,
+    index_map: &HashMap<u16, u16>,
+    namespace_uris: &[String],
+) -> Result<String, OPCUAImportError> {
+    if is_standard_node_id(node_id) {
+        return Ok(node_id.to_string());
+    }
+    let (namespace, identifier) = node_id
+        .trim_start_matches("ns=")
+        .split_once(';')
+        .ok_or_else(|| OPCUAImportError::InvalidNodeId(node_id.to_string()))?;
+    let index: u16 = namespace
+        .parse()
+        .map_err(|_| OPCUAImportError::InvalidNodeId(node_id.to_string()))?;
+    if let Some(new_index) = index_map.get(&index) {
+        Ok(format!("ns={};{}", new_index, identifier))
+    } else {
+        Err(OPCUAImportError::MissingNamespace(
+            namespace_uris
+                .get(index as usize)
+                .cloned()
+                .unwrap_or_else(|| index.to_string()),
+        ))
+    }
+}
+
+//A mapping with the default templates, custom templates can be added with Mapping::from_strs
+pub fn opcua_mapping() -> Mapping {
+    Mapping::from_str(OPCUA_TEMPLATES).unwrap()
+}
+
+impl OPCUAAddressSpace {
+    pub fn expand_templates(
+        &self,
+        mapping: &mut Mapping,
+        options: &OPCUAImportOptions,
+    ) -> Result<(), MappingError> {
+        let base_iri = options.base_iri.as_str();
+        let mut node_rows =
+            Rows::new(&["node", "nodeClass", "browseName", "displayName", "nodeId"]);
+        let mut type_definition_rows = Rows::new(&["node", "typeDefinition"]);
+        let mut timeseries_rows = Rows::new(&["node", "timeseries", "externalId", "datatype"]);
+        for n in &self.nodes {
+            let node = node_iri(&n.node_id, base_iri);
+            node_rows.push(
+                &n.node_id,
+                vec![
+                    node.clone(),
+                    OPCUA_PREFIX_IRI.to_string() + &n.node_class,
+                    n.browse_name.clone(),
+                    n.display_name.clone(),
+                    n.node_id.clone(),
+                ],
+            );
+            if let Some(type_definition) = &n.type_definition {
+                type_definition_rows.push(
+                    &n.node_id,
+                    vec![node.clone(), node_iri(type_definition, base_iri)],
+                );
+            }
+            //The node id is the external id used by the OPC UA history read backend
+            if n.historizing {
+                let data_type = n.data_type.as_deref().unwrap_or(BASE_DATA_TYPE);
+                timeseries_rows.push(
+                    &n.node_id,
+                    vec![
+                        node.clone(),
+                        node.clone() + "_timeseries",
+                        n.node_id.clone(),
+                        data_type_iri(data_type, base_iri),
+                    ],
+                );
+            }
+        }
+
+        let mut subtype_rows = Rows::new(&["type", "superType"]);
+        let mut reference_rows = Rows::new(&["source", "referenceType", "target"]);
+        for r in &self.references {
+            if r.reference_type == HAS_SUBTYPE {
+                subtype_rows.push(
+                    &r.target,
+                    vec![node_iri(&r.target, base_iri), node_iri(&r.source, base_iri)],
+                );
+            } else if r.reference_type != HAS_TYPE_DEFINITION {
+                reference_rows.push(
+                    &format!("{} {} {}", r.source, r.reference_type, r.target),
+                    vec![
+                        node_iri(&r.source, base_iri),
+                        reference_type_iri(&r.reference_type, base_iri),
+                        node_iri(&r.target, base_iri),
+                    ],
+                );
+            }
+        }
+
+        for (template, rows) in [
+            (&options.node_template, node_rows),
+            (&options.type_definition_template, type_definition_rows),
+            (&options.timeseries_template, timeseries_rows),
+            (&options.subtype_template, subtype_rows),
+            (&options.reference_template, reference_rows),
+        ] {
+            if let Some(df) = rows.into_df() {
+                mapping.expand(template, df, Default::default())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//Columns of strings with a unique Key column, as required by the mapping
+struct Rows {
+    names: Vec<String>,
+    keys: Vec<String>,
+    seen_keys: HashSet<String>,
+    columns: Vec<Vec<String>>,
+}
+
+impl Rows {
+    fn new(names: &[&str]) -> Rows {
+        Rows {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            keys: vec![],
+            seen_keys: HashSet::new(),
+            columns: vec![vec![]; names.len()],
+        }
+    }
+
+    fn push(&mut self, key: &str, values: Vec<String>) {
+        if self.seen_keys.insert(key.to_string()) {
+            self.keys.push(key.to_string());
+            for (column, value) in self.columns.iter_mut().zip(values) {
+                column.push(value);
+            }
+        }
+    }
+
+    fn into_df(self) -> Option<DataFrame> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let mut series = vec![Series::new("Key", self.keys)];
+        for (name, column) in self.names.iter().zip(self.columns) {
+            series.push(Series::new(name, column));
+        }
+        Some(DataFrame::new(series).unwrap())
+    }
+}
+
+fn is_standard_node_id(node_id: &str) -> bool {
+    !node_id.starts_with("ns=")
+}
+
+//Strips the namespace index of namespace 0, so that node ids have a single form
+fn normalize_node_id(node_id: &str) -> String {
+    node_id.trim().trim_start_matches("ns=0;").to_string()
+}
+
+fn node_iri(node_id: &str, base_iri: &str) -> String {
+    if is_standard_node_id(node_id) {
+        OPCUA_PREFIX_IRI.to_string() + &encode_iri_part(node_id)
+    } else {
+        base_iri.to_string() + &encode_iri_part(node_id)
+    }
+}
+
+fn reference_type_iri(reference_type: &str, base_iri: &str) -> String {
+    for (node_id, name) in STANDARD_REFERENCE_TYPES {
+        if node_id == reference_type {
+            return OPCUA_PREFIX_IRI.to_string() + name;
+        }
+    }
+    node_iri(reference_type, base_iri)
+}
+
+fn data_type_iri(data_type: &str, base_iri: &str) -> String {
+    for (node_id, _, datatype) in STANDARD_DATA_TYPES {
+        if node_id == data_type {
+            return datatype.as_str().to_string();
+        }
+    }
+    node_iri(data_type, base_iri)
+}
+
+//Percent encodes characters that may not occur in IRIs, string node ids can contain anything
+fn encode_iri_part(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if c.is_alphanumeric() || "-._~=;".contains(c) {
+            out.push(c);
+        } else {
+            let mut buffer = [0; 4];
+            for b in c.encode_utf8(&mut buffer).bytes() {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    out
+}
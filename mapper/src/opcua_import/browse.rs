@@ -0,0 +1,191 @@
+use super::{OPCUAAddressSpace, OPCUAImportError, OPCUANode, OPCUAReference};
+use opcua_client::prelude::{
+    AttributeId, AttributeService, BrowseDescription, BrowseDescriptionResultMask, BrowseDirection,
+    NodeClass, NodeId, QualifiedName, ReadValueId, ReferenceDescription, ReferenceTypeId, Session,
+    TimestampsToReturn, UAString, Variant, ViewService,
+};
+use std::collections::HashSet;
+
+const OBJECTS_FOLDER: u32 = 85;
+const TYPES_FOLDER: u32 = 86;
+const SERVER_NAMESPACE_ARRAY: u32 = 2255;
+//Servers limit the number of nodes in each read, see MaxNodesPerRead
+const MAX_NODES_PER_READ: usize = 500;
+
+//Nodes in namespace 0 that are browsed through to find the nodes of the other namespaces
+const TRAVERSED_STANDARD_NODES: [u32; 8] = [
+    OBJECTS_FOLDER,
+    TYPES_FOLDER,
+    88, //ObjectTypesFolder
+    89, //VariableTypesFolder
+    58, //BaseObjectType
+    61, //FolderType
+    62, //BaseVariableType
+    63, //BaseDataVariableType
+];
+
+impl OPCUAAddressSpace {
+    //Browses the objects and types of the server, except those in namespace 0
+    pub fn browse(session: &Session) -> Result<OPCUAAddressSpace, OPCUAImportError> {
+        let traversed: Vec<NodeId> = TRAVERSED_STANDARD_NODES
+            .iter()
+            .map(|i| NodeId::new(0, *i))
+            .collect();
+        let mut address_space = OPCUAAddressSpace::default();
+        address_space.namespace_uris = read_namespace_array(session)?;
+        let mut added_nodes = HashSet::new();
+        let mut added_references = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut to_browse = vec![NodeId::new(0, TYPES_FOLDER), NodeId::new(0, OBJECTS_FOLDER)];
+        while let Some(node_id) = to_browse.pop() {
+            if !visited.insert(node_id.clone()) {
+                continue;
+            }
+            for r in browse_node(session, &node_id)? {
+                let target = r.node_id.node_id.clone();
+                if target.namespace == 0 {
+                    if traversed.contains(&target) {
+                        to_browse.push(target.clone());
+                    }
+                    //References within namespace 0 are part of the standard
+                    if node_id.namespace == 0 {
+                        continue;
+                    }
+                } else {
+                    to_browse.push(target.clone());
+                    if added_nodes.insert(target.clone()) {
+                        address_space.nodes.push(reference_description_to_node(&r));
+                    }
+                }
+                let reference = OPCUAReference {
+                    source: node_id.to_string(),
+                    reference_type: r.reference_type_id.to_string(),
+                    target: target.to_string(),
+                };
+                if added_references.insert(reference.clone()) {
+                    address_space.references.push(reference);
+                }
+            }
+        }
+        read_variable_attributes(session, &mut address_space.nodes)?;
+        Ok(address_space)
+    }
+}
+
+//The namespace array of the server, which NodeSet namespaces can be remapped to
+pub fn read_namespace_array(session: &Session) -> Result<Vec<String>, OPCUAImportError> {
+    let value_to_read = ReadValueId {
+        node_id: NodeId::new(0, SERVER_NAMESPACE_ARRAY),
+        attribute_id: AttributeId::Value as u32,
+        index_range: UAString::null(),
+        data_encoding: QualifiedName::null(),
+    };
+    let data_values = session
+        .read(&[value_to_read], TimestampsToReturn::Neither, 0.0)
+        .map_err(OPCUAImportError::ReadError)?;
+    let mut namespace_uris = vec![];
+    if let Some(Variant::Array(array)) = data_values.get(0).and_then(|d| d.value.as_ref()) {
+        for v in &array.values {
+            if let Variant::String(uri) = v {
+                namespace_uris.push(uri.to_string());
+            }
+        }
+    }
+    Ok(namespace_uris)
+}
+
+fn browse_node(
+    session: &Session,
+    node_id: &NodeId,
+) -> Result<Vec<ReferenceDescription>, OPCUAImportError> {
+    let description = BrowseDescription {
+        node_id: node_id.clone(),
+        browse_direction: BrowseDirection::Forward,
+        reference_type_id: ReferenceTypeId::HierarchicalReferences.into(),
+        include_subtypes: true,
+        node_class_mask: 0,
+        result_mask: BrowseDescriptionResultMask::all().bits() as u32,
+    };
+    let mut results = session
+        .browse(&[description])
+        .map_err(OPCUAImportError::BrowseError)?
+        .unwrap_or_default();
+    let mut references = vec![];
+    //Servers may split the references of a node using continuation points
+    while let Some(result) = results.pop() {
+        if result.status_code.is_bad() {
+            return Err(OPCUAImportError::BrowseError(result.status_code));
+        }
+        references.extend(result.references.unwrap_or_default());
+        if result.continuation_point.is_null() {
+            break;
+        }
+        results = session
+            .browse_next(false, &[result.continuation_point])
+            .map_err(OPCUAImportError::BrowseError)?
+            .unwrap_or_default();
+    }
+    Ok(references)
+}
+
+fn reference_description_to_node(r: &ReferenceDescription) -> OPCUANode {
+    let type_definition = if r.type_definition.node_id.is_null() {
+        None
+    } else {
+        Some(r.type_definition.node_id.to_string())
+    };
+    OPCUANode {
+        node_id: r.node_id.node_id.to_string(),
+        node_class: format!("{:?}", r.node_class),
+        browse_name: r.browse_name.name.to_string(),
+        display_name: r.display_name.text.to_string(),
+        type_definition,
+        data_type: None,
+        historizing: false,
+    }
+}
+
+//The data type and whether the history is kept are only available as attributes of variables
+fn read_variable_attributes(
+    session: &Session,
+    nodes: &mut Vec<OPCUANode>,
+) -> Result<(), OPCUAImportError> {
+    let mut variables: Vec<&mut OPCUANode> = nodes
+        .iter_mut()
+        .filter(|n| n.node_class == format!("{:?}", NodeClass::Variable))
+        .collect();
+    if variables.is_empty() {
+        return Ok(());
+    }
+    let mut values_to_read = vec![];
+    for v in &variables {
+        for attribute_id in [AttributeId::DataType, AttributeId::Historizing] {
+            values_to_read.push(ReadValueId {
+                node_id: v
+                    .node_id
+                    .parse()
+                    .map_err(|_| OPCUAImportError::InvalidNodeId(v.node_id.clone()))?,
+                attribute_id: attribute_id as u32,
+                index_range: UAString::null(),
+                data_encoding: QualifiedName::null(),
+            });
+        }
+    }
+    let mut data_values = vec![];
+    for chunk in values_to_read.chunks(MAX_NODES_PER_READ) {
+        data_values.extend(
+            session
+                .read(chunk, TimestampsToReturn::Neither, 0.0)
+                .map_err(OPCUAImportError::ReadError)?,
+        );
+    }
+    for (v, values) in variables.iter_mut().zip(data_values.chunks(2)) {
+        if let Some(Variant::NodeId(data_type)) = &values[0].value {
+            v.data_type = Some(data_type.to_string());
+        }
+        if let Some(Variant::Boolean(historizing)) = &values[1].value {
+            v.historizing = *historizing;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use super::{
+    normalize_node_id, OPCUAAddressSpace, OPCUAImportError, OPCUANode, OPCUAReference,
+    BASE_DATA_TYPE, HAS_TYPE_DEFINITION, STANDARD_DATA_TYPES, STANDARD_NAMESPACE_URI,
+    STANDARD_REFERENCE_TYPES,
+};
+use roxmltree::{Document, Node};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::Path;
+
+impl OPCUAAddressSpace {
+    pub fn from_nodeset_file<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<OPCUAAddressSpace, OPCUAImportError> {
+        let s = read_to_string(path)?;
+        OPCUAAddressSpace::from_nodeset_str(&s)
+    }
+
+    //Namespace indices are kept as written in the NodeSet, they index its NamespaceUris.
+    //Use remap_namespaces to get the node ids used by a server.
+    pub fn from_nodeset_str(s: &str) -> Result<OPCUAAddressSpace, OPCUAImportError> {
+        let document = Document::parse(s)?;
+        let root = document.root_element();
+        let mut aliases = HashMap::new();
+        for (node_id, name) in STANDARD_REFERENCE_TYPES {
+            aliases.insert(name.to_string(), node_id.to_string());
+        }
+        for (node_id, name, _) in STANDARD_DATA_TYPES {
+            aliases.insert(name.to_string(), node_id.to_string());
+        }
+        for a in child_elements(&root, "Aliases").flat_map(|c| child_elements(&c, "Alias")) {
+            let alias = required_attribute(&a, "Alias")?;
+            aliases.insert(alias.to_string(), normalize_node_id(a.text().unwrap_or("")));
+        }
+        let resolve = |s: &str| {
+            aliases
+                .get(s.trim())
+                .cloned()
+                .unwrap_or_else(|| normalize_node_id(s))
+        };
+
+        let mut address_space = OPCUAAddressSpace::default();
+        address_space
+            .namespace_uris
+            .push(STANDARD_NAMESPACE_URI.to_string());
+        for u in child_elements(&root, "NamespaceUris").flat_map(|c| child_elements(&c, "Uri")) {
+            address_space
+                .namespace_uris
+                .push(u.text().unwrap_or("").trim().to_string());
+        }
+        let mut added_references = HashSet::new();
+        for c in root.children().filter(|c| c.is_element()) {
+            let node_class = match c.tag_name().name() {
+                "UAObject" => "Object",
+                "UAVariable" => "Variable",
+                "UAMethod" => "Method",
+                "UAObjectType" => "ObjectType",
+                "UAVariableType" => "VariableType",
+                "UAReferenceType" => "ReferenceType",
+                "UADataType" => "DataType",
+                "UAView" => "View",
+                _ => continue,
+            };
+            let node_id = normalize_node_id(required_attribute(&c, "NodeId")?);
+            //Browse names are qualified with the namespace index, as in 1:MyObject
+            let browse_name = required_attribute(&c, "BrowseName")?;
+            let browse_name = match browse_name.split_once(':') {
+                Some((index, name)) if index.parse::<u16>().is_ok() => name,
+                _ => browse_name,
+            };
+            let display_name = child_elements(&c, "DisplayName")
+                .next()
+                .and_then(|d| d.text())
+                .unwrap_or(browse_name);
+            let (data_type, historizing) = if node_class == "Variable" {
+                (
+                    Some(resolve(c.attribute("DataType").unwrap_or(BASE_DATA_TYPE))),
+                    c.attribute("Historizing") == Some("true"),
+                )
+            } else {
+                (None, false)
+            };
+
+            let mut type_definition = None;
+            for r in
+                child_elements(&c, "References").flat_map(|rs| child_elements(&rs, "Reference"))
+            {
+                let reference_type = resolve(required_attribute(&r, "ReferenceType")?);
+                let target = normalize_node_id(r.text().unwrap_or(""));
+                let is_forward = r.attribute("IsForward") != Some("false");
+                if reference_type == HAS_TYPE_DEFINITION && is_forward {
+                    type_definition = Some(target);
+                    continue;
+                }
+                //Inverse references are stored as the forward reference they describe
+                let reference = if is_forward {
+                    OPCUAReference {
+                        source: node_id.clone(),
+                        reference_type,
+                        target,
+                    }
+                } else {
+                    OPCUAReference {
+                        source: target,
+                        reference_type,
+                        target: node_id.clone(),
+                    }
+                };
+                if added_references.insert(reference.clone()) {
+                    address_space.references.push(reference);
+                }
+            }
+            address_space.nodes.push(OPCUANode {
+                node_id,
+                node_class: node_class.to_string(),
+                browse_name: browse_name.to_string(),
+                display_name: display_name.trim().to_string(),
+                type_definition,
+                data_type,
+                historizing,
+            });
+        }
+        Ok(address_space)
+    }
+}
+
+fn child_elements<'a, 'input>(
+    node: &Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn required_attribute<'a>(node: &Node<'a, '_>, name: &str) -> Result<&'a str, OPCUAImportError> {
+    node.attribute(name).ok_or_else(|| {
+        OPCUAImportError::InvalidNodeSet(format!(
+            "Element {} is missing attribute {}",
+            node.tag_name().name(),
+            name
+        ))
+    })
+}
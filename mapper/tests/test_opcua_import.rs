@@ -0,0 +1,219 @@
+use mapper::opcua_import::{opcua_mapping, OPCUAAddressSpace, OPCUAImportOptions};
+use opcua_client::prelude::{ClientBuilder, IdentityToken, UserTokenPolicy};
+use opcua_server::prelude::*;
+use oxrdf::vocab::{rdf, rdfs, xsd};
+use oxrdf::{Literal, NamedNode, Subject, Term, Triple};
+use rstest::*;
+use serial_test::serial;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::thread;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[fixture]
+fn testdata_path() -> PathBuf {
+    let manidir = env!("CARGO_MANIFEST_DIR");
+    let mut testdata_path = PathBuf::new();
+    testdata_path.push(manidir);
+    testdata_path.push("tests");
+    testdata_path.push("mapper_testdata");
+    testdata_path
+}
+
+fn triple(subject: &str, predicate: &str, object: Term) -> Triple {
+    Triple {
+        subject: Subject::NamedNode(NamedNode::new_unchecked(subject)),
+        predicate: NamedNode::new_unchecked(predicate),
+        object,
+    }
+}
+
+fn iri(s: &str) -> Term {
+    Term::NamedNode(NamedNode::new_unchecked(s))
+}
+
+fn string(s: &str) -> Term {
+    Term::Literal(Literal::new_typed_literal(s, xsd::STRING))
+}
+
+#[rstest]
+fn test_import_nodeset(testdata_path: PathBuf) {
+    let mut file_path = testdata_path.clone();
+    file_path.push("opcua_nodeset.xml");
+    let address_space = OPCUAAddressSpace::from_nodeset_file(file_path).unwrap();
+    assert_eq!(address_space.nodes.len(), 3);
+    //The inverse and forward HasComponent references between the widget and the variable are the same reference
+    assert_eq!(address_space.references.len(), 3);
+
+    let mut mapping = opcua_mapping();
+    address_space
+        .expand_templates(
+            &mut mapping,
+            &OPCUAImportOptions::new("http://example.org/plant#"),
+        )
+        .unwrap();
+    let triples: HashSet<Triple> = mapping.export_oxrdf_triples().into_iter().collect();
+
+    let opcua = "https://github.com/magbak/otit_swt/opcua#";
+    let otit_swt = "https://github.com/magbak/otit_swt#";
+    let widget = "http://example.org/plant#ns=1;i=5001";
+    let widget_type = "http://example.org/plant#ns=1;i=1001";
+    let temperature = "http://example.org/plant#ns=1;s=ts1";
+    let timeseries = "http://example.org/plant#ns=1;s=ts1_timeseries";
+    let expected_triples = [
+        triple(widget, rdf::TYPE.as_str(), iri(&format!("{}Object", opcua))),
+        triple(widget, rdf::TYPE.as_str(), iri(widget_type)),
+        triple(widget, &format!("{}browseName", opcua), string("Widget1")),
+        triple(widget, rdfs::LABEL.as_str(), string("Widget 1")),
+        triple(
+            widget_type,
+            rdfs::SUB_CLASS_OF.as_str(),
+            iri(&format!("{}i=58", opcua)),
+        ),
+        triple(
+            &format!("{}i=85", opcua),
+            &format!("{}Organizes", opcua),
+            iri(widget),
+        ),
+        triple(widget, &format!("{}HasComponent", opcua), iri(temperature)),
+        triple(
+            temperature,
+            &format!("{}hasTimeseries", otit_swt),
+            iri(timeseries),
+        ),
+        triple(
+            timeseries,
+            &format!("{}hasExternalId", otit_swt),
+            string("ns=1;s=ts1"),
+        ),
+        triple(
+            timeseries,
+            &format!("{}hasDatatype", otit_swt),
+            iri(xsd::DOUBLE.as_str()),
+        ),
+    ];
+    for t in expected_triples {
+        assert!(triples.contains(&t), "Missing triple {}", t);
+    }
+    //Only the historized variable has a time series
+    let n_timeseries = triples
+        .iter()
+        .filter(|t| t.predicate.as_str() == format!("{}hasTimeseries", otit_swt))
+        .count();
+    assert_eq!(n_timeseries, 1);
+}
+
+#[rstest]
+fn test_remap_nodeset_namespaces(testdata_path: PathBuf) {
+    let mut file_path = testdata_path.clone();
+    file_path.push("opcua_nodeset.xml");
+    let mut address_space = OPCUAAddressSpace::from_nodeset_file(file_path).unwrap();
+    let server_namespace_uris = vec![
+        "http://opcfoundation.org/UA/".to_string(),
+        "urn:server_uri".to_string(),
+        "http://example.org/plant/".to_string(),
+    ];
+    address_space
+        .remap_namespaces(&server_namespace_uris)
+        .unwrap();
+    let node_ids: HashSet<&str> = address_space
+        .nodes
+        .iter()
+        .map(|n| n.node_id.as_str())
+        .collect();
+    assert!(node_ids.contains("ns=2;s=ts1"));
+    assert!(node_ids.contains("ns=2;i=5001"));
+    assert!(address_space
+        .references
+        .iter()
+        .any(|r| r.source == "ns=2;i=5001" && r.target == "ns=2;s=ts1"));
+    //Namespaces that are not on the server can not be remapped
+    assert!(address_space
+        .remap_namespaces(&server_namespace_uris[..2])
+        .is_err());
+}
+
+#[rstest]
+#[serial]
+fn test_browse() {
+    let port = 1240;
+    let endpoint = format!("opc.tcp://{}:{}/", hostname().unwrap(), port);
+    let server = ServerBuilder::new()
+        .application_name("Server Name")
+        .application_uri("urn:server_uri")
+        .discovery_urls(vec![endpoint.clone().into()])
+        .create_sample_keypair(true)
+        .pki_dir("./pki-server")
+        .discovery_server_url(None)
+        .host_and_port(hostname().unwrap(), port)
+        .endpoints(
+            [(
+                "",
+                "/",
+                SecurityPolicy::None,
+                MessageSecurityMode::None,
+                &[ANONYMOUS_USER_TOKEN_ID],
+            )]
+            .iter()
+            .map(|v| {
+                (
+                    v.0.to_string(),
+                    ServerEndpoint::from((v.1, v.2, v.3, &v.4[..])),
+                )
+            })
+            .collect(),
+        )
+        .server()
+        .unwrap();
+    {
+        let address_space = server.address_space();
+        let mut address_space = address_space.write().unwrap();
+        let folder_id = address_space
+            .add_folder("Sensors", "Sensors", &NodeId::objects_folder_id())
+            .unwrap();
+        VariableBuilder::new(&NodeId::new(1, "ts1"), "Temperature", "Temperature")
+            .data_type(DataTypeId::Double)
+            .value(0.0f64)
+            .historizing(true)
+            .organized_by(&folder_id)
+            .insert(&mut address_space);
+    }
+    thread::spawn(move || server.run());
+    sleep(Duration::from_secs(2));
+
+    let mut client = ClientBuilder::new()
+        .application_name("OPC UA Import Test")
+        .application_uri("urn:OPCUAImportTest")
+        .trust_server_certs(true)
+        .create_sample_keypair(true)
+        .pki_dir("./pki-client")
+        .session_retry_limit(3)
+        .client()
+        .unwrap();
+    let endpoint_description: EndpointDescription = (
+        endpoint.as_str(),
+        SecurityPolicy::None.to_str(),
+        MessageSecurityMode::None,
+        UserTokenPolicy::anonymous(),
+    )
+        .into();
+    let session = client
+        .connect_to_endpoint(endpoint_description, IdentityToken::Anonymous)
+        .unwrap();
+    let address_space = OPCUAAddressSpace::browse(&session.read().unwrap()).unwrap();
+    session.write().unwrap().disconnect();
+    assert_eq!(
+        address_space.namespace_uris.get(0),
+        Some(&"http://opcfoundation.org/UA/".to_string())
+    );
+    let variable = address_space
+        .nodes
+        .iter()
+        .find(|n| n.node_id == "ns=1;s=ts1")
+        .expect("Variable not browsed");
+    assert_eq!(variable.node_class, "Variable");
+    assert_eq!(variable.browse_name, "Temperature");
+    assert_eq!(variable.data_type, Some("i=11".to_string()));
+    assert!(variable.historizing);
+}
@@ -0,0 +1,45 @@
+use arrow_python_utils::to_python::to_py_series;
+use arrow_python_utils::to_rust::polars_series_to_rust_series;
+use hybrid::function_registry::{PolarsAggregate, PolarsFunction};
+use polars::prelude::{map_multiple, Expr, GetOutput, PolarsError, Series};
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use std::sync::Arc;
+
+//Python functions are called with polars Series arguments and should return a polars Series
+pub fn python_function(function: PyObject) -> PolarsFunction {
+    Arc::new(move |args: Vec<Expr>| {
+        let function = function.clone();
+        map_multiple(
+            move |series: &mut [Series]| call_python_function(&function, series),
+            args,
+            GetOutput::same_type(),
+        )
+    })
+}
+
+//Python aggregates are called once per group and should return a polars Series of length one
+pub fn python_aggregate(aggregate: PyObject) -> PolarsAggregate {
+    Arc::new(move |expr: Expr| {
+        let aggregate = aggregate.clone();
+        expr.apply(
+            move |series: Series| call_python_function(&aggregate, &[series]),
+            GetOutput::same_type(),
+        )
+        .first()
+    })
+}
+
+fn call_python_function(function: &PyObject, series: &[Series]) -> Result<Series, PolarsError> {
+    Python::with_gil(|py| {
+        let pyarrow = PyModule::import(py, "pyarrow")?;
+        let polars = PyModule::import(py, "polars")?;
+        let mut py_series = vec![];
+        for s in series {
+            py_series.push(to_py_series(s, py, pyarrow, polars)?);
+        }
+        let result = function.call1(py, PyTuple::new(py, py_series))?;
+        polars_series_to_rust_series(result.as_ref(py))
+    })
+    .map_err(|err: PyErr| PolarsError::ComputeError(format!("{}", err).into()))
+}
@@ -1,7 +1,10 @@
 use oxrdf::IriParseError;
+use regex::Error as RegexError;
 use thiserror::Error;
 
+use hybrid::config::ConfigError as RustConfigError;
 use hybrid::timeseries_database::arrow_flight_sql_database::ArrowFlightSQLError as RustArrowFlightSQLError;
+use hybrid::timeseries_database::opcua_history_read::OPCUAHistoryReadError as RustOPCUAHistoryReadError;
 use pyo3::{create_exception, exceptions::PyException, prelude::*};
 
 #[derive(Error, Debug)]
@@ -9,8 +12,14 @@ pub enum PyQueryError {
     #[error(transparent)]
     ArrowFlightSQLError(#[from] RustArrowFlightSQLError),
     #[error(transparent)]
+    OPCUAHistoryReadError(#[from] RustOPCUAHistoryReadError),
+    #[error(transparent)]
+    ConfigError(#[from] RustConfigError),
+    #[error(transparent)]
     DatatypeIRIParseError(#[from] IriParseError),
     #[error(transparent)]
+    TableDiscoveryPatternError(#[from] RegexError),
+    #[error(transparent)]
     QueryExecutionError(Box<dyn std::error::Error>),
     #[error("DSL parsing error")]
     DSLParsingError,
@@ -26,9 +35,18 @@ impl std::convert::From<PyQueryError> for PyErr {
             PyQueryError::ArrowFlightSQLError(err) => {
                 ArrowFlightSQLError::new_err(format!("{}", err))
             }
+            PyQueryError::OPCUAHistoryReadError(err) => {
+                OPCUAHistoryReadError::new_err(format!("{}", err))
+            }
+            PyQueryError::ConfigError(err) => {
+                ConfigError::new_err(format!("{}", err))
+            }
             PyQueryError::DatatypeIRIParseError(err) => {
                 DatatypeIRIParseError::new_err(format!("{}", err))
             }
+            PyQueryError::TableDiscoveryPatternError(err) => {
+                TableDiscoveryPatternError::new_err(format!("{}", err))
+            }
             PyQueryError::QueryExecutionError(err) => {
                 QueryExecutionError::new_err(format!("{}", err))
             }
@@ -46,7 +64,10 @@ impl std::convert::From<PyQueryError> for PyErr {
 }
 
 create_exception!(exceptions, ArrowFlightSQLError, PyException);
+create_exception!(exceptions, OPCUAHistoryReadError, PyException);
+create_exception!(exceptions, ConfigError, PyException);
 create_exception!(exceptions, DatatypeIRIParseError, PyException);
+create_exception!(exceptions, TableDiscoveryPatternError, PyException);
 create_exception!(exceptions, QueryExecutionError, PyException);
 create_exception!(exceptions, DSLParsingError, PyException);
 create_exception!(exceptions, MissingTimeSeriesDatabaseError, PyException);
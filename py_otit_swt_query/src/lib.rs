@@ -1,22 +1,33 @@
 pub mod errors;
+mod custom_functions;
 
+use crate::custom_functions::{python_aggregate, python_function};
 use crate::errors::PyQueryError;
 use arrow_python_utils::to_python::to_py_df;
 use dsl::connective_mapping::ConnectiveMapping;
-use dsl::costants::{REPLACE_STR_LITERAL, REPLACE_VARIABLE_NAME};
+use dsl::name_templates::{name_template, type_name_template};
 use dsl::parser::ts_query;
 use dsl::translator::Translator;
 use hybrid::timeseries_database::arrow_flight_sql_database::ArrowFlightSQLDatabase as RustArrowFlightSQLDatabase;
-use hybrid::timeseries_database::opcua_history_read::OPCUAHistoryRead as RustOPCUAHistoryRead;
-use hybrid::timeseries_database::timeseries_sql_rewrite::TimeSeriesTable as RustTimeSeriesTable;
+use hybrid::timeseries_database::table_discovery::TableDiscovery as RustTableDiscovery;
+use hybrid::timeseries_database::opcua_history_read::{
+    OPCUAAggregateConfiguration, OPCUAClientConfig, OPCUAHistoryRead as RustOPCUAHistoryRead,
+    OPCUAHistoryReadError, OPCUAIdentity, OPCUASynchronization,
+};
+use hybrid::timeseries_database::timeseries_sql_rewrite::{
+    PartitionScheme, TimeSeriesPartition, TimeSeriesTable as RustTimeSeriesTable,
+};
+use hybrid::config::{ConfigError, EngineConfig};
 use hybrid::engine::Engine as RustEngine;
+use hybrid::function_registry::{CustomAggregate, CustomFunction, FunctionRegistry};
+use hybrid::timeseries_database::TimeSeriesQueryable;
 use hybrid::pushdown_setting::{PushdownSetting, all_pushdowns};
 use log::debug;
-use oxrdf::vocab::{rdf, xsd};
-use oxrdf::{IriParseError, Literal, NamedNode, Variable};
+use oxrdf::{IriParseError, NamedNode};
 use pyo3::prelude::*;
-use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use tokio::runtime::{Builder, Runtime};
 
 #[pyclass(unsendable)]
@@ -25,6 +36,7 @@ pub struct Engine {
     endpoint: String,
     connective_mapping: Option<ConnectiveMapping>,
     name_predicate: Option<String>,
+    function_registry: FunctionRegistry,
 }
 
 #[pymethods]
@@ -36,6 +48,26 @@ impl Engine {
             endpoint: endpoint.to_string(),
             connective_mapping: None,
             name_predicate: None,
+            function_registry: FunctionRegistry::new(),
+        })
+    }
+
+    //Creates an engine with the endpoint, time series database and vocabulary given in a YAML or TOML file
+    #[staticmethod]
+    pub fn from_config(path: &str) -> PyResult<Engine> {
+        let config = EngineConfig::from_file(path).map_err(PyQueryError::from)?;
+        let engine = Runtime::new()
+            .unwrap()
+            .block_on(RustEngine::from_config(&config))
+            .map_err(PyQueryError::from)?;
+        Ok(Engine {
+            engine: Some(engine),
+            endpoint: config.sparql_endpoint.clone(),
+            connective_mapping: Some(ConnectiveMapping {
+                map: config.vocabulary.connective_mapping.clone(),
+            }),
+            name_predicate: config.vocabulary.name_predicate.clone(),
+            function_registry: FunctionRegistry::new(),
         })
     }
 
@@ -49,18 +81,24 @@ impl Engine {
             new_tables.push(t.to_rust_table().map_err(PyQueryError::from)?);
         }
 
-        let afsqldb_result = Runtime::new()
-            .unwrap()
-            .block_on(RustArrowFlightSQLDatabase::new(
-                &endpoint,
-                &db.username,
-                &db.password,
-                new_tables,
-            ));
-        let db = afsqldb_result.map_err(PyQueryError::from)?;
-        self.engine = Some(RustEngine::new(
-                    all_pushdowns(), Box::new(db)
-                ));
+        let runtime = Runtime::new().unwrap();
+        let afsqldb_result = runtime.block_on(RustArrowFlightSQLDatabase::new(
+            &endpoint,
+            &db.username,
+            &db.password,
+            new_tables,
+        ));
+        let mut afsqldb = afsqldb_result.map_err(PyQueryError::from)?;
+        //Discovered tables come after the given tables, which are preferred for the same datatype
+        if db.discover_tables {
+            let discovery = db.to_rust_table_discovery()?;
+            let discovered = runtime
+                .block_on(afsqldb.discover_tables(&discovery))
+                .map_err(PyQueryError::from)?;
+            afsqldb.add_time_series_tables(discovered);
+        }
+        let db = afsqldb;
+        self.engine = Some(self.create_engine(all_pushdowns(), Box::new(db)));
         Ok(())
     }
 
@@ -68,10 +106,58 @@ impl Engine {
         if self.engine.is_some() {
             return Err(PyQueryError::TimeSeriesDatabaseAlreadyDefined.into());
         }
-        let actual_db = RustOPCUAHistoryRead::new(&db.endpoint, db.namespace);
-        self.engine = Some(RustEngine::new(
-                    [PushdownSetting::GroupBy].into(), Box::new(actual_db)
-                ));
+        let config = db.to_rust_config()?;
+        let mut actual_db = RustOPCUAHistoryRead::from_config(&db.endpoint, db.namespace, &config)
+            .map_err(PyQueryError::from)?
+            .with_aggregate_configuration(db.to_rust_aggregate_configuration());
+        if db.read_at_time {
+            actual_db = actual_db.with_synchronization(OPCUASynchronization::ReadAtTime);
+        }
+        let mut pushdown_settings: HashSet<PushdownSetting> = [PushdownSetting::GroupBy].into();
+        if db.synchronization_pushdown {
+            pushdown_settings.insert(PushdownSetting::Synchronization);
+        }
+        self.engine = Some(self.create_engine(pushdown_settings, Box::new(actual_db)));
+        Ok(())
+    }
+
+    #[args(sql_function_name = "None")]
+    pub fn register_function(
+        &mut self,
+        iri: &str,
+        function: PyObject,
+        sql_function_name: Option<String>,
+    ) -> PyResult<()> {
+        let mut custom_function = CustomFunction::new(python_function(function));
+        if let Some(name) = sql_function_name {
+            custom_function = custom_function.with_sql_function_name(&name);
+        }
+        if let Some(engine) = &mut self.engine {
+            engine.register_function(iri, custom_function.clone());
+        }
+        self.function_registry.register_function(iri, custom_function);
+        Ok(())
+    }
+
+    #[args(sql_aggregate_name = "None", opcua_aggregate_function = "None")]
+    pub fn register_aggregate(
+        &mut self,
+        iri: &str,
+        aggregate: PyObject,
+        sql_aggregate_name: Option<String>,
+        opcua_aggregate_function: Option<u32>,
+    ) -> PyResult<()> {
+        let mut custom_aggregate = CustomAggregate::new(python_aggregate(aggregate));
+        if let Some(name) = sql_aggregate_name {
+            custom_aggregate = custom_aggregate.with_sql_aggregate_name(&name);
+        }
+        if let Some(node_id) = opcua_aggregate_function {
+            custom_aggregate = custom_aggregate.with_opcua_aggregate_function(node_id);
+        }
+        if let Some(engine) = &mut self.engine {
+            engine.register_aggregate(iri, custom_aggregate.clone());
+        }
+        self.function_registry.register_aggregate(iri, custom_aggregate);
         Ok(())
     }
 
@@ -134,28 +220,21 @@ impl Engine {
     }
 }
 
-fn type_name_template(predicate: &str) -> Vec<TriplePattern> {
-    let type_variable = Variable::new_unchecked("type_var");
-    let type_triple = TriplePattern {
-        subject: TermPattern::Variable(Variable::new_unchecked(REPLACE_VARIABLE_NAME)),
-        predicate: NamedNodePattern::NamedNode(NamedNode::from(rdf::TYPE)),
-        object: TermPattern::Variable(type_variable.clone()),
-    };
-    let type_name_triple = TriplePattern {
-        subject: TermPattern::Variable(type_variable),
-        predicate: NamedNodePattern::NamedNode(NamedNode::new(predicate).unwrap()),
-        object: TermPattern::Literal(Literal::new_typed_literal(REPLACE_STR_LITERAL, xsd::STRING)),
-    };
-    vec![type_triple, type_name_triple]
-}
-
-fn name_template(predicate: &str) -> Vec<TriplePattern> {
-    let name_triple = TriplePattern {
-        subject: TermPattern::Variable(Variable::new_unchecked(REPLACE_VARIABLE_NAME)),
-        predicate: NamedNodePattern::NamedNode(NamedNode::new_unchecked(predicate)),
-        object: TermPattern::Literal(Literal::new_typed_literal(REPLACE_STR_LITERAL, xsd::STRING)),
-    };
-    vec![name_triple]
+impl Engine {
+    fn create_engine(
+        &self,
+        pushdown_settings: HashSet<PushdownSetting>,
+        time_series_database: Box<dyn TimeSeriesQueryable>,
+    ) -> RustEngine {
+        let mut engine = RustEngine::new(pushdown_settings, time_series_database);
+        for (iri, custom_function) in self.function_registry.functions() {
+            engine.register_function(iri, custom_function.clone());
+        }
+        for (iri, custom_aggregate) in self.function_registry.aggregates() {
+            engine.register_aggregate(iri, custom_aggregate.clone());
+        }
+        engine
+    }
 }
 
 #[pyclass]
@@ -166,17 +245,42 @@ pub struct ArrowFlightSQLDatabase {
     username: String,
     password: String,
     tables: Vec<TimeSeriesTable>,
+    discover_tables: bool,
+    //Patterns are regular expressions, unset patterns use the default naming conventions
+    schema_pattern: Option<String>,
+    table_pattern: Option<String>,
+    identifier_column_pattern: Option<String>,
+    timestamp_column_pattern: Option<String>,
+    value_column_pattern: Option<String>,
+    quality_column_pattern: Option<String>,
 }
 
 #[pymethods]
 impl ArrowFlightSQLDatabase {
     #[new]
+    #[args(
+        tables = "vec![]",
+        discover_tables = "false",
+        schema_pattern = "None",
+        table_pattern = "None",
+        identifier_column_pattern = "None",
+        timestamp_column_pattern = "None",
+        value_column_pattern = "None",
+        quality_column_pattern = "None"
+    )]
     pub fn new(
         host: String,
         port: u16,
         username: String,
         password: String,
         tables: Vec<TimeSeriesTable>,
+        discover_tables: bool,
+        schema_pattern: Option<String>,
+        table_pattern: Option<String>,
+        identifier_column_pattern: Option<String>,
+        timestamp_column_pattern: Option<String>,
+        value_column_pattern: Option<String>,
+        quality_column_pattern: Option<String>,
     ) -> ArrowFlightSQLDatabase {
         ArrowFlightSQLDatabase {
             username,
@@ -184,8 +288,59 @@ impl ArrowFlightSQLDatabase {
             host,
             port,
             tables,
+            discover_tables,
+            schema_pattern,
+            table_pattern,
+            identifier_column_pattern,
+            timestamp_column_pattern,
+            value_column_pattern,
+            quality_column_pattern,
         }
     }
+
+    //The tables that discovery would use, these can be adjusted and given as tables instead
+    pub fn propose_tables(&self) -> PyResult<Vec<TimeSeriesTable>> {
+        let endpoint = format!("http://{}:{}", &self.host, &self.port);
+        let discovery = self.to_rust_table_discovery()?;
+        let runtime = Runtime::new().unwrap();
+        let mut afsqldb = runtime
+            .block_on(RustArrowFlightSQLDatabase::new(
+                &endpoint,
+                &self.username,
+                &self.password,
+                vec![],
+            ))
+            .map_err(PyQueryError::from)?;
+        let tables = runtime
+            .block_on(afsqldb.discover_tables(&discovery))
+            .map_err(PyQueryError::from)?;
+        Ok(tables.iter().map(TimeSeriesTable::from_rust_table).collect())
+    }
+}
+
+impl ArrowFlightSQLDatabase {
+    fn to_rust_table_discovery(&self) -> Result<RustTableDiscovery, PyQueryError> {
+        let mut discovery = RustTableDiscovery::new();
+        if let Some(p) = &self.schema_pattern {
+            discovery = discovery.with_schema_pattern(Regex::new(p)?);
+        }
+        if let Some(p) = &self.table_pattern {
+            discovery = discovery.with_table_pattern(Regex::new(p)?);
+        }
+        if let Some(p) = &self.identifier_column_pattern {
+            discovery = discovery.with_identifier_column_pattern(Regex::new(p)?);
+        }
+        if let Some(p) = &self.timestamp_column_pattern {
+            discovery = discovery.with_timestamp_column_pattern(Regex::new(p)?);
+        }
+        if let Some(p) = &self.value_column_pattern {
+            discovery = discovery.with_value_column_pattern(Regex::new(p)?);
+        }
+        if let Some(p) = &self.quality_column_pattern {
+            discovery = discovery.with_quality_column_pattern(Some(Regex::new(p)?));
+        }
+        Ok(discovery)
+    }
 }
 
 #[pyclass]
@@ -193,31 +348,186 @@ impl ArrowFlightSQLDatabase {
 pub struct OPCUAHistoryRead {
     namespace: u16,
     endpoint: String,
+    security_policy: String,
+    security_mode: String,
+    username: Option<String>,
+    password: Option<String>,
+    user_certificate_path: Option<String>,
+    user_private_key_path: Option<String>,
+    pki_dir: Option<String>,
+    certificate_path: Option<String>,
+    private_key_path: Option<String>,
+    trust_server_certs: bool,
+    read_at_time: bool,
+    //Synchronized series are read and joined by the backend instead of by the engine
+    synchronization_pushdown: bool,
+    use_server_aggregate_defaults: bool,
+    treat_uncertain_as_bad: bool,
+    percent_data_bad: u8,
+    percent_data_good: u8,
+    use_sloped_extrapolation: bool,
 }
 
 #[pymethods]
 impl OPCUAHistoryRead {
     #[new]
-    pub fn new(endpoint: String, namespace: u16) -> OPCUAHistoryRead {
+    #[args(
+        security_policy = "\"None\".to_string()",
+        security_mode = "\"None\".to_string()",
+        username = "None",
+        password = "None",
+        user_certificate_path = "None",
+        user_private_key_path = "None",
+        pki_dir = "None",
+        certificate_path = "None",
+        private_key_path = "None",
+        trust_server_certs = "false",
+        read_at_time = "false",
+        synchronization_pushdown = "true",
+        use_server_aggregate_defaults = "false",
+        treat_uncertain_as_bad = "false",
+        percent_data_bad = "0",
+        percent_data_good = "0",
+        use_sloped_extrapolation = "false"
+    )]
+    pub fn new(
+        endpoint: String,
+        namespace: u16,
+        security_policy: String,
+        security_mode: String,
+        username: Option<String>,
+        password: Option<String>,
+        user_certificate_path: Option<String>,
+        user_private_key_path: Option<String>,
+        pki_dir: Option<String>,
+        certificate_path: Option<String>,
+        private_key_path: Option<String>,
+        trust_server_certs: bool,
+        read_at_time: bool,
+        synchronization_pushdown: bool,
+        use_server_aggregate_defaults: bool,
+        treat_uncertain_as_bad: bool,
+        percent_data_bad: u8,
+        percent_data_good: u8,
+        use_sloped_extrapolation: bool,
+    ) -> OPCUAHistoryRead {
         OPCUAHistoryRead {
             namespace,
             endpoint,
+            security_policy,
+            security_mode,
+            username,
+            password,
+            user_certificate_path,
+            user_private_key_path,
+            pki_dir,
+            certificate_path,
+            private_key_path,
+            trust_server_certs,
+            read_at_time,
+            synchronization_pushdown,
+            use_server_aggregate_defaults,
+            treat_uncertain_as_bad,
+            percent_data_bad,
+            percent_data_good,
+            use_sloped_extrapolation,
+        }
+    }
+}
+
+impl OPCUAHistoryRead {
+    fn to_rust_config(&self) -> Result<OPCUAClientConfig, PyQueryError> {
+        let mut config = OPCUAClientConfig::default()
+            .with_security_names(&self.security_policy, &self.security_mode)?
+            .with_trust_server_certs(self.trust_server_certs);
+        if let Some(pki_dir) = &self.pki_dir {
+            config = config.with_pki_dir(PathBuf::from(pki_dir));
+        }
+        match (&self.certificate_path, &self.private_key_path) {
+            (Some(certificate_path), Some(private_key_path)) => {
+                config = config.with_certificate(
+                    PathBuf::from(certificate_path),
+                    PathBuf::from(private_key_path),
+                );
+            }
+            (None, None) => {}
+            _ => {
+                return Err(invalid_security_configuration(
+                    "certificate_path and private_key_path must be set together",
+                ))
+            }
+        }
+        match (
+            &self.username,
+            &self.password,
+            &self.user_certificate_path,
+            &self.user_private_key_path,
+        ) {
+            (Some(username), Some(password), None, None) => {
+                config = config.with_identity(OPCUAIdentity::UserName(
+                    username.clone(),
+                    password.clone(),
+                ));
+            }
+            (None, None, Some(certificate_path), Some(private_key_path)) => {
+                config = config.with_identity(OPCUAIdentity::X509(
+                    PathBuf::from(certificate_path),
+                    PathBuf::from(private_key_path),
+                ));
+            }
+            (None, None, None, None) => {}
+            _ => {
+                return Err(invalid_security_configuration(
+                    "Either both username and password or both user_certificate_path and user_private_key_path must be set",
+                ))
+            }
+        }
+        Ok(config)
+    }
+
+    fn to_rust_aggregate_configuration(&self) -> OPCUAAggregateConfiguration {
+        OPCUAAggregateConfiguration {
+            use_server_capabilities_defaults: self.use_server_aggregate_defaults,
+            treat_uncertain_as_bad: self.treat_uncertain_as_bad,
+            percent_data_bad: self.percent_data_bad,
+            percent_data_good: self.percent_data_good,
+            use_sloped_extrapolation: self.use_sloped_extrapolation,
         }
     }
 }
 
+fn invalid_security_configuration(message: &str) -> PyQueryError {
+    PyQueryError::from(OPCUAHistoryReadError::InvalidSecurityConfiguration(
+        message.to_string(),
+    ))
+}
+
 #[pyclass]
 #[derive(Clone)]
 pub struct TimeSeriesTable {
+    #[pyo3(get)]
     pub schema: Option<String>,
+    #[pyo3(get)]
     pub time_series_table: String,
+    #[pyo3(get)]
     pub value_column: String,
+    #[pyo3(get)]
+    pub quality_column: Option<String>,
+    #[pyo3(get)]
     pub timestamp_column: String,
+    #[pyo3(get)]
     pub identifier_column: String,
+    #[pyo3(get)]
     pub value_datatype: String,
+    #[pyo3(get)]
     pub year_column: Option<String>,
+    #[pyo3(get)]
     pub month_column: Option<String>,
+    #[pyo3(get)]
     pub day_column: Option<String>,
+    #[pyo3(get)]
+    pub wide_value_columns: Option<HashMap<String, String>>,
+    partitions: Vec<TimeSeriesPartition>,
 }
 
 #[pymethods]
@@ -233,19 +543,50 @@ impl TimeSeriesTable {
         year_column: Option<String>,
         month_column: Option<String>,
         day_column: Option<String>,
+        quality_column: Option<String>,
+        wide_value_columns: Option<HashMap<String, String>>,
     ) -> TimeSeriesTable {
         TimeSeriesTable {
             schema,
             time_series_table,
             value_column,
+            quality_column,
             timestamp_column,
             identifier_column,
             value_datatype,
             year_column,
             month_column,
-            day_column
+            day_column,
+            wide_value_columns,
+            partitions: vec![],
         }
     }
+
+    //Timestamp partitions must be added from the coarsest to the finest
+    #[args(integer = "false")]
+    pub fn add_timestamp_partition(&mut self, column: String, format: String, integer: bool) {
+        let scheme = if integer {
+            PartitionScheme::TimestampInteger(format)
+        } else {
+            PartitionScheme::TimestampString(format)
+        };
+        self.partitions.push(TimeSeriesPartition { column, scheme });
+    }
+
+    pub fn add_identifier_partition(&mut self, column: String, mapping: HashMap<String, String>) {
+        self.partitions.push(TimeSeriesPartition {
+            column,
+            scheme: PartitionScheme::IdentifierMapping(mapping),
+        });
+    }
+
+    //The partition is the hash (crc32 or fnv1a_64) of the identifier modulo the given number
+    pub fn add_identifier_hash_partition(&mut self, column: String, hash: String, modulo: u64) -> PyResult<()> {
+        let scheme = PartitionScheme::identifier_hash(&hash, modulo)
+            .ok_or_else(|| PyQueryError::from(ConfigError::InvalidPartitionHash(hash, modulo)))?;
+        self.partitions.push(TimeSeriesPartition { column, scheme });
+        Ok(())
+    }
 }
 
 impl TimeSeriesTable {
@@ -254,14 +595,34 @@ impl TimeSeriesTable {
             schema: self.schema.clone(),
             time_series_table: self.time_series_table.clone(),
             value_column: self.value_column.clone(),
+            quality_column: self.quality_column.clone(),
             timestamp_column: self.timestamp_column.clone(),
             identifier_column: self.identifier_column.clone(),
             value_datatype: NamedNode::new(&self.value_datatype)?,
             year_column: self.year_column.clone(),
             month_column: self.month_column.clone(),
-            day_column: self.day_column.clone()
+            day_column: self.day_column.clone(),
+            partitions: self.partitions.clone(),
+            wide_value_columns: self.wide_value_columns.clone(),
         })
     }
+
+    fn from_rust_table(table: &RustTimeSeriesTable) -> TimeSeriesTable {
+        TimeSeriesTable {
+            schema: table.schema.clone(),
+            time_series_table: table.time_series_table.clone(),
+            value_column: table.value_column.clone(),
+            quality_column: table.quality_column.clone(),
+            timestamp_column: table.timestamp_column.clone(),
+            identifier_column: table.identifier_column.clone(),
+            value_datatype: table.value_datatype.as_str().to_string(),
+            year_column: table.year_column.clone(),
+            month_column: table.month_column.clone(),
+            day_column: table.day_column.clone(),
+            wide_value_columns: table.wide_value_columns.clone(),
+            partitions: table.partitions.clone(),
+        }
+    }
 }
 
 #[pymodule]
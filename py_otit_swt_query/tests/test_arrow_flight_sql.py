@@ -88,3 +88,14 @@ def test_dsl_query(dremio_testdata, oxigraph_testdata):
     expected_df = pl.read_csv(expected_csv, parse_dates=True)
     expected_df = expected_df.sort(["BigWidget_ImportantSensor__path_name", "timestamp"])
     pl.testing.assert_frame_equal(df, expected_df, check_dtype=False)
+
+
+def test_propose_tables(dremio_testdata):
+    arrow_flight_sql_database = ArrowFlightSQLDatabase(host=DREMIO_HOST, port=DREMIO_PORT, username="dremio",
+                                                       password="dremio123", schema_pattern="^my_nas$")
+    tables = arrow_flight_sql_database.propose_tables()
+    assert len(tables) == 1
+    assert tables[0].time_series_table == "ts.parquet"
+    assert tables[0].identifier_column == "id"
+    assert tables[0].timestamp_column == "ts"
+    assert tables[0].value_column == "v"